@@ -0,0 +1,60 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use std::num::NonZero;
+use video_less::terminal_sink::fuzzing::{self, DiffOptions};
+use video_less::{Glyphs, Palette};
+
+#[derive(Debug, Arbitrary)]
+struct Input<'a> {
+    width: u8,
+    height: u8,
+    offset: (u8, u8),
+    compact: bool,
+    repeat: bool,
+    threads: u8,
+    precision: u8,
+    xterm256: bool,
+    spaces: bool,
+    first: &'a [u8],
+    /// which bytes of the first frame change for the second, and to what
+    changes: Vec<(u16, u8)>,
+}
+
+fuzz_target!(|input: Input| {
+    let (width, height) = (u32::from(input.width), u32::from(input.height));
+    let len = width as usize * height as usize * 3;
+    if input.first.is_empty() || len == 0 {
+        return;
+    }
+
+    let first = input
+        .first
+        .iter()
+        .copied()
+        .cycle()
+        .take(len)
+        .collect::<Vec<_>>();
+    let mut second = first.clone();
+    for (at, byte) in input.changes {
+        second[usize::from(at) % len] = byte;
+    }
+
+    let options = DiffOptions {
+        offset: (u16::from(input.offset.0), u16::from(input.offset.1)),
+        compact: input.compact,
+        repeat: input.repeat,
+        threads: NonZero::new(usize::from(input.threads % 4) + 1).unwrap(),
+        precision: input.precision % 8 + 1,
+        palette: match input.xterm256 {
+            true => Palette::Xterm256,
+            false => Palette::Truecolor,
+        },
+        glyphs: match input.spaces {
+            true => Glyphs::Spaces,
+            false => Glyphs::Blocks,
+        },
+    };
+    fuzzing::diff_matches_overwrite((width, height), &first, &second, options);
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use video_less::terminal_sink::fuzzing;
+
+fuzz_target!(|input: (u32, u32, &[u8])| {
+    let (width, height, buffer) = input;
+    fuzzing::image_from_buffer(width, height, buffer);
+});
@@ -0,0 +1,35 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use video_less::terminal_sink::fuzzing;
+
+#[derive(Debug, Arbitrary)]
+struct Input<'a> {
+    /// kept small enough to not run out of memory
+    sizes: Vec<(u8, u8)>,
+    width: u8,
+    height: u8,
+    pixels: &'a [u8],
+    to: (u8, u8),
+}
+
+fuzz_target!(|input: Input| {
+    let sizes = input
+        .sizes
+        .iter()
+        .map(|&(width, height)| (u16::from(width) * 3, u16::from(height)))
+        .collect::<Vec<_>>();
+    fuzzing::resize_matrix(&sizes);
+
+    let (width, height) = (u32::from(input.width), u32::from(input.height));
+    // a buffer of exactly the right length most of the time, the pixels repeating over it
+    let len = width as usize * height as usize * 3;
+    let pixels = match input.pixels.is_empty() {
+        true => vec![0; len],
+        false => input.pixels.iter().copied().cycle().take(len).collect(),
+    };
+    let to = (u16::from(input.to.0), u16::from(input.to.1));
+    fuzzing::resize_image(width, height, &pixels, to);
+    fuzzing::resize_image(width, height, input.pixels, to);
+});
@@ -0,0 +1,177 @@
+//! The file `transcode` writes, a line with the size it was rendered at,
+//! then every frame as a line with its timestamp in microseconds and its length in bytes,
+//! followed by the escape sequences drawing it
+use crate::cancel::CancellationToken;
+use crate::error::PlayerError;
+use crate::terminal::{self, Event, Key, Terminal};
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const MAGIC: &str = "video-less ansi dump v1";
+
+pub fn header((width, height): (u16, u16)) -> String {
+    format!("{MAGIC} {width}x{height}\n")
+}
+
+pub fn frame_header(pts: Duration, len: usize) -> String {
+    format!("{} {len}\n", pts.as_micros())
+}
+
+/// A frame of a dump, shown `pts` into its video
+#[derive(Debug, PartialEq, Eq)]
+struct Frame<'a> {
+    pts: Duration,
+    bytes: &'a [u8],
+}
+
+/// takes the next line off `data`, without its newline
+fn line<'a>(data: &mut &'a [u8]) -> Option<&'a str> {
+    let end = data.iter().position(|&byte| byte == b'\n')?;
+    let line = std::str::from_utf8(&data[..end]).ok()?;
+    *data = &data[end + 1..];
+    Some(line)
+}
+
+/// the size the dump was rendered at and its frames
+fn parse(mut data: &[u8]) -> Result<((u16, u16), Vec<Frame<'_>>), String> {
+    let size = line(&mut data)
+        .and_then(|header| header.strip_prefix(MAGIC)?.strip_prefix(' '))
+        .and_then(|size| size.split_once('x'))
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .ok_or("not an ansi dump")?;
+
+    let mut frames = vec![];
+    while !data.is_empty() {
+        let (pts, len) = line(&mut data)
+            .and_then(|header| header.split_once(' '))
+            .and_then(|(pts, len)| Some((pts.parse().ok()?, len.parse::<usize>().ok()?)))
+            .filter(|&(_, len)| len <= data.len())
+            .ok_or_else(|| format!("frame {} is cut off or broken", frames.len()))?;
+
+        let (bytes, rest) = data.split_at(len);
+        frames.push(Frame {
+            pts: Duration::from_micros(pts),
+            bytes,
+        });
+        data = rest;
+    }
+
+    Ok((size, frames))
+}
+
+/// waits until `due`, unless the token gets cancelled first
+fn wait_until(due: Instant, token: &CancellationToken) {
+    // short naps, so quitting doesn't wait for the next frame
+    const NAP: Duration = Duration::from_millis(50);
+
+    while !token.is_cancelled()
+        && let Some(left) = due.checked_duration_since(Instant::now())
+    {
+        thread::sleep(left.min(NAP));
+    }
+}
+
+/// plays back a dump in the terminal at the speed it was recorded at, until quit with q
+pub fn play(path: &Path) -> Result<(), PlayerError> {
+    let data = std::fs::read(path).map_err(|source| PlayerError::Open {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let ((width, height), frames) = parse(&data).map_err(|message| PlayerError::Open {
+        path: path.to_path_buf(),
+        source: io::Error::new(io::ErrorKind::InvalidData, message),
+    })?;
+
+    if let Some((columns, rows)) = terminal::size()
+        && (columns < width || rows < height)
+    {
+        return Err(PlayerError::Terminal(io::Error::other(format!(
+            "the dump needs {width}x{height} cells, the terminal only has {columns}x{rows}"
+        ))));
+    }
+
+    let Terminal {
+        mut output,
+        restore,
+    } = terminal::take().map_err(PlayerError::Terminal)?;
+
+    let token = CancellationToken::default();
+    let keys = thread::spawn({
+        let token = token.clone();
+        move || {
+            let quit = terminal::events(token.clone()).any(|event| {
+                matches!(
+                    event,
+                    Event::Key(Key::Ctrl('c') | Key::Char('q' | 'Q') | Key::Esc)
+                )
+            });
+            if quit {
+                token.cancel()
+            }
+        }
+    });
+
+    let mut clock = None;
+    let mut last_pts = Duration::ZERO;
+    let mut res = Ok(());
+    for frame in frames {
+        // the next video of a playlist starts its timestamps over
+        let (started, first) = match clock {
+            Some(clock) if frame.pts >= last_pts => clock,
+            _ => *clock.insert((Instant::now(), frame.pts)),
+        };
+        last_pts = frame.pts;
+
+        wait_until(started + (frame.pts - first), &token);
+        if token.is_cancelled() {
+            break;
+        }
+
+        if let Err(err) = output.write_all(frame.bytes).and_then(|()| output.flush()) {
+            res = Err(PlayerError::Terminal(err));
+            break;
+        }
+    }
+
+    token.cancel();
+    keys.join().unwrap();
+    drop(output);
+    drop(restore);
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frames_come_back_out() {
+        let mut dump = header((120, 40)).into_bytes();
+        for (pts, bytes) in [(0, &b"\x1b[2Jfirst"[..]), (41_708, b"se\ncond")] {
+            dump.extend(frame_header(Duration::from_micros(pts), bytes.len()).bytes());
+            dump.extend(bytes);
+        }
+
+        let (size, frames) = parse(&dump).unwrap();
+        assert_eq!(size, (120, 40));
+        assert_eq!(
+            frames,
+            [
+                Frame {
+                    pts: Duration::ZERO,
+                    bytes: b"\x1b[2Jfirst",
+                },
+                Frame {
+                    pts: Duration::from_micros(41_708),
+                    bytes: b"se\ncond",
+                },
+            ]
+        );
+
+        dump.pop();
+        assert!(parse(&dump).is_err());
+        assert!(parse(b"not a dump\n").is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use glib::object::{Cast, ObjectExt};
+use gst::prelude::{ElementExt, GstBinExt, GstBinExtManual};
+use video_less::error::PlayerError;
+use video_less::gstreamer_element;
+
+const EQUALIZER: &str = "equalizer";
+const VOLUME: &str = "volume";
+const SPECTRUM: &str = "spectrum";
+/// how many bands the spectrum splits the sound into
+const SPECTRUM_BANDS: u32 = 64;
+/// quieter bands than this many dB show as nothing
+const SPECTRUM_FLOOR: f32 = -80.0;
+/// how often the spectrum gets posted, about 20 times a second
+const SPECTRUM_INTERVAL: gst::ClockTime = gst::ClockTime::from_mseconds(50);
+/// name of the bin holding everything audio
+pub const NAME: &str = "audio_sink";
+
+#[derive(clap::ValueEnum, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EqPreset {
+    Flat,
+    Bass,
+    Treble,
+    Vocal,
+}
+
+impl EqPreset {
+    const ALL: [EqPreset; 4] = [Self::Flat, Self::Bass, Self::Treble, Self::Vocal];
+
+    /// gains in dB for the bands from 29Hz up to 15kHz
+    fn gains(self) -> [f64; 10] {
+        match self {
+            Self::Flat => [0.0; 10],
+            Self::Bass => [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            Self::Treble => [0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 4.0, 5.0, 6.0],
+            Self::Vocal => [-2.0, -2.0, -1.0, 1.0, 3.0, 3.0, 2.0, 1.0, 0.0, -1.0],
+        }
+    }
+
+    fn apply(self, equalizer: &gst::Element) {
+        EqGains(self.gains()).apply(equalizer);
+    }
+}
+
+impl From<EqPreset> for EqGains {
+    fn from(preset: EqPreset) -> Self {
+        Self(preset.gains())
+    }
+}
+
+/// Gains in dB for the ten bands, written as `3:2:0:-2` with the bands left out at 0
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EqGains(pub [f64; 10]);
+
+impl EqGains {
+    /// what equalizer-10bands takes
+    const RANGE: std::ops::RangeInclusive<f64> = -24.0..=12.0;
+
+    fn apply(self, equalizer: &gst::Element) {
+        for (band, gain) in self.0.into_iter().enumerate() {
+            equalizer.set_property(&format!("band{band}"), gain);
+        }
+    }
+}
+
+impl std::fmt::Display for EqGains {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (band, gain) in self.0.iter().enumerate() {
+            if band > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{gain}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for EqGains {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut gains = [0.0; 10];
+        let mut bands = s.split(':');
+        for (gain, band) in gains.iter_mut().zip(&mut bands) {
+            *gain = band
+                .trim()
+                .parse()
+                .map_err(|_| format!("`{band}` isn't a gain in dB"))?;
+            if !Self::RANGE.contains(gain) {
+                return Err(format!("{gain} dB is outside of -24 to 12 dB"));
+            }
+        }
+        if bands.next().is_some() {
+            return Err("there are only 10 bands".to_string());
+        }
+        Ok(Self(gains))
+    }
+}
+
+/// a `NAME=GAINS` equalizer preset, for `--eq-preset`
+pub fn parse_named_gains(s: &str) -> Result<(String, EqGains), String> {
+    let (name, gains) = s
+        .split_once('=')
+        .ok_or_else(|| "expected NAME=GAINS".to_string())?;
+    Ok((name.trim().to_string(), gains.parse()?))
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub equalizer: Option<EqPreset>,
+    /// gains the equalizer starts with instead of a preset's
+    pub eq: Option<EqGains>,
+    /// filters the sound goes through after the built in ones, as a pipeline description
+    pub filter: Option<String>,
+    /// mix a bit of each channel into the other, for headphones
+    pub crossfeed: bool,
+    /// what the volume starts at, 1 being as loud as the file is
+    pub volume: f64,
+    pub mute: bool,
+    /// what the sound ends up in instead of `autoaudiosink`, as a pipeline description
+    pub sink: Option<String>,
+}
+
+/// what the equalizer of the pipeline is at, `None` when it has none
+pub fn eq_gains(pipeline: &gst::Pipeline) -> Option<EqGains> {
+    let equalizer = pipeline.by_name(EQUALIZER)?;
+    Some(EqGains(core::array::from_fn(|band| {
+        equalizer.property::<f64>(&format!("band{band}"))
+    })))
+}
+
+/// switches the equalizer of the pipeline to the next preset and gives it, `None` when it has none
+pub fn cycle_equalizer(pipeline: &gst::Pipeline) -> Option<EqPreset> {
+    let equalizer = pipeline.by_name(EQUALIZER)?;
+    let gains = eq_gains(pipeline)?;
+    let current = EqPreset::ALL
+        .iter()
+        .position(|&preset| EqGains::from(preset) == gains);
+    let next = EqPreset::ALL[current.map_or(0, |i| (i + 1) % EqPreset::ALL.len())];
+
+    next.apply(&equalizer);
+    Some(next)
+}
+
+/// changes the volume of the pipeline by `by`, between muted and twice as loud
+pub fn adjust_volume(pipeline: &gst::Pipeline, by: f64) {
+    let Some(volume) = pipeline.by_name(VOLUME) else {
+        return;
+    };
+
+    let level = volume.property::<f64>("volume");
+    volume.set_property("volume", (level + by).clamp(0.0, 2.0));
+}
+
+/// sets the volume of the pipeline, 1 being as loud as the file is
+pub fn set_volume(pipeline: &gst::Pipeline, level: f64) {
+    if let Some(volume) = pipeline.by_name(VOLUME) {
+        volume.set_property("volume", level.clamp(0.0, 2.0));
+    }
+}
+
+/// mutes the pipeline, or unmutes it when it already is
+pub fn toggle_mute(pipeline: &gst::Pipeline) {
+    if let Some(volume) = pipeline.by_name(VOLUME) {
+        volume.set_property("mute", !volume.property::<bool>("mute"));
+    }
+}
+
+/// starts or stops the spectrum of the pipeline getting posted on its bus
+pub fn show_spectrum(pipeline: &gst::Pipeline, shown: bool) {
+    if let Some(spectrum) = pipeline.by_name(SPECTRUM) {
+        spectrum.set_property("post-messages", shown);
+    }
+}
+
+/// how loud every band is from 0 to 1, lowest first, when `structure` is what `spectrum` posts
+pub fn spectrum_levels(structure: &gst::StructureRef) -> Option<Vec<f32>> {
+    if structure.name() != SPECTRUM {
+        return None;
+    }
+    let magnitudes = structure.get::<gst::List>("magnitude").ok()?;
+    let levels = magnitudes
+        .iter()
+        .filter_map(|magnitude| magnitude.get::<f32>().ok())
+        .map(|db| ((db - SPECTRUM_FLOOR) / -SPECTRUM_FLOOR).clamp(0.0, 1.0))
+        .collect();
+    Some(levels)
+}
+
+/// the volume of the pipeline and whether it's muted, `None` when it has no sound
+pub fn loudness(pipeline: &gst::Pipeline) -> Option<(f64, bool)> {
+    let volume = pipeline.by_name(VOLUME)?;
+    Some((volume.property("volume"), volume.property("mute")))
+}
+
+pub fn create(options: &Options) -> Result<gst::Element, PlayerError> {
+    let audio_handler = gst::Bin::with_name(NAME);
+    let audio_convert = gstreamer_element("audioconvert")?;
+    let audio_resample = gstreamer_element("audioresample")?;
+    // volume and the equalizer stay in front so their keys keep working
+    let audio_sink = match &options.sink {
+        Some(description) => gst::parse::bin_from_description(description, true)
+            .map_err(PlayerError::description(description))?
+            .upcast(),
+        None => gstreamer_element("autoaudiosink")?,
+    };
+    // in front of the volume so it shows the file and not how loud it's turned up,
+    // spectrum lives in plugins-good, and the analyzer is only missing without it
+    let spectrum = gst::ElementFactory::make("spectrum")
+        .name(SPECTRUM)
+        .property("bands", SPECTRUM_BANDS)
+        .property("threshold", SPECTRUM_FLOOR as i32)
+        .property("interval", SPECTRUM_INTERVAL.nseconds())
+        .property("post-messages", false)
+        .build()
+        .ok();
+    let volume = gst::ElementFactory::make("volume")
+        .name(VOLUME)
+        .property("volume", options.volume.clamp(0.0, 2.0))
+        .property("mute", options.mute)
+        .build()
+        .map_err(PlayerError::element("volume"))?;
+
+    let gains = options
+        .eq
+        .or_else(|| options.equalizer.map(|preset| EqGains(preset.gains())));
+    let equalizer = gains
+        .map(|gains| {
+            let equalizer = gst::ElementFactory::make("equalizer-10bands")
+                .name(EQUALIZER)
+                .build()
+                .map_err(PlayerError::element("equalizer-10bands"))?;
+            gains.apply(&equalizer);
+            Ok(equalizer)
+        })
+        .transpose()?;
+    // bs2b lives in plugins-bad, so go without it when it isn't installed
+    let crossfeed = options
+        .crossfeed
+        .then(|| {
+            gstreamer_element("bs2b")
+                .inspect_err(|err| tracing::warn!(%err, "playing without --crossfeed"))
+                .ok()
+        })
+        .flatten();
+
+    let filter = options
+        .filter
+        .as_deref()
+        .map(|description| {
+            gst::parse::bin_from_description(description, true)
+                .map(Cast::upcast::<gst::Element>)
+                .map_err(PlayerError::description(description))
+        })
+        .transpose()?;
+
+    let filters = equalizer
+        .into_iter()
+        .chain(crossfeed)
+        .chain(filter)
+        .collect::<Vec<_>>();
+    // the filters may leave the samples in a format the sink can't take
+    let filter_convert = (!filters.is_empty())
+        .then(|| gstreamer_element("audioconvert"))
+        .transpose()?;
+
+    let audio_line = [&audio_convert, &audio_resample]
+        .into_iter()
+        .chain(&spectrum)
+        .chain([&volume])
+        .chain(&filters)
+        .chain(&filter_convert)
+        .chain([&audio_sink])
+        .collect::<Vec<_>>();
+
+    let link = PlayerError::link;
+    audio_handler
+        .add_many(&audio_line)
+        .map_err(link("the audio elements"))?;
+    gst::Element::link_many(&audio_line).map_err(link("the audio elements"))?;
+
+    let pad = gst::GhostPad::with_target(&audio_convert.static_pad("sink").unwrap())
+        .map_err(link("the audio sink pad"))?;
+    audio_handler
+        .add_pad(&pad)
+        .map_err(link("the audio sink pad"))?;
+
+    Ok(audio_handler.upcast())
+}
+
+#[cfg(test)]
+mod test {
+    use super::EqGains;
+
+    #[test]
+    fn parses_gains() {
+        let gains = "3:2:0:-2.5".parse::<EqGains>().unwrap();
+        assert_eq!(gains.0[..5], [3.0, 2.0, 0.0, -2.5, 0.0]);
+
+        assert!("1:2:3:4:5:6:7:8:9:10:11".parse::<EqGains>().is_err());
+        assert!("20".parse::<EqGains>().is_err());
+        assert!("loud".parse::<EqGains>().is_err());
+    }
+
+    #[test]
+    fn gains_print_as_they_parse() {
+        let gains = EqGains([6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, -1.5, 0.0]);
+        assert_eq!(gains.to_string().parse::<EqGains>(), Ok(gains));
+    }
+}
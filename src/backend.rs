@@ -0,0 +1,179 @@
+//! The `playbin3` alternative to the pipeline `make_pipeline_and_bus` builds by hand
+use crate::PipelineOptions;
+use crate::audio_sink;
+use glib::object::{Cast, ObjectExt};
+use gst::prelude::{ElementExt, GstBinExt, GstBinExtManual, PipelineExt};
+use std::path::Path;
+use video_less::error::PlayerError;
+use video_less::{flag, gstreamer_element, terminal_sink};
+
+/// What gets the frames out of a file
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// a pipeline built element by element, every option applies
+    #[default]
+    Manual,
+    /// playbin3, which takes care of decoding, subtitles and buffering by itself
+    Playbin,
+}
+
+/// the name the sink of a `--pipeline` description goes by
+pub const CUSTOM_SINK: &str = "termsink";
+
+/// the elements between playbin's video output and the terminal, behind one sink pad
+fn video_bin(
+    sink: &terminal_sink::TerminalVideoSink,
+    video: &Path,
+    options: &PipelineOptions,
+) -> Result<gst::Element, PlayerError> {
+    let freeze = match crate::image::kind(video) {
+        Some(crate::image::Kind::Still) => Some(crate::image::freeze(options.image_duration)?),
+        _ => None,
+    };
+    let download = options.hwdec.and_then(crate::hwdec::HwDec::download);
+    let rate = options
+        .max_fps
+        .map(|max_fps| {
+            gst::ElementFactory::make("videorate")
+                .property("max-rate", i32::try_from(max_fps.get()).unwrap_or(i32::MAX))
+                .property("drop-only", true)
+                .build()
+                .map_err(PlayerError::element("videorate"))
+        })
+        .transpose()?;
+    let scale = sink.scale_elements()?;
+    let queue = crate::leaky_queue(video, options.sink_queue)?;
+    let convert = gstreamer_element("videoconvert")?;
+    let video_sink = sink.element();
+
+    let video_line = freeze
+        .iter()
+        .flatten()
+        .chain(&download)
+        .chain(&rate)
+        .chain(&scale)
+        .chain(&queue)
+        .chain([&convert, &video_sink])
+        .collect::<Vec<_>>();
+
+    let bin = gst::Bin::with_name("video_sink");
+    let link = PlayerError::link;
+    bin.add_many(&video_line)
+        .map_err(link("the video elements"))?;
+    gst::Element::link_many(&video_line).map_err(link("the video elements"))?;
+
+    let pad = video_line[0].static_pad("sink").unwrap();
+    let ghost_pad = gst::GhostPad::with_target(&pad).unwrap();
+    bin.add_pad(&ghost_pad).unwrap();
+
+    Ok(bin.upcast())
+}
+
+/// plays `video` with playbin3, the terminal sink getting its video and our audio bin its sound
+pub fn playbin(
+    sink: &terminal_sink::TerminalVideoSink,
+    video: &Path,
+    options: &PipelineOptions,
+) -> Result<(gst::Pipeline, gst::Bus), PlayerError> {
+    let open_error = |source| PlayerError::Open {
+        path: video.to_path_buf(),
+        source,
+    };
+    let uri_of = |path: &Path| {
+        let path = std::path::absolute(path).map_err(open_error)?;
+        glib::filename_to_uri(&path, None).map_err(|err| open_error(std::io::Error::other(err)))
+    };
+
+    let uri: String = match crate::live::uri(video) {
+        Some(uri) => uri.to_owned(),
+        None => uri_of(video)?.into(),
+    };
+
+    let playbin = gst::ElementFactory::make("playbin3")
+        .property("uri", uri)
+        .property("video-sink", video_bin(sink, video, options)?)
+        .build()
+        .map_err(PlayerError::element("playbin3"))?;
+    crate::live::configure_playbin(&playbin, options.latency);
+
+    match !options.no_audio && !flag("NO_AUDIO_OUTPUT", false) {
+        true => playbin.set_property("audio-sink", audio_sink::create(&options.audio)?),
+        // a fakesink would still hold playback back to the sound's clock
+        false => {
+            let flags = playbin.property_value("flags");
+            let flags = glib::FlagsClass::with_type(flags.type_())
+                .and_then(|class| class.builder_with_value(flags))
+                .and_then(|flags| flags.unset_by_nick("audio").build())
+                .unwrap();
+            playbin.set_property_from_value("flags", &flags);
+        }
+    }
+
+    #[cfg(feature = "sub-auto")]
+    if options.sub_auto
+        && let Some(subtitle) = crate::subtitles::find_local(video)
+    {
+        playbin.set_property("suburi", uri_of(&subtitle)?);
+    }
+
+    tracing::debug!(video = %video.display(), "building a playbin");
+
+    if let Some(ref log) = options.decoder_log {
+        crate::log_decoders(playbin.downcast_ref().unwrap(), log, video);
+    }
+
+    let pipeline = playbin
+        .downcast::<gst::Pipeline>()
+        .expect("playbin3 is a pipeline");
+    if let Some(latency) = options.latency {
+        pipeline.set_latency(latency);
+    }
+
+    // failing to start posts an error that the bus loop reports
+    let _ = pipeline.set_state(gst::State::Playing);
+
+    let bus = pipeline.bus().expect("pipelines always have a bus");
+
+    Ok((pipeline, bus))
+}
+
+/// plays a whole pipeline description, which draws to the terminal through an appsink
+/// named [`CUSTOM_SINK`] or through a `termvideosink` of its own
+pub fn custom(
+    sink: &terminal_sink::TerminalVideoSink,
+    description: &str,
+) -> Result<(gst::Pipeline, gst::Bus), PlayerError> {
+    let invalid = |message: &str| PlayerError::Description {
+        description: description.to_string(),
+        source: glib::Error::new(gst::ParseError::NoSuchElement, message),
+    };
+
+    let pipeline = gst::parse::launch(description)
+        .map_err(PlayerError::description(description))?
+        .downcast::<gst::Pipeline>()
+        .map_err(|_| invalid("the description has to be a whole pipeline"))?;
+
+    let adopted = pipeline
+        .by_name(CUSTOM_SINK)
+        .is_some_and(|element| sink.adopt(&element));
+    let draws_itself = || {
+        pipeline
+            .iterate_sinks()
+            .into_iter()
+            .any(|element| element.is_ok_and(|element| element.is::<video_less::TermVideoSink>()))
+    };
+    if !adopted && !draws_itself() {
+        return Err(invalid(&format!(
+            "the pipeline needs an appsink named {CUSTOM_SINK} or a termvideosink"
+        )));
+    }
+
+    tracing::debug!(description, "building a pipeline from a description");
+
+    // failing to start posts an error that the bus loop reports
+    let _ = pipeline.set_state(gst::State::Playing);
+
+    let bus = pipeline.bus().expect("pipelines always have a bus");
+
+    Ok((pipeline, bus))
+}
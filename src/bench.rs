@@ -0,0 +1,163 @@
+//! `videoplayer bench`, decoding a file as fast as it goes and drawing every frame
+//! the way playing would, into nowhere, to see how fast the renderer is and where its time goes
+use glib::object::Cast;
+use gst::prelude::{ElementExt, GstBinExtManual, GstObjectExt, PadExt};
+use gst_app::AppSink;
+use gstreamer_app as gst_app;
+use gstreamer_video as gst_video;
+use std::io::Write;
+use std::num::NonZero;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use video_less::error::PlayerError;
+use video_less::{TerminalRenderer, gstreamer_element, terminal_sink};
+
+/// What gets measured, the rest is drawn like the player does by default
+pub struct Options {
+    pub frames: u64,
+    pub size: (u16, u16),
+    pub compact: bool,
+    pub palette: terminal_sink::Palette,
+    pub glyphs: terminal_sink::Glyphs,
+    pub threads: NonZero<usize>,
+    pub scaler: terminal_sink::Scaler,
+}
+
+fn build(video: &Path) -> Result<(gst::Pipeline, AppSink), PlayerError> {
+    let source = gst::ElementFactory::make("filesrc")
+        .property("location", video)
+        .build()
+        .map_err(PlayerError::element("filesrc"))?;
+    let decode = gstreamer_element("decodebin")?;
+    let convert = gstreamer_element("videoconvert")?;
+    let sink = AppSink::builder()
+        .caps(&TerminalRenderer::caps())
+        .sync(false)
+        .build();
+
+    let pipeline = gst::Pipeline::new();
+    let link = PlayerError::link;
+    pipeline
+        .add_many([&source, &decode, &convert, sink.upcast_ref()])
+        .map_err(link("the bench elements"))?;
+    source
+        .link(&decode)
+        .map_err(link("the bench source to its decoder"))?;
+    convert
+        .link(&sink)
+        .map_err(link("the bench converter to its sink"))?;
+
+    // the sound gets left undecoded
+    let video_entry = convert.static_pad("sink").unwrap();
+    decode.connect_pad_added(move |_, src_pad| {
+        let caps = src_pad
+            .current_caps()
+            .unwrap_or_else(|| src_pad.query_caps(None));
+        let is_video = caps
+            .structure(0)
+            .is_some_and(|structure| structure.name().starts_with("video/"));
+        if is_video && !video_entry.is_linked() {
+            let _ = src_pad.link(&video_entry);
+        }
+    });
+
+    Ok((pipeline, sink))
+}
+
+fn millis(total: Duration, frames: u64) -> f64 {
+    total.as_secs_f64() * 1000.0 / frames.max(1) as f64
+}
+
+/// the first error the pipeline posted, if it did
+fn pipeline_error(pipeline: &gst::Pipeline) -> Option<PlayerError> {
+    let message = pipeline.bus()?.pop_filtered(&[gst::MessageType::Error])?;
+    let gst::MessageView::Error(err) = message.view() else {
+        return None;
+    };
+    Some(PlayerError::Playback {
+        element: err
+            .src()
+            .map(|s| s.path_string().to_string())
+            .unwrap_or_else(|| "unknown".to_string()),
+        error: err.error(),
+        debug: err.debug().map(|debug| debug.to_string()),
+    })
+}
+
+/// renders up to `options.frames` frames of `video`, and prints how long it took
+pub fn run(video: &Path, options: &Options) -> Result<(), PlayerError> {
+    let (pipeline, sink) = build(video)?;
+    let mut renderer = TerminalRenderer::new(options.compact, options.threads);
+    renderer.set_style(options.palette, options.glyphs);
+    renderer.set_scaler(options.scaler);
+
+    let _ = pipeline.set_state(gst::State::Playing);
+    let started = Instant::now();
+    let mut rendering = Duration::ZERO;
+    let mut bytes = 0_u64;
+    let mut frames = 0;
+    // where the escape sequences would have gone
+    let mut output = std::io::sink();
+
+    while frames < options.frames {
+        let Ok(sample) = sink.pull_sample() else {
+            break;
+        };
+        let Some(info) = sample
+            .caps()
+            .and_then(|caps| gst_video::VideoInfo::from_caps(caps).ok())
+        else {
+            continue;
+        };
+        let Some(buffer) = sample.buffer() else {
+            continue;
+        };
+        let Ok(frame) = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, &info) else {
+            continue;
+        };
+
+        let start = Instant::now();
+        if let Some(escapes) = renderer.render(&frame, options.size, frames == 0, None) {
+            bytes += escapes.len() as u64;
+            let _ = output.write_all(escapes);
+        }
+        rendering += start.elapsed();
+        frames += 1;
+    }
+    let elapsed = started.elapsed();
+
+    let error = pipeline_error(&pipeline);
+    let _ = pipeline.set_state(gst::State::Null);
+    if frames == 0 {
+        return Err(error.unwrap_or_else(|| PlayerError::NoFrame {
+            video: video.to_path_buf(),
+            position: gst::ClockTime::ZERO,
+        }));
+    }
+
+    let timings = renderer.timings();
+    let (columns, rows) = options.size;
+    println!("frames      {frames} at {columns}x{rows}");
+    println!(
+        "render      {:.1} fps",
+        frames as f64 / rendering.as_secs_f64()
+    );
+    println!(
+        "overall     {:.1} fps, decoding included",
+        frames as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "frame       {:.1} kB",
+        bytes as f64 / frames as f64 / 1000.0
+    );
+    for (stage, total) in [
+        ("unpack", timings.unpack),
+        ("filters", timings.filters),
+        ("resize", timings.resize),
+        ("pixels", timings.post_filters),
+        ("diff", timings.diff),
+    ] {
+        println!("{stage:<12}{:.3} ms", millis(total, timings.frames));
+    }
+    Ok(())
+}
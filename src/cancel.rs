@@ -0,0 +1,46 @@
+//! Telling threads blocked on the terminal to give up, what [`QuitHandler`](crate::QuitHandler)
+//! cancels once it's dropped
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+type Waker = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+/// A flag shared between threads that, once cancelled, stays cancelled
+/// and wakes up everything that registered interest in it
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<Inner>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        let wakers = {
+            let mut wakers = self.0.wakers.lock();
+            self.0.cancelled.store(true, Ordering::Release);
+            core::mem::take(&mut *wakers)
+        };
+
+        for waker in wakers {
+            waker()
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    /// runs `waker` once the token is cancelled, or right away if it already is
+    pub fn on_cancel(&self, waker: impl FnOnce() + Send + 'static) {
+        let mut wakers = self.0.wakers.lock();
+        if self.is_cancelled() {
+            drop(wakers);
+            return waker();
+        }
+        wakers.push(Box::new(waker));
+    }
+}
@@ -0,0 +1,674 @@
+use crate::{audio_sink, backend, clip, hwdec, logging, preview, wall};
+use clap::CommandFactory;
+use std::num::NonZero;
+use std::path::PathBuf;
+use video_less::{terminal, terminal_sink};
+
+#[derive(Debug, Clone)]
+pub struct Size {
+    pub width: u16,
+    pub height: u16,
+}
+
+impl std::str::FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (w, h) = s.split_once('x').ok_or_else(|| {
+            "size must be in the form {WIDTH}x{HEIGHT} (e.g. 800x600)".to_string()
+        })?;
+
+        let parse = |v: &str| v.parse::<NonZero<u16>>().map(NonZero::get);
+
+        let width = parse(w).map_err(|_| "width must be a positive integer".to_string())?;
+        let height = parse(h).map_err(|_| "height must be a positive integer".to_string())?;
+
+        Ok(Size { width, height })
+    }
+}
+
+pub fn conflict(message: &str) -> ! {
+    Cli::command()
+        .error(clap::error::ErrorKind::ArgumentConflict, message)
+        .exit()
+}
+
+/// Where the config file comes from, taken before or after any subcommand
+#[derive(clap::Args, Debug)]
+pub struct ConfigArgs {
+    /// Read default options from FILE instead of ~/.config/video-less/config.toml,
+    /// written as `option = value` with the long option names
+    #[arg(long, value_name = "FILE", global = true)]
+    config: Option<PathBuf>,
+
+    /// Don't read any config file
+    #[arg(long, conflicts_with = "config", global = true)]
+    no_config: bool,
+}
+
+/// What cells can be drawn with, for everything that renders
+#[derive(clap::Args, Debug)]
+pub struct CellArgs {
+    /// Which colors the terminal can show, found out from the terminal when playing on it
+    /// and truecolor otherwise when not given
+    #[arg(long, value_name = "PALETTE", value_enum)]
+    pub palette: Option<terminal_sink::Palette>,
+
+    /// What cells get drawn with, spaces for terminals without the block characters,
+    /// found out from the terminal when playing on it and blocks otherwise when not given
+    #[arg(long, value_name = "GLYPHS", value_enum)]
+    pub glyphs: Option<terminal_sink::Glyphs>,
+}
+
+impl CellArgs {
+    /// what was asked for, with the rest found out from the terminal when `detect` is set
+    pub fn resolve(&self, detect: bool) -> (terminal_sink::Palette, terminal_sink::Glyphs) {
+        let capabilities = (detect && (self.palette.is_none() || self.glyphs.is_none()))
+            .then(terminal::capabilities::detect);
+        let palette = self.palette.unwrap_or_else(|| match capabilities {
+            Some(capabilities) if !capabilities.truecolor => {
+                eprintln!("warning: the terminal seems to lack 24 bit colors, using 256");
+                terminal_sink::Palette::Xterm256
+            }
+            _ => terminal_sink::Palette::Truecolor,
+        });
+        let glyphs = self.glyphs.unwrap_or_else(|| match capabilities {
+            Some(capabilities) if !capabilities.unicode => {
+                eprintln!("warning: the terminal seems to lack block characters, using spaces");
+                terminal_sink::Glyphs::Spaces
+            }
+            _ => terminal_sink::Glyphs::Blocks,
+        });
+        (palette, glyphs)
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct PreviewArgs {
+    pub file: PathBuf,
+
+    /// How far into the file the frame is, as [[HH:]MM:]SS
+    #[arg(long, value_name = "TIME", default_value = "0", value_parser = preview::parse_time)]
+    pub at: gst::ClockTime,
+
+    /// How many columns and rows the frame gets to fill, the size of the terminal by default
+    #[arg(long, value_parser = clap::value_parser!(Size))]
+    pub size: Option<Size>,
+
+    /// Draw into the cells of a file manager's preview pane on the terminal, WxH@X,Y
+    /// counting from 0, and exit with 1 so lf doesn't cache the preview
+    #[arg(long, value_name = "GEOMETRY", conflicts_with = "size")]
+    pub preview_geometry: Option<preview::Geometry>,
+
+    #[command(flatten)]
+    pub cells: CellArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct WallArgs {
+    /// Video files or stream URLs, one for each tile
+    #[arg(required = true)]
+    pub videos: Vec<PathBuf>,
+
+    /// How many tiles across and down, like 3x2, the squarest grid they fit into by default
+    #[arg(long, value_name = "COLUMNSxROWS")]
+    pub grid: Option<wall::Grid>,
+
+    #[command(flatten)]
+    pub cells: CellArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CompareArgs {
+    pub first: PathBuf,
+    pub second: PathBuf,
+
+    #[command(flatten)]
+    pub cells: CellArgs,
+
+    /// How many times taller than wide a terminal cell is, 2 unless given
+    #[arg(long, value_name = "RATIO")]
+    pub cell_aspect: Option<terminal_sink::CellAspect>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct BenchArgs {
+    pub file: PathBuf,
+
+    /// How many frames to render before stopping, fewer when the file ends first
+    #[arg(long, value_name = "N", default_value = "500")]
+    pub frames: u64,
+
+    /// The size in cells frames get rendered at, in the form COLUMNSxROWS
+    #[arg(long, value_parser = clap::value_parser!(Size), default_value = "160x48")]
+    pub size: Size,
+
+    /// Draw cells with whichever glyph takes the fewest bytes
+    #[arg(long)]
+    pub compact: bool,
+
+    #[command(flatten)]
+    pub cells: CellArgs,
+
+    /// Diff the rows of every frame on N threads
+    #[arg(long, value_name = "N", default_value = "1")]
+    pub render_threads: NonZero<usize>,
+
+    /// How frames get scaled to the cells they're drawn in
+    #[arg(long, value_name = "FILTER", default_value_t, value_enum)]
+    pub scaler: terminal_sink::Scaler,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ProbeArgs {
+    pub file: PathBuf,
+
+    /// Print a json object instead of a line per thing found
+    #[cfg(feature = "serde")]
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Play videos on the terminal, what giving them without a subcommand does too
+    Play(PlayArgs),
+    /// Print one frame of FILE where the cursor is and exit, without taking over the terminal
+    Preview(PreviewArgs),
+    /// Print the duration, streams and tags of FILE, and which plugins it needs that are missing
+    Probe(ProbeArgs),
+    /// Decode FILE as fast as it goes and render its frames into nowhere,
+    /// printing how fast the renderer is and how long each of its steps takes
+    Bench(BenchArgs),
+    /// Stream to everyone connecting over TCP or telnet at ADDR instead of drawing on the terminal
+    Serve {
+        /// Where to listen, e.g. 0.0.0.0:2323
+        #[arg(value_name = "ADDR")]
+        addr: std::net::SocketAddr,
+
+        #[command(flatten)]
+        playback: PlaybackArgs,
+
+        #[command(flatten)]
+        render: RenderArgs,
+
+        #[command(flatten)]
+        session: SessionArgs,
+
+        #[command(flatten)]
+        debug: DebugArgs,
+    },
+    /// Render every frame to an ANSI dump at --size instead of the terminal, as fast as they decode,
+    /// to play back later with --play-ansi, or with --height write a small proxy of the file
+    /// that's cheap to play and seek in
+    Transcode {
+        /// Where the dump or the proxy goes
+        #[arg(long, short, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Write a matroska proxy PIXELS tall with every frame a keyframe instead of a dump
+        #[arg(long, value_name = "PIXELS")]
+        height: Option<NonZero<u32>>,
+
+        /// The frame rate of the proxy, the file's when left out
+        #[arg(long, value_name = "N", requires = "height")]
+        fps: Option<NonZero<u32>>,
+
+        #[command(flatten)]
+        playback: PlaybackArgs,
+
+        #[command(flatten)]
+        render: RenderArgs,
+
+        #[command(flatten)]
+        debug: DebugArgs,
+    },
+    /// Play VIDEOS all at once, each in a tile of the terminal, without sound,
+    /// like a wall of camera streams
+    Wall(WallArgs),
+    /// Play FIRST and SECOND by the same clock over the same part of the terminal,
+    /// FIRST left of a divider the arrow keys move and SECOND right of it
+    Compare(CompareArgs),
+    /// Print a completion script for SHELL, e.g. `videoplayer completions fish | source`
+    Completions {
+        #[arg(value_name = "SHELL")]
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page, for `videoplayer man > ~/.local/share/man/man1/videoplayer.1`
+    Man,
+    /// List the recently played files, newest first, numbered the way --from-history takes them
+    History {
+        /// How many to list
+        #[arg(long, short = 'n', value_name = "COUNT", default_value_t = 20)]
+        count: usize,
+    },
+}
+
+#[derive(clap::Parser, Debug)]
+#[command(name = "videoplayer")]
+#[command(about = "Simple video player CLI")]
+// the config file comes in as options in front of the real ones, which then override them
+#[command(args_override_self = true)]
+pub struct Cli {
+    // a bare path gets `play` put in front of it before parsing
+    #[command(subcommand)]
+    pub command: Command,
+
+    #[command(flatten)]
+    pub config: ConfigArgs,
+}
+
+/// Everything about playing on the terminal, serve and transcode take the groups that don't
+/// need one of its own
+#[derive(clap::Args, Debug)]
+pub struct PlayArgs {
+    #[command(flatten)]
+    pub playback: PlaybackArgs,
+
+    #[command(flatten)]
+    pub render: RenderArgs,
+
+    #[command(flatten)]
+    pub audio: AudioArgs,
+
+    #[command(flatten)]
+    pub session: SessionArgs,
+
+    #[command(flatten)]
+    pub terminal: TerminalArgs,
+
+    #[command(flatten)]
+    pub debug: DebugArgs,
+}
+
+/// What gets played, and which part of it
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "Playback")]
+pub struct PlaybackArgs {
+    /// Video files or stream URLs, such as rtsp:// or https:// ones, to play one after the other (positional)
+    pub videos: Vec<PathBuf>,
+
+    /// Pick something to play from the recently played files
+    #[arg(long, conflicts_with = "videos")]
+    pub recent: bool,
+
+    /// Play the Nth most recently played file again, as `videoplayer history` numbers them
+    #[arg(long, value_name = "N", conflicts_with_all = ["videos", "recent"])]
+    pub from_history: Option<NonZero<usize>>,
+
+    /// Play a whole gst-launch style pipeline instead of files, drawing what reaches
+    /// an appsink named termsink, e.g. "videotestsrc ! videoconvert ! appsink name=termsink"
+    #[arg(
+        long,
+        value_name = "DESCRIPTION",
+        conflicts_with_all = ["videos", "recent", "from_history"]
+    )]
+    pub pipeline: Option<String>,
+
+    /// What builds the pipeline, playbin takes care of subtitles and buffering by itself
+    #[arg(long, value_name = "BACKEND", default_value_t, value_enum)]
+    pub backend: backend::Backend,
+
+    /// Prefer hardware video decoders from BACKEND, or turn them off
+    #[arg(long, value_name = "BACKEND")]
+    pub hwdec: Option<hwdec::HwDec>,
+
+    /// Build the pipeline again up to N times a file when it fails in a way it might get over,
+    /// like a network source timing out, going on from where it got to
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+
+    /// How long to wait before trying again, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    pub retry_delay: u64,
+
+    /// Buffer MS milliseconds of live streams, more rides out a flaky network, less stays closer to live
+    #[arg(long, value_name = "MS")]
+    pub latency: Option<u64>,
+
+    /// Show still images for SECS seconds before moving on, instead of until quitting
+    #[arg(long, value_name = "SECS")]
+    pub image_duration: Option<NonZero<u32>>,
+
+    /// Play animated GIFs once instead of looping them
+    #[arg(long)]
+    pub no_gif_loop: bool,
+
+    /// Play every file over and over, or just the part --start and --end leave, until quitting
+    #[arg(long = "loop")]
+    pub loop_playback: bool,
+
+    /// Start every file at TIME, [[HH:]MM:]SS
+    #[arg(long, value_name = "TIME", value_parser = preview::parse_time)]
+    pub start: Option<gst::ClockTime>,
+
+    /// Stop every file at TIME, [[HH:]MM:]SS, from its start rather than from --start
+    #[arg(long, value_name = "TIME", value_parser = preview::parse_time)]
+    pub end: Option<gst::ClockTime>,
+
+    /// Stop every file TIME after --start, [[HH:]MM:]SS
+    #[arg(long, value_name = "TIME", value_parser = preview::parse_time, conflicts_with = "end")]
+    pub duration: Option<gst::ClockTime>,
+
+    /// Cut the part of the file --start, --end and --duration leave out into FILE and exit
+    /// instead of playing it
+    #[arg(long, value_name = "FILE", conflicts_with = "pipeline")]
+    pub export_clip: Option<PathBuf>,
+
+    /// How --export-clip and the e key write clips
+    #[arg(long, value_name = "MODE", value_enum, default_value_t)]
+    pub export_mode: clip::ExportMode,
+
+    /// Show the subtitle file named after each video when there is one next to it
+    #[cfg(feature = "sub-auto")]
+    #[arg(long)]
+    pub sub_auto: bool,
+}
+
+/// How frames become cells, wherever the cells end up
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "Rendering")]
+pub struct RenderArgs {
+    /// Window size in the form WIDTHxHEIGHT, e.g. 1280x720
+    #[arg(long, value_parser = clap::value_parser!(Size))]
+    pub size: Option<Size>,
+
+    #[command(flatten)]
+    pub cells: CellArgs,
+
+    /// Draw cells with whichever glyph takes the fewest bytes, useful over slow links such as SSH
+    #[arg(long)]
+    pub compact: bool,
+
+    /// How many times taller than wide a terminal cell is, 2 unless --font-cell says otherwise
+    #[arg(long, value_name = "RATIO")]
+    pub cell_aspect: Option<terminal_sink::CellAspect>,
+
+    /// The pixels a cell of the terminal font takes up, WIDTHxHEIGHT like 9x20, for the aspect
+    /// of fonts that aren't twice as tall as wide and the shape --render symbols matches cells in
+    #[arg(long, value_name = "SIZE", value_parser = clap::value_parser!(Size))]
+    pub font_cell: Option<Size>,
+
+    /// Paint the cells around the video with COLOR, e.g. #101010, or checkerboard
+    #[arg(long, value_name = "COLOR")]
+    pub background: Option<terminal_sink::Background>,
+
+    /// Scale frames down to about the terminal size right after decoding, which saves converting
+    /// and resizing big videos at their full size
+    #[arg(long, value_name = "MODE", default_value_t, value_enum)]
+    pub decode_scale: terminal_sink::DecodeScale,
+
+    /// How the highlights of 10 bit HDR video get brought down to what the terminal shows
+    #[arg(long, value_name = "CURVE", default_value_t, value_enum)]
+    pub tone_map: terminal_sink::ToneMap,
+
+    /// Draw the video the way people with a kind of color blindness see it,
+    /// for checking what gets lost to them
+    #[arg(long, value_name = "FILTER", default_value_t, value_enum)]
+    pub color_filter: terminal_sink::ColorFilter,
+
+    /// Draw the pixels of the video, edges for line art that reads on terminals with few colors,
+    /// or symbols for whichever of the --symbols glyphs matches each cell best
+    #[arg(long = "render", value_name = "MODE", default_value_t, value_enum)]
+    pub render_mode: terminal_sink::RenderMode,
+
+    /// The glyphs --render symbols picks from, some of half, quadrants, eighths, shades, wedges
+    /// and ascii joined with +, blocks being the first three and all all of them
+    #[arg(long, value_name = "SET", default_value = "blocks")]
+    pub symbols: terminal_sink::SymbolSet,
+
+    /// Add a glyph of the terminal font to the ones --render symbols picks from, its bitmap
+    /// as rows of # and . split by /, best kept in a [symbol] table of the config file
+    #[arg(long, value_name = "TEXT=BITMAP")]
+    pub symbol: Vec<terminal_sink::Symbol>,
+
+    /// Diff the rows of every frame on N threads
+    #[arg(long, value_name = "N", default_value = "1")]
+    pub render_threads: NonZero<usize>,
+
+    /// What to do with frames that come in while the last one is still being drawn
+    #[arg(long, value_name = "MODE", default_value = "latest")]
+    pub queue_mode: terminal_sink::QueueMode,
+
+    /// Leave out frames that are more than MS milliseconds late by the time the sink gets them,
+    /// rather than passing on every one however late
+    #[arg(long, value_name = "MS")]
+    pub max_lateness: Option<u64>,
+
+    /// Tell the decoders how late frames are, so they skip the ones that won't make it in time
+    #[arg(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    pub qos: bool,
+
+    /// Keep only the newest frame waiting to be drawn, for the least latency,
+    /// instead of the few --queue-mode latest keeps and whatever fifo asks for
+    #[arg(long)]
+    pub drop: bool,
+
+    /// Put a queue holding up to N frames in front of the terminal sink that drops the oldest
+    /// once it's full, so a terminal that can't keep up never holds up a live stream and its sound,
+    /// files are left to --queue-mode latest
+    #[arg(long, value_name = "N")]
+    pub sink_queue: Option<NonZero<u32>>,
+
+    /// Drop frames so at most N frames per second get rendered
+    #[arg(long, value_name = "N")]
+    pub max_fps: Option<NonZero<u32>>,
+
+    /// Filters to run on every frame, in order, e.g. crop=640:360,grayscale,sharpen=0.5
+    ///
+    /// Known filters: crop=W:H[:X:Y], rotate=90|180|270, grayscale, sharpen[=AMOUNT],
+    /// lut=gamma=G:contrast=C:brightness=B:invert
+    #[arg(long = "vf", value_name = "CHAIN")]
+    pub vf: Option<terminal_sink::filters::ChainSpec>,
+
+    /// An effect run over frames once they're down to the terminal size,
+    /// invert, sepia or posterize[:N] to N levels a channel, cycled with f while playing
+    #[arg(long, value_name = "EFFECT")]
+    pub filter: Option<terminal_sink::PostFilter>,
+
+    /// How frames get scaled to the cells they're drawn in, cycled with r while playing
+    #[arg(long, value_name = "FILTER", default_value_t, value_enum)]
+    pub scaler: terminal_sink::Scaler,
+
+    /// Bits of precision per color channel, lower values make frames cheaper to update,
+    /// 5 unless the profile says otherwise
+    #[arg(
+        long,
+        value_name = "BITS",
+        value_parser = clap::value_parser!(u8).range(1..=8)
+    )]
+    pub diff_precision: Option<u8>,
+
+    /// Where the terminal is, which picks the defaults of --max-fps, --diff-precision and
+    /// synchronized updates, found out from the ssh variables and how long the terminal takes
+    /// to answer when not given
+    #[arg(long, value_enum)]
+    pub profile: Option<terminal::profile::Profile>,
+
+    /// Keep the old colors of cells whose channels moved by at most DELTA since they were drawn,
+    /// which stops camera noise from redrawing nearly every cell every frame
+    #[arg(long, value_name = "DELTA", default_value_t = 0)]
+    pub diff_threshold: u8,
+
+    /// Draw at most CELLS changed cells a frame and leave the rest to the next frames,
+    /// so 9600 baud serial lines and laggy ssh sessions keep up, at the cost of some tearing
+    #[arg(long, value_name = "CELLS")]
+    pub max_cells_per_frame: Option<NonZero<usize>>,
+
+    /// Keep what gets written to the terminal under RATE bytes a second, like 200k,
+    /// by skipping frames and drawing with fewer colors and smaller when that isn't enough
+    #[arg(long, value_name = "RATE")]
+    pub max_bandwidth: Option<terminal_sink::Bandwidth>,
+
+    /// Draw the frame rates, dropped frames, frame sizes and latency over the video,
+    /// F2 turns them on and off while playing
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Show the position and duration in CORNER of the video, over a seek bar along its bottom
+    /// row, t turns it on and off while playing
+    #[arg(
+        long,
+        value_name = "CORNER",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "bottom-right"
+    )]
+    pub show_time: Option<terminal_sink::Corner>,
+}
+
+/// The sound, which only playing on the terminal has
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "Audio")]
+pub struct AudioArgs {
+    /// Play without sound
+    #[arg(long)]
+    pub no_audio: bool,
+
+    /// Start at PERCENT volume, instead of where the last run left it
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u32).range(0..=200))]
+    pub volume: Option<u32>,
+
+    /// Run the audio through an equalizer, starting with PRESET instead of where the last run
+    /// left it, E switches presets while playing
+    #[arg(long, value_name = "PRESET")]
+    pub equalizer: Option<audio_sink::EqPreset>,
+
+    /// Start the equalizer at GAINS, dB for each of the 10 bands like 3:2:0:-2,
+    /// or at a preset defined with --eq-preset
+    #[arg(long, value_name = "GAINS")]
+    pub eq: Option<String>,
+
+    /// Define an equalizer preset for --eq, best kept in an [eq-preset] table of the config file
+    #[arg(long, value_name = "NAME=GAINS", value_parser = audio_sink::parse_named_gains)]
+    pub eq_preset: Vec<(String, audio_sink::EqGains)>,
+
+    /// Run the sound through DESCRIPTION, e.g. "audioecho delay=250000000 intensity=0.3"
+    #[arg(long, value_name = "DESCRIPTION")]
+    pub af: Option<String>,
+
+    /// Mix the stereo channels a little for more natural sound on headphones
+    #[arg(long)]
+    pub crossfeed: bool,
+
+    /// Send the sound to DESCRIPTION instead of the default output, e.g. "pulsesink device=X"
+    /// or "jackaudiosink port-pattern=..."
+    #[arg(long, value_name = "DESCRIPTION")]
+    pub audio_sink: Option<String>,
+}
+
+/// Controlling playback while it goes, from the keyboard or from other programs
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "Controls")]
+pub struct SessionArgs {
+    /// How many seconds the left and right keys seek by
+    #[arg(long, value_name = "SECS", default_value_t = 5)]
+    pub seek_step: u32,
+
+    /// Where the s key saves the frame on screen as a png
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub screenshot_dir: PathBuf,
+
+    /// Where the e key exports the part marked with { and } to
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub clip_dir: PathBuf,
+
+    /// Take commands as lines of json on a unix socket at PATH, like mpv's --input-ipc-server
+    #[cfg(all(unix, feature = "ipc"))]
+    #[arg(long, value_name = "PATH")]
+    pub ipc_socket: Option<PathBuf>,
+
+    /// Print a line of json on stdout for every change to playback and every second of it,
+    /// the video going to /dev/tty instead
+    #[cfg(feature = "serde")]
+    #[arg(long)]
+    pub status_json: bool,
+}
+
+/// The terminal the video gets drawn on, which neither serve nor transcode have
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "Terminal")]
+pub struct TerminalArgs {
+    /// How to drive the terminal
+    #[arg(long, value_name = "BACKEND", default_value_t, value_enum)]
+    pub terminal_backend: terminal::Backend,
+
+    /// Draw the video on the terminal at PATH, such as /dev/pts/3, reading keys from this one
+    #[cfg(unix)]
+    #[arg(long, value_name = "PATH")]
+    pub tty: Option<PathBuf>,
+
+    /// Draw the video in ROWS rows from the prompt down instead of taking over the screen,
+    /// the last frame is left behind in the scrollback
+    #[cfg(unix)]
+    #[arg(long, value_name = "ROWS", conflicts_with = "tty")]
+    pub inline: Option<NonZero<u16>>,
+
+    /// Pause while the terminal doesn't have focus, and play again once it's back
+    #[arg(long)]
+    pub pause_on_unfocus: bool,
+
+    /// Play back a dump made with the transcode subcommand, no decoding involved
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["videos", "recent", "from_history", "pipeline", "export_clip"]
+    )]
+    pub play_ansi: Option<PathBuf>,
+
+    /// Record everything drawn to FILE as an asciinema cast, to replay or embed later
+    #[arg(long, value_name = "FILE")]
+    pub record_cast: Option<PathBuf>,
+
+    /// How often to check the terminal size while paused, in milliseconds, at least 1
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 280,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub resize_poll: u64,
+
+    /// How long the terminal size has to stay the same before redrawing, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 100)]
+    pub resize_debounce: u64,
+}
+
+/// Finding out what went wrong
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "Debugging")]
+pub struct DebugArgs {
+    /// Log at LEVEL and up, to --log-file
+    #[arg(long, value_name = "LEVEL", value_enum)]
+    pub log_level: Option<logging::LogLevel>,
+
+    /// Where the log goes instead of the state directory's video-less.log,
+    /// GStreamer's own debug output included
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// Log every second how far the frames getting onto the terminal are from the clock
+    /// the sound plays by, and with correct hand them over earlier when the terminal keeps falling behind
+    #[arg(
+        long,
+        value_name = "MODE",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "log"
+    )]
+    pub sync_debug: Option<terminal_sink::SyncDebug>,
+
+    /// Print which decoders got used once playback is over
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Write a graph of the pipeline into DIR every time it changes state or fails,
+    /// and where F12 puts one, for bug reports about files that don't play
+    #[arg(long, value_name = "DIR")]
+    pub dump_dot: Option<PathBuf>,
+
+    /// Write every rendered cell grid to FILE as JSON lines
+    #[cfg(feature = "serde")]
+    #[arg(long, value_name = "FILE")]
+    pub dump_grid: Option<PathBuf>,
+}
@@ -0,0 +1,155 @@
+//! Cutting the part of a file between two points out into a file of its own, marked with the
+//! `{` and `}` keys and exported with `e`, or given with `--start` and `--end` to `--export-clip`,
+//! by a pipeline of its own so playing goes on meanwhile
+use crate::encode;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use video_less::error::PlayerError;
+use video_less::gstreamer_element;
+use video_less::terminal_sink::LiveSettings;
+
+/// how long what became of an export stays on screen
+const SHOWN_FOR: Duration = Duration::from_secs(3);
+
+/// How the streams of a clip get written
+#[derive(clap::ValueEnum, Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ExportMode {
+    /// decode and encode again as h264 and opus, starting on the exact frame marked
+    #[default]
+    Reencode,
+    /// keep the streams as they are, starting at the keyframe before the frame marked
+    Copy,
+}
+
+/// Where a clip goes from and to, the end of the file when there's no end
+pub type Range = (gst::ClockTime, Option<gst::ClockTime>);
+
+/// the clip between `start` and `end`, from the start or to the end of the file when one isn't marked,
+/// `None` when neither is or the end comes first
+pub fn range(start: Option<gst::ClockTime>, end: Option<gst::ClockTime>) -> Option<Range> {
+    if start.is_none() && end.is_none() {
+        return None;
+    }
+    let start = start.unwrap_or(gst::ClockTime::ZERO);
+    match end {
+        Some(end) if end <= start => None,
+        end => Some((start, end)),
+    }
+}
+
+/// `<stem>-<start>-<end>.mkv` in `dir`, the times in milliseconds
+pub fn output_path(dir: &Path, video: &Path, (start, end): Range) -> PathBuf {
+    let stem = video
+        .file_stem()
+        .map_or_else(|| "clip".into(), |stem| stem.to_string_lossy());
+    let end = end.map_or_else(|| "end".to_string(), |end| end.mseconds().to_string());
+    dir.join(format!("{stem}-{}-{end}.mkv", start.mseconds()))
+}
+
+/// the elements a stream of `media_type` goes through on the way to the muxer,
+/// `None` for streams the clip leaves out
+fn branch(media_type: &str, mode: ExportMode) -> Result<Option<Vec<gst::Element>>, PlayerError> {
+    let names: &[&str] = match mode {
+        ExportMode::Copy
+            if media_type.starts_with("video/") || media_type.starts_with("audio/") =>
+        {
+            &["queue"]
+        }
+        ExportMode::Reencode if media_type.starts_with("video/") => {
+            &["queue", "videoconvert", "x264enc"]
+        }
+        ExportMode::Reencode if media_type.starts_with("audio/") => {
+            &["queue", "audioconvert", "audioresample", "opusenc"]
+        }
+        _ => return Ok(None),
+    };
+    names
+        .iter()
+        .map(|name| gstreamer_element(name))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+/// writes `range` of `video` to `output`, returning once it's all there
+pub fn export(
+    video: &Path,
+    range: Range,
+    output: &Path,
+    mode: ExportMode,
+) -> Result<(), PlayerError> {
+    let demux = match mode {
+        ExportMode::Reencode => "decodebin",
+        ExportMode::Copy => "parsebin",
+    };
+    let branch = Box::new(move |caps: &gst::CapsRef| {
+        let media_type = caps
+            .structure(0)
+            .map(|structure| structure.name().to_string())
+            .unwrap_or_default();
+        branch(&media_type, mode)
+    });
+    let pipeline = encode::build(video, output, demux, branch)?;
+    encode::run(&pipeline, output, Some(range))
+}
+
+/// exports the part between the marks of `video` into `dir` off the bus thread,
+/// then says where it went, `None` for what isn't a file
+pub fn spawn(
+    video: Option<PathBuf>,
+    (start, end): (Option<gst::ClockTime>, Option<gst::ClockTime>),
+    dir: PathBuf,
+    mode: ExportMode,
+    settings: Arc<LiveSettings>,
+) {
+    let Some(video) = video else {
+        settings.notify("only files have clips to cut out".to_string(), SHOWN_FOR);
+        return;
+    };
+    let Some(range) = range(start, end) else {
+        let message = "mark where the clip starts with { and where it ends with } first";
+        settings.notify(message.to_string(), SHOWN_FOR);
+        return;
+    };
+
+    std::thread::spawn(move || {
+        let output = output_path(&dir, &video, range);
+        settings.notify("exporting the clip".to_string(), SHOWN_FOR);
+        let message = match std::fs::create_dir_all(&dir)
+            .map_err(|err| PlayerError::Open {
+                path: dir.clone(),
+                source: err,
+            })
+            .and_then(|()| export(&video, range, &output, mode))
+        {
+            Ok(()) => format!("exported {}", output.display()),
+            Err(err) => format!("couldn't export the clip: {err}"),
+        };
+        settings.notify(message, SHOWN_FOR);
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        let at = gst::ClockTime::from_seconds;
+        assert_eq!(range(None, None), None);
+        assert_eq!(range(Some(at(5)), None), Some((at(5), None)));
+        assert_eq!(range(None, Some(at(5))), Some((at(0), Some(at(5)))));
+        assert_eq!(range(Some(at(5)), Some(at(2))), None);
+    }
+
+    #[test]
+    fn output_names() {
+        let at = gst::ClockTime::from_mseconds;
+        let path = output_path(
+            Path::new("clips"),
+            Path::new("/videos/demo.mp4"),
+            (at(1500), Some(at(4000))),
+        );
+        assert_eq!(path, Path::new("clips/demo-1500-4000.mkv"));
+    }
+}
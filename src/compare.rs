@@ -0,0 +1,246 @@
+//! `videoplayer compare`, two videos in one pipeline so they play by the same clock, drawn over
+//! the same part of the terminal with the first one left of a divider and the second right of it,
+//! the arrow keys moving the divider, for comparing encodes of the same video side by side
+use crate::wall::{self, RESIZE_POLL, Update};
+use gst::prelude::ElementExt;
+use parking_lot::Mutex;
+use std::io::Write;
+use std::num::NonZero;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use video_less::cancel::CancellationToken;
+use video_less::error::PlayerError;
+use video_less::terminal::{self, Key};
+use video_less::terminal_sink::CellAspect;
+use video_less::terminal_sink::filters::ChainSpec;
+use video_less::{RenderTarget, TerminalRenderer, terminal_sink};
+
+/// how many columns the arrow keys move the divider
+const STEP: u16 = 2;
+
+/// the cells a frame `width` by `height` pixels takes up fitted into `size`,
+/// a cell being a pixel wide and `aspect` tall
+fn fit((width, height): (u32, u32), (columns, rows): (u16, u16), aspect: CellAspect) -> (u16, u16) {
+    if width == 0 || height == 0 {
+        return (0, 0);
+    }
+    let aspect = aspect.get();
+    let scale = f64::min(
+        f64::from(columns) / f64::from(width),
+        f64::from(rows) * aspect / f64::from(height),
+    );
+    let cells = |pixels: f64, most: u16| (pixels.round() as u16).clamp(1, most.max(1));
+    (
+        cells(f64::from(width) * scale, columns),
+        cells(f64::from(height) * scale / aspect, rows),
+    )
+}
+
+/// the pixels from `x` on, `width` of them, of a frame `pixels` wide that the columns `from..to`
+/// of the `columns` the video is show
+fn crop(pixels: u32, (from, to): (u16, u16), columns: u16) -> (u32, u32) {
+    let at = |column: u16| (u64::from(pixels) * u64::from(column) / u64::from(columns)) as u32;
+    (at(from), at(to) - at(from))
+}
+
+/// the size of the frame of `sample` in pixels
+fn frame_size(sample: &gst::Sample) -> Option<(u32, u32)> {
+    let structure = sample.caps()?.structure(0)?;
+    let width = structure.get::<i32>("width").ok()?;
+    let height = structure.get::<i32>("height").ok()?;
+    Some((u32::try_from(width).ok()?, u32::try_from(height).ok()?))
+}
+
+/// One of the two videos, its renderer cropping frames to the part of it that's shown
+struct Side {
+    renderer: TerminalRenderer,
+    last: Option<gst::Sample>,
+    /// the crop the renderer has, `x`, `width` and `height` in pixels
+    crop: Option<(u32, u32, u32)>,
+}
+
+impl Side {
+    /// draws the columns `from..to` of the `area` the video takes up
+    fn draw(
+        &mut self,
+        (origin, area): ((u16, u16), (u16, u16)),
+        (from, to): (u16, u16),
+        output: &mut impl Write,
+        fresh_redraw: bool,
+    ) -> std::io::Result<()> {
+        let Some(sample) = &self.last else {
+            return Ok(());
+        };
+        let Some((width, height)) = frame_size(sample) else {
+            return Ok(());
+        };
+        let (x, cropped) = crop(width, (from, to), area.0);
+        if cropped == 0 {
+            return Ok(());
+        }
+
+        if self.crop != Some((x, cropped, height)) {
+            let spec = format!("crop={cropped}:{height}:{x}:0")
+                .parse::<ChainSpec>()
+                .unwrap();
+            self.renderer.set_filters(spec.build());
+            self.crop = Some((x, cropped, height));
+        }
+        let mut target = RenderTarget {
+            origin: (origin.0 + from, origin.1),
+            size: (to - from, area.1),
+            writer: output,
+        };
+        wall::draw(&mut self.renderer, sample, &mut target, fresh_redraw)
+    }
+}
+
+/// plays `first` left of the divider and `second` right of it until q is pressed,
+/// fitted to cells `cell_aspect` times taller than wide
+pub fn play(
+    (first, second): (&Path, &Path),
+    palette: terminal_sink::Palette,
+    glyphs: terminal_sink::Glyphs,
+    cell_aspect: CellAspect,
+) -> Result<(), PlayerError> {
+    let (updates, received) = mpsc::channel();
+    let slots = [Arc::<Mutex<Option<gst::Sample>>>::default(), Arc::default()];
+    // one pipeline, one clock
+    let pipeline = gst::Pipeline::new();
+    for (index, video) in [first, second].into_iter().enumerate() {
+        let slot = Arc::clone(&slots[index]);
+        wall::add_video(&pipeline, video, index, slot, updates.clone())?;
+    }
+    wall::watch(&pipeline, 0, updates.clone());
+
+    let terminal::Terminal {
+        mut output,
+        restore,
+    } = terminal::take().map_err(PlayerError::Terminal)?;
+    let token = CancellationToken::default();
+    wall::forward_keys(token.clone(), updates);
+    let mut playing = true;
+    let _ = pipeline.set_state(gst::State::Playing);
+
+    let mut sides: [Side; 2] = std::array::from_fn(|_| {
+        let mut renderer = TerminalRenderer::new(false, NonZero::<usize>::MIN);
+        renderer.set_style(palette, glyphs);
+        Side {
+            renderer,
+            last: None,
+            crop: None,
+        }
+    });
+    let mut stopped = None::<String>;
+    // the column of the divider, from the left of the video, the middle until it's moved
+    let mut divider = None::<u16>;
+    let mut layout = None;
+    let res = loop {
+        let mut moved = false;
+        let updated = match received.recv_timeout(RESIZE_POLL) {
+            Ok(Update::Key(key)) if wall::quits(key) => break Ok(()),
+            Ok(Update::Key(Key::Left)) => {
+                divider = divider.map(|divider| divider.saturating_sub(STEP));
+                moved = true;
+                None
+            }
+            Ok(Update::Key(Key::Right)) => {
+                divider = divider.map(|divider| divider.saturating_add(STEP));
+                moved = true;
+                None
+            }
+            Ok(Update::Key(Key::Char(' '))) => {
+                playing = !playing;
+                let state = match playing {
+                    true => gst::State::Playing,
+                    false => gst::State::Paused,
+                };
+                let _ = pipeline.set_state(state);
+                None
+            }
+            Ok(Update::Key(_)) | Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break Ok(()),
+            Ok(Update::Frame(index)) => {
+                if let Some(sample) = slots[index].lock().take() {
+                    sides[index].last = Some(sample);
+                }
+                Some(index)
+            }
+            Ok(Update::Stopped(_, message)) => {
+                stopped = Some(message);
+                moved = true;
+                None
+            }
+        };
+
+        // the first video decides the shape the two of them get
+        let Some(frame) = sides[0].last.as_ref().and_then(frame_size) else {
+            continue;
+        };
+        let screen = terminal::size().unwrap_or((80, 24));
+        let area = fit(frame, screen, cell_aspect);
+        let origin = ((screen.0 - area.0) / 2, (screen.1 - area.1) / 2);
+        let divider = *divider.insert(divider.unwrap_or(area.0 / 2).min(area.0));
+        // a new layout draws everything from a clear screen, there's no telling what's left over
+        let redraw = layout.replace((origin, area, divider)) != Some((origin, area, divider));
+        if moved || redraw {
+            let _ = output.write_all(terminal::CLEAR.as_bytes());
+        }
+        let fresh_redraw = moved || redraw;
+
+        let [left, right] = &mut sides;
+        let mut res = Ok(());
+        if fresh_redraw || updated == Some(0) {
+            res = res
+                .and_then(|()| left.draw((origin, area), (0, divider), &mut output, fresh_redraw));
+        }
+        if fresh_redraw || updated == Some(1) {
+            let from = (divider + 1).min(area.0);
+            res = res.and_then(|()| {
+                right.draw((origin, area), (from, area.0), &mut output, fresh_redraw)
+            });
+        }
+        if fresh_redraw && divider < area.0 {
+            let line = (0..area.1)
+                .map(|row| terminal::goto(origin.0 + divider + 1, origin.1 + row + 1) + "\u{2502}")
+                .collect::<String>();
+            res = res.and_then(|()| write!(output, "\x1b[0m{line}"));
+        }
+        if fresh_redraw && let Some(message) = &stopped {
+            res = res
+                .and_then(|()| output.write_all(wall::caption(origin, area, message).as_bytes()));
+        }
+        if let Err(err) = res.and_then(|()| output.flush()) {
+            break Err(PlayerError::Terminal(err));
+        }
+    };
+
+    token.cancel();
+    let _ = pipeline.set_state(gst::State::Null);
+    drop(restore);
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fits_into_the_terminal() {
+        // 4:3 on 80x24 is as tall as the terminal, with a cell two pixels tall
+        let double = CellAspect::default();
+        assert_eq!(fit((640, 480), (80, 24), double), (64, 24));
+        assert_eq!(fit((1080, 1920), (80, 24), double), (27, 24));
+        // the same frame takes up fewer columns with squarer cells
+        let squarer = CellAspect::new(1.5).unwrap();
+        assert_eq!(fit((640, 480), (80, 24), squarer), (48, 24));
+    }
+
+    #[test]
+    fn crops_follow_the_columns() {
+        assert_eq!(crop(1920, (0, 40), 80), (0, 960));
+        assert_eq!(crop(1280, (41, 80), 80), (656, 624));
+        assert_eq!(crop(1920, (0, 0), 80), (0, 0));
+    }
+}
@@ -0,0 +1,256 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use video_less::error::PlayerError;
+
+/// `$XDG_CONFIG_HOME/video-less/config.toml`, or `~/.config/video-less/config.toml` when that isn't set,
+/// `%APPDATA%\video-less\config.toml` on windows
+fn default_file() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+
+    Some(base.join("video-less").join("config.toml"))
+}
+
+/// which config file the command line asks for, `None` for `--no-config`
+fn requested(args: &[OsString]) -> Option<(PathBuf, bool)> {
+    let mut args = args.iter().skip(1).take_while(|arg| *arg != "--");
+    let mut file = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "--no-config" {
+            return None;
+        }
+
+        if arg == "--config" {
+            file = args.next().map(PathBuf::from);
+        } else if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            file = Some(PathBuf::from(path));
+        }
+    }
+
+    match file {
+        Some(file) => Some((file, true)),
+        None => default_file().map(|file| (file, false)),
+    }
+}
+
+/// where in `args` the subcommand is and which one it is, the first argument that's neither
+/// an option nor the value of one, `None` when that isn't a subcommand
+fn subcommand<'a>(
+    args: &[OsString],
+    command: &'a clap::Command,
+) -> Option<(usize, &'a clap::Command)> {
+    // without a subcommand everything is an option of `play`, any of them could come first
+    let takes_value = |found: &dyn Fn(&clap::Arg) -> bool| {
+        command
+            .get_arguments()
+            .chain(
+                command
+                    .get_subcommands()
+                    .flat_map(clap::Command::get_arguments),
+            )
+            .any(|arg| found(arg) && arg.get_action().takes_values())
+    };
+
+    let mut index = 1;
+    while let Some(arg) = args.get(index) {
+        let arg = arg.to_str()?;
+        let skip_value = if arg == "--" {
+            return None;
+        } else if let Some(long) = arg.strip_prefix("--") {
+            !long.contains('=') && takes_value(&|found| found.get_long() == Some(long))
+        } else if let Some(short) = arg.strip_prefix('-')
+            && !short.is_empty()
+        {
+            let mut chars = short.chars();
+            let (short, rest) = (chars.next(), chars.next());
+            rest.is_none() && takes_value(&|found| found.get_short() == short)
+        } else {
+            return command.find_subcommand(arg).map(|found| (index, found));
+        };
+        index += 1 + usize::from(skip_value);
+    }
+    None
+}
+
+fn longs(command: &clap::Command) -> impl Iterator<Item = &str> {
+    command.get_arguments().filter_map(clap::Arg::get_long)
+}
+
+/// turns every `option = value` into the `--option=value` it stands for, leaving out the options
+/// only other subcommands than `target` take
+fn to_args(
+    contents: &str,
+    command: &clap::Command,
+    target: &clap::Command,
+) -> Result<Vec<OsString>, String> {
+    let table = contents
+        .parse::<toml::Table>()
+        .map_err(|err| err.to_string())?;
+
+    let mut args = vec![];
+    for (key, value) in table {
+        let known = longs(command)
+            .chain(command.get_subcommands().flat_map(longs))
+            .any(|long| long == key);
+        if !known || matches!(key.as_str(), "config" | "no-config" | "help") {
+            return Err(format!("unknown option `{key}`"));
+        }
+        if !longs(target).any(|long| long == key) {
+            continue;
+        }
+
+        let values = match value {
+            toml::Value::Array(values) => values,
+            // named things, such as `[eq-preset]` with a `name = "..."` per preset
+            toml::Value::Table(table) => table
+                .into_iter()
+                .map(|(name, value)| match value {
+                    toml::Value::String(value) => {
+                        Ok(toml::Value::String(format!("{name}={value}")))
+                    }
+                    _ => Err(format!("`{key}.{name}` has to be a string")),
+                })
+                .collect::<Result<_, _>>()?,
+            value => vec![value],
+        };
+
+        for value in values {
+            let value = match value {
+                toml::Value::Boolean(true) => {
+                    args.push(OsString::from(format!("--{key}")));
+                    continue;
+                }
+                toml::Value::Boolean(false) => continue,
+                toml::Value::String(value) => value,
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                _ => return Err(format!("`{key}` has to be a string, number or boolean")),
+            };
+            args.push(OsString::from(format!("--{key}={value}")));
+        }
+    }
+
+    Ok(args)
+}
+
+/// the command line with `play` filled in when it has no subcommand, and the options from
+/// the config file right behind the subcommand, so anything given on the command line wins
+pub fn args(command: &clap::Command) -> Result<Vec<OsString>, PlayerError> {
+    let mut args = std::env::args_os().collect::<Vec<_>>();
+
+    // a bare path plays it, only asking for help or the version is left to the top level
+    let only_help = args[1..]
+        .iter()
+        .all(|arg| matches!(arg.to_str(), Some("-h" | "--help" | "-V" | "--version")));
+    let at = match subcommand(&args, command) {
+        Some((at, _)) => at,
+        None if only_help => return Ok(args),
+        None => {
+            args.insert(1, OsString::from("play"));
+            1
+        }
+    };
+
+    let Some((file, explicit)) = requested(&args) else {
+        return Ok(args);
+    };
+
+    let contents = match std::fs::read_to_string(&file) {
+        Ok(contents) => contents,
+        // not having a config file is fine, unless it was asked for
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && !explicit => return Ok(args),
+        Err(err) => {
+            return Err(PlayerError::Config {
+                path: file,
+                message: err.to_string(),
+            });
+        }
+    };
+
+    // the defaults go behind the subcommand, the one that takes them
+    let target = args[at]
+        .to_str()
+        .and_then(|name| command.find_subcommand(name))
+        .unwrap_or(command);
+
+    let defaults = to_args(&contents, command, target).map_err(|message| PlayerError::Config {
+        path: file,
+        message,
+    })?;
+
+    let program = args.drain(..=at).collect::<Vec<_>>();
+    Ok(program.into_iter().chain(defaults).chain(args).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command() -> clap::Command {
+        clap::Command::new("test")
+            .arg(clap::Arg::new("seek-step").long("seek-step"))
+            .arg(
+                clap::Arg::new("no-audio")
+                    .long("no-audio")
+                    .action(clap::ArgAction::SetTrue),
+            )
+    }
+
+    #[test]
+    fn options_become_flags() {
+        let args = to_args("seek-step = 10\nno-audio = true\n", &command(), &command()).unwrap();
+        assert_eq!(args, ["--no-audio", "--seek-step=10"]);
+
+        let args = to_args("no-audio = false", &command(), &command()).unwrap();
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn tables_become_named_values() {
+        let command = command().arg(clap::Arg::new("eq-preset").long("eq-preset"));
+        let args = to_args("[eq-preset]\nnight = \"-3:-2\"\n", &command, &command).unwrap();
+        assert_eq!(args, ["--eq-preset=night=-3:-2"]);
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        assert!(to_args("volume = 3", &command(), &command()).is_err());
+    }
+
+    #[test]
+    fn subcommands_take_their_own_options() {
+        let preview = clap::Command::new("preview").arg(clap::Arg::new("at").long("at"));
+        let command = command().subcommand(preview.clone());
+
+        let args = to_args("seek-step = 10\nat = 5\n", &command, &preview).unwrap();
+        assert_eq!(args, ["--at=5"]);
+        let args = to_args("seek-step = 10\nat = 5\n", &command, &command).unwrap();
+        assert_eq!(args, ["--seek-step=10"]);
+    }
+
+    #[test]
+    fn the_subcommand_can_come_after_options() {
+        let config = clap::Arg::new("config").long("config");
+        let command = command().arg(config).subcommand(clap::Command::new("play"));
+        let at = |args: &[&str]| {
+            let args = args.iter().map(OsString::from).collect::<Vec<_>>();
+            subcommand(&args, &command).map(|(at, found)| (at, found.get_name().to_string()))
+        };
+
+        assert_eq!(at(&["vl", "play", "a.mp4"]), Some((1, "play".to_string())));
+        assert_eq!(
+            at(&["vl", "--config", "x.toml", "play"]),
+            Some((3, "play".to_string()))
+        );
+        assert_eq!(
+            at(&["vl", "--config=x.toml", "--no-audio", "play"]),
+            Some((3, "play".to_string()))
+        );
+        assert_eq!(at(&["vl", "--seek-step", "10", "a.mp4"]), None);
+        assert_eq!(at(&["vl", "--", "play"]), None);
+    }
+}
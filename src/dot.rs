@@ -0,0 +1,66 @@
+//! Graphviz snapshots of the pipeline, what `GST_DEBUG_BIN_TO_DOT_FILE` writes, taken on every state
+//! change and error with `--dump-dot` and whenever F12 gets pressed, for bug reports about files
+//! that don't play
+use gst::prelude::Cast;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use video_less::error::PlayerError;
+
+/// how long where F12 put the graph stays on screen
+pub const SHOWN_FOR: Duration = Duration::from_secs(3);
+
+/// `<unix milliseconds>-<reason>.dot` in `dir`, sorting in the order they were taken
+fn path(dir: &Path, reason: &str, at: SystemTime) -> PathBuf {
+    let millis = at
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis());
+    dir.join(format!("{millis}-{reason}.dot"))
+}
+
+/// writes the graph of `pipeline` as it is now into `dir`, returning where it went
+pub fn dump(pipeline: &gst::Pipeline, dir: &Path, reason: &str) -> Result<PathBuf, PlayerError> {
+    let open_error = |source| PlayerError::Open {
+        path: dir.to_path_buf(),
+        source,
+    };
+    std::fs::create_dir_all(dir).map_err(open_error)?;
+
+    let graph = gst::debug_bin_to_dot_data(pipeline, gst::DebugGraphDetails::all());
+    let path = path(dir, reason, SystemTime::now());
+    std::fs::write(&path, graph.as_bytes()).map_err(|source| PlayerError::Open {
+        path: path.clone(),
+        source,
+    })?;
+    tracing::debug!(path = %path.display(), "wrote the pipeline graph");
+    Ok(path)
+}
+
+/// takes a snapshot into `dir` when `message` is the pipeline changing state or an error
+pub fn on_message(pipeline: &gst::Pipeline, message: &gst::Message, dir: &Path) {
+    let reason = match message.view() {
+        gst::MessageView::StateChanged(change)
+            if change.src() == Some(pipeline.upcast_ref::<gst::Object>()) =>
+        {
+            format!("{:?}-to-{:?}", change.old(), change.current()).to_lowercase()
+        }
+        gst::MessageView::Error(_) => "error".to_string(),
+        _ => return,
+    };
+    if let Err(err) = dump(pipeline, dir, &reason) {
+        tracing::warn!(%err, "couldn't write the pipeline graph");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names_sort_by_time() {
+        let at = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        assert_eq!(
+            path(Path::new("dots"), "paused-to-playing", at),
+            Path::new("dots/1700000000123-paused-to-playing.dot")
+        );
+    }
+}
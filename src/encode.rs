@@ -0,0 +1,159 @@
+//! Pipelines writing what's in a file into a matroska file of its own, every stream going
+//! through whatever elements the caller picks for it, for clips and proxies
+use glib::object::Cast;
+use gst::prelude::{ElementExt, ElementExtManual, GstBinExtManual, GstObjectExt, PadExt};
+use std::path::Path;
+use video_less::error::PlayerError;
+use video_less::gstreamer_element;
+
+/// What a stream goes through on the way to the muxer, for the caps it came out with,
+/// `None` for streams left out
+pub type Branch =
+    dyn Fn(&gst::CapsRef) -> Result<Option<Vec<gst::Element>>, PlayerError> + Send + Sync;
+
+/// `video` decoded by `demux`, which is `decodebin` or `parsebin`, every stream through `branch`
+/// into a matroska file at `output`
+pub fn build(
+    video: &Path,
+    output: &Path,
+    demux: &str,
+    branch: Box<Branch>,
+) -> Result<gst::Pipeline, PlayerError> {
+    let source = gst::ElementFactory::make("filesrc")
+        .property("location", video)
+        .build()
+        .map_err(PlayerError::element("filesrc"))?;
+    let demux = gstreamer_element(demux)?;
+    let mux = gstreamer_element("matroskamux")?;
+    let sink = gst::ElementFactory::make("filesink")
+        .property("location", output)
+        .build()
+        .map_err(PlayerError::element("filesink"))?;
+
+    let pipeline = gst::Pipeline::new();
+    let link = PlayerError::link;
+    pipeline
+        .add_many([&source, &demux, &mux, &sink])
+        .map_err(link("the encoding elements"))?;
+    source
+        .link(&demux)
+        .map_err(link("the encoding source to its demuxer"))?;
+    mux.link(&sink)
+        .map_err(link("the encoding muxer to its file"))?;
+
+    demux.connect_pad_added(move |demux, src_pad| {
+        let caps = src_pad
+            .current_caps()
+            .unwrap_or_else(|| src_pad.query_caps(None));
+        let Some(bin) = demux
+            .parent()
+            .and_then(|parent| parent.downcast::<gst::Bin>().ok())
+        else {
+            return;
+        };
+
+        let linked = branch(&caps).and_then(|branch| {
+            // streams left out still have to go somewhere for the rest to flow
+            let (branch, muxed) = match branch {
+                Some(branch) => (branch, true),
+                None => (
+                    vec![
+                        gst::ElementFactory::make("fakesink")
+                            .property("sync", false)
+                            .property("async", false)
+                            .build()
+                            .map_err(PlayerError::element("fakesink"))?,
+                    ],
+                    false,
+                ),
+            };
+            bin.add_many(&branch).map_err(link("a stream"))?;
+            if branch.len() > 1 {
+                gst::Element::link_many(&branch).map_err(link("a stream"))?;
+            }
+            if muxed {
+                let last = branch.last().unwrap();
+                last.link(&mux).map_err(link("a stream to the muxer"))?;
+            }
+            for element in &branch {
+                let _ = element.sync_state_with_parent();
+            }
+            let entry = branch[0].static_pad("sink").unwrap();
+            src_pad.link(&entry).map_err(|err| PlayerError::Link {
+                what: "a stream",
+                source: glib::bool_error!("{err:?}"),
+            })?;
+            Ok(())
+        });
+
+        if let Err(err) = linked {
+            tracing::error!(%err, ?caps, "linking a stream to encode failed");
+            gst::element_error!(
+                demux,
+                gst::CoreError::Negotiation,
+                ("Failed to link a stream; {err}")
+            );
+        }
+    });
+
+    Ok(pipeline)
+}
+
+/// waits until `bus` posts a message of `kind`, or an error
+fn wait(bus: &gst::Bus, kind: gst::MessageType) -> Result<(), PlayerError> {
+    let message = bus
+        .timed_pop_filtered(gst::ClockTime::NONE, &[kind, gst::MessageType::Error])
+        .unwrap();
+    match message.view() {
+        gst::MessageView::Error(err) => Err(PlayerError::Playback {
+            element: err
+                .src()
+                .map(|s| s.path_string().to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            error: err.error(),
+            debug: err.debug().map(|debug| debug.to_string()),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// runs `pipeline` until everything went into `output`, or only what's between
+/// the start and end of `range`, the end of the file when there's no end
+pub fn run(
+    pipeline: &gst::Pipeline,
+    output: &Path,
+    range: Option<(gst::ClockTime, Option<gst::ClockTime>)>,
+) -> Result<(), PlayerError> {
+    let bus = pipeline.bus().expect("pipelines always have a bus");
+
+    let written = (|| {
+        let _ = pipeline.set_state(gst::State::Paused);
+        wait(&bus, gst::MessageType::AsyncDone)?;
+
+        if let Some((start, end)) = range {
+            let stop_type = match end {
+                Some(_) => gst::SeekType::Set,
+                None => gst::SeekType::None,
+            };
+            pipeline
+                .seek(
+                    1.0,
+                    gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                    gst::SeekType::Set,
+                    start,
+                    stop_type,
+                    end,
+                )
+                .map_err(|_| PlayerError::Export {
+                    output: output.to_path_buf(),
+                    reason: "the file can't be seeked in".to_string(),
+                })?;
+        }
+
+        let _ = pipeline.set_state(gst::State::Playing);
+        wait(&bus, gst::MessageType::Eos)
+    })();
+
+    let _ = pipeline.set_state(gst::State::Null);
+    written
+}
@@ -0,0 +1,157 @@
+//! What everything in here fails with
+use std::fmt;
+use std::path::PathBuf;
+
+/// what the player exits with when a file can't be played for lack of a plugin,
+/// `EX_UNAVAILABLE` from sysexits.h
+const MISSING_PLUGINS_EXIT_CODE: i32 = 69;
+
+/// Everything that makes the player give up, reported by `main` once the terminal is restored
+#[derive(Debug)]
+pub enum PlayerError {
+    /// a file on the command line couldn't be opened
+    Open {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// the plugin providing an element isn't installed
+    Element {
+        name: String,
+        source: glib::BoolError,
+    },
+    /// elements that should fit together didn't
+    Link {
+        what: &'static str,
+        source: glib::BoolError,
+    },
+    /// the terminal couldn't be switched to raw mode or the alternate screen
+    Terminal(std::io::Error),
+    /// another player holds the terminal
+    SeatTaken(PathBuf),
+    /// the config file couldn't be read or has options that don't exist
+    Config { path: PathBuf, message: String },
+    /// decoding needs plugins that aren't installed, `hint` says how to get them
+    MissingPlugins { video: PathBuf, hint: String },
+    /// a pipeline description given on the command line didn't parse
+    Description {
+        description: String,
+        source: glib::Error,
+    },
+    /// a file had no frame to show where one was asked for
+    NoFrame {
+        video: PathBuf,
+        position: gst::ClockTime,
+    },
+    /// the discoverer couldn't make sense of a file
+    Probe { video: PathBuf, source: glib::Error },
+    /// a clip couldn't be cut out of a file
+    Export { output: PathBuf, reason: String },
+    /// the pipeline stopped with an error while playing
+    Playback {
+        element: String,
+        error: glib::Error,
+        debug: Option<String>,
+    },
+}
+
+impl PlayerError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            PlayerError::MissingPlugins { .. } => MISSING_PLUGINS_EXIT_CODE,
+            _ => -1,
+        }
+    }
+
+    /// for [`Result::map_err`] on a freshly built element
+    pub fn element(name: &str) -> impl FnOnce(glib::BoolError) -> Self {
+        move |source| PlayerError::Element {
+            name: name.to_string(),
+            source,
+        }
+    }
+
+    /// for [`Result::map_err`] on parsing a pipeline description
+    pub fn description(description: &str) -> impl FnOnce(glib::Error) -> Self {
+        move |source| PlayerError::Description {
+            description: description.to_string(),
+            source,
+        }
+    }
+
+    /// for [`Result::map_err`] on opening or creating the file at `path`
+    pub fn open(path: &std::path::Path) -> impl FnOnce(std::io::Error) -> Self {
+        let path = path.to_path_buf();
+        move |source| PlayerError::Open { path, source }
+    }
+
+    /// for [`Result::map_err`] on adding and linking elements
+    pub fn link(what: &'static str) -> impl FnOnce(glib::BoolError) -> Self {
+        move |source| PlayerError::Link { what, source }
+    }
+}
+
+impl fmt::Display for PlayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayerError::Open { path, source } => {
+                write!(f, "couldn't open file {}: {source}", path.display())
+            }
+            PlayerError::Element { name, source } => {
+                write!(f, "couldn't create the {name} element: {source}")
+            }
+            PlayerError::Link { what, source } => write!(f, "couldn't link {what}: {source}"),
+            PlayerError::Terminal(err) => write!(f, "couldn't set up the terminal: {err}"),
+            PlayerError::SeatTaken(device) => write!(
+                f,
+                "another player is already running on {}",
+                device.display()
+            ),
+            PlayerError::Config { path, message } => {
+                write!(f, "bad config file {}: {message}", path.display())
+            }
+            PlayerError::MissingPlugins { video, hint } => {
+                write!(f, "can't play {}\n{hint}", video.display())
+            }
+            PlayerError::Description {
+                description,
+                source,
+            } => write!(f, "couldn't parse {description:?}: {source}"),
+            PlayerError::NoFrame { video, position } => {
+                write!(f, "{} has no frame at {position}", video.display())
+            }
+            PlayerError::Probe { video, source } => {
+                write!(f, "couldn't probe {}: {source}", video.display())
+            }
+            PlayerError::Export { output, reason } => {
+                write!(f, "couldn't export {}: {reason}", output.display())
+            }
+            PlayerError::Playback {
+                element,
+                error,
+                debug,
+            } => {
+                writeln!(f, "Error received from element {element:?}: {error}")?;
+                write!(f, "Debugging information: {debug:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlayerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PlayerError::Open { source, .. } => Some(source),
+            PlayerError::Element { source, .. } | PlayerError::Link { source, .. } => Some(source),
+            PlayerError::Terminal(err) => Some(err),
+            PlayerError::Description { source, .. } | PlayerError::Probe { source, .. } => {
+                Some(source)
+            }
+            PlayerError::Playback { error, .. } => Some(error),
+            PlayerError::SeatTaken(_)
+            | PlayerError::Config { .. }
+            | PlayerError::MissingPlugins { .. }
+            | PlayerError::NoFrame { .. }
+            | PlayerError::Export { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! Files the player keeps between runs
+use std::path::Path;
+
+/// writes `contents` to a temporary file next to `file` and moves it over, creating the
+/// directory first, so quitting or crashing halfway never leaves half of it behind
+pub fn write_atomic(file: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let temp = file.with_extension("tmp");
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(temp, file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::write_atomic;
+
+    #[test]
+    fn replaces_the_whole_file() {
+        let dir = std::env::temp_dir().join(format!("video-less-fs-{}", std::process::id()));
+        let file = dir.join("state").join("volume");
+
+        write_atomic(&file, "0.5 muted\n").unwrap();
+        write_atomic(&file, "1\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "1\n");
+        assert!(!file.with_extension("tmp").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
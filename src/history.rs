@@ -0,0 +1,180 @@
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// how many files the history remembers
+const MAX_ENTRIES: usize = 200;
+
+/// `$XDG_STATE_HOME/video-less`, or `~/.local/state/video-less` when that isn't set,
+/// `%LOCALAPPDATA%\video-less` on windows
+pub fn state_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+
+    Some(base.join("video-less"))
+}
+
+pub struct Entry {
+    pub played_at: SystemTime,
+    pub path: PathBuf,
+}
+
+/// Recently played files, stored oldest first as `<unix seconds>\t<path>` lines
+pub struct History {
+    file: Option<PathBuf>,
+    entries: Vec<Entry>,
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl History {
+    pub fn load() -> Self {
+        let file = state_dir().map(|dir| dir.join("history"));
+
+        let entries = file
+            .as_ref()
+            .and_then(|file| std::fs::read_to_string(file).ok())
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (secs, path) = line.split_once('\t')?;
+                let secs = secs.parse().ok()?;
+                Some(Entry {
+                    played_at: SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                    path: PathBuf::from(path),
+                })
+            })
+            .collect();
+
+        Self { file, entries }
+    }
+
+    /// newest first
+    pub fn recent(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().rev()
+    }
+
+    /// the `n`th most recently played file, counting from 1
+    pub fn nth(&self, n: usize) -> Option<&Path> {
+        self.recent()
+            .nth(n.checked_sub(1)?)
+            .map(|entry| entry.path.as_path())
+    }
+
+    /// the newest entry that isn't `current`
+    pub fn before(&self, current: &Path) -> Option<&Path> {
+        let current = absolute(current);
+        self.recent()
+            .find(|entry| entry.path != current)
+            .map(|entry| entry.path.as_path())
+    }
+
+    /// moves `path` to the top of the history and saves it, failing to save is not worth stopping playback for
+    pub fn record(&mut self, path: &Path) {
+        let path = absolute(path);
+        self.entries.retain(|entry| entry.path != path);
+        self.entries.push(Entry {
+            played_at: SystemTime::now(),
+            path,
+        });
+
+        let excess = self.entries.len().saturating_sub(MAX_ENTRIES);
+        self.entries.drain(..excess);
+
+        let _ = self.save();
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let Some(ref file) = self.file else {
+            return Ok(());
+        };
+
+        let mut contents = String::new();
+        for entry in &self.entries {
+            // the format is line based, so these paths can't be stored
+            let Some(path) = entry.path.to_str().filter(|path| !path.contains('\n')) else {
+                continue;
+            };
+
+            let secs = entry
+                .played_at
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let _ = writeln!(contents, "{secs}\t{path}");
+        }
+
+        crate::fs_util::write_atomic(file, contents)
+    }
+}
+
+fn ago(time: SystemTime) -> String {
+    let secs = SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default()
+        .as_secs();
+
+    match secs {
+        ..60 => "just now".to_string(),
+        ..3600 => format!("{} min ago", secs / 60),
+        ..86400 => format!("{} h ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
+
+/// `entries` a line each, numbered the way `--from-history` takes them
+fn listing(entries: &[&Entry]) -> String {
+    let mut listing = String::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let _ = writeln!(
+            listing,
+            "{:>3}  {:<12}  {}",
+            i + 1,
+            ago(entry.played_at),
+            entry.path.display()
+        );
+    }
+    listing
+}
+
+/// `videoplayer history`, printing the `count` most recently played files
+pub fn print(history: &History, count: usize) {
+    let entries = history.recent().take(count).collect::<Vec<_>>();
+    if entries.is_empty() {
+        eprintln!("nothing has been played yet");
+        return;
+    }
+    print!("{}", listing(&entries));
+}
+
+/// lists the history and asks which file to play, before the terminal gets taken over
+pub fn pick_recent(history: &History) -> Option<PathBuf> {
+    let entries = history.recent().take(20).collect::<Vec<_>>();
+    if entries.is_empty() {
+        eprintln!("nothing has been played yet");
+        return None;
+    }
+
+    let mut stderr = std::io::stderr().lock();
+    let _ = write!(stderr, "{}", listing(&entries));
+    let _ = write!(stderr, "play which? [1] ");
+    let _ = stderr.flush();
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer).ok()?;
+
+    let pick = match answer.trim() {
+        "" => 1,
+        answer => answer.parse::<usize>().ok()?,
+    };
+
+    entries
+        .get(pick.checked_sub(1)?)
+        .map(|entry| entry.path.clone())
+}
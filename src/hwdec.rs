@@ -0,0 +1,71 @@
+use gst::prelude::{GstObjectExt, PluginFeatureExt};
+use video_less::gstreamer_element;
+
+/// Which hardware video decoders the pipeline should prefer
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HwDec {
+    /// any hardware decoder that is installed
+    #[default]
+    Auto,
+    /// VA-API, on Linux
+    Vaapi,
+    /// NVDEC, on NVIDIA GPUs
+    Nvdec,
+    /// VideoToolbox, on macOS
+    Videotoolbox,
+    /// software decoding only
+    Off,
+}
+
+fn is_hardware_decoder(factory: &gst::ElementFactory) -> bool {
+    factory
+        .metadata(gst::ELEMENT_METADATA_KLASS)
+        .is_some_and(|klass| klass.contains("Decoder") && klass.contains("Hardware"))
+}
+
+impl HwDec {
+    fn matches(self, factory: &gst::ElementFactory) -> bool {
+        let name = factory.name();
+        match self {
+            HwDec::Auto => true,
+            // both the va plugin (vah264dec) and the older vaapi one (vaapih264dec)
+            HwDec::Vaapi => name.starts_with("va"),
+            HwDec::Nvdec => name.starts_with("nv"),
+            HwDec::Videotoolbox => name.starts_with("vtdec"),
+            HwDec::Off => false,
+        }
+    }
+
+    /// reranks the hardware video decoders so decodebin picks them, or never picks them when off,
+    /// has to happen before any pipeline gets built
+    pub fn apply(self) {
+        let decoders = gst::ElementFactory::factories_with_type(
+            gst::ElementFactoryType::DECODER | gst::ElementFactoryType::MEDIA_VIDEO,
+            gst::Rank::NONE,
+        );
+
+        for factory in decoders
+            .iter()
+            .filter(|factory| is_hardware_decoder(factory))
+        {
+            match self {
+                HwDec::Off => factory.set_rank(gst::Rank::NONE),
+                backend if backend.matches(factory) => factory.set_rank(gst::Rank::PRIMARY + 1),
+                _ => {}
+            }
+        }
+    }
+
+    /// the element copying frames out of gpu memory right after decoding, if the backend needs one
+    pub fn download(self) -> Option<gst::Element> {
+        let name = match self {
+            // also converts to one of the sink's formats on the gpu
+            HwDec::Vaapi => "vapostproc",
+            HwDec::Nvdec => "cudadownload",
+            // the rest hand over system memory as long as downstream asks for it
+            HwDec::Auto | HwDec::Videotoolbox | HwDec::Off => return None,
+        };
+
+        gstreamer_element(name).ok()
+    }
+}
@@ -0,0 +1,104 @@
+//! Still images and animated GIFs, which decode to one frame or a short loop
+//! rather than to a video that ends on its own
+use glib::object::ObjectExt;
+use std::io::Read;
+use std::num::NonZero;
+use std::path::Path;
+use video_less::error::PlayerError;
+use video_less::gstreamer_element;
+
+/// frames per second of a still, it never changes so this only decides how soon it can end
+const STILL_FPS: i32 = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    /// shown until quit, or for `--image-duration`
+    Still,
+    /// played round and round
+    Animated,
+}
+
+/// what kind of image `video` is going by its extension, `None` for anything else,
+/// webp being either so those get a look inside
+pub fn kind(video: &Path) -> Option<Kind> {
+    let extension = video.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "png" | "jpg" | "jpeg" | "bmp" | "tif" | "tiff" => Some(Kind::Still),
+        "gif" => Some(Kind::Animated),
+        "webp" => {
+            let mut header = [0; WEBP_HEADER];
+            let read = std::fs::File::open(video).and_then(|mut file| file.read(&mut header));
+            Some(webp_kind(&header[..read.unwrap_or(0)]))
+        }
+        _ => None,
+    }
+}
+
+/// the bytes of a webp up to the flags of its extended header
+const WEBP_HEADER: usize = 21;
+
+/// whether the webp starting with `header` is animated, which only the flags of the extended
+/// format can say, anything else is a still
+fn webp_kind(header: &[u8]) -> Kind {
+    const ANIMATION: u8 = 0x02;
+    let animated = header.len() == WEBP_HEADER
+        && &header[..4] == b"RIFF"
+        && &header[8..16] == b"WEBPVP8X"
+        && header[20] & ANIMATION != 0;
+    match animated {
+        true => Kind::Animated,
+        false => Kind::Still,
+    }
+}
+
+/// an imagefreeze repeating the one frame of a still, and a capsfilter keeping it slow,
+/// ending after `duration` if there is one
+pub fn freeze(duration: Option<NonZero<u32>>) -> Result<[gst::Element; 2], PlayerError> {
+    let buffers = duration.map_or(-1, |secs| {
+        i32::try_from(secs.get())
+            .unwrap_or(i32::MAX)
+            .saturating_mul(STILL_FPS)
+    });
+    let freeze = gst::ElementFactory::make("imagefreeze")
+        .property("num-buffers", buffers)
+        .build()
+        .map_err(PlayerError::element("imagefreeze"))?;
+
+    let rate = gstreamer_element("capsfilter")?;
+    rate.set_property(
+        "caps",
+        gst::Caps::builder("video/x-raw")
+            .field("framerate", gst::Fraction::new(STILL_FPS, 1))
+            .build(),
+    );
+
+    Ok([freeze, rate])
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Kind, kind, webp_kind};
+    use std::path::Path;
+
+    #[test]
+    fn kinds_by_extension() {
+        assert_eq!(kind(Path::new("cat.PNG")), Some(Kind::Still));
+        assert_eq!(kind(Path::new("photos/cat.jpeg")), Some(Kind::Still));
+        assert_eq!(kind(Path::new("dance.gif")), Some(Kind::Animated));
+        assert_eq!(kind(Path::new("movie.mkv")), None);
+        assert_eq!(kind(Path::new("README")), None);
+    }
+
+    #[test]
+    fn animated_webps_play_round() {
+        let header = |flags: u8| {
+            let mut header = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0".to_vec();
+            header.push(flags);
+            header
+        };
+        assert_eq!(webp_kind(&header(0x02)), Kind::Animated);
+        assert_eq!(webp_kind(&header(0x10)), Kind::Still);
+        assert_eq!(webp_kind(b"RIFF\0\0\0\0WEBPVP8 "), Kind::Still);
+        assert_eq!(kind(Path::new("missing.webp")), Some(Kind::Still));
+    }
+}
@@ -1,11 +1,19 @@
+use crate::media_info::MediaInfo;
+use crate::seek_preview::Previewer;
 use glib::WeakRef;
-use gst::message::Eos;
 use gst::prelude::{ElementExt, ElementExtManual};
 use gst::{Bus, Pipeline, State};
+use parking_lot::Mutex;
 use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use termion::event::Key;
-use termion::input::TermRead;
+use std::time::{Duration, Instant};
+use video_less::QuitHandler;
+use video_less::cancel::CancellationToken;
+use video_less::terminal::{self, Event, Key, Mouse, MouseAction};
+use video_less::terminal_sink::LiveSettings;
 
 fn seek_error_to_bus<T>(bus: &Bus, result: Result<T, impl Display>) -> Option<T> {
     match result {
@@ -18,78 +26,665 @@ fn seek_error_to_bus<T>(bus: &Bus, result: Result<T, impl Display>) -> Option<T>
     }
 }
 
+/// returns whether the seek went out
 fn seek_absolute(
     pipeline: &Pipeline,
     bus: &Bus,
     new_position: gst::ClockTime,
     flags: gst::SeekFlags,
-) {
+) -> bool {
     let result = pipeline.seek_simple(flags, new_position);
 
-    seek_error_to_bus(bus, result);
+    seek_error_to_bus(bus, result).is_some()
+}
+
+/// `offset` nanoseconds away from `position`
+fn offset_from(position: gst::ClockTime, offset: i64) -> gst::ClockTime {
+    let seek_offset = gst::ClockTime::from_nseconds(offset.unsigned_abs());
+
+    match offset {
+        0.. => position.saturating_add(seek_offset),
+        ..0 => position.saturating_sub(seek_offset),
+    }
+}
+
+/// `fraction` of the way into a file `duration` long, nowhere when that isn't known
+fn fraction_of_duration(duration: Option<gst::ClockTime>, fraction: f64) -> Option<gst::ClockTime> {
+    let position = (duration?.nseconds() as f64 * fraction.clamp(0.0, 1.0)) as u64;
+    Some(gst::ClockTime::from_nseconds(position))
 }
 
-fn seek_relative(pipeline: &Pipeline, bus: &Bus, offset: i8) {
-    if let Some(current_position) = pipeline.query_position::<gst::ClockTime>() {
-        let seek_offset = gst::ClockTime::from_seconds(offset.unsigned_abs().into());
+/// how long a seek gets to finish before the next one goes out anyway
+const SEEK_DEBOUNCE: Duration = Duration::from_millis(250);
+/// how long the position a clip got marked at stays on screen
+const MARK_SHOWN_FOR: Duration = Duration::from_secs(2);
+/// how long the preset the equalizer switched to stays over the video
+const EQUALIZER_SHOWN_FOR: Duration = Duration::from_secs(2);
 
-        let new_position = match offset {
-            0.. => current_position.saturating_add(seek_offset),
-            ..0 => current_position.saturating_sub(seek_offset),
+#[derive(Debug, Copy, Clone)]
+enum SeekTarget {
+    /// seconds from where the file is at, or where it's already seeking to
+    Relative(i64),
+    Absolute(gst::ClockTime),
+    Fraction(f64),
+}
+
+/// Seeks asked for while the pipeline is still flushing for an earlier one,
+/// so holding an arrow key doesn't bury it in flushing seeks
+#[derive(Debug, Default)]
+struct SeekQueue {
+    /// when the seek the pipeline is busy with went out, and where to
+    in_flight: Option<(Instant, gst::ClockTime)>,
+    /// the latest seek asked for since, standing in for all the ones before it
+    pending: Option<(gst::ClockTime, gst::SeekFlags)>,
+}
+
+/// Where a seek goes and whether it goes out right away
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct PlannedSeek {
+    position: gst::ClockTime,
+    flags: gst::SeekFlags,
+    /// waits for the seek in flight, which got out too recently
+    queued: bool,
+}
+
+impl SeekQueue {
+    /// plans a seek to `target` asked for at `now`, with the pipeline at `playing_at` and
+    /// `duration` long, relative ones count from where the seeks before head instead
+    fn plan(
+        &self,
+        now: Instant,
+        target: SeekTarget,
+        playing_at: Option<gst::ClockTime>,
+        duration: Option<gst::ClockTime>,
+    ) -> Option<PlannedSeek> {
+        // mid seek the position the pipeline reports is the one it's leaving
+        let headed_for = self
+            .pending
+            .map(|(position, _)| position)
+            .or(self.in_flight.map(|(_, position)| position));
+        let accurate = gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE;
+        let (position, flags) = match target {
+            SeekTarget::Relative(offset) => {
+                (offset_from(headed_for.or(playing_at)?, offset), accurate)
+            }
+            SeekTarget::Absolute(position) => (position, accurate),
+            SeekTarget::Fraction(fraction) => (
+                fraction_of_duration(duration, fraction)?,
+                gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT,
+            ),
         };
 
-        seek_absolute(
-            pipeline,
-            bus,
-            new_position,
-            gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
-        )
+        let queued = self
+            .in_flight
+            .is_some_and(|(since, _)| now.saturating_duration_since(since) < SEEK_DEBOUNCE);
+        Some(PlannedSeek {
+            position,
+            flags,
+            queued,
+        })
     }
 }
 
-fn play_controls(bus: &WeakRef<Bus>, pipeline: &WeakRef<Pipeline>) {
-    let event_stream = std::io::stdin()
-        .lock()
-        .keys()
-        .map_while(Result::ok)
-        .map_while(|event| {
-            pipeline
-                .upgrade()
-                .and_then(|pipe| Some((pipe, bus.upgrade()?)))
-                .filter(|(pipeline, _)| pipeline.current_state() != State::Null)
-                .map(|(pipeline, bus)| (event, pipeline, bus))
+/// name of the application message posted on the bus when the user wants to quit
+pub const QUIT: &str = "video-less-quit";
+
+/// name of the application message posted on the bus to skip to the next file, which unlike
+/// an EOS doesn't look like the file ending to anything that loops it
+pub const NEXT: &str = "video-less-next";
+
+/// name of the application message posted on the bus to go back to the previous file
+pub const PREVIOUS: &str = "video-less-previous";
+
+/// name of the application message posted on the bus to play the file in its `path` field next
+pub const LOAD: &str = "video-less-load";
+
+/// name of the application message posted on the bus to export the part of the file between
+/// its `start` and `end` fields, in nanoseconds and each left out when it wasn't marked
+pub const EXPORT_CLIP: &str = "video-less-export-clip";
+
+/// name of the application message posted on the bus to write a graph of the pipeline
+pub const DUMP_DOT: &str = "video-less-dump-dot";
+
+/// Everything that can be done to the playing file, by a key or from outside
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    TogglePause,
+    Play,
+    Pause,
+    /// nanoseconds forward, or back when negative
+    Seek(i64),
+    SeekTo(gst::ClockTime),
+    /// seeks to this far through the file, from 0 to 1
+    SeekFraction(f64),
+    Next,
+    Previous,
+    /// plays this file right after the current one, which it stops
+    Load(PathBuf),
+    Quit,
+    CycleEqualizer,
+    AdjustPrecision(i8),
+    /// scales frames with the next scaler
+    CycleScaler,
+    /// runs the next of the `--filter` effects over frames, or none
+    CycleFilter,
+    AdjustVolume(f64),
+    SetVolume(f64),
+    ToggleMute,
+    Screenshot,
+    /// shows or hides what's known about the file
+    ToggleInfo,
+    /// shows or hides the frame rates, drops and latency
+    ToggleStats,
+    /// shows or hides the position in a corner of the video
+    ToggleClock,
+    /// shows or hides the spectrum of the sound under the video
+    ToggleSpectrum,
+    /// marks where the position is as the start of the clip to export
+    MarkClipStart,
+    MarkClipEnd,
+    /// cuts the marked part out into a file of its own
+    ExportClip,
+    /// writes a graph of the pipeline for bug reports
+    DumpDot,
+    /// gives the terminal back to the shell, as Ctrl-Z would
+    Suspend,
+}
+
+struct Attached {
+    generation: u64,
+    bus: WeakRef<Bus>,
+    pipeline: WeakRef<Pipeline>,
+    /// whether the file is playing or paused
+    state: State,
+    /// held paused until the buffers are full, whatever `state` says
+    buffering: bool,
+    info: MediaInfo,
+    preview: Option<Previewer>,
+    seeks: SeekQueue,
+    /// where the clip to export starts and ends
+    clip: (Option<gst::ClockTime>, Option<gst::ClockTime>),
+}
+
+/// The pipeline that key presses currently apply to,
+/// and whether quitting came in while there was none
+#[derive(Clone, Default)]
+pub struct Controls(Arc<Mutex<Option<Attached>>>, Arc<AtomicBool>);
+
+impl Controls {
+    /// `preview` shows thumbnails of where seeks go, until the next file gets attached
+    pub fn attach(
+        &self,
+        bus: &Bus,
+        pipeline: &Pipeline,
+        info: MediaInfo,
+        preview: Option<Previewer>,
+    ) {
+        let mut lock = self.0.lock();
+        let generation = lock.as_ref().map_or(0, |attached| attached.generation + 1);
+        *lock = Some(Attached {
+            generation,
+            bus: bus.downgrade(),
+            pipeline: pipeline.downgrade(),
+            // every new pipeline starts out playing
+            state: State::Playing,
+            buffering: false,
+            info,
+            preview,
+            seeks: SeekQueue::default(),
+            clip: (None, None),
         });
+        drop(lock);
+
+        if self.1.swap(false, Ordering::SeqCst) {
+            tracing::debug!("quitting as asked to in between files");
+            let structure = gst::Structure::new_empty(QUIT);
+            let _ = bus.post(gst::message::Application::new(structure));
+        }
+    }
+
+    /// lets the seek queued up behind the one the pipeline just finished go out,
+    /// to be called on every `AsyncDone`
+    pub fn seek_done(&self) {
+        let mut lock = self.0.lock();
+        let Some(attached) = &mut *lock else {
+            return;
+        };
+        attached.seeks.in_flight = None;
+        let Some((position, flags)) = attached.seeks.pending.take() else {
+            return;
+        };
+        let (Some(pipeline), Some(bus)) = (attached.pipeline.upgrade(), attached.bus.upgrade())
+        else {
+            return;
+        };
+
+        tracing::debug!(%position, "issuing the queued seek");
+        if seek_absolute(&pipeline, &bus, position, flags) {
+            attached.seeks.in_flight = Some((Instant::now(), position));
+        }
+    }
 
-    let mut state = State::Playing;
+    /// seeks to `target` unless the last seek is still going, then it's queued up
+    /// to go out once that one is done, returns where it lands
+    fn seek(
+        &self,
+        generation: u64,
+        pipeline: &Pipeline,
+        bus: &Bus,
+        target: SeekTarget,
+    ) -> Option<gst::ClockTime> {
+        let mut lock = self.0.lock();
+        let attached = lock
+            .as_mut()
+            .filter(|attached| attached.generation == generation)?;
+        let queue = &mut attached.seeks;
+
+        let now = Instant::now();
+        let PlannedSeek {
+            position,
+            flags,
+            queued,
+        } = queue.plan(
+            now,
+            target,
+            pipeline.query_position::<gst::ClockTime>(),
+            pipeline.query_duration::<gst::ClockTime>(),
+        )?;
+        if queued {
+            tracing::trace!(%position, "queueing the seek behind the one in flight");
+            queue.pending = Some((position, flags));
+        } else if seek_absolute(pipeline, bus, position, flags) {
+            queue.pending = None;
+            queue.in_flight = Some((now, position));
+        }
+
+        Some(position)
+    }
+
+    /// adds tags found in the current file to what the info panel shows
+    pub fn merge_tags(&self, tags: &gst::TagList) {
+        if let Some(attached) = &mut *self.0.lock() {
+            attached.info.merge(tags);
+        }
+    }
 
-    for (event, pipeline, bus) in event_stream {
-        let last_state = state;
+    fn current(&self) -> Option<(u64, Pipeline, Bus, State)> {
+        let lock = self.0.lock();
+        let attached = lock.as_ref()?;
+        let pipeline = attached.pipeline.upgrade()?;
+        let bus = attached.bus.upgrade()?;
+        // actions in between pipelines do nothing
+        if pipeline.current_state() == State::Null {
+            return None;
+        }
+        Some((attached.generation, pipeline, bus, attached.state))
+    }
+
+    /// whether there's a file and it's playing rather than paused
+    pub fn playing(&self) -> bool {
+        self.current()
+            .is_some_and(|(.., state)| state == State::Playing)
+    }
+
+    /// where the current file is at and how long it is
+    pub fn position(&self) -> Option<(gst::ClockTime, Option<gst::ClockTime>)> {
+        let (_, pipeline, ..) = self.current()?;
+        let position = pipeline.query_position::<gst::ClockTime>()?;
+        Some((position, pipeline.query_duration::<gst::ClockTime>()))
+    }
+
+    /// marks where `pipeline` is at as where the clip to export starts, or ends
+    fn mark_clip(
+        &self,
+        settings: &LiveSettings,
+        generation: u64,
+        pipeline: &Pipeline,
+        start: bool,
+    ) {
+        let Some(position) = pipeline.query_position::<gst::ClockTime>() else {
+            return;
+        };
+        if let Some(attached) = &mut *self.0.lock()
+            && attached.generation == generation
+        {
+            match start {
+                true => attached.clip.0 = Some(position),
+                false => attached.clip.1 = Some(position),
+            }
+        }
+        let which = if start { "starts" } else { "ends" };
+        settings.notify(format!("the clip {which} at {position:.1}"), MARK_SHOWN_FOR);
+    }
+
+    /// does `action` to the current file, returns false if there was none to do it to,
+    /// quitting instead waits for the next file to get attached
+    pub fn apply(&self, settings: &LiveSettings, action: Action) -> bool {
+        let Some((generation, pipeline, bus, last_state)) = self.current() else {
+            if action == Action::Quit {
+                self.1.store(true, Ordering::SeqCst);
+                return true;
+            }
+            tracing::debug!(?action, "nothing is playing to apply this to");
+            return false;
+        };
+        tracing::debug!(?action, "applying");
 
-        match event {
-            Key::Right => seek_relative(&pipeline, &bus, 5),
-            Key::Left => seek_relative(&pipeline, &bus, -5),
-            Key::Char(' ') => {
-                state = match state {
-                    State::Playing => State::Paused,
-                    State::Paused => State::Playing,
-                    _ => unreachable!(),
+        let toggled = match last_state {
+            State::Playing => State::Paused,
+            State::Paused => State::Playing,
+            _ => unreachable!(),
+        };
+
+        let post = |name: &str, path: Option<&PathBuf>| {
+            let structure = gst::Structure::builder(name)
+                .field_if_some("path", path.map(|path| path.to_string_lossy().into_owned()))
+                .build();
+            bus.post(gst::message::Application::new(structure)).unwrap();
+        };
+
+        let mut state = last_state;
+        let mut seeked_to = None;
+        match action {
+            Action::TogglePause => state = toggled,
+            Action::Play => state = State::Playing,
+            Action::Pause => state = State::Paused,
+            Action::Seek(offset) => {
+                seeked_to = self.seek(generation, &pipeline, &bus, SeekTarget::Relative(offset));
+            }
+            Action::SeekTo(position) => {
+                seeked_to = self.seek(generation, &pipeline, &bus, SeekTarget::Absolute(position));
+            }
+            Action::SeekFraction(fraction) => {
+                seeked_to = self.seek(generation, &pipeline, &bus, SeekTarget::Fraction(fraction));
+            }
+            Action::Next => post(NEXT, None),
+            Action::Previous => post(PREVIOUS, None),
+            Action::Load(path) => post(LOAD, Some(&path)),
+            Action::Quit => post(QUIT, None),
+            Action::DumpDot => post(DUMP_DOT, None),
+            Action::CycleEqualizer => {
+                let message = match crate::audio_sink::cycle_equalizer(&pipeline) {
+                    Some(preset) => {
+                        let name = clap::ValueEnum::to_possible_value(&preset).unwrap();
+                        format!("equalizer: {}", name.get_name())
+                    }
+                    None => "no equalizer, start with --equalizer to get one".to_string(),
                 };
+                settings.notify(message, EQUALIZER_SHOWN_FOR);
             }
-            Key::Up => state = State::Playing,
-            Key::Down => state = State::Paused,
-            Key::Ctrl('c') | Key::Char('q' | 'Q') | Key::Esc => {
-                bus.post(Eos::new()).unwrap();
-                break;
+            Action::AdjustPrecision(by) => settings.adjust_precision(by),
+            Action::CycleScaler => settings.cycle_scaler(),
+            Action::CycleFilter => settings.cycle_post_filter(),
+            Action::AdjustVolume(by) => crate::audio_sink::adjust_volume(&pipeline, by),
+            Action::SetVolume(volume) => crate::audio_sink::set_volume(&pipeline, volume),
+            Action::ToggleMute => crate::audio_sink::toggle_mute(&pipeline),
+            Action::Screenshot => settings.request_screenshot(),
+            Action::ToggleStats => settings.toggle_stats(),
+            Action::ToggleClock => settings.toggle_clock(),
+            Action::ToggleSpectrum => {
+                crate::audio_sink::show_spectrum(&pipeline, settings.toggle_spectrum());
             }
-            _ => {}
+            Action::MarkClipStart => self.mark_clip(settings, generation, &pipeline, true),
+            Action::MarkClipEnd => self.mark_clip(settings, generation, &pipeline, false),
+            Action::ExportClip => {
+                let clip = match &*self.0.lock() {
+                    Some(attached) if attached.generation == generation => attached.clip,
+                    _ => (None, None),
+                };
+                let structure = gst::Structure::builder(EXPORT_CLIP)
+                    .field_if_some("start", clip.0.map(gst::ClockTime::nseconds))
+                    .field_if_some("end", clip.1.map(gst::ClockTime::nseconds))
+                    .build();
+                bus.post(gst::message::Application::new(structure)).unwrap();
+            }
+            #[cfg(unix)]
+            Action::Suspend => video_less::suspend::request(),
+            #[cfg(not(unix))]
+            Action::Suspend => {}
+            Action::ToggleInfo => settings.toggle_panel(|| match &*self.0.lock() {
+                Some(attached) => attached.info.describe(&pipeline),
+                None => String::new(),
+            }),
+        }
+
+        if let Some(position) = seeked_to
+            && let Some(attached) = &*self.0.lock()
+            && attached.generation == generation
+            && let Some(preview) = &attached.preview
+        {
+            preview.request(position);
         }
 
-        if last_state != state {
+        if last_state != state
+            && let Some(attached) = &mut *self.0.lock()
+            && attached.generation == generation
+        {
+            // a buffering pipeline picks the state up once it's done
+            if !attached.buffering {
+                seek_error_to_bus(&bus, pipeline.set_state(state));
+            }
+            attached.state = state;
+        }
+
+        true
+    }
+
+    /// keeps the pipeline paused while its buffers fill up, and lets it go on as it was after,
+    /// to be called on every `Buffering` message of a pipeline that isn't live
+    pub fn buffering(&self, percent: i32) {
+        let mut lock = self.0.lock();
+        let Some(attached) = &mut *lock else {
+            return;
+        };
+        let buffering = percent < 100;
+        if attached.buffering == buffering {
+            return;
+        }
+        attached.buffering = buffering;
+
+        let Some(pipeline) = attached.pipeline.upgrade() else {
+            return;
+        };
+        let state = match buffering {
+            true => State::Paused,
+            false => attached.state,
+        };
+        tracing::debug!(percent, ?state, "buffering");
+        if let Some(bus) = attached.bus.upgrade() {
             seek_error_to_bus(&bus, pipeline.set_state(state));
         }
     }
 }
 
-pub fn start(bus: WeakRef<Bus>, pipeline: WeakRef<Pipeline>) {
-    thread::spawn(move || play_controls(&bus, &pipeline));
+/// what a key or click does
+fn action(event: Event, settings: &LiveSettings, seek_step: i64) -> Option<Action> {
+    let seek_step = seek_step * gst::ClockTime::SECOND.nseconds() as i64;
+    let action = match event {
+        Event::Key(Key::Right) => Action::Seek(seek_step),
+        Event::Key(Key::Left) => Action::Seek(-seek_step),
+        Event::Key(Key::Char(' ')) => Action::TogglePause,
+        Event::Key(Key::Up) => Action::Play,
+        Event::Key(Key::Down) => Action::Pause,
+        Event::Key(Key::Char('n' | 'N')) => Action::Next,
+        Event::Key(Key::Char('p' | 'P')) => Action::Previous,
+        Event::Key(Key::Char('E')) => Action::CycleEqualizer,
+        Event::Key(Key::Char('e')) => Action::ExportClip,
+        Event::Key(Key::Char('{')) => Action::MarkClipStart,
+        Event::Key(Key::Char('}')) => Action::MarkClipEnd,
+        Event::Key(Key::Char('m' | 'M')) => Action::ToggleMute,
+        Event::Key(Key::Char('s' | 'S')) => Action::Screenshot,
+        Event::Key(Key::Char('i' | 'I')) => Action::ToggleInfo,
+        Event::Key(Key::F(2)) => Action::ToggleStats,
+        Event::Key(Key::F(12)) => Action::DumpDot,
+        Event::Key(Key::Char('[')) => Action::AdjustPrecision(-1),
+        Event::Key(Key::Char(']')) => Action::AdjustPrecision(1),
+        Event::Key(Key::Char('r' | 'R')) => Action::CycleScaler,
+        Event::Key(Key::Char('f' | 'F')) => Action::CycleFilter,
+        Event::Key(Key::Char('t' | 'T')) => Action::ToggleClock,
+        Event::Key(Key::Char('v' | 'V')) => Action::ToggleSpectrum,
+        Event::Key(Key::Ctrl('c') | Key::Char('q' | 'Q') | Key::Esc) => Action::Quit,
+        // raw mode turns Ctrl-Z into a key too
+        Event::Key(Key::Ctrl('z')) => Action::Suspend,
+        // the bottom row of the video doubles as the seek bar
+        Event::Mouse(Mouse {
+            action: MouseAction::Left,
+            x,
+            y,
+        }) => Action::SeekFraction(settings.video_area()?.seek_fraction((x, y))?),
+        Event::Mouse(Mouse {
+            action: MouseAction::Middle,
+            ..
+        }) => Action::TogglePause,
+        Event::Mouse(Mouse {
+            action: MouseAction::WheelUp,
+            ..
+        }) => Action::AdjustVolume(0.05),
+        Event::Mouse(Mouse {
+            action: MouseAction::WheelDown,
+            ..
+        }) => Action::AdjustVolume(-0.05),
+        _ => return None,
+    };
+
+    Some(action)
+}
+
+fn play_controls(
+    controls: &Controls,
+    settings: &LiveSettings,
+    seek_step: i64,
+    pause_on_unfocus: bool,
+    token: CancellationToken,
+) {
+    // only playback paused for losing focus gets resumed on getting it back
+    let mut paused_unfocused = false;
+    for event in terminal::events(token) {
+        tracing::trace!(?event, "input");
+        if let Event::Focus(focused) = event {
+            if !pause_on_unfocus {
+                continue;
+            }
+            if !focused && controls.playing() {
+                paused_unfocused = controls.apply(settings, Action::Pause);
+            } else if focused && std::mem::take(&mut paused_unfocused) {
+                controls.apply(settings, Action::Play);
+            }
+            continue;
+        }
+
+        let Some(action) = action(event, settings, seek_step) else {
+            continue;
+        };
+
+        // pausing or playing by hand overrides what focus did
+        if matches!(action, Action::TogglePause | Action::Play | Action::Pause) {
+            paused_unfocused = false;
+        }
+        let quit = action == Action::Quit;
+        if controls.apply(settings, action) && quit {
+            break;
+        }
+    }
+}
+
+/// reads keys until quitting, the arrows seek by `seek_step` seconds,
+/// with `pause_on_unfocus` playback pauses while the terminal doesn't have focus
+pub fn start(
+    quit_handler: &mut QuitHandler,
+    controls: Controls,
+    settings: Arc<LiveSettings>,
+    seek_step: u32,
+    pause_on_unfocus: bool,
+) {
+    let token = quit_handler.token();
+    let jh = thread::spawn(move || {
+        play_controls(
+            &controls,
+            &settings,
+            seek_step.into(),
+            pause_on_unfocus,
+            token,
+        )
+    });
+    quit_handler.add(move || jh.join().unwrap());
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PlannedSeek, SEEK_DEBOUNCE, SeekQueue, SeekTarget};
+    use super::{fraction_of_duration, offset_from};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn seeks_keep_parts_of_a_second() {
+        let at = gst::ClockTime::from_seconds(10);
+        assert_eq!(
+            offset_from(at, 500_000_000),
+            gst::ClockTime::from_mseconds(10_500)
+        );
+        assert_eq!(
+            offset_from(at, -250_000_000),
+            gst::ClockTime::from_mseconds(9_750)
+        );
+        assert_eq!(offset_from(at, -20_000_000_000), gst::ClockTime::ZERO);
+    }
+
+    #[test]
+    fn seeks_along_the_duration() {
+        let minute = Some(gst::ClockTime::from_seconds(60));
+        let seconds = |fraction| fraction_of_duration(minute, fraction).map(|at| at.seconds());
+        assert_eq!(seconds(0.0), Some(0));
+        assert_eq!(seconds(0.5), Some(30));
+        assert_eq!(seconds(1.0), Some(60));
+        assert_eq!(seconds(1.5), Some(60));
+        assert_eq!(fraction_of_duration(None, 0.5), None);
+    }
+
+    const SECOND: i64 = 1_000_000_000;
+
+    fn relative(queue: &SeekQueue, now: Instant, offset: i64) -> PlannedSeek {
+        let playing_at = Some(gst::ClockTime::from_seconds(10));
+        queue
+            .plan(now, SeekTarget::Relative(offset), playing_at, None)
+            .unwrap()
+    }
+
+    #[test]
+    fn idle_seeks_go_out_from_the_position() {
+        let planned = relative(&SeekQueue::default(), Instant::now(), 5 * SECOND);
+        assert_eq!(planned.position, gst::ClockTime::from_seconds(15));
+        assert!(!planned.queued);
+
+        let queue = SeekQueue::default();
+        assert_eq!(
+            queue.plan(Instant::now(), SeekTarget::Relative(SECOND), None, None),
+            None
+        );
+    }
+
+    #[test]
+    fn busy_seeks_queue_up_behind_the_pending_one() {
+        let now = Instant::now();
+        let queue = SeekQueue {
+            in_flight: Some((now, gst::ClockTime::from_seconds(20))),
+            pending: Some((gst::ClockTime::from_seconds(30), gst::SeekFlags::FLUSH)),
+        };
+        let planned = relative(&queue, now + SEEK_DEBOUNCE / 2, 5 * SECOND);
+        assert_eq!(planned.position, gst::ClockTime::from_seconds(35));
+        assert!(planned.queued);
+    }
+
+    #[test]
+    fn seeks_go_out_once_the_debounce_is_over() {
+        let now = Instant::now();
+        let queue = SeekQueue {
+            in_flight: Some((now, gst::ClockTime::from_seconds(20))),
+            pending: None,
+        };
+        let later = now + SEEK_DEBOUNCE + Duration::from_millis(1);
+        let planned = relative(&queue, later, -5 * SECOND);
+        assert_eq!(planned.position, gst::ClockTime::from_seconds(15));
+        assert!(!planned.queued);
+    }
 }
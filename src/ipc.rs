@@ -0,0 +1,188 @@
+//! A socket taking one json command per line, along the lines of mpv's ipc:
+//! `{"command": ["seek", 10], "request_id": 1}` is answered with
+//! `{"error": "success", "data": null, "request_id": 1}`
+use crate::input_handler::{Action, Controls};
+use serde_json::{Value, json};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use video_less::terminal_sink::LiveSettings;
+
+/// what a command asks for, `Ok(None)` for commands that only answer
+fn parse(command: &[Value]) -> Result<Option<Action>, String> {
+    let name = command
+        .first()
+        .and_then(Value::as_str)
+        .ok_or("the command has to start with its name")?;
+    let number = || {
+        command
+            .get(1)
+            .and_then(Value::as_f64)
+            .ok_or_else(|| format!("{name} needs a number"))
+    };
+
+    let action = match name {
+        "pause" => Action::Pause,
+        "play" => Action::Play,
+        "toggle-pause" => Action::TogglePause,
+        "seek" => {
+            let seconds = number()?;
+            match command.get(2).and_then(Value::as_str) {
+                None | Some("relative") => Action::Seek((seconds * 1e9) as i64),
+                Some("absolute") => Action::SeekTo(gst::ClockTime::from_nseconds(
+                    (seconds.max(0.0) * 1e9) as u64,
+                )),
+                Some("absolute-percent") => Action::SeekFraction(seconds / 100.0),
+                Some(mode) => return Err(format!("unknown seek mode {mode}")),
+            }
+        }
+        "set-volume" => Action::SetVolume(number()?),
+        "load-file" => {
+            let path = command
+                .get(1)
+                .and_then(Value::as_str)
+                .ok_or("load-file needs a path")?;
+            Action::Load(PathBuf::from(path))
+        }
+        "next" => Action::Next,
+        "previous" => Action::Previous,
+        "screenshot" => Action::Screenshot,
+        "toggle-mute" => Action::ToggleMute,
+        "quit" => Action::Quit,
+        "get-position" => return Ok(None),
+        _ => return Err(format!("unknown command {name}")),
+    };
+
+    Ok(Some(action))
+}
+
+fn respond(controls: &Controls, settings: &LiveSettings, line: &str) -> Value {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return json!({ "error": format!("invalid json: {err}") }),
+    };
+
+    let command = request
+        .get("command")
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+
+    let (error, data) = match parse(command) {
+        Err(err) => (err, Value::Null),
+        Ok(Some(action)) => match controls.apply(settings, action) {
+            true => ("success".to_string(), Value::Null),
+            false => ("nothing is playing".to_string(), Value::Null),
+        },
+        Ok(None) => match controls.position() {
+            Some((position, duration)) => (
+                "success".to_string(),
+                json!({
+                    "position": position.nseconds() as f64 / 1e9,
+                    "duration": duration.map(|duration| duration.nseconds() as f64 / 1e9),
+                }),
+            ),
+            None => ("nothing is playing".to_string(), Value::Null),
+        },
+    };
+
+    let mut response = json!({ "error": error, "data": data });
+    if let Some(id) = request.get("request_id") {
+        response["request_id"] = id.clone();
+    }
+    response
+}
+
+fn serve(controls: &Controls, settings: &LiveSettings, stream: UnixStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut response = respond(controls, settings, &line).to_string();
+        response.push('\n');
+        writer.write_all(response.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// listens on `path` for commands to the current file, the socket goes away once the returned
+/// closure is called
+pub fn start(
+    path: &Path,
+    controls: Controls,
+    settings: Arc<LiveSettings>,
+) -> io::Result<impl FnOnce() + 'static> {
+    // a socket left behind by a player that didn't get to clean up, anything else stays
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "something that isn't a socket is in the way",
+            ));
+        }
+        if UnixStream::connect(path).is_err() {
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    let listener = UnixListener::bind(path)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (controls, settings) = (controls.clone(), Arc::clone(&settings));
+            thread::spawn(move || serve(&controls, &settings, stream));
+        }
+    });
+
+    let path = path.to_path_buf();
+    Ok(move || {
+        let _ = std::fs::remove_file(path);
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commands_become_actions() {
+        let parse =
+            |json: &str| parse(serde_json::from_str::<Vec<Value>>(json).unwrap().as_slice());
+
+        assert_eq!(
+            parse(r#"["seek", -5]"#),
+            Ok(Some(Action::Seek(-5_000_000_000)))
+        );
+        assert_eq!(
+            parse(r#"["seek", 0.5]"#),
+            Ok(Some(Action::Seek(500_000_000)))
+        );
+        assert_eq!(
+            parse(r#"["seek", 1.5, "absolute"]"#),
+            Ok(Some(Action::SeekTo(gst::ClockTime::from_mseconds(1500))))
+        );
+        assert_eq!(
+            parse(r#"["set-volume", 0.5]"#),
+            Ok(Some(Action::SetVolume(0.5)))
+        );
+        assert_eq!(parse(r#"["get-position"]"#), Ok(None));
+        assert!(parse(r#"["set-volume"]"#).is_err());
+        assert!(parse(r#"["dance"]"#).is_err());
+    }
+
+    #[test]
+    fn leaves_files_that_arent_sockets_alone() {
+        let path = std::env::temp_dir().join(format!("video-less-ipc-{}", std::process::id()));
+        std::fs::write(&path, "notes").unwrap();
+
+        let settings = Arc::new(LiveSettings::new(5, false));
+        let err = start(&path, Controls::default(), settings).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "notes");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
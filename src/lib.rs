@@ -0,0 +1,117 @@
+//! Video playback in the terminal, on top of GStreamer
+//!
+//! [`TerminalVideoSink`] is a sink element for pipelines to draw into the terminal through,
+//! [`TerminalRenderer`] does the drawing without any pipeline around it, handing back the escape
+//! sequences for every frame so they can go wherever, or drawing into a [`RenderTarget`]
+//! rectangle of the terminal, like the area a TUI laid out for a video.
+//! Built as a plugin the library also gives GStreamer the `termvideosink` element.
+//!
+//! Around them [`ansi_dump`] plays back what [`TerminalVideoSinkBuilder::dump_ansi`] wrote,
+//! and on unix [`shutdown`] and [`suspend`] turn SIGINT, SIGTERM and SIGTSTP into quitting
+//! and stepping out of the terminal, instead of leaving it in raw mode.
+//!
+//! A panic on the render thread takes the sink down with it, whatever was drawn stays on the
+//! terminal until it gets restored, the player itself builds with `panic = "abort"`.
+extern crate gstreamer as gst;
+extern crate gstreamer_app as gst_app;
+extern crate gstreamer_base as gst_base;
+extern crate gstreamer_video as gst_video;
+
+use crate::cancel::CancellationToken;
+use crate::error::PlayerError;
+
+mod resize_image;
+#[cfg(unix)]
+mod self_pipe;
+mod term_size;
+#[cfg(unix)]
+mod terminal_guard;
+
+pub mod ansi_dump;
+pub mod cancel;
+pub mod error;
+#[cfg(unix)]
+pub mod shutdown;
+#[cfg(unix)]
+pub mod suspend;
+pub mod terminal;
+pub mod terminal_sink;
+
+pub use terminal_sink::termvideosink::TermVideoSink;
+pub use terminal_sink::{
+    Background, CellAspect, ColorFilter, DecodeScale, Glyphs, ImageRef, LiveSettings, Palette,
+    PixelTransform, PostFilter, QueueMode, RenderMode, RenderStats, RenderTarget, RenderedFrame,
+    Resizer, Scaler, StageTimings, Symbol, SymbolSet, TerminalRenderer, TerminalVideoSink,
+    TerminalVideoSinkBuilder, ToneMap, VideoArea,
+};
+
+fn plugin_init(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
+    terminal_sink::termvideosink::register(Some(plugin))
+}
+
+gst::plugin_define!(
+    video_less,
+    "Video in the terminal",
+    plugin_init,
+    env!("CARGO_PKG_VERSION"),
+    "unknown",
+    env!("CARGO_PKG_NAME"),
+    env!("CARGO_PKG_NAME"),
+    "https://github.com/vrtgs/gstreamer-terminal-video-player"
+);
+
+/// Whether the environment variable `flag` is set to yes, `y` or nothing at all, `default` when
+/// it isn't set, the way every switch the sink and the player read from the environment works
+pub fn flag(flag: &str, default: bool) -> bool {
+    std::env::var_os(flag).map_or(default, |str| {
+        let mut str = str.into_encoded_bytes();
+        str.make_ascii_lowercase();
+        matches!(str.trim_ascii(), b"y" | b"yes" | b"")
+    })
+}
+
+/// Makes a `name` element with its defaults, failing with which plugin is missing
+pub fn gstreamer_element(name: &str) -> Result<gst::Element, PlayerError> {
+    gst::ElementFactory::make(name)
+        .build()
+        .map_err(PlayerError::element(name))
+}
+
+/// Stops whatever was started with it once dropped, like the renderer letting go of the terminal
+pub struct QuitHandler {
+    token: CancellationToken,
+    callbacks: Vec<Box<dyn FnOnce()>>,
+}
+
+impl QuitHandler {
+    pub fn new() -> Self {
+        Self {
+            token: CancellationToken::default(),
+            callbacks: vec![],
+        }
+    }
+
+    /// cancelled right before the callbacks run
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn add(&mut self, callback: impl FnOnce() + 'static) {
+        self.callbacks.push(Box::new(callback))
+    }
+}
+
+impl Default for QuitHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for QuitHandler {
+    fn drop(&mut self) {
+        self.token.cancel();
+        for callback in self.callbacks.drain(..) {
+            callback()
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Network streams such as RTSP, RTMP and HLS, which have no file to open
+//! and whose sources decide on their own clock and latency
+use glib::object::ObjectExt;
+use glib::prelude::{StaticType, ToValue};
+use gst::prelude::GstObjectExt;
+use std::path::Path;
+use video_less::error::PlayerError;
+
+/// the URI `video` is when it names a stream rather than a file, `scheme://` and all
+pub fn uri(video: &Path) -> Option<&str> {
+    let video = video.to_str()?;
+    let (scheme, _) = video.split_once("://")?;
+    let is_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    is_scheme.then_some(video)
+}
+
+/// sets the `latency` of the network sources `decoder` makes, for the ones that have one,
+/// rtspsrc and srtsrc take milliseconds but not all of them as the same type,
+/// sources with any other kind of latency keep their own
+fn set_source_latency(decoder: &gst::Element, latency: gst::ClockTime) {
+    decoder.connect("source-setup", false, move |args| {
+        let source = args[1].get::<gst::Element>().unwrap();
+        let Some(pspec) = source.find_property("latency") else {
+            return None;
+        };
+
+        let ms = latency.mseconds();
+        let value = match pspec.value_type() {
+            ty if ty == u32::static_type() => u32::try_from(ms).unwrap_or(u32::MAX).to_value(),
+            ty if ty == i32::static_type() => i32::try_from(ms).unwrap_or(i32::MAX).to_value(),
+            // no telling what unit anything else is in
+            _ => {
+                tracing::debug!(source = %source.name(), "the source latency isn't in milliseconds");
+                return None;
+            }
+        };
+        tracing::debug!(source = %source.name(), ms, "setting the source latency");
+        source.set_property_from_value("latency", &value);
+        None
+    });
+}
+
+/// the decoder that opens `uri` by itself, standing in for a source and decodebin
+pub fn decoder(uri: &str, latency: Option<gst::ClockTime>) -> Result<gst::Element, PlayerError> {
+    let decoder = gst::ElementFactory::make("uridecodebin3")
+        .property("uri", uri)
+        .build()
+        .or_else(|_| {
+            gst::ElementFactory::make("uridecodebin")
+                .property("uri", uri)
+                .build()
+        })
+        .map_err(PlayerError::element("uridecodebin3"))?;
+
+    if let Some(latency) = latency {
+        set_source_latency(&decoder, latency);
+    }
+    Ok(decoder)
+}
+
+/// playbin sets its sources up the same way as uridecodebin does
+pub fn configure_playbin(playbin: &gst::Element, latency: Option<gst::ClockTime>) {
+    if let Some(latency) = latency {
+        set_source_latency(playbin, latency);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::uri;
+    use std::path::Path;
+
+    #[test]
+    fn only_schemes_make_streams() {
+        assert!(uri(Path::new("rtsp://camera.local:554/stream")).is_some());
+        assert!(uri(Path::new("https://example.com/live/index.m3u8")).is_some());
+        assert!(uri(Path::new("videos/clip.mp4")).is_none());
+        assert!(uri(Path::new("C:\\videos\\clip.mp4")).is_none());
+        assert!(uri(Path::new("weird ://name.mkv")).is_none());
+    }
+}
@@ -0,0 +1,103 @@
+//! Logging to a file, nothing can go to stderr while the video covers the terminal
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use video_less::error::PlayerError;
+
+#[derive(clap::ValueEnum, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn tracing(self) -> tracing::Level {
+        match self {
+            Self::Error => tracing::Level::ERROR,
+            Self::Warn => tracing::Level::WARN,
+            Self::Info => tracing::Level::INFO,
+            Self::Debug => tracing::Level::DEBUG,
+            Self::Trace => tracing::Level::TRACE,
+        }
+    }
+
+    /// gstreamer's info is chattier than ours, so everything is a step quieter on its side
+    fn gstreamer(self) -> gst::DebugLevel {
+        match self {
+            Self::Error => gst::DebugLevel::Error,
+            Self::Warn => gst::DebugLevel::Warning,
+            Self::Info => gst::DebugLevel::Fixme,
+            Self::Debug => gst::DebugLevel::Info,
+            Self::Trace => gst::DebugLevel::Debug,
+        }
+    }
+}
+
+/// `video-less.log` in the state directory, next to the history
+pub fn default_file() -> Option<PathBuf> {
+    crate::history::state_dir().map(|dir| dir.join("video-less.log"))
+}
+
+/// hands what gstreamer logs to tracing instead of letting it print over the video
+fn forward_gstreamer(level: LogLevel) {
+    gst::log::remove_default_log_function();
+    gst::log::add_log_function(|category, level, file, function, line, object, message| {
+        let Some(message) = message.get() else {
+            return;
+        };
+        let category = category.name();
+        let object = object.map(|object| object.to_string()).unwrap_or_default();
+        macro_rules! forward {
+            ($level:expr) => {
+                tracing::event!(
+                    target: "gstreamer",
+                    $level,
+                    category,
+                    object = %object,
+                    "{file}:{line}:{function}: {message}"
+                )
+            };
+        }
+
+        match level {
+            gst::DebugLevel::Error => forward!(tracing::Level::ERROR),
+            gst::DebugLevel::Warning => forward!(tracing::Level::WARN),
+            gst::DebugLevel::Fixme | gst::DebugLevel::Info => forward!(tracing::Level::INFO),
+            gst::DebugLevel::Debug => forward!(tracing::Level::DEBUG),
+            _ => forward!(tracing::Level::TRACE),
+        }
+    });
+    gst::log::set_active(true);
+    gst::log::set_default_threshold(level.gstreamer());
+}
+
+/// sends everything at `level` and up to `file`, which gets appended to
+pub fn init(level: LogLevel, file: &Path) -> Result<(), PlayerError> {
+    let open_error = |source| PlayerError::Open {
+        path: file.to_path_buf(),
+        source,
+    };
+
+    if let Some(dir) = file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(open_error)?;
+    }
+    let out = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)
+        .map_err(open_error)?;
+
+    tracing_subscriber::fmt()
+        .with_max_level(level.tracing())
+        .with_writer(std::sync::Mutex::new(out))
+        .with_ansi(false)
+        .with_thread_names(true)
+        .init();
+
+    forward_gstreamer(level);
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "logging started");
+
+    Ok(())
+}
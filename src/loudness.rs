@@ -0,0 +1,102 @@
+//! The volume, whether it's muted and the equalizer, remembered between runs next to the history
+use crate::audio_sink::EqGains;
+use crate::fs_util::write_atomic;
+use std::path::PathBuf;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Loudness {
+    /// 1 being as loud as the file is
+    pub volume: f64,
+    pub mute: bool,
+}
+
+impl Default for Loudness {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            mute: false,
+        }
+    }
+}
+
+fn file() -> Option<PathBuf> {
+    crate::history::state_dir().map(|dir| dir.join("volume"))
+}
+
+fn equalizer_file() -> Option<PathBuf> {
+    crate::history::state_dir().map(|dir| dir.join("equalizer"))
+}
+
+/// the gains the last run left the equalizer at, `None` when it had none
+pub fn load_equalizer() -> Option<EqGains> {
+    let contents = std::fs::read_to_string(equalizer_file()?).ok()?;
+    contents.trim().parse().ok()
+}
+
+/// stored as the gains `--eq` takes
+pub fn save_equalizer(gains: EqGains) -> std::io::Result<()> {
+    match equalizer_file() {
+        Some(file) => write_atomic(&file, format!("{gains}\n")),
+        None => Ok(()),
+    }
+}
+
+/// stored as the volume, followed by ` muted` when it is
+fn parse(contents: &str) -> Option<Loudness> {
+    let mut words = contents.split_whitespace();
+    let volume = words.next()?.parse::<f64>().ok()?;
+    let mute = match words.next() {
+        None => false,
+        Some("muted") => true,
+        Some(_) => return None,
+    };
+
+    Some(Loudness {
+        volume: volume.clamp(0.0, 2.0),
+        mute,
+    })
+}
+
+impl Loudness {
+    /// what the last run left it at, or as loud as the file is when there was none
+    pub fn load() -> Self {
+        file()
+            .and_then(|file| std::fs::read_to_string(file).ok())
+            .and_then(|contents| parse(&contents))
+            .unwrap_or_default()
+    }
+
+    pub fn save(self) -> std::io::Result<()> {
+        let Some(file) = file() else {
+            return Ok(());
+        };
+
+        let contents = match self.mute {
+            true => format!("{} muted\n", self.volume),
+            false => format!("{}\n", self.volume),
+        };
+        write_atomic(&file, contents)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Loudness, parse};
+
+    #[test]
+    fn reads_what_save_writes() {
+        let quiet = Loudness {
+            volume: 0.6,
+            mute: true,
+        };
+        assert_eq!(parse("0.6 muted\n"), Some(quiet));
+        assert_eq!(
+            parse("3"),
+            Some(Loudness {
+                volume: 2.0,
+                mute: false
+            })
+        );
+        assert_eq!(parse("loud"), None);
+    }
+}
@@ -1,132 +1,275 @@
 extern crate gstreamer as gst;
-extern crate gstreamer_app as gst_app;
-extern crate gstreamer_video as gst_video;
-
-use crate::gst::prelude::ElementExtManual;
-use clap::Parser;
-use glib::object::ObjectExt;
-use gst::prelude::{ElementExt, GstBinExt, GstBinExtManual, GstObjectExt, PadExt};
-use std::os::fd::IntoRawFd;
-use std::path::PathBuf;
 
+use clap::{CommandFactory, Parser};
+use cli::{Cli, Command, PreviewArgs, conflict};
+use glib::object::{Cast, ObjectExt};
+use gst::prelude::{ElementExt, GstBinExt, GstBinExtManual, GstObjectExt, PadExt, PipelineExt};
+use std::num::NonZero;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use video_less::error::PlayerError;
+use video_less::{flag, gstreamer_element, terminal, terminal_sink};
+
+mod audio_sink;
+mod backend;
+mod bench;
+mod cli;
+mod clip;
+mod compare;
+mod config;
+mod dot;
+mod encode;
+mod fs_util;
+mod history;
+mod hwdec;
+mod image;
 mod input_handler;
+#[cfg(all(unix, feature = "ipc"))]
+mod ipc;
 mod launch;
-mod resize_image;
-mod term_size;
-mod terminal_sink;
-
-pub(crate) fn flag(flag: &str, default: bool) -> bool {
-    std::env::var_os(flag).map_or(default, |str| {
-        let mut str = str.into_encoded_bytes();
-        str.make_ascii_lowercase();
-        matches!(str.trim_ascii(), b"y" | b"yes" | b"")
-    })
-}
-
-fn get_source(video: PathBuf) -> gst::Element {
-    macro_rules! exit {
-        ($($msg: tt)+) => {
-            {
-                eprintln!($($msg)+);
-                std::process::exit(-1);
-            }
-        };
+mod live;
+mod logging;
+mod loudness;
+mod media_info;
+mod missing_plugin;
+mod play;
+mod preview;
+mod probe;
+mod proxy;
+mod retry;
+mod seat;
+mod seek_preview;
+#[cfg(feature = "serde")]
+mod status;
+#[cfg(feature = "sub-auto")]
+mod subtitles;
+mod wall;
+
+// a render thread unwinding would leave playback stuck with the terminal taken over
+#[cfg(not(test))]
+const _: () = assert!(cfg!(panic = "abort"));
+
+fn get_source(video: &Path, file: std::fs::File) -> Result<gst::Element, PlayerError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::{AsRawFd, IntoRawFd};
+
+        let _ = video;
+        let fd = file.as_raw_fd();
+        gst::ElementFactory::make("fdsrc")
+            .name("source")
+            .property("fd", fd)
+            .build()
+            .inspect(|_| {
+                // if the element was built forget the file
+                // and DO NOT drop it
+                let _fd = file.into_raw_fd();
+            })
+            .map_err(PlayerError::element("fdsrc"))
     }
 
-    match std::fs::File::open(&video) {
-        Ok(file) => {
-            #[cfg(unix)]
-            {
-                use std::os::unix::io::AsRawFd;
-
-                let fd = file.as_raw_fd();
-                gst::ElementFactory::make("fdsrc")
-                    .name("source")
-                    .property("fd", fd)
-                    .build()
-                    .inspect(|_| {
-                        // if the element was built forget the file
-                        // and DO NOT drop it
-                        let _fd = file.into_raw_fd();
-                    })
-                    .unwrap()
-            }
-
-            #[cfg(not(unix))]
-            {
-                drop(file);
-                gst::ElementFactory::make("filesrc")
-                    .name("source")
-                    .property("location", file_path)
-                    .build()
-                    .unwrap()
-            }
-        }
-        Err(err) => exit!("couldn't open file: {err}"),
+    #[cfg(not(unix))]
+    {
+        drop(file);
+        gst::ElementFactory::make("filesrc")
+            .name("source")
+            .property("location", video)
+            .build()
+            .map_err(PlayerError::element("filesrc"))
     }
 }
 
-fn gstreamer_element(name: &str) -> Result<gst::Element, glib::BoolError> {
-    gst::ElementFactory::make(name).build()
+struct PipelineOptions {
+    backend: backend::Backend,
+    /// a whole pipeline to play instead of files
+    pipeline: Option<String>,
+    max_fps: Option<NonZero<u32>>,
+    /// how many frames a leaky queue in front of the sink holds, no queue when `None`
+    sink_queue: Option<NonZero<u32>>,
+    hwdec: Option<hwdec::HwDec>,
+    /// how long stills stay up, forever when `None`
+    image_duration: Option<NonZero<u32>>,
+    /// what the pipeline and the network sources buffer, instead of what they work out
+    latency: Option<gst::ClockTime>,
+    /// where the decoders that got picked are noted down for `--verbose`
+    decoder_log: Option<Arc<parking_lot::Mutex<Vec<String>>>>,
+    audio: audio_sink::Options,
+    no_audio: bool,
+    #[cfg(feature = "sub-auto")]
+    sub_auto: bool,
 }
 
-mod audio_sink {
-    use crate::gstreamer_element;
-    use glib::object::Cast;
-    use gst::prelude::{ElementExt, GstBinExtManual};
-
-    pub fn create() -> gst::Element {
-        let audio_handler = gst::Bin::with_name("audio_sink");
-        let audio_convert = gstreamer_element("audioconvert").unwrap();
-        let audio_resample = gstreamer_element("audioresample").unwrap();
-        let audio_sink = gstreamer_element("autoaudiosink").unwrap();
-        let audio_line = [&audio_convert, &audio_resample, &audio_sink];
-
-        audio_handler.add_many(audio_line).unwrap();
-        gst::Element::link_many(audio_line).unwrap();
-
-        let pad = gst::GhostPad::with_target(&audio_convert.static_pad("sink").unwrap()).unwrap();
-        audio_handler.add_pad(&pad).unwrap();
+/// a queue letting go of its oldest frames once `buffers` wait in it,
+/// so decoding, and with it the sound, never waits on a renderer that fell behind
+///
+/// only streams get one, a file would no longer be held back by the sink waiting on the clock
+/// and get decoded as fast as it goes, nearly every frame dropped on the way to the end
+fn leaky_queue(
+    video: &Path,
+    buffers: Option<NonZero<u32>>,
+) -> Result<Option<gst::Element>, PlayerError> {
+    let Some(buffers) = buffers.filter(|_| live::uri(video).is_some()) else {
+        return Ok(None);
+    };
+    gst::ElementFactory::make("queue")
+        .property_from_str("leaky", "downstream")
+        .property("max-size-buffers", buffers.get())
+        .property("max-size-bytes", 0_u32)
+        .property("max-size-time", 0_u64)
+        .build()
+        .map(Some)
+        .map_err(PlayerError::element("queue"))
+}
 
-        audio_handler.upcast()
-    }
+/// notes down which video decoders `bin` ends up using for `video`
+fn log_decoders(bin: &gst::Bin, log: &Arc<parking_lot::Mutex<Vec<String>>>, video: &Path) {
+    let log = Arc::clone(log);
+    let video = video.display().to_string();
+    bin.connect_deep_element_added(move |_, _, element| {
+        let is_video_decoder = element
+            .factory()
+            .and_then(|factory| factory.metadata(gst::ELEMENT_METADATA_KLASS))
+            .is_some_and(|klass| klass.contains("Decoder/Video"));
+
+        if is_video_decoder && let Some(factory) = element.factory() {
+            log.lock()
+                .push(format!("decoding {video} with {}", factory.name()));
+        }
+    });
 }
 
 fn make_pipeline_and_bus(
-    quit_handler: &mut QuitHandler,
-    video: PathBuf,
-    size: Option<(u16, u16)>,
-) -> (gst::Pipeline, gst::Bus) {
-    let source = get_source(video);
-    let decode = gstreamer_element("decodebin3")
-        .or_else(|_| gstreamer_element("decodebin"))
-        .unwrap();
-
-    let convert = gstreamer_element("videoconvert").unwrap();
-
-    let video_sink = terminal_sink::create(quit_handler, size);
+    sink: &terminal_sink::TerminalVideoSink,
+    (video, file): (PathBuf, Option<std::fs::File>),
+    options: &PipelineOptions,
+) -> Result<(gst::Pipeline, gst::Bus), PlayerError> {
+    if let Some(ref description) = options.pipeline {
+        drop(file);
+        return backend::custom(sink, description);
+    }
+    if options.backend == backend::Backend::Playbin {
+        // playbin opens the file by itself
+        drop(file);
+        return backend::playbin(sink, &video, options);
+    }
 
-    let audio_sink = (!flag("NO_AUDIO_OUTPUT", false)).then(audio_sink::create);
+    // streams have no file, uridecodebin opens them by itself
+    let (source, decode) = match file {
+        Some(file) => (
+            Some(get_source(&video, file)?),
+            gstreamer_element("decodebin3").or_else(|_| gstreamer_element("decodebin"))?,
+        ),
+        None => (
+            None,
+            live::decoder(&video.to_string_lossy(), options.latency)?,
+        ),
+    };
+
+    // get hardware decoded frames into system memory first
+    let download = options.hwdec.and_then(hwdec::HwDec::download);
+
+    // drop frames before they get converted
+    let rate = options
+        .max_fps
+        .map(|max_fps| {
+            gst::ElementFactory::make("videorate")
+                .property("max-rate", i32::try_from(max_fps.get()).unwrap_or(i32::MAX))
+                .property("drop-only", true)
+                .build()
+                .map_err(PlayerError::element("videorate"))
+        })
+        .transpose()?;
+
+    #[cfg(feature = "sub-auto")]
+    let subtitles = options
+        .sub_auto
+        .then(|| subtitles::Layer::find(&video))
+        .flatten();
+    #[cfg(feature = "sub-auto")]
+    let overlay = subtitles.as_ref().map(subtitles::Layer::overlay);
+    #[cfg(not(feature = "sub-auto"))]
+    let overlay = None;
+
+    // a still would otherwise be over as soon as its one frame is drawn
+    let freeze = match image::kind(&video) {
+        Some(image::Kind::Still) => Some(image::freeze(options.image_duration)?),
+        _ => None,
+    };
+    let scale = sink.scale_elements()?;
+    let queue = leaky_queue(&video, options.sink_queue)?;
+    let convert = gstreamer_element("videoconvert")?;
+
+    let video_sink = sink.element();
+
+    let audio_sink = (!options.no_audio && !flag("NO_AUDIO_OUTPUT", false))
+        .then(|| audio_sink::create(&options.audio))
+        .transpose()?;
 
     let pipeline = gst::Pipeline::new();
+    if let Some(latency) = options.latency {
+        pipeline.set_latency(latency);
+    }
 
+    let video_line = freeze
+        .iter()
+        .flatten()
+        .chain(&download)
+        .chain(&rate)
+        .chain(&scale)
+        .chain(overlay)
+        .chain(&queue)
+        .chain([&convert, &video_sink])
+        .collect::<Vec<_>>();
+    tracing::debug!(
+        video = %video.display(),
+        decoder = %decode.factory().map(|factory| factory.name()).unwrap_or_default(),
+        video_line = ?video_line.iter().map(|element| element.name()).collect::<Vec<_>>(),
+        audio = audio_sink.is_some(),
+        "building the pipeline"
+    );
+
+    if let Some(ref log) = options.decoder_log {
+        log_decoders(pipeline.upcast_ref(), log, &video);
+    }
+
+    let link = PlayerError::link;
     pipeline
-        .add_many([&source, &decode, &convert, &video_sink])
-        .unwrap();
+        .add_many(source.iter().chain([&decode]))
+        .map_err(link("the source to the decoder"))?;
+    pipeline
+        .add_many(&video_line)
+        .map_err(link("the video elements"))?;
 
     if let Some(ref audio_sink) = audio_sink {
-        pipeline.add(audio_sink).unwrap();
+        pipeline.add(audio_sink).map_err(link("the audio sink"))?;
+    }
+
+    if let Some(ref source) = source {
+        source
+            .link(&decode)
+            .map_err(link("the source to the decoder"))?;
+    }
+    gst::Element::link_many(&video_line).map_err(link("the video elements"))?;
+
+    #[cfg(feature = "sub-auto")]
+    if let Some(ref subtitles) = subtitles {
+        subtitles.link(&pipeline)?;
     }
 
-    source.link(&decode).unwrap();
-    convert.link(&video_sink).unwrap();
+    // the overlay names its video pad differently
+    let video_entry = video_line[0]
+        .static_pad("sink")
+        .or_else(|| video_line[0].static_pad("video_sink"))
+        .unwrap();
 
-    decode.connect_pad_added(move |_decode, src_pad| {
+    // a failed link shows up on the bus like any other error instead of taking the process down
+    decode.connect_pad_added(move |decode, src_pad| {
         let caps = src_pad
             .current_caps()
             .unwrap_or_else(|| src_pad.query_caps(None));
         let structure = caps.structure(0).unwrap();
         let media_type = structure.name().as_str();
+        tracing::debug!(pad = %src_pad.name(), media_type, "the decoder added a pad");
 
         if media_type.starts_with("audio/") {
             let Some(ref audio_sink) = audio_sink else {
@@ -137,113 +280,163 @@ fn make_pipeline_and_bus(
             if sink_pad.is_linked() {
                 return;
             }
-            src_pad.link(&sink_pad).expect("Failed to link audio pad");
+            if let Err(err) = src_pad.link(&sink_pad) {
+                tracing::error!(?err, "linking the audio pad failed");
+                gst::element_error!(
+                    decode,
+                    gst::CoreError::Negotiation,
+                    ("Failed to link audio pad; {err:?}")
+                );
+            }
         } else if media_type.starts_with("video/") {
-            let sink_pad = convert.static_pad("sink").unwrap();
-            if sink_pad.is_linked() {
+            if video_entry.is_linked() {
                 return;
             }
-            src_pad.link(&sink_pad).expect("Failed to link video pad");
+            if let Err(err) = src_pad.link(&video_entry) {
+                tracing::error!(?err, "linking the video pad failed");
+                gst::element_error!(
+                    decode,
+                    gst::CoreError::Negotiation,
+                    ("Failed to link video pad; {err:?}")
+                );
+            }
         }
     });
 
-    pipeline.set_state(gst::State::Playing).unwrap();
+    // failing to start posts an error that the bus loop reports
+    let _ = pipeline.set_state(gst::State::Playing);
 
-    let bus = pipeline.bus().unwrap();
-
-    (pipeline, bus)
-}
+    let bus = pipeline.bus().expect("pipelines always have a bus");
 
-pub struct QuitHandler {
-    callbacks: Vec<Box<dyn FnOnce()>>,
+    Ok((pipeline, bus))
 }
 
-impl QuitHandler {
-    pub fn add(&mut self, callback: impl FnOnce() + 'static) {
-        self.callbacks.push(Box::new(callback))
-    }
-}
-
-impl Drop for QuitHandler {
-    fn drop(&mut self) {
-        for callback in self.callbacks.drain(..) {
-            callback()
+fn preview_main(args: PreviewArgs) -> Result<(), PlayerError> {
+    let placement = match (args.preview_geometry, args.size) {
+        (Some(geometry), _) => preview::Placement::Pane(geometry),
+        (None, Some(size)) => preview::Placement::Lines((size.width, size.height)),
+        // one row is left for the prompt coming back after it
+        (None, None) => {
+            let (columns, rows) = terminal::size().unwrap_or((80, 24));
+            preview::Placement::Lines((columns, rows.saturating_sub(1).max(1)))
         }
+    };
+    let (palette, glyphs) = args.cells.resolve(false);
+    preview::show(&args.file, args.at, placement, palette, glyphs)?;
+    if args.preview_geometry.is_some() {
+        std::process::exit(preview::DRAWN_EXIT_CODE);
     }
+    Ok(())
 }
 
-#[derive(Debug, Clone)]
-struct Size {
-    width: u16,
-    height: u16,
-}
-
-impl std::str::FromStr for Size {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (w, h) = s.split_once('x').ok_or_else(|| {
-            "size must be in the form {WIDTH}x{HEIGHT} (e.g. 800x600)".to_string()
-        })?;
-
-        let parse = |v: &str| v.parse::<u16>();
-
-        let width = parse(w).map_err(|_| "width must be a positive integer".to_string())?;
-        let height = parse(h).map_err(|_| "height must be a positive integer".to_string())?;
-
-        Ok(Size { width, height })
-    }
-}
-
-#[derive(clap::Parser, Debug)]
-#[command(name = "videoplayer")]
-#[command(about = "Simple video player CLI")]
-struct Cli {
-    /// Video file to play (positional)
-    video: PathBuf,
-
-    /// Window size in the form WIDTHxHEIGHT, e.g. 1280x720
-    #[arg(long, value_parser = clap::value_parser!(Size))]
-    size: Option<Size>,
-}
-
-fn program_main() {
-    let cli = Cli::parse();
-
-    let mut quit_handler = QuitHandler { callbacks: vec![] };
-
-    let size = cli.size.map(|size| (size.width, size.height));
-    let (pipeline, bus) = make_pipeline_and_bus(&mut quit_handler, cli.video, size);
-
-    let defer = defer::defer(|| {
-        pipeline.set_state(gst::State::Null).unwrap();
-    });
-
-    input_handler::start(bus.downgrade(), pipeline.downgrade());
-
-    for msg in bus.iter_timed(None) {
-        use gst::MessageView;
-
-        match msg.view() {
-            MessageView::Error(err) => {
-                drop((bus, defer));
-                drop(pipeline);
-                drop(quit_handler);
-
-                eprintln!("{}", termion::clear::All);
-
-                eprintln!(
-                    "Error received from element {:?}: {}",
-                    err.src()
-                        .map(|s| s.path_string())
-                        .unwrap_or_else(|| glib::gstr!("unknown").to_owned()),
-                    err.error()
-                );
-                eprintln!("Debugging information: {:?}", err.debug());
-                break;
+fn program_main() -> Result<(), PlayerError> {
+    let cli = Cli::parse_from(config::args(&Cli::command())?);
+    match cli.command {
+        Command::Play(args) => play::run(
+            args.playback,
+            args.render,
+            args.debug,
+            play::Output::Terminal {
+                terminal: args.terminal,
+                audio: args.audio,
+                session: args.session,
+            },
+        ),
+        Command::Preview(args) => preview_main(args),
+        #[cfg(feature = "serde")]
+        Command::Probe(args) => probe::show(&args.file, args.json),
+        #[cfg(not(feature = "serde"))]
+        Command::Probe(args) => probe::show(&args.file, false),
+        Command::Bench(args) => {
+            let (palette, glyphs) = args.cells.resolve(false);
+            let options = bench::Options {
+                frames: args.frames,
+                size: (args.size.width, args.size.height),
+                compact: args.compact,
+                palette,
+                glyphs,
+                threads: args.render_threads,
+                scaler: args.scaler,
+            };
+            bench::run(&args.file, &options)
+        }
+        Command::Wall(args) => {
+            let grid = args
+                .grid
+                .unwrap_or_else(|| wall::Grid::fitting(args.videos.len()));
+            if grid.tiles() < args.videos.len() {
+                conflict("the grid has fewer tiles than there are videos");
             }
-            MessageView::Eos(_) => break,
-            _ => (),
+            let (palette, glyphs) = args.cells.resolve(false);
+            wall::play(&args.videos, grid, palette, glyphs)
+        }
+        Command::Compare(args) => {
+            let (palette, glyphs) = args.cells.resolve(false);
+            compare::play(
+                (&args.first, &args.second),
+                palette,
+                glyphs,
+                args.cell_aspect.unwrap_or_default(),
+            )
+        }
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+            Ok(())
+        }
+        Command::Man => {
+            // nobody reading all of it, like `| head`, is fine
+            let _ = clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout());
+            Ok(())
+        }
+        Command::History { count } => {
+            history::print(&history::History::load(), count);
+            Ok(())
+        }
+        Command::Serve {
+            addr,
+            playback,
+            render,
+            session,
+            debug,
+        } => play::run(
+            playback,
+            render,
+            debug,
+            play::Output::Serve { addr, session },
+        ),
+        Command::Transcode {
+            output,
+            height: Some(height),
+            fps,
+            playback,
+            ..
+        } => {
+            let [video] = playback.videos.as_slice() else {
+                conflict("transcode --height makes a proxy of one file");
+            };
+            proxy::transcode(video, &output, proxy::Options { height, fps })?;
+            eprintln!("wrote {}", output.display());
+            Ok(())
+        }
+        Command::Transcode {
+            output,
+            height: None,
+            playback,
+            render,
+            debug,
+            ..
+        } => {
+            if render.size.is_none() {
+                Cli::command()
+                    .error(
+                        clap::error::ErrorKind::MissingRequiredArgument,
+                        "transcode needs --size, what the dump gets rendered at",
+                    )
+                    .exit()
+            }
+            play::run(playback, render, debug, play::Output::Dump(output))
         }
     }
 }
@@ -251,5 +444,9 @@ fn program_main() {
 fn main() {
     // launch::run is only required to set up the application environment on macOS
     // (but not necessary in normal Cocoa applications where this is set up automatically)
-    launch::run(program_main);
+    // everything was dropped by now, so the terminal is back to normal
+    if let Err(err) = launch::run(program_main) {
+        eprintln!("{err}");
+        std::process::exit(err.exit_code());
+    }
 }
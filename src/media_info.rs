@@ -0,0 +1,105 @@
+use gst::prelude::{ElementExt, GstBinExt, PadExt};
+use gstreamer_video as gst_video;
+
+/// adds `part` to a line made of comma separated parts
+fn append(line: &mut Option<String>, part: String) {
+    match line {
+        Some(line) => {
+            line.push_str(", ");
+            line.push_str(&part);
+        }
+        None => *line = Some(part),
+    }
+}
+
+/// What the `i` panel knows about a file, from its tags and what got negotiated
+pub struct MediaInfo {
+    tags: gst::TagList,
+    file_size: Option<u64>,
+}
+
+impl MediaInfo {
+    /// streams have no `file`, and so no size
+    pub fn new(file: Option<&std::fs::File>) -> Self {
+        Self {
+            tags: gst::TagList::new(),
+            file_size: file
+                .and_then(|file| file.metadata().ok())
+                .map(|metadata| metadata.len()),
+        }
+    }
+
+    /// takes in tags posted on the bus, later ones win
+    pub fn merge(&mut self, tags: &gst::TagList) {
+        self.tags = self.tags.merge(tags, gst::TagMergeMode::Replace);
+    }
+
+    fn tag<'a, T: gst::Tag<'a, TagType = &'a str>>(&'a self) -> Option<&'a str> {
+        self.tags.get::<T>().map(|value| value.get())
+    }
+
+    /// one line per thing that's known, `name  value`
+    pub fn describe(&self, pipeline: &gst::Pipeline) -> String {
+        let mut lines = vec![];
+
+        if let Some(title) = self.tag::<gst::tags::Title>() {
+            lines.push(("title", title.to_string()));
+        }
+        if let Some(artist) = self.tag::<gst::tags::Artist>() {
+            lines.push(("artist", artist.to_string()));
+        }
+        if let Some(container) = self.tag::<gst::tags::ContainerFormat>() {
+            lines.push(("container", container.to_string()));
+        }
+
+        let caps = |element: &str| {
+            pipeline
+                .by_name(element)?
+                .static_pad("sink")?
+                .current_caps()
+        };
+
+        let mut video = self.tag::<gst::tags::VideoCodec>().map(str::to_string);
+        if let Some(info) = caps(video_less::terminal_sink::ELEMENT_NAME)
+            .and_then(|caps| gst_video::VideoInfo::from_caps(&caps).ok())
+        {
+            append(&mut video, format!("{}x{}", info.width(), info.height()));
+            let fps = info.fps();
+            if fps.numer() > 0 && fps.denom() > 0 {
+                append(
+                    &mut video,
+                    format!("{:.2} fps", f64::from(fps.numer()) / f64::from(fps.denom())),
+                );
+            }
+        }
+        if let Some(video) = video {
+            lines.push(("video", video));
+        }
+
+        let mut audio = self.tag::<gst::tags::AudioCodec>().map(str::to_string);
+        if let Some(structure) = caps(crate::audio_sink::NAME)
+            .as_ref()
+            .and_then(|caps| caps.structure(0))
+        {
+            if let Ok(rate) = structure.get::<i32>("rate") {
+                append(&mut audio, format!("{rate} Hz"));
+            }
+            if let Ok(channels) = structure.get::<i32>("channels") {
+                append(&mut audio, format!("{channels} channels"));
+            }
+        }
+        if let Some(audio) = audio {
+            lines.push(("audio", audio));
+        }
+
+        if let Some(size) = self.file_size {
+            lines.push(("size", format!("{:.1} MB", size as f64 / 1_000_000.0)));
+        }
+
+        lines
+            .into_iter()
+            .map(|(name, value)| format!("{name:<10} {value}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
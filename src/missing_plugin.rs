@@ -0,0 +1,178 @@
+use std::fmt::Write as _;
+
+/// A plugin gstreamer went looking for and didn't find,
+/// out of the `missing-plugin` element messages decodebin posts before giving up
+pub struct MissingPlugin {
+    pub description: String,
+    detail: String,
+}
+
+impl MissingPlugin {
+    /// parses the structure the way `gst_missing_plugin_message_parse_*` would
+    pub fn parse(structure: &gst::StructureRef) -> Option<Self> {
+        if !structure.has_name("missing-plugin") {
+            return None;
+        }
+
+        let detail = match structure.get::<gst::Caps>("detail") {
+            Ok(caps) => caps
+                .structure(0)
+                .map(|s| s.name().to_string())
+                .unwrap_or_default(),
+            Err(_) => structure.get::<String>("detail").unwrap_or_default(),
+        };
+        let description = structure
+            .get::<String>("name")
+            .unwrap_or_else(|_| detail.clone());
+
+        Some(Self {
+            description,
+            detail,
+        })
+    }
+
+    /// parses what discoverers give for what's missing, e.g.
+    /// `gstreamer|1.0|videoplayer|H.264 (High Profile) decoder|decoder-video/x-h264`
+    pub fn from_installer_detail(installer_detail: &str) -> Self {
+        let mut fields = installer_detail.split('|').skip(3);
+        let description = fields.next().unwrap_or(installer_detail).to_string();
+        // the caps come after the kind of element, like `decoder-`, and before their fields
+        let detail = fields
+            .next()
+            .and_then(|wanted| wanted.split_once('-'))
+            .map(|(_, caps)| caps.split(',').next().unwrap_or(caps).trim().to_string())
+            .unwrap_or_default();
+
+        Self {
+            description,
+            detail,
+        }
+    }
+
+    /// the patent encumbered formats only libav ships decoders for on most distros
+    fn needs_libav(&self) -> bool {
+        [
+            "video/x-h264",
+            "video/x-h265",
+            "video/mpeg",
+            "video/x-divx",
+            "video/x-msmpeg",
+            "video/x-wmv",
+            "audio/mpeg",
+            "audio/x-ac3",
+            "audio/x-eac3",
+            "audio/x-dts",
+            "audio/x-wma",
+        ]
+        .contains(&self.detail.as_str())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Distro {
+    Debian,
+    Fedora,
+    Arch,
+    OpenSuse,
+    MacOs,
+    Windows,
+    Unknown,
+}
+
+impl Distro {
+    fn detect() -> Self {
+        if cfg!(target_os = "macos") {
+            return Distro::MacOs;
+        }
+        if cfg!(windows) {
+            return Distro::Windows;
+        }
+
+        let release = std::fs::read_to_string("/etc/os-release").unwrap_or_default();
+        let ids = release
+            .lines()
+            .filter_map(|line| {
+                line.strip_prefix("ID=")
+                    .or_else(|| line.strip_prefix("ID_LIKE="))
+            })
+            .flat_map(|ids| ids.trim_matches('"').split_whitespace())
+            .collect::<Vec<_>>();
+
+        let is = |names: &[&str]| ids.iter().any(|id| names.contains(id));
+        match () {
+            _ if is(&["debian", "ubuntu"]) => Distro::Debian,
+            _ if is(&["fedora", "rhel"]) => Distro::Fedora,
+            _ if is(&["arch"]) => Distro::Arch,
+            _ if is(&["suse", "opensuse"]) => Distro::OpenSuse,
+            _ => Distro::Unknown,
+        }
+    }
+
+    fn install_command(self, libav: bool) -> String {
+        let (command, bad, libav_package) = match self {
+            Distro::Debian => (
+                "sudo apt install",
+                "gstreamer1.0-plugins-bad",
+                "gstreamer1.0-libav",
+            ),
+            Distro::Fedora => (
+                "sudo dnf install",
+                "gstreamer1-plugins-bad-free",
+                "gstreamer1-plugin-libav",
+            ),
+            Distro::Arch => ("sudo pacman -S", "gst-plugins-bad", "gst-libav"),
+            Distro::OpenSuse => (
+                "sudo zypper install",
+                "gstreamer-plugins-bad",
+                "gstreamer-plugins-libav",
+            ),
+            Distro::MacOs => return "brew install gstreamer".to_string(),
+            Distro::Windows => {
+                return "the complete installer from https://gstreamer.freedesktop.org/download/"
+                    .to_string();
+            }
+            Distro::Unknown => {
+                return "install the gstreamer plugins-good, plugins-bad and libav packages"
+                    .to_string();
+            }
+        };
+
+        let mut line = format!("{command} {bad}");
+        if libav {
+            let _ = write!(line, " {libav_package}");
+        }
+        line
+    }
+}
+
+/// what to tell the user to get `missing` installed
+pub fn hint(missing: &[MissingPlugin]) -> String {
+    let mut hint = String::from("missing gstreamer plugins for:\n");
+    for plugin in missing {
+        let _ = writeln!(hint, "  {}", plugin.description);
+    }
+
+    let libav = missing.iter().any(MissingPlugin::needs_libav);
+    let _ = write!(hint, "try: {}", Distro::detect().install_command(libav));
+    hint
+}
+
+#[cfg(test)]
+mod test {
+    use super::MissingPlugin;
+
+    #[test]
+    fn reads_installer_details() {
+        let plugin = MissingPlugin::from_installer_detail(
+            "gstreamer|1.0|videoplayer|H.264 (High Profile) decoder|decoder-video/x-h264, profile=(string)high",
+        );
+        assert_eq!(plugin.description, "H.264 (High Profile) decoder");
+        assert_eq!(plugin.detail, "video/x-h264");
+        assert!(plugin.needs_libav());
+
+        let plugin =
+            MissingPlugin::from_installer_detail("gstreamer|1.0|videoplayer|bs2b|element-bs2b");
+        assert_eq!(plugin.detail, "bs2b");
+        assert!(!plugin.needs_libav());
+    }
+}
@@ -0,0 +1,747 @@
+use crate::PipelineOptions;
+use crate::cli::conflict;
+use crate::cli::{AudioArgs, Cli, DebugArgs, PlaybackArgs, RenderArgs, SessionArgs, TerminalArgs};
+#[cfg(all(unix, feature = "ipc"))]
+use crate::ipc;
+#[cfg(feature = "serde")]
+use crate::status;
+use crate::{audio_sink, input_handler, media_info, missing_plugin, seek_preview};
+use crate::{clip, dot, history, image, live, logging, loudness, retry, seat};
+use clap::CommandFactory;
+#[cfg(feature = "serde")]
+use glib::object::Cast;
+use gst::prelude::{ElementExt, ElementExtManual, GstBinExt, GstObjectExt};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use video_less::error::PlayerError;
+use video_less::{QuitHandler, ansi_dump, flag, terminal, terminal_sink};
+
+/// how long a file skipped for not opening stays on screen
+const SKIPPED_SHOWN_FOR: Duration = Duration::from_secs(3);
+
+/// The part of every file `--start`, `--end` and `--duration` leave to be played
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Trim {
+    start: gst::ClockTime,
+    /// the end of the file when `None`
+    end: Option<gst::ClockTime>,
+}
+
+impl Trim {
+    fn new(
+        start: Option<gst::ClockTime>,
+        end: Option<gst::ClockTime>,
+        duration: Option<gst::ClockTime>,
+    ) -> Result<Option<Trim>, &'static str> {
+        let start_at = start.unwrap_or(gst::ClockTime::ZERO);
+        let end = end.or(duration.map(|duration| start_at + duration));
+        if end.is_some_and(|end| end <= start_at) {
+            return Err("--end has to come after --start");
+        }
+        Ok((start.is_some() || end.is_some()).then_some(Trim {
+            start: start_at,
+            end,
+        }))
+    }
+
+    /// goes to the start of the part, as a segment when it loops so it ends with a `SegmentDone`
+    /// rather than an EOS and the next time round can go without flushing, which makes no gap
+    fn seek(self, pipeline: &gst::Pipeline, looping: bool, flush: bool) -> bool {
+        let mut flags = gst::SeekFlags::ACCURATE;
+        if flush {
+            flags |= gst::SeekFlags::FLUSH;
+        }
+        if looping {
+            flags |= gst::SeekFlags::SEGMENT;
+        }
+        let stop_type = match self.end {
+            Some(_) => gst::SeekType::Set,
+            None => gst::SeekType::None,
+        };
+        let res = pipeline.seek(
+            1.0,
+            flags,
+            gst::SeekType::Set,
+            self.start,
+            stop_type,
+            self.end,
+        );
+        if let Err(err) = &res {
+            tracing::warn!(%err, trim = ?self, "seeking to the part to play failed");
+        }
+        res.is_ok()
+    }
+}
+
+/// Where the frames end up
+pub enum Output {
+    /// the terminal, with the sound and the keys
+    Terminal {
+        terminal: TerminalArgs,
+        audio: AudioArgs,
+        session: SessionArgs,
+    },
+    /// everyone connecting at `addr`, with the keys of the terminal it runs on
+    Serve {
+        addr: SocketAddr,
+        session: SessionArgs,
+    },
+    /// an ANSI dump at the path, nothing else
+    Dump(PathBuf),
+}
+
+/// plays everything `playback` asks for until the end of the playlist or quitting
+pub fn run(
+    playback: PlaybackArgs,
+    render: RenderArgs,
+    debug: DebugArgs,
+    output: Output,
+) -> Result<(), PlayerError> {
+    // neither a server nor a dump has a terminal of its own
+    let (screen, audio, session, serve, dump) = match output {
+        Output::Terminal {
+            terminal: screen,
+            audio,
+            session,
+        } => (Some(screen), Some(audio), Some(session), None, None),
+        Output::Serve { addr, session } => (None, None, Some(session), Some(addr), None),
+        Output::Dump(path) => (None, None, None, None, Some(path)),
+    };
+    let play_ansi = screen.as_ref().and_then(|screen| screen.play_ansi.clone());
+
+    let nothing_given = playback.videos.is_empty()
+        && !playback.recent
+        && playback.from_history.is_none()
+        && playback.pipeline.is_none();
+    if nothing_given && play_ansi.is_none() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "nothing to play was given, such as videos or --pipeline",
+            )
+            .exit()
+    }
+
+    // asking for a file without a level means the usual amount, where the drift gets logged too
+    if (debug.log_level.is_some() || debug.log_file.is_some() || debug.sync_debug.is_some())
+        && let Some(file) = debug.log_file.clone().or_else(logging::default_file)
+    {
+        logging::init(debug.log_level.unwrap_or(logging::LogLevel::Info), &file)?;
+    }
+
+    // later presets win, which puts the command line over the config file
+    let eq = audio.as_ref().and_then(|audio| {
+        let eq = audio.eq.as_deref()?;
+        let preset = audio.eq_preset.iter().rev().find(|(name, _)| name == eq);
+        Some(match preset {
+            Some(&(_, gains)) => gains,
+            None => eq.parse().unwrap_or_else(|err| {
+                Cli::command()
+                    .error(
+                        clap::error::ErrorKind::InvalidValue,
+                        format!("--eq {eq} is neither a preset nor gains: {err}"),
+                    )
+                    .exit()
+            }),
+        })
+    });
+
+    let terminal_backend = screen
+        .as_ref()
+        .map(|screen| screen.terminal_backend)
+        .unwrap_or_default();
+    if let Some(ref dump) = play_ansi {
+        terminal::select(terminal_backend);
+        return ansi_dump::play(dump);
+    }
+
+    let mut history = history::History::load();
+    // a description is the one entry of its playlist, going by its text
+    let videos = match (&playback.pipeline, playback.recent, playback.from_history) {
+        (Some(description), ..) => vec![PathBuf::from(description)],
+        (None, true, _) => match history::pick_recent(&history) {
+            Some(video) => vec![video],
+            None => return Ok(()),
+        },
+        (None, false, Some(n)) => match history.nth(n.get()) {
+            Some(video) => vec![video.to_path_buf()],
+            None => Cli::command()
+                .error(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!(
+                        "--from-history {n} goes back further than the {} files played so far",
+                        history.recent().count()
+                    ),
+                )
+                .exit(),
+        },
+        (None, false, None) => playback.videos,
+    };
+
+    // find out about missing files before taking over the terminal
+    let mut playlist = videos
+        .into_iter()
+        .map(|video| match live::uri(&video) {
+            _ if playback.pipeline.is_some() => Ok((video, None)),
+            Some(_) => Ok((video, None)),
+            None => match std::fs::File::open(&video) {
+                Ok(file) => Ok((video, Some(file))),
+                Err(source) => Err(PlayerError::Open {
+                    path: video,
+                    source,
+                }),
+            },
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // clap exits without giving the terminal back, so this goes before it's taken
+    let trim = Trim::new(playback.start, playback.end, playback.duration)
+        .unwrap_or_else(|err| conflict(err));
+    if let Some(ref output) = playback.export_clip {
+        let [(video, Some(_))] = playlist.as_slice() else {
+            conflict("--export-clip cuts one clip out of one file");
+        };
+        let Some(trim) = trim else {
+            conflict("--export-clip needs --start, --end or --duration to know what to cut out");
+        };
+        clip::export(video, (trim.start, trim.end), output, playback.export_mode)?;
+        eprintln!("exported {}", output.display());
+        return Ok(());
+    }
+
+    // two players on one terminal would corrupt each other's frames and steal each other's keys
+    // neither a dump nor a server touches the terminal at all
+    let headless = screen.is_none();
+
+    let _seat = match headless || flag("NO_TTY", false) {
+        true => None,
+        false => match seat::claim() {
+            Ok(seat) => seat,
+            Err(device) => return Err(PlayerError::SeatTaken(device)),
+        },
+    };
+
+    // crossterm draws on stdout, where the status has to go
+    #[cfg(feature = "serde")]
+    let status_json = session.as_ref().is_some_and(|session| session.status_json);
+    #[cfg(feature = "serde")]
+    if status_json && !headless && terminal_backend == terminal::Backend::Crossterm {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--status-json needs the termion backend, which draws on /dev/tty",
+            )
+            .exit();
+    }
+    terminal::select(terminal_backend);
+    let pause_on_unfocus = screen
+        .as_ref()
+        .is_some_and(|screen| screen.pause_on_unfocus);
+    if pause_on_unfocus {
+        terminal::report_focus();
+    }
+    #[cfg(unix)]
+    if let Some(tty) = screen.as_ref().and_then(|screen| screen.tty.clone()) {
+        terminal::redirect(&tty).map_err(|source| PlayerError::Open { path: tty, source })?;
+    }
+    let mut quit_handler = QuitHandler::new();
+
+    // a dump or a server has no terminal to be far from
+    let profile = match (render.profile, headless) {
+        (Some(profile), _) => profile,
+        (None, true) => terminal::profile::Profile::Local,
+        (None, false) => terminal::profile::detect(),
+    };
+    tracing::debug!(?profile, "picked the defaults for the terminal");
+    let defaults = profile.defaults();
+
+    let stats = Arc::new(terminal_sink::RenderStats::default());
+    let settings = Arc::new(terminal_sink::LiveSettings::new(
+        render.diff_precision.unwrap_or(defaults.precision),
+        render.stats,
+    ));
+    settings.set_scaler(render.scaler);
+    settings.set_post_filter(render.filter);
+    if let Some(corner) = render.show_time {
+        settings.show_clock(corner);
+    }
+
+    // neither a dump nor a server has a terminal to ask
+    let (palette, glyphs) = render.cells.resolve(!headless);
+    let font_cell = render.font_cell.map(|cell| (cell.width, cell.height));
+    let font_aspect = font_cell.and_then(|(width, height)| {
+        terminal_sink::CellAspect::new(f64::from(height) / f64::from(width))
+    });
+    let cell_aspect = render.cell_aspect.or(font_aspect).unwrap_or_default();
+
+    #[cfg(unix)]
+    if let Some(rows) = screen.as_ref().and_then(|screen| screen.inline) {
+        terminal::inline::reserve(rows).map_err(PlayerError::Terminal)?;
+    }
+
+    let mut sink = terminal_sink::TerminalVideoSink::builder()
+        .size(render.size.map(|size| (size.width, size.height)))
+        .compact(render.compact)
+        .palette(palette)
+        .glyphs(glyphs)
+        .background(render.background)
+        .tone_map(render.tone_map)
+        .color_filter(render.color_filter)
+        .render_mode(render.render_mode)
+        .symbols(render.symbols.with(render.symbol))
+        .font_cell(font_cell)
+        .decode_scale(render.decode_scale)
+        .render_threads(render.render_threads)
+        .queue_mode(render.queue_mode)
+        .max_lateness(render.max_lateness.map(Duration::from_millis))
+        .qos(render.qos)
+        .drop_when_full(render.drop)
+        .filters(render.vf)
+        .cell_aspect(cell_aspect)
+        .diff_threshold(render.diff_threshold)
+        .max_cells_per_frame(render.max_cells_per_frame)
+        .max_bandwidth(render.max_bandwidth)
+        .synchronized_updates(defaults.synchronized)
+        .serve(serve)
+        .dump_ansi(dump)
+        .sync_debug(debug.sync_debug)
+        .settings(Arc::clone(&settings))
+        .stats(Arc::clone(&stats));
+    if let Some(ref session) = session {
+        sink = sink.screenshot_dir(session.screenshot_dir.clone());
+    }
+    if let Some(ref screen) = screen {
+        sink = sink.record_cast(screen.record_cast.clone()).resize_polling(
+            terminal_sink::ResizePolling {
+                interval: Duration::from_millis(screen.resize_poll),
+                debounce: Duration::from_millis(screen.resize_debounce),
+            },
+        );
+    }
+    #[cfg(feature = "serde")]
+    let sink = sink.grid_dump(debug.dump_grid);
+    let sink = sink.start(&mut quit_handler)?;
+
+    // runs after the renderer let go of the terminal
+    if flag("PRINT_STATS", false) {
+        quit_handler.add(move || eprintln!("{stats}"));
+    }
+
+    if let Some(hwdec) = playback.hwdec {
+        hwdec.apply();
+    }
+
+    let decoder_log = debug
+        .verbose
+        .then(Arc::<parking_lot::Mutex<Vec<String>>>::default);
+    if let Some(ref log) = decoder_log {
+        let log = Arc::clone(log);
+        quit_handler.add(move || {
+            for line in log.lock().iter() {
+                eprintln!("{line}");
+            }
+        });
+    }
+
+    let loudness = std::cell::Cell::new(match audio.as_ref().and_then(|audio| audio.volume) {
+        Some(percent) => loudness::Loudness {
+            volume: f64::from(percent) / 100.0,
+            mute: false,
+        },
+        None => loudness::Loudness::load(),
+    });
+    // the equalizer carries over the same way, unless one is asked for
+    let preset = audio.as_ref().and_then(|audio| audio.equalizer);
+    let equalizer = std::cell::Cell::new(
+        eq.or(preset.map(audio_sink::EqGains::from))
+            .or_else(loudness::load_equalizer),
+    );
+
+    let mut options = PipelineOptions {
+        backend: playback.backend,
+        pipeline: playback.pipeline,
+        max_fps: render.max_fps.or(defaults.max_fps),
+        sink_queue: render.sink_queue,
+        hwdec: playback.hwdec,
+        image_duration: playback.image_duration,
+        latency: playback.latency.map(gst::ClockTime::from_mseconds),
+        decoder_log,
+        audio: audio_sink::Options {
+            equalizer: preset,
+            eq: equalizer.get(),
+            filter: audio.as_ref().and_then(|audio| audio.af.clone()),
+            crossfeed: audio.as_ref().is_some_and(|audio| audio.crossfeed),
+            sink: audio.as_ref().and_then(|audio| audio.audio_sink.clone()),
+            volume: 1.0,
+            mute: false,
+        },
+        // playing the sound would hold a dump back to real time, and there's nobody listening to a server
+        no_audio: audio.as_ref().is_none_or(|audio| audio.no_audio),
+        #[cfg(feature = "sub-auto")]
+        sub_auto: playback.sub_auto,
+    };
+
+    let controls = input_handler::Controls::default();
+    #[cfg(feature = "serde")]
+    if status_json {
+        status::report_positions(&mut quit_handler, controls.clone());
+    }
+    #[cfg(all(unix, feature = "ipc"))]
+    if let Some(path) = session
+        .as_ref()
+        .and_then(|session| session.ipc_socket.as_ref())
+    {
+        let remove =
+            ipc::start(path, controls.clone(), Arc::clone(&settings)).map_err(|source| {
+                PlayerError::Open {
+                    path: path.clone(),
+                    source,
+                }
+            })?;
+        quit_handler.add(remove);
+    }
+    // raw mode turns Ctrl-C into a key, this covers everything else
+    #[cfg(unix)]
+    {
+        let (controls, settings) = (controls.clone(), Arc::clone(&settings));
+        video_less::shutdown::install(&mut quit_handler, move || {
+            controls.apply(&settings, input_handler::Action::Quit);
+        });
+    }
+    #[cfg(unix)]
+    if !headless {
+        let (controls, live) = (controls.clone(), Arc::clone(&settings));
+        video_less::suspend::install(&mut quit_handler, Arc::clone(&settings), move |pause| {
+            match pause {
+                true => controls.playing() && controls.apply(&live, input_handler::Action::Pause),
+                false => controls.apply(&live, input_handler::Action::Play),
+            }
+        });
+    }
+    // a dump has nobody at the keyboard
+    if let Some(ref session) = session {
+        input_handler::start(
+            &mut quit_handler,
+            controls.clone(),
+            Arc::clone(&settings),
+            session.seek_step,
+            pause_on_unfocus,
+        );
+    }
+
+    let policy = retry::Policy {
+        retries: playback.retries,
+        delay: Duration::from_millis(playback.retry_delay),
+    };
+    // the last file that failed and got another go, and whether that go is up next
+    let mut retried: Option<retry::Retry> = None;
+    let mut retrying = false;
+
+    let mut index = 0;
+    'playlist: while index < playlist.len() {
+        let resume = core::mem::take(&mut retrying).then_some(retried).flatten();
+        if resume.is_some() {
+            std::thread::sleep(policy.delay);
+        }
+        let video = playlist[index].0.clone();
+        // files played before have to be opened again
+        let file = match playlist[index].1.take() {
+            Some(file) => Some(file),
+            // streams have nothing to open
+            None if live::uri(&video).is_some() || options.pipeline.is_some() => None,
+            None => match std::fs::File::open(&video) {
+                Ok(file) => Some(file),
+                Err(err) => {
+                    tracing::warn!(%err, video = %video.display(), "skipping a file that can't be opened");
+                    let message = format!("skipped {}: {err}", video.display());
+                    settings.notify(message, SKIPPED_SHOWN_FOR);
+                    index += 1;
+                    continue 'playlist;
+                }
+            },
+        };
+
+        if options.pipeline.is_none() {
+            history.record(&video);
+        }
+        let mut previous = history.before(&video).map(Path::to_path_buf);
+        let mut next = index + 1;
+        let mut missing_plugins = vec![];
+        let kind = image::kind(&video);
+        let loops = options.pipeline.is_none()
+            && (playback.loop_playback
+                || (kind == Some(image::Kind::Animated) && !playback.no_gif_loop));
+        // a whole pipeline given and a still have nothing to trim
+        let trim = trim.filter(|_| options.pipeline.is_none() && kind != Some(image::Kind::Still));
+        // the rest of the part to play from where the last go got to
+        let resume = resume.and_then(|retry| retry.position).map(|start| Trim {
+            start,
+            end: trim.and_then(|trim| trim.end),
+        });
+
+        sink.show_loading();
+        #[cfg(feature = "serde")]
+        if status_json {
+            status::file(&video);
+        }
+
+        let info = media_info::MediaInfo::new(file.as_ref());
+        // there's nothing to seek to ahead of a live stream
+        let preview = (!headless && file.is_some() && !flag("NO_SEEK_PREVIEW", false))
+            .then(|| seek_preview::Previewer::new(video.clone(), Arc::clone(&settings)));
+        // the volume carries over from one file to the next, and to the next run
+        let loudness::Loudness { volume, mute } = loudness.get();
+        (options.audio.volume, options.audio.mute) = (volume, mute);
+        options.audio.eq = equalizer.get();
+        let (pipeline, bus) = crate::make_pipeline_and_bus(&sink, (video, file), &options)?;
+        controls.attach(&bus, &pipeline, info, preview);
+        // the spectrum stays up from one file to the next
+        audio_sink::show_spectrum(&pipeline, settings.spectrum_shown());
+        // held back until it's prerolled and seeked, so nothing before the start plays
+        let mut untrimmed = trim.is_some() || resume.is_some();
+        if untrimmed {
+            let _ = pipeline.set_state(gst::State::Paused);
+        }
+
+        let defer = defer::defer(|| {
+            if let Some((volume, mute)) = audio_sink::loudness(&pipeline) {
+                let now = loudness::Loudness { volume, mute };
+                if now != loudness.get() {
+                    loudness.set(now);
+                    let _ = now.save();
+                }
+            }
+            if let Some(gains) = audio_sink::eq_gains(&pipeline)
+                && Some(gains) != equalizer.get()
+            {
+                equalizer.set(Some(gains));
+                let _ = loudness::save_equalizer(gains);
+            }
+            pipeline.set_state(gst::State::Null).unwrap();
+        });
+
+        for msg in bus.iter_timed(None) {
+            use gst::MessageView;
+
+            if let Some(dir) = &debug.dump_dot {
+                dot::on_message(&pipeline, &msg, dir);
+            }
+            match msg.view() {
+                MessageView::Error(err) => {
+                    let again = policy
+                        .next(retried, index, retry::position(&pipeline))
+                        .filter(|_| retry::transient(&err.error()));
+                    if let Some(again) = again {
+                        tracing::warn!(
+                            error = %err.error(),
+                            attempt = again.attempts,
+                            position = ?again.position,
+                            "trying again after an error"
+                        );
+                        let message = format!(
+                            "{}, trying again ({}/{})",
+                            err.error(),
+                            again.attempts,
+                            policy.retries
+                        );
+                        settings.notify(message, policy.delay + retry::SHOWN_FOR);
+                        (retried, retrying) = (Some(again), true);
+                        next = index;
+                        break;
+                    }
+
+                    drop((bus, defer));
+                    drop(pipeline);
+                    drop(quit_handler);
+
+                    eprintln!("{}", terminal::CLEAR);
+
+                    // the error that follows a missing plugin doesn't say much
+                    if !missing_plugins.is_empty() {
+                        return Err(PlayerError::MissingPlugins {
+                            video: playlist[index].0.clone(),
+                            hint: missing_plugin::hint(&missing_plugins),
+                        });
+                    }
+
+                    return Err(PlayerError::Playback {
+                        element: err
+                            .src()
+                            .map(|s| s.path_string().to_string())
+                            .unwrap_or_else(|| "unknown".to_string()),
+                        error: err.error(),
+                        debug: err.debug().map(|debug| debug.to_string()),
+                    });
+                }
+                MessageView::Element(element) => {
+                    let Some(structure) = element.structure() else {
+                        continue;
+                    };
+                    if let Some(missing) = missing_plugin::MissingPlugin::parse(structure) {
+                        missing_plugins.push(missing);
+                    } else if let Some(levels) = audio_sink::spectrum_levels(structure) {
+                        settings.update_spectrum(levels);
+                    }
+                }
+                MessageView::Tag(tag) => {
+                    #[cfg(feature = "serde")]
+                    if status_json {
+                        status::tags(&tag.tags());
+                    }
+                    controls.merge_tags(&tag.tags());
+                }
+                #[cfg(feature = "serde")]
+                MessageView::StateChanged(change)
+                    if status_json
+                        && change.src() == Some(pipeline.upcast_ref::<gst::Object>()) =>
+                {
+                    status::state(change.current());
+                }
+                MessageView::AsyncDone(_) if untrimmed => {
+                    untrimmed = false;
+                    if let Some(resume) = resume {
+                        // looping without a part to play goes round on EOS, not as a segment
+                        resume.seek(&pipeline, loops && trim.is_some(), true);
+                    } else if let Some(trim) = trim {
+                        trim.seek(&pipeline, loops, true);
+                    }
+                    if controls.playing() {
+                        let _ = pipeline.set_state(gst::State::Playing);
+                    }
+                }
+                MessageView::AsyncDone(_) => controls.seek_done(),
+                // round the looping part again, the buffers of the next time round follow straight on
+                MessageView::SegmentDone(_) => {
+                    if let Some(trim) = trim {
+                        trim.seek(&pipeline, loops, false);
+                    }
+                }
+                // live sources can't wait, they get shown however far buffering is
+                MessageView::Buffering(buffering) => {
+                    let percent = buffering.percent();
+                    sink.show_buffering(percent.clamp(0, 100) as u8);
+                    if buffering.buffering_stats().0 != gst::BufferingMode::Live {
+                        controls.buffering(percent);
+                    }
+                }
+                // a live source joining or changing its mind, spread the latency out again
+                MessageView::Latency(_) => {
+                    if let Err(err) = pipeline.recalculate_latency() {
+                        tracing::warn!(%err, "recalculating the latency failed");
+                    }
+                }
+                // going through paused makes the pipeline pick a new clock
+                MessageView::ClockLost(_) => {
+                    tracing::debug!("the clock got lost, picking a new one");
+                    let _ = pipeline.set_state(gst::State::Paused);
+                    let _ = pipeline.set_state(gst::State::Playing);
+                }
+                // around again, or from scratch when the decoder can't seek
+                // the file doesn't end, so clients of --status-json aren't told it did
+                MessageView::Eos(_) if loops => {
+                    let seeked = match trim {
+                        Some(trim) => trim.seek(&pipeline, loops, true),
+                        None => pipeline
+                            .seek_simple(gst::SeekFlags::FLUSH, gst::ClockTime::ZERO)
+                            .is_ok(),
+                    };
+                    if !seeked {
+                        next = index;
+                        break;
+                    }
+                }
+                // only the file really ending, skipping to the next file posts its own message
+                MessageView::Eos(_) => {
+                    #[cfg(feature = "serde")]
+                    if status_json {
+                        status::eos();
+                    }
+                    break;
+                }
+                MessageView::Application(app)
+                    if app
+                        .structure()
+                        .is_some_and(|s| s.has_name(input_handler::QUIT)) =>
+                {
+                    break 'playlist;
+                }
+                MessageView::Application(app)
+                    if app
+                        .structure()
+                        .is_some_and(|s| s.has_name(input_handler::NEXT)) =>
+                {
+                    break;
+                }
+                MessageView::Application(app)
+                    if app
+                        .structure()
+                        .is_some_and(|s| s.has_name(input_handler::PREVIOUS)) =>
+                {
+                    // go back in the playlist, or to whatever was played before it
+                    if index > 0 {
+                        next = index - 1;
+                    } else if let Some(previous) = previous.take() {
+                        playlist.insert(0, (previous, None));
+                        next = 0;
+                    } else {
+                        continue;
+                    }
+                    break;
+                }
+                MessageView::Application(app)
+                    if app
+                        .structure()
+                        .is_some_and(|s| s.has_name(input_handler::LOAD)) =>
+                {
+                    let Some(path) = app.structure().and_then(|s| s.get::<String>("path").ok())
+                    else {
+                        continue;
+                    };
+                    playlist.insert(index + 1, (PathBuf::from(path), None));
+                    break;
+                }
+                MessageView::Application(app)
+                    if app
+                        .structure()
+                        .is_some_and(|s| s.has_name(input_handler::EXPORT_CLIP)) =>
+                {
+                    let (Some(structure), Some(session)) = (app.structure(), &session) else {
+                        continue;
+                    };
+                    let at = |field| {
+                        let nanos = structure.get::<u64>(field).ok();
+                        nanos.map(gst::ClockTime::from_nseconds)
+                    };
+                    let video = &playlist[index].0;
+                    let is_file = options.pipeline.is_none() && live::uri(video).is_none();
+                    clip::spawn(
+                        is_file.then(|| video.clone()),
+                        (at("start"), at("end")),
+                        session.clip_dir.clone(),
+                        playback.export_mode,
+                        Arc::clone(&settings),
+                    );
+                }
+                MessageView::Application(app)
+                    if app
+                        .structure()
+                        .is_some_and(|s| s.has_name(input_handler::DUMP_DOT)) =>
+                {
+                    let dir = debug.dump_dot.as_deref().unwrap_or(Path::new("."));
+                    let message = match dot::dump(&pipeline, dir, "key") {
+                        Ok(path) => format!("wrote {}", path.display()),
+                        Err(err) => format!("couldn't write the pipeline graph: {err}"),
+                    };
+                    settings.notify(message, dot::SHOWN_FOR);
+                }
+                _ => (),
+            }
+        }
+
+        index = next;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+//! `videoplayer preview`, printing a single frame where the cursor is and exiting,
+//! for shell scripts and the previewers of file managers like ranger and lf
+use crate::seek_preview;
+use gstreamer_video as gst_video;
+use std::io::Write;
+use std::num::NonZero;
+use std::path::Path;
+use video_less::error::PlayerError;
+use video_less::{RenderTarget, TerminalRenderer, terminal_sink};
+
+/// `[[HH:]MM:]SS`, the seconds can have a fraction
+pub fn parse_time(s: &str) -> Result<gst::ClockTime, String> {
+    let fields = s.split(':').collect::<Vec<_>>();
+    if fields.len() > 3 {
+        return Err("the time must be in the form [[HH:]MM:]SS".to_string());
+    }
+
+    let mut seconds = 0.0;
+    for field in fields {
+        let value = field
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite() && *value >= 0.0)
+            .ok_or_else(|| format!("{field:?} isn't a number of seconds, minutes or hours"))?;
+        seconds = seconds * 60.0 + value;
+    }
+
+    Ok(gst::ClockTime::from_nseconds((seconds * 1e9) as u64))
+}
+
+/// what tells lf that a preview got drawn on the terminal by itself, nothing printed is cached
+pub const DRAWN_EXIT_CODE: i32 = 1;
+
+/// The cells of a file manager's preview pane, `{WIDTH}x{HEIGHT}@{X},{Y}` counting from 0
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Geometry {
+    pub size: (u16, u16),
+    pub origin: (u16, u16),
+}
+
+impl std::str::FromStr for Geometry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let form = || "the geometry must be in the form {WIDTH}x{HEIGHT}@{X},{Y}".to_string();
+        let (size, origin) = s.split_once('@').ok_or_else(form)?;
+        let (width, height) = size.split_once('x').ok_or_else(form)?;
+        let (x, y) = origin.split_once(',').ok_or_else(form)?;
+
+        let parse = |v: &str| v.trim().parse::<u16>().map_err(|_| form());
+        let size = (parse(width)?, parse(height)?);
+        if size.0 == 0 || size.1 == 0 {
+            return Err("the preview pane can't be empty".to_string());
+        }
+
+        Ok(Geometry {
+            size,
+            origin: (parse(x)?, parse(y)?),
+        })
+    }
+}
+
+/// Where the frame goes
+#[derive(Debug, Copy, Clone)]
+pub enum Placement {
+    /// printed line by line from the cursor, fitted into this many cells
+    Lines((u16, u16)),
+    /// drawn straight onto the terminal, without touching a cell outside
+    Pane(Geometry),
+}
+
+/// the terminal itself, since file managers read what their previewers print
+fn pane_output() -> Box<dyn Write> {
+    match std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(tty) => Box::new(tty),
+        Err(_) => Box::new(std::io::stdout()),
+    }
+}
+
+/// shows the frame at `position` of `video` at `placement`
+pub fn show(
+    video: &Path,
+    position: gst::ClockTime,
+    placement: Placement,
+    palette: terminal_sink::Palette,
+    glyphs: terminal_sink::Glyphs,
+) -> Result<(), PlayerError> {
+    let sample = seek_preview::frame_at(video, position)?;
+    let no_frame = || PlayerError::NoFrame {
+        video: video.to_path_buf(),
+        position,
+    };
+
+    let info = sample
+        .caps()
+        .and_then(|caps| gst_video::VideoInfo::from_caps(caps).ok())
+        .ok_or_else(no_frame)?;
+    let buffer = sample.buffer().ok_or_else(no_frame)?;
+    let frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, &info)
+        .map_err(|_| no_frame())?;
+
+    let mut renderer = TerminalRenderer::new(false, NonZero::<usize>::MIN);
+    renderer.set_style(palette, glyphs);
+    let res = match placement {
+        Placement::Lines(size) => {
+            let lines = renderer.render_lines(&frame, size).ok_or_else(no_frame)?;
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&lines).and_then(|()| stdout.flush())
+        }
+        Placement::Pane(Geometry { size, origin }) => {
+            let mut target = RenderTarget {
+                origin,
+                size,
+                writer: pane_output(),
+            };
+            renderer
+                .render_to(&frame, &mut target, true)
+                .and_then(|()| target.writer.write_all(b"\x1b[0m"))
+                .and_then(|()| target.writer.flush())
+        }
+    };
+    res.map_err(PlayerError::Terminal)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Geometry, parse_time};
+
+    #[test]
+    fn times() {
+        let secs = gst::ClockTime::from_seconds;
+        assert_eq!(parse_time("90"), Ok(secs(90)));
+        assert_eq!(parse_time("00:01:00"), Ok(secs(60)));
+        assert_eq!(parse_time("1:02:03"), Ok(secs(3723)));
+        assert_eq!(parse_time("1.5"), Ok(gst::ClockTime::from_mseconds(1500)));
+        assert!(parse_time("1:2:3:4").is_err());
+        assert!(parse_time("-5").is_err());
+        assert!(parse_time("soon").is_err());
+    }
+
+    #[test]
+    fn lf_geometry() {
+        assert_eq!(
+            "80x24@40,1".parse(),
+            Ok(Geometry {
+                size: (80, 24),
+                origin: (40, 1),
+            })
+        );
+        assert!("80x24".parse::<Geometry>().is_err());
+        assert!("0x24@0,0".parse::<Geometry>().is_err());
+    }
+}
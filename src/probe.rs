@@ -0,0 +1,382 @@
+//! `videoplayer probe`, what a discoverer finds out about a file without playing it,
+//! for scripts and for finding out why something won't play
+use crate::missing_plugin::{self, MissingPlugin};
+use glib::object::Cast;
+use gst::prelude::GstValueExt;
+use gstreamer_pbutils as gst_pbutils;
+use gstreamer_pbutils::prelude::DiscovererStreamInfoExt;
+use std::fmt::Write as _;
+use std::path::Path;
+use video_less::error::PlayerError;
+
+/// how long discovering gets before giving up on a file
+const TIMEOUT: gst::ClockTime = gst::ClockTime::from_seconds(10);
+
+/// What a stream is besides its codec
+enum Details {
+    Video {
+        width: u32,
+        height: u32,
+        /// `None` for variable framerates and pictures
+        fps: Option<f64>,
+        image: bool,
+    },
+    Audio {
+        channels: u32,
+        sample_rate: u32,
+        language: Option<String>,
+    },
+    Subtitles {
+        language: Option<String>,
+    },
+    Other,
+}
+
+struct Stream {
+    /// `video`, `audio`, `subtitles` or whatever else the discoverer calls it
+    kind: String,
+    codec: Option<String>,
+    bitrate: Option<u32>,
+    details: Details,
+}
+
+/// Everything printed about a file
+struct Report {
+    uri: String,
+    duration: Option<gst::ClockTime>,
+    seekable: bool,
+    live: bool,
+    tags: Vec<(String, String)>,
+    streams: Vec<Stream>,
+    /// what gstreamer would have to install to decode all of it
+    missing: Vec<String>,
+}
+
+#[cfg(feature = "serde")]
+fn seconds(time: gst::ClockTime) -> f64 {
+    time.nseconds() as f64 / 1e9
+}
+
+fn text(value: &glib::SendValue) -> Option<String> {
+    match value.get::<String>() {
+        Ok(text) => Some(text),
+        Err(_) => value.serialize().ok().map(|value| value.to_string()),
+    }
+}
+
+fn uri(video: &Path) -> Result<String, PlayerError> {
+    if let Some(uri) = crate::live::uri(video) {
+        return Ok(uri.to_string());
+    }
+
+    let path = std::path::absolute(video).map_err(|source| PlayerError::Open {
+        path: video.to_path_buf(),
+        source,
+    })?;
+    glib::filename_to_uri(&path, None)
+        .map(String::from)
+        .map_err(|source| PlayerError::Probe {
+            video: video.to_path_buf(),
+            source,
+        })
+}
+
+fn stream(info: &gst_pbutils::DiscovererStreamInfo) -> Stream {
+    let codec = info
+        .caps()
+        .map(|caps| gst_pbutils::pb_utils_get_codec_description(&caps).to_string());
+
+    let (bitrate, details) =
+        if let Some(video) = info.downcast_ref::<gst_pbutils::DiscovererVideoInfo>() {
+            let fps = video.framerate();
+            let fps = (fps.numer() > 0 && fps.denom() > 0)
+                .then(|| f64::from(fps.numer()) / f64::from(fps.denom()));
+            let details = Details::Video {
+                width: video.width(),
+                height: video.height(),
+                fps,
+                image: video.is_image(),
+            };
+            (video.bitrate(), details)
+        } else if let Some(audio) = info.downcast_ref::<gst_pbutils::DiscovererAudioInfo>() {
+            let details = Details::Audio {
+                channels: audio.channels(),
+                sample_rate: audio.sample_rate(),
+                language: audio.language().map(String::from),
+            };
+            (audio.bitrate(), details)
+        } else if let Some(subtitles) = info.downcast_ref::<gst_pbutils::DiscovererSubtitleInfo>() {
+            let language = subtitles.language().map(String::from);
+            (0, Details::Subtitles { language })
+        } else {
+            (0, Details::Other)
+        };
+
+    Stream {
+        kind: info.stream_type_nick().to_string(),
+        codec,
+        bitrate: (bitrate > 0).then_some(bitrate),
+        details,
+    }
+}
+
+/// `Discoverer::discover_uri`, except that what was found comes back along with the error,
+/// it's the only place the plugins decoding needed and didn't find are written down
+fn discover_uri(
+    discoverer: &gst_pbutils::Discoverer,
+    uri: &str,
+) -> (Option<gst_pbutils::DiscovererInfo>, Option<glib::Error>) {
+    use glib::translate::{FromGlibPtrFull, ToGlibPtr};
+
+    let mut error = std::ptr::null_mut();
+    // Safety: the discoverer and the uri outlive the call,
+    // and the info and the error it gives, either of which can be null, are ours to free
+    unsafe {
+        let info = gst_pbutils::ffi::gst_discoverer_discover_uri(
+            discoverer.to_glib_none().0,
+            uri.to_glib_none().0,
+            &mut error,
+        );
+        (Option::from_glib_full(info), Option::from_glib_full(error))
+    }
+}
+
+fn discover(video: &Path) -> Result<Report, PlayerError> {
+    let error = |source| PlayerError::Probe {
+        video: video.to_path_buf(),
+        source,
+    };
+    let uri = uri(video)?;
+    let discoverer = gst_pbutils::Discoverer::new(TIMEOUT).map_err(error)?;
+    let (info, failure) = discover_uri(&discoverer, &uri);
+
+    let missing = info
+        .iter()
+        .flat_map(|info| info.missing_elements_installer_details())
+        .map(|detail| MissingPlugin::from_installer_detail(&detail))
+        .collect::<Vec<_>>();
+    let info = match (info, failure) {
+        (Some(info), None) => info,
+        // the error only says that something couldn't be decoded, not what with
+        _ if !missing.is_empty() => {
+            return Err(PlayerError::MissingPlugins {
+                video: video.to_path_buf(),
+                hint: missing_plugin::hint(&missing),
+            });
+        }
+        (_, Some(failure)) => return Err(error(failure)),
+        (None, None) => {
+            let nothing = glib::Error::new(gst::CoreError::Failed, "the discoverer found nothing");
+            return Err(error(nothing));
+        }
+    };
+
+    let tags = info
+        .tags()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|(name, value)| Some((name.to_string(), text(&value)?)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // the containers themselves are streams too, only what's inside them gets listed
+    let streams = info
+        .stream_list()
+        .iter()
+        .filter(|info| {
+            info.downcast_ref::<gst_pbutils::DiscovererContainerInfo>()
+                .is_none()
+        })
+        .map(stream)
+        .collect();
+
+    let missing = missing
+        .into_iter()
+        .map(|plugin| plugin.description)
+        .collect();
+
+    Ok(Report {
+        uri,
+        duration: info.duration(),
+        seekable: info.is_seekable(),
+        live: info.is_live(),
+        tags,
+        streams,
+        missing,
+    })
+}
+
+impl Report {
+    /// one line per thing that's known, `name  value`, like the `i` panel
+    fn pretty(&self) -> String {
+        let mut out = String::new();
+        let mut line = |name: &str, value: &dyn std::fmt::Display| {
+            let _ = writeln!(out, "{name:<12}{value}");
+        };
+
+        line("uri", &self.uri);
+        if let Some(duration) = self.duration {
+            line("duration", &duration);
+        }
+        line("seekable", &self.seekable);
+        if self.live {
+            line("live", &self.live);
+        }
+
+        for stream in &self.streams {
+            let mut parts = stream.codec.iter().cloned().collect::<Vec<_>>();
+            match &stream.details {
+                Details::Video {
+                    width,
+                    height,
+                    fps,
+                    image,
+                } => {
+                    parts.push(format!("{width}x{height}"));
+                    match (fps, image) {
+                        (_, true) => parts.push("still image".to_string()),
+                        (Some(fps), false) => parts.push(format!("{fps:.2} fps")),
+                        (None, false) => {}
+                    }
+                }
+                Details::Audio {
+                    channels,
+                    sample_rate,
+                    language,
+                } => {
+                    parts.push(format!("{sample_rate} Hz"));
+                    parts.push(format!("{channels} channels"));
+                    parts.extend(language.clone());
+                }
+                Details::Subtitles { language } => parts.extend(language.clone()),
+                Details::Other => {}
+            }
+            if let Some(bitrate) = stream.bitrate {
+                parts.push(format!("{} kb/s", bitrate / 1000));
+            }
+            line(&stream.kind, &parts.join(", "));
+        }
+
+        for (name, value) in &self.tags {
+            line(name, value);
+        }
+        for missing in &self.missing {
+            line("missing", missing);
+        }
+        out
+    }
+
+    #[cfg(feature = "serde")]
+    fn json(&self) -> serde_json::Value {
+        use serde_json::{Value, json};
+
+        let streams = self
+            .streams
+            .iter()
+            .map(|stream| {
+                let mut value = match &stream.details {
+                    Details::Video {
+                        width,
+                        height,
+                        fps,
+                        image,
+                    } => json!({
+                        "width": width,
+                        "height": height,
+                        "framerate": fps,
+                        "image": image,
+                    }),
+                    Details::Audio {
+                        channels,
+                        sample_rate,
+                        language,
+                    } => json!({
+                        "channels": channels,
+                        "sample-rate": sample_rate,
+                        "language": language,
+                    }),
+                    Details::Subtitles { language } => json!({ "language": language }),
+                    Details::Other => json!({}),
+                };
+                value["type"] = Value::from(stream.kind.as_str());
+                value["codec"] = json!(stream.codec);
+                value["bitrate"] = json!(stream.bitrate);
+                value
+            })
+            .collect::<Vec<_>>();
+
+        let tags = self
+            .tags
+            .iter()
+            .map(|(name, value)| (name.clone(), Value::from(value.as_str())))
+            .collect::<serde_json::Map<_, _>>();
+
+        json!({
+            "uri": self.uri,
+            "duration": self.duration.map(seconds),
+            "seekable": self.seekable,
+            "live": self.live,
+            "streams": streams,
+            "tags": tags,
+            "missing-plugins": self.missing,
+        })
+    }
+}
+
+/// prints what there is to know about `video` on stdout, as a json object when `json` is set
+pub fn show(video: &Path, json: bool) -> Result<(), PlayerError> {
+    let report = discover(video)?;
+    match json {
+        #[cfg(feature = "serde")]
+        true => println!("{:#}", report.json()),
+        _ => print!("{}", report.pretty()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Details, Report, Stream};
+
+    fn report() -> Report {
+        Report {
+            uri: "file:///clip.mkv".to_string(),
+            duration: Some(gst::ClockTime::from_seconds(90)),
+            seekable: true,
+            live: false,
+            tags: vec![("title".to_string(), "a clip".to_string())],
+            streams: vec![Stream {
+                kind: "video".to_string(),
+                codec: Some("H.264".to_string()),
+                bitrate: Some(2_000_000),
+                details: Details::Video {
+                    width: 1280,
+                    height: 720,
+                    fps: Some(30.0),
+                    image: false,
+                },
+            }],
+            missing: vec!["AAC decoder".to_string()],
+        }
+    }
+
+    #[test]
+    fn prints_a_line_each() {
+        let pretty = report().pretty();
+        let lines = pretty.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "uri         file:///clip.mkv");
+        assert!(lines.contains(&"video       H.264, 1280x720, 30.00 fps, 2000 kb/s"));
+        assert!(lines.contains(&"title       a clip"));
+        assert_eq!(lines.last(), Some(&"missing     AAC decoder"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_lists_the_missing_plugins() {
+        let json = report().json();
+        assert_eq!(json["missing-plugins"], serde_json::json!(["AAC decoder"]));
+        assert_eq!(json["streams"][0]["width"], 1280);
+        assert_eq!(json["duration"], 90.0);
+    }
+}
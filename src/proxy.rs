@@ -0,0 +1,123 @@
+//! `videoplayer transcode --height`, a small copy of a file that's cheap to play on a terminal,
+//! scaled down to about what the terminal shows and with every frame a keyframe
+//! so seeking in it never has to decode anything before the frame seeked to
+use crate::encode;
+use gst::prelude::ElementExtManual;
+use std::num::NonZero;
+use std::path::Path;
+use video_less::error::PlayerError;
+use video_less::gstreamer_element;
+
+/// What the proxy gets made at
+pub struct Options {
+    /// how many pixels tall, its width keeps the aspect ratio
+    pub height: NonZero<u32>,
+    /// the frame rate of the file when `None`
+    pub fps: Option<NonZero<u32>>,
+}
+
+/// the width keeping the aspect ratio of frames `size` big with pixels `par` wide at `height`,
+/// rounded to what x264 takes
+fn scaled_width((width, source_height): (i32, i32), par: gst::Fraction, height: u32) -> u32 {
+    let aspect = f64::from(width) * f64::from(par.numer())
+        / f64::from(par.denom())
+        / f64::from(source_height);
+    even((f64::from(height) * aspect).round() as u32)
+}
+
+/// the width keeping the aspect ratio of what `caps` describe at `height`
+fn width_for(caps: &gst::CapsRef, height: u32) -> Option<u32> {
+    let structure = caps.structure(0)?;
+    let size = (
+        structure.get::<i32>("width").ok()?,
+        structure.get::<i32>("height").ok()?,
+    );
+    let par = structure
+        .get::<gst::Fraction>("pixel-aspect-ratio")
+        .unwrap_or(gst::Fraction::new(1, 1));
+    Some(scaled_width(size, par, height))
+}
+
+/// 4:2:0 frames need an even number of pixels either way
+fn even(pixels: u32) -> u32 {
+    (pixels + 1) & !1
+}
+
+fn video_branch(caps: &gst::CapsRef, options: &Options) -> Result<Vec<gst::Element>, PlayerError> {
+    let height = even(options.height.get());
+    let mut scaled = gst::Caps::builder("video/x-raw")
+        .field("height", i32::try_from(height).unwrap_or(i32::MAX))
+        .field("pixel-aspect-ratio", gst::Fraction::new(1, 1));
+    if let Some(width) = width_for(caps, height) {
+        scaled = scaled.field("width", i32::try_from(width).unwrap_or(i32::MAX));
+    }
+    if let Some(fps) = options.fps {
+        let fps = i32::try_from(fps.get()).unwrap_or(i32::MAX);
+        scaled = scaled.field("framerate", gst::Fraction::new(fps, 1));
+    }
+    let filter = gst::ElementFactory::make("capsfilter")
+        .property("caps", scaled.build())
+        .build()
+        .map_err(PlayerError::element("capsfilter"))?;
+
+    // all intra, every frame stands on its own
+    let encoder = gst::ElementFactory::make("x264enc")
+        .property("key-int-max", 1_u32)
+        .property("bframes", 0_u32)
+        .property_from_str("speed-preset", "veryfast")
+        .build()
+        .map_err(PlayerError::element("x264enc"))?;
+
+    Ok(vec![
+        gstreamer_element("queue")?,
+        gstreamer_element("videoconvert")?,
+        gstreamer_element("videoscale")?,
+        gstreamer_element("videorate")?,
+        filter,
+        encoder,
+    ])
+}
+
+fn audio_branch() -> Result<Vec<gst::Element>, PlayerError> {
+    let encoder = gst::ElementFactory::make("opusenc")
+        .property("bitrate", 64_000)
+        .build()
+        .map_err(PlayerError::element("opusenc"))?;
+    Ok(vec![
+        gstreamer_element("queue")?,
+        gstreamer_element("audioconvert")?,
+        gstreamer_element("audioresample")?,
+        encoder,
+    ])
+}
+
+/// writes the proxy of `video` to `output`, returning once it's all there
+pub fn transcode(video: &Path, output: &Path, options: Options) -> Result<(), PlayerError> {
+    let branch = Box::new(move |caps: &gst::CapsRef| {
+        let media_type = caps
+            .structure(0)
+            .map(|structure| structure.name().to_string())
+            .unwrap_or_default();
+        match media_type {
+            video if video.starts_with("video/") => video_branch(caps, &options).map(Some),
+            audio if audio.starts_with("audio/") => audio_branch().map(Some),
+            _ => Ok(None),
+        }
+    });
+    let pipeline = encode::build(video, output, "decodebin", branch)?;
+    encode::run(&pipeline, output, None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keeps_the_aspect_ratio() {
+        let square = gst::Fraction::new(1, 1);
+        assert_eq!(scaled_width((1920, 1080), square, 240), 428);
+        // anamorphic PAL
+        let wide = gst::Fraction::new(16, 15);
+        assert_eq!(scaled_width((720, 576), wide, 240), 320);
+    }
+}
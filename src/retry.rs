@@ -0,0 +1,130 @@
+//! `--retries`, building the pipeline of a file again after an error it can get over,
+//! like a network source timing out or a demuxer choking on a damaged stretch of a stream,
+//! and going on from where it got to instead of giving up on it
+use gst::prelude::{ElementExt, ElementExtManual};
+use std::time::{Duration, Instant};
+
+/// how long the error that's getting retried stays on screen once it plays again
+pub const SHOWN_FOR: Duration = Duration::from_secs(3);
+/// how long a go at a file has to play for to have got over its error,
+/// so the next one gets all the retries again
+const RECOVERED_AFTER: Duration = Duration::from_secs(30);
+
+/// How often and how soon a file gets another go
+#[derive(Debug, Copy, Clone)]
+pub struct Policy {
+    /// how many times a file can fail before the error gets reported
+    pub retries: u32,
+    /// how long to wait before trying again
+    pub delay: Duration,
+}
+
+/// whether going again might get past `error`, rather than running into it every time
+pub fn transient(error: &glib::Error) -> bool {
+    if let Some(error) = error.kind::<gst::ResourceError>() {
+        return matches!(
+            error,
+            gst::ResourceError::Read
+                | gst::ResourceError::OpenRead
+                | gst::ResourceError::Seek
+                | gst::ResourceError::Busy
+        );
+    }
+    if let Some(error) = error.kind::<gst::StreamError>() {
+        return matches!(error, gst::StreamError::Demux | gst::StreamError::Decode);
+    }
+    false
+}
+
+/// where `pipeline` got to, `None` when it can't be seeked back there, like live streams
+pub fn position(pipeline: &gst::Pipeline) -> Option<gst::ClockTime> {
+    let mut seeking = gst::query::Seeking::new(gst::Format::Time);
+    if !pipeline.query(&mut seeking) || !seeking.result().0 {
+        return None;
+    }
+    pipeline.query_position::<gst::ClockTime>()
+}
+
+/// A file that failed and gets played again
+#[derive(Debug, Copy, Clone)]
+pub struct Retry {
+    /// where in the playlist it is
+    pub index: usize,
+    /// how many times it failed so far
+    pub attempts: u32,
+    /// how far it got, to pick up from there
+    pub position: Option<gst::ClockTime>,
+    /// when this go got set up
+    pub since: Instant,
+}
+
+impl Policy {
+    /// another go at the file at `index` after an error, moving on from the retry it already was,
+    /// `None` once it failed as often as it may in a row
+    pub fn next(
+        self,
+        last: Option<Retry>,
+        index: usize,
+        position: Option<gst::ClockTime>,
+    ) -> Option<Retry> {
+        let last = last
+            .filter(|last| last.index == index)
+            // a go that played on for a while got over it, this is an error of its own
+            .filter(|last| last.since.elapsed() < self.delay + RECOVERED_AFTER);
+        let attempts = last.map_or(0, |last| last.attempts);
+        (attempts < self.retries).then(|| Retry {
+            index,
+            attempts: attempts + 1,
+            // a failure before getting anywhere goes back to where the last one got
+            position: position.or(last.and_then(|last| last.position)),
+            since: Instant::now(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn classifies_errors() {
+        let error = |kind| glib::Error::new(kind, "");
+        assert!(transient(&error(gst::ResourceError::Read)));
+        assert!(transient(&glib::Error::new(gst::StreamError::Demux, "")));
+        assert!(!transient(&error(gst::ResourceError::NotFound)));
+        assert!(!transient(&glib::Error::new(
+            gst::StreamError::CodecNotFound,
+            ""
+        )));
+    }
+
+    #[test]
+    fn gives_up_after_the_retries() {
+        let policy = Policy {
+            retries: 2,
+            delay: Duration::ZERO,
+        };
+        let at = gst::ClockTime::from_seconds;
+        let first = policy.next(None, 3, Some(at(10))).unwrap();
+        let second = policy.next(Some(first), 3, None).unwrap();
+        assert_eq!((second.attempts, second.position), (2, Some(at(10))));
+        assert!(policy.next(Some(second), 3, Some(at(12))).is_none());
+        // another file starts counting over
+        let other = policy.next(Some(second), 4, None).unwrap();
+        assert_eq!((other.attempts, other.position), (1, None));
+    }
+
+    #[test]
+    fn counts_over_once_it_played_again() {
+        let policy = Policy {
+            retries: 1,
+            delay: Duration::ZERO,
+        };
+        let mut last = policy.next(None, 0, None).unwrap();
+        assert!(policy.next(Some(last), 0, None).is_none());
+
+        last.since = Instant::now() - 2 * RECOVERED_AFTER;
+        let again = policy.next(Some(last), 0, None).unwrap();
+        assert_eq!(again.attempts, 1);
+    }
+}
@@ -0,0 +1,63 @@
+use std::fs::File;
+use std::path::PathBuf;
+
+/// the device behind stdin or stdout, whichever is a terminal
+#[cfg(unix)]
+fn terminal_device() -> Option<PathBuf> {
+    use std::ffi::CStr;
+
+    [libc::STDIN_FILENO, libc::STDOUT_FILENO]
+        .into_iter()
+        .find_map(|fd| {
+            // Safety: ttyname is only called from the main thread before anything else is spawned
+            let name = unsafe { libc::ttyname(fd) };
+            (!name.is_null()).then(|| {
+                let name = unsafe { CStr::from_ptr(name) };
+                PathBuf::from(name.to_string_lossy().into_owned())
+            })
+        })
+}
+
+/// An exclusive lock on the terminal device, held for as long as the player runs
+/// so that a second player in the same terminal refuses to start
+/// instead of both fighting over the input and interleaving their frames
+pub struct SeatLock {
+    _device: File,
+}
+
+/// claims the terminal, failing with its path if another player holds it,
+/// not being attached to one means there is nothing to fight over
+#[cfg(unix)]
+pub fn claim() -> Result<Option<SeatLock>, PathBuf> {
+    use std::fs::OpenOptions;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let Some(path) = terminal_device() else {
+        return Ok(None);
+    };
+
+    // don't make it our controlling terminal by accident
+    let Ok(device) = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(&path)
+    else {
+        return Ok(None);
+    };
+
+    // the lock lives on the device itself, so no lock files are left behind
+    // and it goes away if the process dies
+    match unsafe { libc::flock(device.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } {
+        0 => Ok(Some(SeatLock { _device: device })),
+        _ if std::io::Error::last_os_error().kind() == std::io::ErrorKind::WouldBlock => Err(path),
+        // locks not being supported shouldn't keep anyone from watching
+        _ => Ok(None),
+    }
+}
+
+/// consoles have no device to lock, so every player gets its own
+#[cfg(not(unix))]
+pub fn claim() -> Result<Option<SeatLock>, PathBuf> {
+    Ok(None)
+}
@@ -0,0 +1,206 @@
+//! Thumbnails of where a seek lands, decoded by a second pipeline of their own
+//! that only ever seeks to keyframes, so holding a seek key shows where it's going
+//! well before the real pipeline gets there
+use glib::object::Cast;
+use gst::prelude::{ElementExt, ElementExtManual, GstBinExtManual, PadExt};
+use gst_app::AppSink;
+use gstreamer_app as gst_app;
+use gstreamer_video as gst_video;
+use parking_lot::{Condvar, Mutex, MutexGuard};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use video_less::error::PlayerError;
+use video_less::gstreamer_element;
+use video_less::terminal_sink::LiveSettings;
+
+/// how long a thumbnail stays up after the last seek
+const SHOWN_FOR: Duration = Duration::from_millis(1500);
+/// thumbnails only get drawn small, so there's no use converting more pixels than this
+const WIDTH: i32 = 240;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Request {
+    Idle,
+    Seek(gst::ClockTime),
+    Stop,
+}
+
+struct Shared {
+    request: Mutex<Request>,
+    changed: Condvar,
+}
+
+/// Shows a thumbnail of the frame a seek in `video` heads for, until dropped
+pub struct Previewer {
+    shared: Arc<Shared>,
+}
+
+impl Previewer {
+    /// the pipeline only gets built once the first thumbnail is asked for
+    pub fn new(video: PathBuf, settings: Arc<LiveSettings>) -> Self {
+        let shared = Arc::new(Shared {
+            request: Mutex::new(Request::Idle),
+            changed: Condvar::new(),
+        });
+
+        let worker = Arc::clone(&shared);
+        thread::spawn(move || run(&video, &settings, &worker));
+
+        Self { shared }
+    }
+
+    /// shows the keyframe closest to `position`, replacing any thumbnail not decoded yet
+    pub fn request(&self, position: gst::ClockTime) {
+        *self.shared.request.lock() = Request::Seek(position);
+        self.shared.changed.notify_one();
+    }
+}
+
+impl Drop for Previewer {
+    fn drop(&mut self) {
+        *self.shared.request.lock() = Request::Stop;
+        self.shared.changed.notify_one();
+    }
+}
+
+fn build(video: &Path) -> Result<(gst::Pipeline, AppSink), PlayerError> {
+    let source = gst::ElementFactory::make("filesrc")
+        .property("location", video)
+        .build()
+        .map_err(PlayerError::element("filesrc"))?;
+    let decode = gstreamer_element("decodebin")?;
+    let convert = gstreamer_element("videoconvert")?;
+    let scale = gstreamer_element("videoscale")?;
+    let sink = AppSink::builder()
+        .caps(
+            &gst_video::VideoCapsBuilder::new()
+                .format(gst_video::VideoFormat::Rgb)
+                .width(WIDTH)
+                .build(),
+        )
+        .sync(false)
+        .build();
+
+    let pipeline = gst::Pipeline::new();
+    let video_line = [&convert, &scale, sink.upcast_ref::<gst::Element>()];
+
+    let link = PlayerError::link;
+    pipeline
+        .add_many([&source, &decode])
+        .map_err(link("the preview source to its decoder"))?;
+    pipeline
+        .add_many(video_line)
+        .map_err(link("the preview elements"))?;
+    source
+        .link(&decode)
+        .map_err(link("the preview source to its decoder"))?;
+    gst::Element::link_many(video_line).map_err(link("the preview elements"))?;
+
+    // the sound gets left undecoded
+    let video_entry = convert.static_pad("sink").unwrap();
+    decode.connect_pad_added(move |_, src_pad| {
+        let caps = src_pad
+            .current_caps()
+            .unwrap_or_else(|| src_pad.query_caps(None));
+        let is_video = caps
+            .structure(0)
+            .is_some_and(|structure| structure.name().starts_with("video/"));
+        if is_video && !video_entry.is_linked() {
+            let _ = src_pad.link(&video_entry);
+        }
+    });
+
+    Ok((pipeline, sink))
+}
+
+/// the keyframe at or before `position`
+fn thumbnail(
+    (pipeline, sink): &(gst::Pipeline, AppSink),
+    position: gst::ClockTime,
+) -> Option<gst::Sample> {
+    pipeline
+        .seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT, position)
+        .ok()?;
+    sink.try_pull_preroll(gst::ClockTime::from_seconds(2))
+}
+
+/// the frame at `position` in `video`, decoded by a pipeline of its own that's gone afterwards
+pub fn frame_at(video: &Path, position: gst::ClockTime) -> Result<gst::Sample, PlayerError> {
+    let (pipeline, sink) = build(video)?;
+    let _ = pipeline.set_state(gst::State::Paused);
+    // seeking only works once the first frame is there
+    let prerolled = pipeline.state(gst::ClockTime::from_seconds(5)).0.is_ok();
+
+    let sample = prerolled
+        .then(|| match position.is_zero() {
+            true => sink.try_pull_preroll(gst::ClockTime::from_seconds(5)),
+            false => {
+                let flags = gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE;
+                pipeline.seek_simple(flags, position).ok()?;
+                sink.try_pull_preroll(gst::ClockTime::from_seconds(5))
+            }
+        })
+        .flatten();
+    let _ = pipeline.set_state(gst::State::Null);
+
+    sample.ok_or_else(|| PlayerError::NoFrame {
+        video: video.to_path_buf(),
+        position,
+    })
+}
+
+fn run(video: &Path, settings: &LiveSettings, shared: &Shared) {
+    let mut pipeline = None;
+    let mut shown = false;
+
+    let mut lock = shared.request.lock();
+    loop {
+        let position = match std::mem::replace(&mut *lock, Request::Idle) {
+            Request::Stop => break,
+            Request::Seek(position) => position,
+            Request::Idle if shown => {
+                let expired = shared.changed.wait_for(&mut lock, SHOWN_FOR).timed_out();
+                if expired && *lock == Request::Idle {
+                    settings.hide_preview();
+                    shown = false;
+                }
+                continue;
+            }
+            Request::Idle => {
+                shared.changed.wait(&mut lock);
+                continue;
+            }
+        };
+
+        let sample = MutexGuard::unlocked(&mut lock, || {
+            if pipeline.is_none() {
+                let built = build(video)
+                    .inspect_err(|err| tracing::warn!(%err, "no seek previews"))
+                    .ok()?;
+                let _ = built.0.set_state(gst::State::Paused);
+                pipeline = Some(built);
+            }
+            thumbnail(pipeline.as_ref()?, position)
+        });
+
+        match sample {
+            Some(sample) => {
+                settings.show_preview(sample);
+                shown = true;
+            }
+            // a file that can't be previewed won't get any better
+            None if pipeline.is_none() => break,
+            None => {}
+        }
+    }
+
+    drop(lock);
+    if shown {
+        settings.hide_preview();
+    }
+    if let Some((pipeline, _)) = pipeline {
+        let _ = pipeline.set_state(gst::State::Null);
+    }
+}
@@ -0,0 +1,120 @@
+//! Signal handlers can't do much more than write a byte, so they write the signal into a pipe
+//! and a thread of its own reads it and does the rest
+use std::os::fd::RawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread::{self, JoinHandle};
+
+/// written to the pipe to stop the thread reading it, signal numbers are never 0
+const STOP: u8 = 0;
+
+fn write_byte(fd: RawFd, byte: u8) {
+    // SAFETY: write is async signal safe, and byte is one readable byte
+    unsafe {
+        libc::write(fd, (&raw const byte).cast(), 1);
+    }
+}
+
+/// makes `handler` what `signal` runs, `SIG_DFL` to go back to what it normally does
+pub(crate) fn set_handler(signal: libc::c_int, handler: libc::sighandler_t, flags: libc::c_int) {
+    // SAFETY: an all zero sigaction is valid, and every handler passed in
+    // only does async signal safe things
+    unsafe {
+        let mut action = std::mem::zeroed::<libc::sigaction>();
+        action.sa_sigaction = handler;
+        action.sa_flags = flags;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signal, &action, std::ptr::null_mut());
+    }
+}
+
+/// The end of a pipe handlers write to, one for each thing handling signals
+pub(crate) struct SelfPipe {
+    /// -1 while nothing reads the other end
+    wake: AtomicI32,
+}
+
+impl SelfPipe {
+    pub(crate) const fn new() -> Self {
+        Self {
+            wake: AtomicI32::new(-1),
+        }
+    }
+
+    /// whether a thread is reading what gets sent
+    pub(crate) fn is_open(&self) -> bool {
+        self.wake.load(Ordering::SeqCst) >= 0
+    }
+
+    /// hands `signal` to the thread reading the pipe, if there is one,
+    /// async signal safe so handlers can call it
+    pub(crate) fn send(&self, signal: libc::c_int) {
+        let fd = self.wake.load(Ordering::SeqCst);
+        if fd >= 0 {
+            write_byte(fd, signal as u8);
+        }
+    }
+
+    /// calls `on_signal` from a thread of its own with everything sent until the reader
+    /// gets closed, `None` when the pipe is open already
+    pub(crate) fn open(
+        &'static self,
+        mut on_signal: impl FnMut(libc::c_int) + Send + 'static,
+    ) -> std::io::Result<Option<Reader>> {
+        let mut fds = [0; 2];
+        // SAFETY: fds has room for both ends
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let [read, write] = fds;
+        if self
+            .wake
+            .compare_exchange(-1, write, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            // SAFETY: both ends were just opened by pipe and nothing else has them
+            unsafe {
+                libc::close(read);
+                libc::close(write);
+            }
+            return Ok(None);
+        }
+
+        let jh = thread::spawn(move || {
+            loop {
+                let mut byte = STOP;
+                // SAFETY: byte is one writable byte, and read stays open as long as this runs
+                let res = unsafe { libc::read(read, (&raw mut byte).cast(), 1) };
+                match res {
+                    1 if byte == STOP => break,
+                    1 => on_signal(libc::c_int::from(byte)),
+                    _ if std::io::Error::last_os_error().kind()
+                        == std::io::ErrorKind::Interrupted => {}
+                    _ => break,
+                }
+            }
+        });
+
+        Ok(Some(Reader {
+            pipe: self,
+            write,
+            jh,
+        }))
+    }
+}
+
+/// The thread reading a [`SelfPipe`], until it's closed
+pub(crate) struct Reader {
+    pipe: &'static SelfPipe,
+    write: RawFd,
+    jh: JoinHandle<()>,
+}
+
+impl Reader {
+    /// stops the thread once it handled what was sent before,
+    /// the pipe stays open for a handler that might still be writing to it
+    pub(crate) fn close(self) {
+        self.pipe.wake.store(-1, Ordering::SeqCst);
+        write_byte(self.write, STOP);
+        self.jh.join().unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+//! Ctrl-C and `kill` quitting the way the q key does, through the bus,
+//! so the pipeline still gets set to Null and the terminal restored by the usual teardown,
+//! even when stdin isn't a terminal and no key ever gets read
+use crate::QuitHandler;
+use crate::self_pipe::{self, SelfPipe};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// the signals quitting gracefully, the rest still kill the process on the spot
+pub(crate) const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+static PIPE: SelfPipe = SelfPipe::new();
+/// set by the first signal, a second one means the teardown is taking too long
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// whether `install` took over `signal` from the terminal guard
+pub(crate) fn handles(signal: libc::c_int) -> bool {
+    PIPE.is_open() && SIGNALS.contains(&signal)
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    if PIPE.is_open() && !REQUESTED.swap(true, Ordering::SeqCst) {
+        PIPE.send(signal);
+        return;
+    }
+
+    crate::terminal_guard::restore_and_raise(signal);
+}
+
+/// calls `quit` from a thread of its own for the first SIGINT or SIGTERM,
+/// the second one kills the process like it normally would
+pub fn install(quit_handler: &mut QuitHandler, quit: impl Fn() + Send + 'static) {
+    let reader = PIPE.open(move |signal| {
+        tracing::info!(signal, "quitting on a signal");
+        quit();
+    });
+    let reader = match reader {
+        Ok(Some(reader)) => reader,
+        // already installed
+        Ok(None) => return,
+        Err(err) => {
+            tracing::warn!(%err, "no graceful shutdown on signals");
+            return;
+        }
+    };
+
+    for signal in SIGNALS {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        self_pipe::set_handler(signal, handler, 0);
+    }
+
+    quit_handler.add(move || {
+        // nothing reads the pipe from here on, signals go back to killing
+        REQUESTED.store(true, Ordering::SeqCst);
+        reader.close();
+    });
+}
@@ -0,0 +1,88 @@
+//! `--status-json`, a line of json on stdout for everything that happens to playback,
+//! for scripts and editors wrapping the player to follow along while the video goes to /dev/tty
+use crate::input_handler::Controls;
+use serde_json::{Map, Value, json};
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use video_less::QuitHandler;
+
+/// how often the position gets reported
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+
+fn seconds(time: gst::ClockTime) -> f64 {
+    time.nseconds() as f64 / 1e9
+}
+
+fn emit(event: Value) {
+    let mut stdout = std::io::stdout().lock();
+    // nobody reading is no reason to stop playing
+    let _ = writeln!(stdout, "{event}");
+    let _ = stdout.flush();
+}
+
+/// a file from the playlist started
+pub fn file(path: &Path) {
+    emit(json!({ "event": "file", "path": path.display().to_string() }));
+}
+
+/// the pipeline went to playing or paused
+pub fn state(state: gst::State) {
+    let state = match state {
+        gst::State::Playing => "playing",
+        gst::State::Paused => "paused",
+        _ => return,
+    };
+    emit(json!({ "event": "state", "state": state }));
+}
+
+fn text<'a, T: gst::Tag<'a, TagType = &'a str>>(tags: &'a gst::TagList) -> Option<&'a str> {
+    tags.get::<T>().map(|value| value.get())
+}
+
+/// what the tags the file came with say about its tracks
+pub fn tags(tags: &gst::TagList) {
+    let known = [
+        ("title", text::<gst::tags::Title>(tags)),
+        ("artist", text::<gst::tags::Artist>(tags)),
+        ("container", text::<gst::tags::ContainerFormat>(tags)),
+        ("video-codec", text::<gst::tags::VideoCodec>(tags)),
+        ("audio-codec", text::<gst::tags::AudioCodec>(tags)),
+    ];
+    let mut event = known
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.to_string(), Value::from(value?))))
+        .collect::<Map<_, _>>();
+    if event.is_empty() {
+        return;
+    }
+
+    event.insert("event".to_string(), Value::from("tags"));
+    emit(Value::Object(event));
+}
+
+/// the file played to its end
+pub fn eos() {
+    emit(json!({ "event": "eos" }));
+}
+
+/// reports where playback is every second, until quitting
+pub fn report_positions(quit_handler: &mut QuitHandler, controls: Controls) {
+    let (stop, stopped) = mpsc::channel::<()>();
+    quit_handler.token().on_cancel(move || drop(stop));
+
+    let jh = thread::spawn(move || {
+        while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(POSITION_INTERVAL) {
+            if let Some((position, duration)) = controls.position() {
+                emit(json!({
+                    "event": "position",
+                    "position": seconds(position),
+                    "duration": duration.map(seconds),
+                }));
+            }
+        }
+    });
+    quit_handler.add(move || jh.join().unwrap());
+}
@@ -0,0 +1,90 @@
+use gst::prelude::{ElementExt, GstBinExtManual};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use video_less::error::PlayerError;
+use video_less::gstreamer_element;
+
+/// the subtitle formats subparse understands
+const EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "vtt", "sub"];
+
+/// looks next to `video` for a subtitle file named after it, like `movie.srt` or `movie.en.srt`
+pub fn find_local(video: &Path) -> Option<PathBuf> {
+    let stem = video.file_stem()?.to_str()?;
+    let dir = video
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let matches = |path: &Path| {
+        let is_subtitle = path.extension().and_then(OsStr::to_str).is_some_and(|ext| {
+            EXTENSIONS
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(ext))
+        });
+
+        let named_after_video = path
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .and_then(|name| name.strip_prefix(stem))
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'));
+
+        is_subtitle && named_after_video
+    };
+
+    // the shortest name is the closest match, `movie.srt` wins over `movie.en.srt`
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| matches(path))
+        .min_by_key(|path| (path.as_os_str().len(), path.clone()))
+}
+
+/// draws a subtitle file over the video before it reaches the terminal
+pub struct Layer {
+    source: gst::Element,
+    parse: gst::Element,
+    overlay: gst::Element,
+}
+
+impl Layer {
+    /// builds a layer for the subtitles next to `video`,
+    /// gives up if there are none or the plugins to show them aren't installed
+    pub fn find(video: &Path) -> Option<Self> {
+        let path = find_local(video)?;
+
+        let source = gst::ElementFactory::make("filesrc")
+            .property("location", path.to_str()?)
+            .build()
+            .ok()?;
+        let parse = gstreamer_element("subparse").ok()?;
+        let overlay = gst::ElementFactory::make("textoverlay")
+            // don't hold back frames while waiting for the next line
+            .property("wait-text", false)
+            .build()
+            .ok()?;
+
+        Some(Self {
+            source,
+            parse,
+            overlay,
+        })
+    }
+
+    pub fn overlay(&self) -> &gst::Element {
+        &self.overlay
+    }
+
+    /// adds the subtitle stream to the pipeline, the overlay has to be added and linked by the caller
+    pub fn link(&self, pipeline: &gst::Pipeline) -> Result<(), PlayerError> {
+        pipeline
+            .add_many([&self.source, &self.parse])
+            .map_err(PlayerError::link("the subtitles"))?;
+        self.source
+            .link(&self.parse)
+            .map_err(PlayerError::link("the subtitles"))?;
+        self.parse
+            .link_pads(Some("src"), &self.overlay, Some("text_sink"))
+            .map_err(PlayerError::link("the subtitles"))
+    }
+}
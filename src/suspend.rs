@@ -0,0 +1,88 @@
+//! Being stopped in the background with SIGTSTP or Ctrl-Z, playback pauses and the shell gets
+//! its terminal back until SIGCONT brings the player back, which then draws the whole frame again
+use crate::QuitHandler;
+use crate::self_pipe::{self, SelfPipe};
+use crate::terminal_sink::LiveSettings;
+use std::sync::Arc;
+
+static PIPE: SelfPipe = SelfPipe::new();
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    PIPE.send(signal);
+}
+
+fn handler() -> libc::sighandler_t {
+    on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t
+}
+
+/// stops the process the way SIGTSTP would have, returns once it got continued
+fn stop() {
+    self_pipe::set_handler(libc::SIGTSTP, libc::SIG_DFL, libc::SA_RESTART);
+    // SAFETY: raising a signal is fine from any thread, with the default action
+    // it stops the process right here until SIGCONT
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+    self_pipe::set_handler(libc::SIGTSTP, handler(), libc::SA_RESTART);
+}
+
+/// stops the process group the way Ctrl-Z does outside of raw mode, after handing back the terminal
+pub fn request() {
+    if !PIPE.is_open() {
+        return;
+    }
+    // SAFETY: kill has no memory safety requirements, pid 0 is the process group of this process
+    unsafe {
+        libc::kill(0, libc::SIGTSTP);
+    }
+}
+
+/// stops drawing and restores the terminal before the process gets stopped, and undoes it once
+/// it's continued, `pause(true)` says whether playback got paused for it,
+/// in which case `pause(false)` plays it again
+pub fn install(
+    quit_handler: &mut QuitHandler,
+    settings: Arc<LiveSettings>,
+    mut pause: impl FnMut(bool) -> bool + Send + 'static,
+) {
+    // only playback paused for stopping gets resumed, SIGSTOP from elsewhere just redraws
+    let mut paused = false;
+    let mut stepped_out = None;
+    let reader = PIPE.open(move |signal| match signal {
+        libc::SIGTSTP => {
+            tracing::info!("suspending");
+            paused = pause(true);
+            settings.suspend();
+            stepped_out = crate::terminal::step_out();
+            stop();
+        }
+        _ => {
+            tracing::info!("continuing");
+            // raw and on the alternate screen before anything gets drawn again
+            drop(stepped_out.take());
+            settings.resume();
+            if std::mem::take(&mut paused) {
+                pause(false);
+            }
+        }
+    });
+    let reader = match reader {
+        Ok(Some(reader)) => reader,
+        // already installed
+        Ok(None) => return,
+        Err(err) => {
+            tracing::warn!(%err, "no pausing on SIGTSTP");
+            return;
+        }
+    };
+
+    for signal in [libc::SIGTSTP, libc::SIGCONT] {
+        self_pipe::set_handler(signal, handler(), libc::SA_RESTART);
+    }
+
+    quit_handler.add(move || {
+        // stopping goes back to how it normally works
+        self_pipe::set_handler(libc::SIGTSTP, libc::SIG_DFL, libc::SA_RESTART);
+        reader.close();
+    });
+}
@@ -1,11 +1,12 @@
+use crate::cancel::CancellationToken;
 use parking_lot::{Condvar, Mutex};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 const DEFAULT_TERM_SIZE: (u16, u16) = (1, 1);
 
 fn get_size_uncached() -> (u16, u16) {
-    termion::terminal_size().unwrap_or(DEFAULT_TERM_SIZE)
+    crate::terminal::size().unwrap_or(DEFAULT_TERM_SIZE)
 }
 
 enum Signal {
@@ -25,6 +26,8 @@ pub struct TerminalSizeUpdater {
 impl TerminalSizeUpdater {
     fn new_inner(
         periodic_interval: Duration,
+        debounce: Duration,
+        token: CancellationToken,
         mut on_size_change: Box<dyn FnMut((u16, u16)) + Send>,
     ) -> Self {
         let initial_size = get_size_uncached();
@@ -39,10 +42,15 @@ impl TerminalSizeUpdater {
             },
         );
 
+        let shared_cancel = Arc::clone(&shared);
+        token.on_cancel(move || signal_exit(&shared_cancel));
+
         let shared_ref = Arc::clone(&shared);
         let interval = periodic_interval;
         std::thread::spawn(move || {
             let mut last_size = initial_size;
+            // a size that differs from the last one, and since when it has been seen
+            let mut pending: Option<((u16, u16), Instant)> = None;
             let mut guard = shared_ref.state.lock();
             loop {
                 let signal = &mut *guard;
@@ -50,12 +58,36 @@ impl TerminalSizeUpdater {
                 match signal {
                     Signal::Active => {
                         let new_size = get_size_uncached();
-                        let old_size = core::mem::replace(&mut last_size, new_size);
-                        if old_size != new_size {
-                            on_size_change(new_size)
-                        }
 
-                        let _ = shared_ref.notification.wait_for(&mut guard, interval);
+                        let wait = match pending {
+                            _ if new_size == last_size => {
+                                pending = None;
+                                interval
+                            }
+                            Some((size, since)) if size == new_size => {
+                                match debounce.checked_sub(since.elapsed()) {
+                                    Some(remaining) if !remaining.is_zero() => remaining,
+                                    _ => {
+                                        pending = None;
+                                        last_size = new_size;
+                                        on_size_change(new_size);
+                                        interval
+                                    }
+                                }
+                            }
+                            // only report a size once it stops changing
+                            _ if !debounce.is_zero() => {
+                                pending = Some((new_size, Instant::now()));
+                                debounce
+                            }
+                            _ => {
+                                last_size = new_size;
+                                on_size_change(new_size);
+                                interval
+                            }
+                        };
+
+                        let _ = shared_ref.notification.wait_for(&mut guard, wait);
                     }
                     Signal::Exit => break,
                 }
@@ -65,11 +97,15 @@ impl TerminalSizeUpdater {
         Self { shared }
     }
 
+    /// polls the terminal size every `periodic_interval`, or when asked to,
+    /// and reports new sizes once they stayed the same for `debounce`
     pub fn new(
         periodic_interval: Duration,
+        debounce: Duration,
+        token: CancellationToken,
         on_size_change: impl FnMut((u16, u16)) + Send + 'static,
     ) -> Self {
-        Self::new_inner(periodic_interval, Box::new(on_size_change))
+        Self::new_inner(periodic_interval, debounce, token, Box::new(on_size_change))
     }
 
     pub fn trigger_reload(&self) {
@@ -77,12 +113,15 @@ impl TerminalSizeUpdater {
     }
 }
 
+fn signal_exit(shared: &Shared) {
+    let mut guard = shared.state.lock();
+    *guard = Signal::Exit;
+    drop(guard);
+    shared.notification.notify_one();
+}
+
 impl Drop for TerminalSizeUpdater {
     fn drop(&mut self) {
-        // signal an exit
-        let mut guard = self.shared.state.lock();
-        *guard = Signal::Exit;
-        drop(guard);
-        self.shared.notification.notify_one();
+        signal_exit(&self.shared);
     }
 }
@@ -0,0 +1,188 @@
+//! Finding out what the terminal can draw before taking it over,
+//! from the environment first and by asking the terminal itself when that says nothing
+
+/// how long the terminal gets to answer, terminals that don't understand a query stay quiet
+#[cfg(unix)]
+const ANSWER_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(200);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// 24 bit colors show as themselves
+    pub truecolor: bool,
+    /// U+2580 shows as a single cell wide half block
+    pub unicode: bool,
+}
+
+/// what `COLORTERM` and `TERM` say about colors, `None` when they don't say anything
+fn truecolor_from_env() -> Option<bool> {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    if matches!(colorterm.as_str(), "truecolor" | "24bit") {
+        return Some(true);
+    }
+
+    // the terminfo entries with the RGB flag set are the `-direct` ones
+    let term = std::env::var("TERM").unwrap_or_default();
+    match term.as_str() {
+        term if term.ends_with("-direct") => Some(true),
+        // the console only has its 16 colors, and approximates everything else badly
+        "linux" | "vt100" | "vt220" | "dumb" => Some(false),
+        _ => None,
+    }
+}
+
+/// whether the locale is a UTF-8 one
+fn unicode_from_env() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        .is_some_and(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+/// the answers to the queries `probe` sends, as far as they got in time
+#[cfg(unix)]
+#[derive(Debug, PartialEq, Eq)]
+struct Answers {
+    /// DECRQSS reported the 24 bit color just set back
+    truecolor: Option<bool>,
+    /// the column the cursor ended up at after the half block, counting from 1
+    column: Option<u16>,
+}
+
+#[cfg(unix)]
+fn parse_answers(answers: &[u8]) -> Answers {
+    let text = String::from_utf8_lossy(answers);
+
+    // DCS 1 $ r <the SGR> ST when the request was valid, DCS 0 $ r ST otherwise
+    let truecolor = text.find("\x1bP").map(|start| {
+        let reply = &text[start..];
+        reply.starts_with("\x1bP1$r") && (reply.contains(":1:2:3") || reply.contains(";1;2;3"))
+    });
+
+    // CSI row ; column R
+    let column = text.rfind("\x1b[").and_then(|start| {
+        let report = text[start + 2..].split_once('R')?.0;
+        report.split_once(';')?.1.parse().ok()
+    });
+
+    Answers { truecolor, column }
+}
+
+/// writes `query` to the terminal and reads what comes back until a cursor report ends it,
+/// or the terminal took too long
+#[cfg(unix)]
+pub(super) fn ask(query: &str) -> Option<Vec<u8>> {
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+
+    let mut tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let fd = tty.as_raw_fd();
+
+    // Safety: an all zero termios is valid, tcgetattr overwrites it anyway
+    let mut saved = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+        return None;
+    }
+    // the answers come in as input, which mustn't be echoed or held back until a newline
+    let mut quiet = saved;
+    quiet.c_lflag &= !(libc::ICANON | libc::ECHO);
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &quiet) };
+
+    let mut answers = vec![];
+    let res = tty.write_all(query.as_bytes()).and_then(|()| tty.flush());
+    if res.is_ok() {
+        let deadline = std::time::Instant::now() + ANSWER_TIMEOUT;
+        // the cursor report comes last, every terminal sends one
+        while !answers.ends_with(b"R") {
+            let Some(left) = deadline.checked_duration_since(std::time::Instant::now()) else {
+                break;
+            };
+            let mut poll = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let ready = unsafe { libc::poll(&mut poll, 1, left.as_millis() as libc::c_int) };
+            if ready <= 0 {
+                break;
+            }
+
+            let mut buffer = [0; 64];
+            match tty.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => answers.extend_from_slice(&buffer[..n]),
+            }
+        }
+    }
+
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &saved) };
+    Some(answers)
+}
+
+/// sets a 24 bit color and asks for it back with DECRQSS, then draws a half block
+/// and asks where the cursor went, cleaning up after itself
+#[cfg(unix)]
+fn probe() -> Option<Answers> {
+    const QUERY: &str = "\x1b[38;2;1;2;3m\x1bP$qm\x1b\\\r\u{2580}\x1b[6n\r\x1b[0m\x1b[2K";
+
+    ask(QUERY).map(|answers| parse_answers(&answers))
+}
+
+/// asks the terminal the player is about to take over what it can draw
+pub fn detect() -> Capabilities {
+    let env_truecolor = truecolor_from_env();
+    let env_unicode = unicode_from_env();
+
+    // the answers of another terminal would go to whatever reads its input
+    #[cfg(unix)]
+    if !super::redirected()
+        && let Some(answers) = probe()
+    {
+        tracing::debug!(?answers, "the terminal answered");
+        return Capabilities {
+            // a terminal that doesn't understand DECRQSS (tmux, most over ssh) says nothing,
+            // which is no reason to think it can't take 24 bit colors
+            truecolor: env_truecolor.or(answers.truecolor).unwrap_or(true),
+            // `\r` then the block leaves the cursor at column 2 when it took one cell
+            unicode: answers.column.map_or(env_unicode, |column| column == 2),
+        };
+    }
+
+    Capabilities {
+        truecolor: env_truecolor.unwrap_or(true),
+        unicode: env_unicode,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::{Answers, parse_answers};
+
+    #[test]
+    fn reads_both_answers() {
+        let answers = parse_answers(b"\x1bP1$r0;38:2::1:2:3m\x1b\\\x1b[12;2R");
+        assert_eq!(
+            answers,
+            Answers {
+                truecolor: Some(true),
+                column: Some(2),
+            }
+        );
+
+        // no DECRQSS, and the block took three cells as latin-1
+        let answers = parse_answers(b"\x1b[5;4R");
+        assert_eq!(
+            answers,
+            Answers {
+                truecolor: None,
+                column: Some(4),
+            }
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use super::{Event, Key, Mouse, MouseAction, Terminal, TerminalBackend};
+use crate::cancel::CancellationToken;
+use crate::flag;
+use crossterm::event::{
+    DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture,
+    Event as CrosstermEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton,
+    MouseEvent, MouseEventKind,
+};
+use crossterm::{cursor, execute, terminal};
+use std::fs::File;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// how long a wait for input lasts before checking the token again
+const POLL_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// set while the terminal is raw and on the alternate screen
+static ARMED: AtomicBool = AtomicBool::new(false);
+/// set while clicks are being reported
+static MOUSE: AtomicBool = AtomicBool::new(false);
+/// set while focus changes are being reported
+static FOCUS: AtomicBool = AtomicBool::new(false);
+
+/// puts the terminal back the way it was, safe to call more than once
+fn restore() {
+    if !ARMED.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    if MOUSE.swap(false, Ordering::SeqCst) {
+        let _ = execute!(std::io::stdout(), DisableMouseCapture);
+    }
+    if FOCUS.swap(false, Ordering::SeqCst) {
+        let _ = execute!(std::io::stdout(), DisableFocusChange);
+    }
+    match super::inline::leave() {
+        Some(leave) => {
+            let _ = execute!(
+                std::io::stdout(),
+                crossterm::style::Print(leave),
+                cursor::Show
+            );
+        }
+        None => {
+            let _ = execute!(
+                std::io::stdout(),
+                cursor::Show,
+                terminal::LeaveAlternateScreen
+            );
+        }
+    }
+    let _ = terminal::disable_raw_mode();
+}
+
+struct Restore {
+    /// `NO_TTY` leaves the terminal alone apart from the cursor
+    took_over: bool,
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        match self.took_over {
+            true => restore(),
+            false => {
+                let _ = execute!(std::io::stdout(), cursor::Show);
+            }
+        }
+
+        #[cfg(unix)]
+        crate::terminal_guard::disarm();
+    }
+}
+
+/// a handle of its own for stdout, which is where crossterm draws
+fn clone_stdout() -> std::io::Result<File> {
+    let stdout = std::io::stdout();
+
+    #[cfg(unix)]
+    let handle = std::os::fd::AsFd::as_fd(&stdout).try_clone_to_owned()?;
+    #[cfg(windows)]
+    let handle = std::os::windows::io::AsHandle::as_handle(&stdout).try_clone_to_owned()?;
+
+    Ok(File::from(handle))
+}
+
+fn translate_mouse(
+    MouseEvent {
+        kind, column, row, ..
+    }: MouseEvent,
+) -> Option<Event> {
+    let action = match kind {
+        MouseEventKind::Down(MouseButton::Left) => MouseAction::Left,
+        MouseEventKind::Down(MouseButton::Middle) => MouseAction::Middle,
+        MouseEventKind::ScrollUp => MouseAction::WheelUp,
+        MouseEventKind::ScrollDown => MouseAction::WheelDown,
+        _ => return None,
+    };
+
+    Some(Event::Mouse(Mouse {
+        action,
+        x: column,
+        y: row,
+    }))
+}
+
+fn translate(event: CrosstermEvent) -> Option<Event> {
+    let (code, modifiers) = match event {
+        CrosstermEvent::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        }) => (code, modifiers),
+        CrosstermEvent::Mouse(mouse) => return translate_mouse(mouse),
+        CrosstermEvent::FocusGained => return Some(Event::Focus(true)),
+        CrosstermEvent::FocusLost => return Some(Event::Focus(false)),
+        _ => return None,
+    };
+
+    let key = match code {
+        KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+            Key::Ctrl(c.to_ascii_lowercase())
+        }
+        KeyCode::Char(c) => Key::Char(c),
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::F(n) => Key::F(n),
+        _ => Key::Other,
+    };
+
+    Some(Event::Key(key))
+}
+
+/// Any terminal crossterm knows how to drive, which includes the windows console
+pub struct Crossterm;
+
+impl TerminalBackend for Crossterm {
+    fn take(&self) -> std::io::Result<Terminal> {
+        // the renderer writes its escape sequences straight to the console,
+        // so windows has to be told to understand them up front
+        #[cfg(windows)]
+        if !crossterm::ansi_support::supports_ansi() {
+            return Err(std::io::Error::other(
+                "the console doesn't understand VT escape sequences",
+            ));
+        }
+
+        let output = clone_stdout()?;
+        let mut stdout = std::io::stdout();
+
+        if flag("NO_TTY", false) {
+            execute!(stdout, cursor::Hide)?;
+            return Ok(Terminal {
+                output,
+                restore: Box::new(Restore { took_over: false }),
+            });
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::fd::AsRawFd;
+            crate::terminal_guard::install(stdout.as_raw_fd());
+        }
+
+        terminal::enable_raw_mode()?;
+        ARMED.store(true, Ordering::SeqCst);
+        let guard = Restore { took_over: true };
+
+        // on unix the terminal guard already covers panics and signals
+        #[cfg(not(unix))]
+        {
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                restore();
+                previous(info)
+            }));
+        }
+
+        // there will be a clear on the first fetch from the size cache
+        // so wait until first render before clearing
+        match super::inline::rows() {
+            Some(_) => execute!(stdout, cursor::Hide)?,
+            None => execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?,
+        }
+        if super::mouse_enabled() {
+            execute!(stdout, EnableMouseCapture)?;
+            MOUSE.store(true, Ordering::SeqCst);
+        }
+        if super::focus_reported() {
+            execute!(stdout, EnableFocusChange)?;
+            FOCUS.store(true, Ordering::SeqCst);
+        }
+        stdout.flush()?;
+
+        Ok(Terminal {
+            output,
+            restore: Box::new(guard),
+        })
+    }
+
+    fn size(&self) -> Option<(u16, u16)> {
+        terminal::size().ok()
+    }
+
+    fn events(&self, token: CancellationToken) -> Box<dyn Iterator<Item = Event>> {
+        Box::new(std::iter::from_fn(move || {
+            loop {
+                if token.is_cancelled() {
+                    return None;
+                }
+
+                match crossterm::event::poll(POLL_TIMEOUT) {
+                    Ok(false) => continue,
+                    Ok(true) => {}
+                    Err(_) => return None,
+                }
+
+                match crossterm::event::read().map(translate) {
+                    Ok(Some(event)) => return Some(event),
+                    Ok(None) => {}
+                    Err(_) => return None,
+                }
+            }
+        }))
+    }
+}
@@ -0,0 +1,65 @@
+//! `--inline`, drawing into a few rows below the prompt on the main screen
+//! rather than taking over the alternate one, so the last frame stays behind in the scrollback
+use std::sync::OnceLock;
+
+/// The rows the video gets drawn in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rows {
+    /// the first one, counting from 0
+    pub top: u16,
+    pub count: u16,
+}
+
+static ROWS: OnceLock<Rows> = OnceLock::new();
+
+/// the row of a `CSI row ; column R` cursor report, counting from 1
+#[cfg(unix)]
+fn cursor_row(answer: &[u8]) -> Option<u16> {
+    let text = std::str::from_utf8(answer).ok()?;
+    let start = text.rfind("\x1b[")?;
+    let report = text[start + 2..].strip_suffix('R')?;
+    report.split_once(';')?.0.parse().ok()
+}
+
+/// makes room for `count` rows starting at the cursor and draws in those from now on,
+/// has to happen before taking the terminal over
+#[cfg(unix)]
+pub fn reserve(count: std::num::NonZero<u16>) -> std::io::Result<()> {
+    // the newlines scroll up as far as it takes, going back up leaves the cursor at the first row
+    let query = format!("{}\x1b[{count}A\x1b[6n", "\n".repeat(count.get().into()));
+    let row = super::capabilities::ask(&query)
+        .as_deref()
+        .and_then(cursor_row)
+        .ok_or_else(|| std::io::Error::other("the terminal didn't say where the cursor is"))?;
+
+    let _ = ROWS.set(Rows {
+        top: row.saturating_sub(1),
+        count: count.get(),
+    });
+    crate::terminal_guard::stay_inline();
+    Ok(())
+}
+
+/// where the video goes when drawing inline
+pub fn rows() -> Option<Rows> {
+    ROWS.get().copied()
+}
+
+/// resets the colors and puts the cursor on the line after the last frame,
+/// where the shell prompt ends up
+pub(super) fn leave() -> Option<String> {
+    let Rows { top, count } = rows()?;
+    Some(format!("\x1b[0m{}\r\n", super::goto(1, top + count)))
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::cursor_row;
+
+    #[test]
+    fn reads_the_row() {
+        assert_eq!(cursor_row(b"\x1b[17;1R"), Some(17));
+        assert_eq!(cursor_row(b"junk\x1b[3;40R"), Some(3));
+        assert_eq!(cursor_row(b"\x1b[3;40"), None);
+    }
+}
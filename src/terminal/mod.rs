@@ -0,0 +1,247 @@
+//! The backend specific parts of driving a terminal: taking it over, its size and its keys,
+//! everything else only ever writes plain escape sequences to it
+use crate::cancel::CancellationToken;
+use std::fs::File;
+#[cfg(unix)]
+use std::io::Write;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub mod capabilities;
+mod crossterm_backend;
+pub mod inline;
+pub mod profile;
+#[cfg(unix)]
+mod termion_backend;
+
+pub const CLEAR: &str = "\x1b[2J";
+pub const HIDE_CURSOR: &str = "\x1b[?25l";
+pub const SHOW_CURSOR: &str = "\x1b[?25h";
+/// the terminal holds off showing anything until the update ends, terminals without it ignore both
+pub const BEGIN_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026h";
+pub const END_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026l";
+
+/// whether clicks and the wheel get reported, taking them means the terminal can't select text
+fn mouse_enabled() -> bool {
+    !crate::flag("NO_MOUSE", false)
+}
+
+/// set when the terminal should say whenever it gains or loses focus
+static FOCUS: AtomicBool = AtomicBool::new(false);
+
+/// has the terminal report focus changes as [`Event::Focus`], has to happen before taking it over
+pub fn report_focus() {
+    FOCUS.store(true, Ordering::Relaxed);
+}
+
+fn focus_reported() -> bool {
+    FOCUS.load(Ordering::Relaxed)
+}
+
+/// moves the cursor to column `x` and row `y`, counting from 1
+pub fn goto(x: u16, y: u16) -> String {
+    format!("\x1b[{y};{x}H")
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Esc,
+    /// a function key, F1 being 1
+    F(u8),
+    Other,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MouseAction {
+    Left,
+    Middle,
+    WheelUp,
+    WheelDown,
+}
+
+/// a click or a turn of the wheel, at a cell counting from 0
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Mouse {
+    pub action: MouseAction,
+    pub x: u16,
+    pub y: u16,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    Key(Key),
+    Mouse(Mouse),
+    /// the terminal window gained focus, or lost it when false
+    Focus(bool),
+}
+
+/// The terminal while the player has it, raw and on the alternate screen unless asked not to
+pub struct Terminal {
+    /// a handle of its own for the output, so it can be written from any thread
+    pub output: File,
+    /// puts the terminal back the way it was when dropped
+    pub restore: Box<dyn Send>,
+}
+
+/// Raw mode, the alternate screen, the size and decoding input, for one way of talking to terminals
+pub trait TerminalBackend: Send + Sync {
+    /// takes over the terminal, the cursor is hidden until it is restored
+    fn take(&self) -> std::io::Result<Terminal>;
+
+    /// the size of the terminal in cells
+    fn size(&self) -> Option<(u16, u16)>;
+
+    /// the keys pressed, mouse buttons clicked and focus changes until the token is cancelled
+    fn events(&self, token: CancellationToken) -> Box<dyn Iterator<Item = Event>>;
+}
+
+#[derive(clap::ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// unix terminals only
+    #[cfg(unix)]
+    #[default]
+    Termion,
+    /// works everywhere, including the windows console
+    #[cfg_attr(not(unix), default)]
+    Crossterm,
+}
+
+impl Backend {
+    fn make(self) -> Box<dyn TerminalBackend> {
+        match self {
+            #[cfg(unix)]
+            Backend::Termion => Box::new(termion_backend::Termion),
+            Backend::Crossterm => Box::new(crossterm_backend::Crossterm),
+        }
+    }
+}
+
+static BACKEND: OnceLock<Box<dyn TerminalBackend>> = OnceLock::new();
+
+/// picks the backend everything below goes through, has to happen before the terminal is used
+pub fn select(backend: Backend) {
+    let _ = BACKEND.set(backend.make());
+}
+
+fn backend() -> &'static dyn TerminalBackend {
+    BACKEND.get_or_init(|| Backend::default().make()).as_ref()
+}
+
+/// where frames go instead of the terminal the player runs in
+#[cfg(unix)]
+static TARGET: OnceLock<File> = OnceLock::new();
+
+/// the size of the terminal `fd` is, if it is one
+#[cfg(unix)]
+pub(crate) fn fd_size(fd: std::os::fd::RawFd) -> Option<(u16, u16)> {
+    // Safety: an all zero winsize is valid, and the ioctl only writes to it
+    let mut size = unsafe { std::mem::zeroed::<libc::winsize>() };
+    let res = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) };
+    (res == 0 && size.ws_col > 0 && size.ws_row > 0).then_some((size.ws_col, size.ws_row))
+}
+
+/// draws on the terminal at `tty` from now on, e.g. another tmux pane,
+/// keys are still read from the one the player runs in; has to happen before taking it over
+#[cfg(unix)]
+pub fn redirect(tty: &std::path::Path) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let target = std::fs::OpenOptions::new().write(true).open(tty)?;
+    if unsafe { libc::isatty(target.as_raw_fd()) } != 1 {
+        return Err(std::io::Error::other("not a terminal"));
+    }
+    let _ = TARGET.set(target);
+    Ok(())
+}
+
+/// whether frames go to another terminal than the one the player runs in
+#[cfg(unix)]
+pub(crate) fn redirected() -> bool {
+    TARGET.get().is_some()
+}
+
+/// leaves the alternate screen of the other terminal, then restores the one the player runs in
+#[cfg(unix)]
+struct Redirected {
+    target: File,
+    _restore: Box<dyn Send>,
+}
+
+#[cfg(unix)]
+impl Drop for Redirected {
+    fn drop(&mut self) {
+        let _ = write!(self.target, "\x1b[?1049l{SHOW_CURSOR}");
+        let _ = self.target.flush();
+    }
+}
+
+/// Gives the terminal back to the shell until dropped, while the process is stopped
+#[cfg(unix)]
+pub(crate) struct SteppedOut(crate::terminal_guard::SteppedOut);
+
+/// cooked mode on the main screen with the cursor showing, the way the shell wants it,
+/// `None` when the terminal isn't taken over
+#[cfg(unix)]
+pub(crate) fn step_out() -> Option<SteppedOut> {
+    crate::terminal_guard::step_out().map(SteppedOut)
+}
+
+#[cfg(unix)]
+impl Drop for SteppedOut {
+    fn drop(&mut self) {
+        // the same modes taking the terminal over turns on, frames get drawn in full afterwards
+        let mut enter = match inline::rows() {
+            Some(_) => HIDE_CURSOR.to_string(),
+            None => format!("\x1b[?1049h{HIDE_CURSOR}"),
+        };
+        if mouse_enabled() {
+            enter.push_str("\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h");
+        }
+        if focus_reported() {
+            enter.push_str("\x1b[?1004h");
+        }
+        self.0.step_in(enter.as_bytes());
+    }
+}
+
+pub fn take() -> std::io::Result<Terminal> {
+    // the terminal the player runs in still goes raw, for its keys
+    let terminal = backend().take()?;
+
+    #[cfg(unix)]
+    if let Some(target) = TARGET.get() {
+        let mut output = target.try_clone()?;
+        write!(output, "\x1b[?1049h{HIDE_CURSOR}")?;
+        output.flush()?;
+        // a panic or signal leaves it on its alternate screen otherwise
+        crate::terminal_guard::restore_target(std::os::fd::AsRawFd::as_raw_fd(target));
+        return Ok(Terminal {
+            output,
+            restore: Box::new(Redirected {
+                target: target.try_clone()?,
+                _restore: terminal.restore,
+            }),
+        });
+    }
+
+    Ok(terminal)
+}
+
+pub fn size() -> Option<(u16, u16)> {
+    #[cfg(unix)]
+    if let Some(target) = TARGET.get() {
+        return fd_size(std::os::fd::AsRawFd::as_raw_fd(target));
+    }
+
+    backend().size()
+}
+
+pub fn events(token: CancellationToken) -> impl Iterator<Item = Event> {
+    backend().events(token)
+}
@@ -0,0 +1,128 @@
+//! How far away the terminal is, from the environment and from how long it takes to answer,
+//! which picks the defaults that keep playing smooth over ssh and serial lines
+use std::num::NonZero;
+use std::time::Duration;
+
+/// Where the terminal sits, each with defaults fitting the link
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Profile {
+    /// on this machine, or so close by it doesn't matter
+    Local,
+    /// at the other end of an ssh session
+    Ssh,
+    /// behind a serial line, every byte counts
+    Serial,
+}
+
+/// What a profile sets when nothing else does
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Defaults {
+    pub max_fps: Option<NonZero<u32>>,
+    pub precision: u8,
+    /// wrap every frame in a synchronized update so it shows all at once
+    pub synchronized: bool,
+}
+
+/// answers quicker than this come from about as near as the machine itself
+const NEARBY: Duration = Duration::from_millis(5);
+
+impl Profile {
+    pub fn defaults(self) -> Defaults {
+        match self {
+            Profile::Local => Defaults {
+                max_fps: None,
+                precision: crate::terminal_sink::DEFAULT_PRECISION,
+                synchronized: true,
+            },
+            Profile::Ssh => Defaults {
+                max_fps: NonZero::new(30),
+                precision: 4,
+                synchronized: true,
+            },
+            // serial terminals are mostly too old to know synchronized updates
+            Profile::Serial => Defaults {
+                max_fps: NonZero::new(10),
+                precision: 3,
+                synchronized: false,
+            },
+        }
+    }
+}
+
+/// whether the player runs in a session someone logged into with ssh
+fn ssh_from_env() -> bool {
+    ["SSH_CONNECTION", "SSH_TTY", "SSH_CLIENT"]
+        .into_iter()
+        .any(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty()))
+}
+
+/// whether `tty` is a serial port, a USB adapter or the UART of a board
+fn is_serial(tty: &str) -> bool {
+    ["/dev/ttyS", "/dev/ttyUSB", "/dev/ttyACM", "/dev/ttyAMA"]
+        .into_iter()
+        .any(|prefix| tty.starts_with(prefix))
+}
+
+#[cfg(unix)]
+fn tty_name() -> Option<String> {
+    use std::os::fd::AsRawFd;
+
+    let fd = std::io::stdout().as_raw_fd();
+    let mut name = [0 as libc::c_char; 128];
+    if unsafe { libc::ttyname_r(fd, name.as_mut_ptr(), name.len()) } != 0 {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// how long the terminal takes to report where the cursor is, `None` when it didn't in time
+#[cfg(unix)]
+fn round_trip() -> Option<Duration> {
+    let start = std::time::Instant::now();
+    let answer = super::capabilities::ask("\x1b[6n")?;
+    answer.ends_with(b"R").then(|| start.elapsed())
+}
+
+/// works out where the terminal about to be taken over is
+pub fn detect() -> Profile {
+    #[cfg(unix)]
+    {
+        // another terminal's answers would go to whatever reads its input
+        if super::redirected() {
+            return Profile::Local;
+        }
+        if tty_name().is_some_and(|tty| is_serial(&tty)) {
+            return Profile::Serial;
+        }
+        if ssh_from_env() {
+            let rtt = round_trip();
+            tracing::debug!(?rtt, "the terminal is on the other end of ssh");
+            // a session to a machine on the same desk doesn't need holding back
+            return match rtt {
+                Some(rtt) if rtt < NEARBY => Profile::Local,
+                _ => Profile::Ssh,
+            };
+        }
+    }
+
+    #[cfg(not(unix))]
+    if ssh_from_env() {
+        return Profile::Ssh;
+    }
+
+    Profile::Local
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_serial;
+
+    #[test]
+    fn serial_ports() {
+        assert!(is_serial("/dev/ttyS0"));
+        assert!(is_serial("/dev/ttyUSB1"));
+        assert!(!is_serial("/dev/pts/3"));
+        assert!(!is_serial("/dev/tty1"));
+    }
+}
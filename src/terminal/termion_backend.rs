@@ -0,0 +1,240 @@
+use super::{Event, Key, Mouse, MouseAction, SHOW_CURSOR, Terminal, TerminalBackend};
+use crate::cancel::CancellationToken;
+use crate::{flag, terminal_guard};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{AsFd, AsRawFd};
+use termion::input::{MouseTerminal, TermRead};
+use termion::raw::IntoRawMode;
+use termion::screen::IntoAlternateScreen;
+
+/// asks the terminal to report focus changes, and to stop again
+const FOCUS_ON: &str = "\x1b[?1004h";
+const FOCUS_OFF: &str = "\x1b[?1004l";
+
+/// what the terminal sends on gaining and losing focus, termion doesn't know these
+const FOCUS_IN: &[u8] = b"\x1b[I";
+const FOCUS_OUT: &[u8] = b"\x1b[O";
+
+/// the raw mode, alternate screen and mouse guards from termion, or nothing for plain stdout
+struct Restore(Option<Box<dyn Write + Send>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if let Some(mut tty) = self.0.take() {
+            if super::focus_reported() {
+                let _ = tty.write_all(FOCUS_OFF.as_bytes());
+            }
+            if let Some(leave) = super::inline::leave() {
+                let _ = tty.write_all(leave.as_bytes());
+            }
+            let _ = tty.write_all(SHOW_CURSOR.as_bytes());
+            let _ = tty.flush();
+        }
+        terminal_guard::disarm();
+    }
+}
+
+fn take() -> std::io::Result<Terminal> {
+    fn make_tty<T: Write + AsFd + AsRawFd + Send + 'static>(
+        tty: T,
+    ) -> std::io::Result<(File, Box<dyn Write + Send>)> {
+        let output = File::from(tty.as_fd().try_clone_to_owned()?);
+        terminal_guard::install(tty.as_raw_fd());
+        let tty = tty.into_raw_mode()?;
+        let tty: Box<dyn Write + Send> = match super::inline::rows() {
+            Some(_) => Box::new(tty),
+            None => Box::new(tty.into_alternate_screen()?),
+        };
+        let tty: Box<dyn Write + Send> = match super::mouse_enabled() {
+            true => Box::new(MouseTerminal::from(tty)),
+            false => tty,
+        };
+        Ok((output, tty))
+    }
+
+    let (output, mut tty) = if flag("NO_TTY", false) {
+        let stdout = std::io::stdout();
+        let output = File::from(stdout.as_fd().try_clone_to_owned()?);
+        (output, Box::new(stdout) as Box<dyn Write + Send>)
+    } else if !flag("USE_STDOUT", false)
+        && let Ok(tty) = termion::get_tty()
+    {
+        make_tty(tty)?
+    } else {
+        make_tty(std::io::stdout())?
+    };
+
+    // there will be a clear on the first fetch from the size cache
+    // so wait until first render before clearing
+    tty.write_all(super::HIDE_CURSOR.as_bytes())?;
+    if super::focus_reported() {
+        tty.write_all(FOCUS_ON.as_bytes())?;
+    }
+    tty.flush()?;
+
+    Ok(Terminal {
+        output,
+        restore: Box::new(Restore(Some(tty))),
+    })
+}
+
+/// Unbuffered stdin that reports an end of file once the token is cancelled
+struct CancellableStdin(CancellationToken);
+
+impl CancellableStdin {
+    // how long a read waits before checking the token again
+    const POLL_TIMEOUT_MS: libc::c_int = 50;
+}
+
+impl Read for CancellableStdin {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if self.0.is_cancelled() {
+                return Ok(0);
+            }
+
+            let mut pollfd = libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+
+            match unsafe { libc::poll(&mut pollfd, 1, Self::POLL_TIMEOUT_MS) } {
+                0 => continue,
+                -1 => {
+                    let err = std::io::Error::last_os_error();
+                    if err.kind() == std::io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return Err(err);
+                }
+                _ => {}
+            }
+
+            // read the fd directly, a buffered read would hide pending input from poll
+            let read =
+                unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+            return match read {
+                -1 => Err(std::io::Error::last_os_error()),
+                n => Ok(n as usize),
+            };
+        }
+    }
+}
+
+fn translate(event: termion::event::Event) -> Option<Event> {
+    use termion::event::{Event as TermionEvent, Key as TermionKey, MouseButton, MouseEvent};
+
+    let key = match event {
+        TermionEvent::Key(key) => key,
+        TermionEvent::Mouse(MouseEvent::Press(button, x, y)) => {
+            let action = match button {
+                MouseButton::Left => MouseAction::Left,
+                MouseButton::Middle => MouseAction::Middle,
+                MouseButton::WheelUp => MouseAction::WheelUp,
+                MouseButton::WheelDown => MouseAction::WheelDown,
+                _ => return None,
+            };
+
+            // termion counts from 1
+            return Some(Event::Mouse(Mouse {
+                action,
+                x: x.saturating_sub(1),
+                y: y.saturating_sub(1),
+            }));
+        }
+        _ => return None,
+    };
+
+    let key = match key {
+        TermionKey::Char(c) => Key::Char(c),
+        TermionKey::Ctrl(c) => Key::Ctrl(c),
+        TermionKey::Left => Key::Left,
+        TermionKey::Right => Key::Right,
+        TermionKey::Up => Key::Up,
+        TermionKey::Down => Key::Down,
+        TermionKey::Esc => Key::Esc,
+        TermionKey::F(n) => Key::F(n),
+        _ => Key::Other,
+    };
+
+    Some(Event::Key(key))
+}
+
+/// the events in one read off stdin, termion gives up on the whole read at a focus change
+/// so those are picked out before it gets to see the rest
+fn parse(mut input: &[u8], events: &mut VecDeque<Event>) {
+    while !input.is_empty() {
+        let focus = input
+            .windows(FOCUS_IN.len())
+            .position(|bytes| bytes == FOCUS_IN || bytes == FOCUS_OUT);
+        let (keys, rest) = input.split_at(focus.unwrap_or(input.len()));
+        events.extend(keys.events().map_while(Result::ok).filter_map(translate));
+
+        let Some(change) = rest.get(..FOCUS_IN.len()) else {
+            break;
+        };
+        events.push_back(Event::Focus(change == FOCUS_IN));
+        input = &rest[FOCUS_IN.len()..];
+    }
+}
+
+fn events(token: CancellationToken) -> impl Iterator<Item = Event> {
+    let mut stdin = CancellableStdin(token);
+    let mut events = VecDeque::new();
+    let mut buf = [0; 256];
+    std::iter::from_fn(move || {
+        loop {
+            if let Some(event) = events.pop_front() {
+                return Some(event);
+            }
+
+            match stdin.read(&mut buf) {
+                Ok(0) => return None,
+                Ok(read) => parse(&buf[..read], &mut events),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => return None,
+            }
+        }
+    })
+}
+
+/// Unix terminals through termion, reading input straight off stdin
+pub struct Termion;
+
+impl TerminalBackend for Termion {
+    fn take(&self) -> std::io::Result<Terminal> {
+        take()
+    }
+
+    fn size(&self) -> Option<(u16, u16)> {
+        termion::terminal_size().ok()
+    }
+
+    fn events(&self, token: CancellationToken) -> Box<dyn Iterator<Item = Event>> {
+        Box::new(events(token))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Event, Key, parse};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn focus_changes_between_keys() {
+        let mut events = VecDeque::new();
+        parse(b"a\x1b[Ob\x1b[I", &mut events);
+        assert_eq!(
+            Vec::from(events),
+            [
+                Event::Key(Key::Char('a')),
+                Event::Focus(false),
+                Event::Key(Key::Char('b')),
+                Event::Focus(true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::os::fd::RawFd;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// turns focus and mouse reporting off, then `cursor::Show` followed by `screen::ToMainScreen`,
+/// spelled out since the signal handler can't format anything
+const RESTORE: &[u8] =
+    b"\x1b[?1004l\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l\x1b[?25h\x1b[?1049l";
+/// the same without going back to the main screen, for when it never left
+const RESTORE_INLINE: &[u8] = b"\x1b[?1004l\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l\x1b[?25h";
+/// `cursor::Show` and `screen::ToMainScreen` for the terminal frames get drawn on with --tty
+const RESTORE_TARGET: &[u8] = b"\x1b[?25h\x1b[?1049l";
+/// and back onto its alternate screen with the cursor hidden
+const ENTER_TARGET: &[u8] = b"\x1b[?1049h\x1b[?25l";
+
+/// the terminal and the mode it was in before it got taken over
+static SAVED: OnceLock<(RawFd, libc::termios)> = OnceLock::new();
+/// cleared once the renderer gave the terminal back by itself
+static ARMED: AtomicBool = AtomicBool::new(false);
+/// set when drawing inline, on the main screen
+static INLINE: AtomicBool = AtomicBool::new(false);
+/// the other terminal frames get drawn on, it only ever goes onto the alternate screen
+static TARGET: OnceLock<RawFd> = OnceLock::new();
+
+/// puts the terminal back the way it was, only async signal safe calls in here
+fn restore() {
+    if !ARMED.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    let Some(&(fd, ref termios)) = SAVED.get() else {
+        return;
+    };
+
+    // Safety: fd stays open for as long as the guard is armed, the target for as long as the process
+    // runs, and termios was filled in by tcgetattr
+    unsafe {
+        let restore = match INLINE.load(Ordering::SeqCst) {
+            true => RESTORE_INLINE,
+            false => RESTORE,
+        };
+        libc::write(fd, restore.as_ptr().cast(), restore.len());
+        libc::tcsetattr(fd, libc::TCSANOW, termios);
+        if let Some(&target) = TARGET.get() {
+            libc::write(target, RESTORE_TARGET.as_ptr().cast(), RESTORE_TARGET.len());
+        }
+    }
+}
+
+/// puts the terminal back, then dies the way `signal` would have killed us
+pub(crate) fn restore_and_raise(signal: libc::c_int) {
+    restore();
+
+    // Safety: signal and raise are async signal safe, and the default action is always a valid handler
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    restore_and_raise(signal);
+}
+
+/// remembers the mode of `fd` before it goes raw, and makes sure it gets restored
+/// when something panics or the process gets told to stop
+pub fn install(fd: RawFd) {
+    // Safety: an all zero termios is valid, tcgetattr overwrites it anyway
+    let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return;
+    }
+
+    if SAVED.set((fd, termios)).is_err() {
+        // already installed, the terminal only gets taken over once
+        return;
+    }
+    ARMED.store(true, Ordering::SeqCst);
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // restore first, so the panic message doesn't end up on the alternate screen
+        restore();
+        previous(info)
+    }));
+
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT] {
+        // those quit through the bus instead, which restores the terminal too
+        if crate::shutdown::handles(signal) {
+            continue;
+        }
+
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        crate::self_pipe::set_handler(signal, handler, 0);
+    }
+}
+
+/// restores the terminal `fd` too, the one frames get drawn on with --tty,
+/// it has to stay open until the process exits
+pub(crate) fn restore_target(fd: RawFd) {
+    let _ = TARGET.set(fd);
+}
+
+/// the terminal stays on the main screen, so restoring it mustn't leave the alternate one
+pub(crate) fn stay_inline() {
+    INLINE.store(true, Ordering::SeqCst);
+}
+
+/// The raw mode the terminal was in before it got handed back to the shell for a while
+pub(crate) struct SteppedOut {
+    fd: RawFd,
+    raw: libc::termios,
+}
+
+/// puts the terminal back the way it was, for as long as the process is stopped,
+/// `None` when it isn't taken over at all
+pub(crate) fn step_out() -> Option<SteppedOut> {
+    if !ARMED.load(Ordering::SeqCst) {
+        return None;
+    }
+    let &(fd, _) = SAVED.get()?;
+
+    // Safety: an all zero termios is valid, tcgetattr overwrites it anyway
+    let mut raw = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut raw) } != 0 {
+        return None;
+    }
+
+    restore();
+    Some(SteppedOut { fd, raw })
+}
+
+impl SteppedOut {
+    /// raw again, with `enter` written to get back onto the alternate screen and such
+    pub(crate) fn step_in(&self, enter: &[u8]) {
+        // Safety: fd stays open for as long as the terminal is taken over and raw came from tcgetattr
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.raw);
+            libc::write(self.fd, enter.as_ptr().cast(), enter.len());
+            if let Some(&target) = TARGET.get() {
+                libc::write(target, ENTER_TARGET.as_ptr().cast(), ENTER_TARGET.len());
+            }
+        }
+        ARMED.store(true, Ordering::SeqCst);
+    }
+}
+
+/// the terminal was restored normally, nothing left to do on a panic or signal
+pub fn disarm() {
+    ARMED.store(false, Ordering::SeqCst);
+}
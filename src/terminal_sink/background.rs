@@ -0,0 +1,123 @@
+//! What the part of the screen the video doesn't cover gets painted with
+use crate::terminal;
+use crate::terminal_sink::diff::{Palette, write_sgr};
+use rgb::Rgb;
+use std::str::FromStr;
+
+/// the two colors of the checkerboard, dark enough not to take the eye off the video
+const CHECKERS: [Rgb<u8>; 2] = [Rgb::new(0x40, 0x40, 0x40), Rgb::new(0x60, 0x60, 0x60)];
+/// how many columns and rows a checker spans, about square with cells twice as tall as wide
+const CHECKER_SIZE: (u16, u16) = (4, 2);
+
+/// What gets drawn around the video instead of whatever the terminal clears to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Background {
+    Color(Rgb<u8>),
+    Checkerboard,
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("checkerboard") {
+            return Ok(Self::Checkerboard);
+        }
+
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .ok_or_else(|| format!("expected a color like #101010 or checkerboard, not {s:?}"))?;
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+
+        Ok(Self::Color(Rgb::new(channel(0), channel(2), channel(4))))
+    }
+}
+
+/// how many pixels across and down of a `frame` pixels big frame drawn on `cells` cells
+/// make a checker as big as the ones around it
+pub(super) fn checker_pixels(
+    (width, height): (u32, u32),
+    (columns, rows): (u16, u16),
+) -> (u16, u16) {
+    let span = |pixels: u32, checker: u16, cells: u16| {
+        let span = f64::from(pixels) * f64::from(checker) / f64::from(cells.max(1));
+        (span.round() as u16).max(1)
+    };
+    (
+        span(width, CHECKER_SIZE.0, columns),
+        span(height, CHECKER_SIZE.1, rows),
+    )
+}
+
+impl Background {
+    /// the color showing through a transparent pixel at `(x, y)` of a frame,
+    /// a checker being `checker` pixels of it across and down
+    pub(super) fn behind(self, (x, y): (u16, u16), (across, down): (u16, u16)) -> Rgb<u8> {
+        match self {
+            Self::Color(color) => color,
+            Self::Checkerboard => CHECKERS[usize::from((x / across + y / down) % 2)],
+        }
+    }
+
+    /// paints the `size` cells at `origin`, with the colors reset afterwards
+    pub(super) fn paint(
+        self,
+        palette: Palette,
+        (x, y): (u16, u16),
+        (width, height): (u16, u16),
+    ) -> Vec<u8> {
+        let mut out = vec![];
+        for row in 0..height {
+            out.extend_from_slice(terminal::goto(x + 1, y + row + 1).as_bytes());
+            match self {
+                // erased cells take the current background color
+                Self::Color(color) => {
+                    write_sgr(&mut out, palette, None, Some(color));
+                    out.extend_from_slice(format!("\x1b[{width}X").as_bytes());
+                }
+                Self::Checkerboard => {
+                    let mut column = 0;
+                    while column < width {
+                        let checker = (column / CHECKER_SIZE.0 + row / CHECKER_SIZE.1) % 2;
+                        let span = (CHECKER_SIZE.0 - column % CHECKER_SIZE.0).min(width - column);
+                        write_sgr(
+                            &mut out,
+                            palette,
+                            None,
+                            Some(CHECKERS[usize::from(checker)]),
+                        );
+                        out.extend(std::iter::repeat_n(b' ', usize::from(span)));
+                        column += span;
+                    }
+                }
+            }
+        }
+        out.extend_from_slice(b"\x1b[0m");
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Background, CHECKER_SIZE, checker_pixels};
+    use rgb::Rgb;
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(
+            "#10a0FF".parse(),
+            Ok(Background::Color(Rgb::new(0x10, 0xa0, 0xff)))
+        );
+        assert_eq!("Checkerboard".parse(), Ok(Background::Checkerboard));
+        assert!("101010".parse::<Background>().is_err());
+        assert!("#10101".parse::<Background>().is_err());
+    }
+
+    #[test]
+    fn checkers_span_cells() {
+        assert_eq!(checker_pixels((80, 40), (80, 40)), CHECKER_SIZE);
+        // a frame 4 pixels a cell across and down
+        assert_eq!(checker_pixels((320, 160), (80, 40)), (16, 8));
+    }
+}
@@ -0,0 +1,200 @@
+//! `--max-bandwidth`, keeping how much gets written to the terminal a second under a budget,
+//! by skipping frames that wouldn't fit and, when that keeps happening, lowering the color precision
+//! and then the size of the video, for playing over slow ssh links
+use std::collections::VecDeque;
+use std::num::NonZero;
+use std::time::{Duration, Instant};
+
+/// Bytes a second, `200k` or `1.5M` with a suffix
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Bandwidth(NonZero<u64>);
+
+impl Bandwidth {
+    pub fn bytes_per_second(self) -> u64 {
+        self.0.get()
+    }
+}
+
+impl std::str::FromStr for Bandwidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, unit) = match s.char_indices().last() {
+            Some((i, 'k' | 'K')) => (&s[..i], 1e3),
+            Some((i, 'm' | 'M')) => (&s[..i], 1e6),
+            Some((i, 'g' | 'G')) => (&s[..i], 1e9),
+            _ => (s, 1.0),
+        };
+
+        let bytes = number
+            .parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite() && *n > 0.0)
+            .map(|n| (n * unit).round() as u64)
+            .and_then(NonZero::new)
+            .ok_or_else(|| format!("{s:?} isn't a number of bytes a second, like 200k"))?;
+        Ok(Bandwidth(bytes))
+    }
+}
+
+/// One step down in quality
+struct Level {
+    /// the most bits a channel keeps, whatever the precision is set to
+    precision: u8,
+    /// how much of the terminal the video gets fitted into
+    size: f64,
+}
+
+/// tried in order, the first one is what the player draws without a budget
+const LEVELS: [Level; 6] = [
+    Level {
+        precision: 8,
+        size: 1.0,
+    },
+    Level {
+        precision: 4,
+        size: 1.0,
+    },
+    Level {
+        precision: 3,
+        size: 1.0,
+    },
+    Level {
+        precision: 3,
+        size: 0.75,
+    },
+    Level {
+        precision: 2,
+        size: 0.75,
+    },
+    Level {
+        precision: 2,
+        size: 0.5,
+    },
+];
+
+/// Follows the bytes written over the last second and picks the level they fit the budget at
+pub(super) struct BandwidthLimiter {
+    budget: u64,
+    /// when the frames of the last second were written and how big they were
+    written: VecDeque<(Instant, u64)>,
+    level: usize,
+    /// frames offered and skipped since the level last got a look
+    offered: u32,
+    skipped: u32,
+    checked_at: Instant,
+}
+
+impl BandwidthLimiter {
+    const WINDOW: Duration = Duration::from_secs(1);
+    /// how long a level gets to show what it does before the next change
+    const SETTLE: Duration = Duration::from_secs(2);
+
+    pub(super) fn new(budget: Bandwidth) -> Self {
+        Self {
+            budget: budget.bytes_per_second(),
+            written: VecDeque::new(),
+            level: 0,
+            offered: 0,
+            skipped: 0,
+            checked_at: Instant::now(),
+        }
+    }
+
+    /// bytes written over the last second
+    fn rate(&mut self, now: Instant) -> u64 {
+        while let Some(&(at, _)) = self.written.front()
+            && now.duration_since(at) > Self::WINDOW
+        {
+            self.written.pop_front();
+        }
+        self.written.iter().map(|&(_, bytes)| bytes).sum()
+    }
+
+    /// whether a frame of about `frame_bytes` would go over the budget, so it's better skipped,
+    /// a frame always goes out when nothing else did for a second
+    pub(super) fn should_skip(&mut self, frame_bytes: u64, now: Instant) -> bool {
+        let rate = self.rate(now);
+        let skip = !self.written.is_empty() && rate + frame_bytes > self.budget;
+        self.offered += 1;
+        self.skipped += u32::from(skip);
+        self.adapt(rate, now);
+        skip
+    }
+
+    pub(super) fn wrote(&mut self, bytes: usize, now: Instant) {
+        self.written.push_back((now, bytes as u64));
+    }
+
+    /// one level down when a quarter of the frames get skipped,
+    /// one up when there's been nothing to skip and room for twice as much
+    fn adapt(&mut self, rate: u64, now: Instant) {
+        if now.duration_since(self.checked_at) < Self::SETTLE {
+            return;
+        }
+
+        let level = if self.skipped * 4 > self.offered {
+            (self.level + 1).min(LEVELS.len() - 1)
+        } else if self.skipped == 0 && rate < self.budget / 2 {
+            self.level.saturating_sub(1)
+        } else {
+            self.level
+        };
+        if level != self.level {
+            tracing::debug!(
+                level,
+                rate,
+                budget = self.budget,
+                "changing the quality to fit the bandwidth"
+            );
+        }
+
+        self.level = level;
+        self.offered = 0;
+        self.skipped = 0;
+        self.checked_at = now;
+    }
+
+    /// `precision`, unless the budget asks for less
+    pub(super) fn precision(&self, precision: u8) -> u8 {
+        precision.min(LEVELS[self.level].precision)
+    }
+
+    /// how much of the terminal the video gets fitted into
+    pub(super) fn size(&self) -> f64 {
+        LEVELS[self.level].size
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rates() {
+        let bytes = |s: &str| s.parse::<Bandwidth>().map(Bandwidth::bytes_per_second);
+        assert_eq!(bytes("200k"), Ok(200_000));
+        assert_eq!(bytes("1.5M"), Ok(1_500_000));
+        assert_eq!(bytes("9600"), Ok(9600));
+        assert!(bytes("0").is_err());
+        assert!(bytes("fast").is_err());
+    }
+
+    #[test]
+    fn skipping_frames_lowers_the_quality() {
+        let mut limiter = BandwidthLimiter::new("10k".parse().unwrap());
+        let start = Instant::now();
+
+        let mut now = start;
+        while now < start + Duration::from_secs(3) {
+            if !limiter.should_skip(4000, now) {
+                limiter.wrote(4000, now);
+            }
+            now += Duration::from_millis(40);
+        }
+
+        assert!(limiter.precision(5) < 5);
+        assert!(limiter.rate(now) <= 10_000);
+    }
+}
@@ -0,0 +1,99 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// appends `text` as a json string
+fn write_json_string(out: &mut Vec<u8>, text: &str) {
+    out.push(b'"');
+    for c in text.chars() {
+        match c {
+            '"' => out.extend_from_slice(b"\\\""),
+            '\\' => out.extend_from_slice(b"\\\\"),
+            '\n' => out.extend_from_slice(b"\\n"),
+            '\r' => out.extend_from_slice(b"\\r"),
+            '\t' => out.extend_from_slice(b"\\t"),
+            c if u32::from(c) < 0x20 => {
+                out.extend_from_slice(format!("\\u{:04x}", u32::from(c)).as_bytes())
+            }
+            c => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    out.push(b'"');
+}
+
+/// Everything written to the terminal as an asciinema v2 recording
+pub struct CastRecorder {
+    out: BufWriter<File>,
+    /// when the header was written, every event is timed from then
+    started: Option<Instant>,
+    size: (u16, u16),
+    line: Vec<u8>,
+}
+
+impl CastRecorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            started: None,
+            size: (0, 0),
+            line: vec![],
+        })
+    }
+
+    fn event(&mut self, started: Instant, code: &str, data: &str) -> io::Result<()> {
+        self.line.clear();
+        let time = started.elapsed().as_secs_f64();
+        self.line
+            .extend_from_slice(format!("[{time:.6}, \"{code}\", ").as_bytes());
+        write_json_string(&mut self.line, data);
+        self.line.extend_from_slice(b"]\n");
+        self.out.write_all(&self.line)
+    }
+
+    /// the terminal is `size` big from now on, the first size goes into the header
+    pub fn resize(&mut self, size: (u16, u16)) -> io::Result<()> {
+        let Some(started) = self.started else {
+            let (width, height) = size;
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            writeln!(
+                self.out,
+                "{{\"version\": 2, \"width\": {width}, \"height\": {height}, \"timestamp\": {timestamp}}}"
+            )?;
+            self.started = Some(Instant::now());
+            self.size = size;
+            return Ok(());
+        };
+
+        if self.size == size {
+            return Ok(());
+        }
+        self.size = size;
+        self.event(started, "r", &format!("{}x{}", size.0, size.1))
+    }
+
+    /// `bytes` were just sent to the terminal, output before the first size is left out
+    pub fn output(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let Some(started) = self.started else {
+            return Ok(());
+        };
+
+        // frames are only ever cut between whole characters
+        self.event(started, "o", &String::from_utf8_lossy(bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::write_json_string;
+
+    #[test]
+    fn escapes_become_json() {
+        let mut out = vec![];
+        write_json_string(&mut out, "\x1b[0m\"\u{2580}\\");
+        assert_eq!(out, "\"\\u001b[0m\\\"\u{2580}\\\\\"".as_bytes());
+    }
+}
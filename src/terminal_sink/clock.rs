@@ -0,0 +1,174 @@
+//! The position and duration drawn in a corner of the video with `--show-time` and the t key,
+//! over a seek bar along its bottom, as part of the overlay so the frame under them
+//! doesn't flicker through
+use crate::terminal_sink::VideoArea;
+use crate::terminal_sink::overlay::{Backdrop, Layer, Overlay, TextStyle, text_width};
+use gst::prelude::ElementExtManual;
+use gst_app::AppSink;
+use rgb::Rgb;
+
+/// what the seek bar is drawn with, clicking it seeks
+const TRACK: &str = "\u{2501}";
+/// the part of the seek bar that's been played
+const PLAYED: TextStyle = TextStyle {
+    fg: Rgb::new(255, 255, 255),
+    backdrop: Backdrop::Dim(40),
+};
+const UNPLAYED: TextStyle = TextStyle {
+    fg: Rgb::new(110, 110, 110),
+    backdrop: Backdrop::Dim(40),
+};
+
+/// Where on the video the time goes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+#[repr(u8)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl Corner {
+    const ALL: [Corner; 4] = [
+        Corner::TopLeft,
+        Corner::TopRight,
+        Corner::BottomLeft,
+        Corner::BottomRight,
+    ];
+
+    pub(super) fn from_index(index: u8) -> Self {
+        Self::ALL[usize::from(index) % Self::ALL.len()]
+    }
+}
+
+/// `HH:MM:SS`
+fn hms(time: gst::ClockTime) -> String {
+    let seconds = time.seconds();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// where in the file the frame of `sample` is
+fn position(sample: &gst::Sample) -> Option<gst::ClockTime> {
+    let pts = sample.buffer()?.pts()?;
+    let segment = sample.segment()?.downcast_ref::<gst::ClockTime>()?;
+    segment.to_stream_time(pts)
+}
+
+/// The text of the clock, worked out again only once the second shown changes
+#[derive(Default)]
+pub(super) struct Clock {
+    second: Option<u64>,
+    text: String,
+    /// how far into the file the second shown is, `None` without a duration
+    fraction: Option<f64>,
+}
+
+impl Clock {
+    /// what the clock says at `sample`, `None` when the frame has no position
+    pub(super) fn text(&mut self, sample: &gst::Sample, app_sink: &AppSink) -> Option<&str> {
+        let position = position(sample)?;
+        if self.second != Some(position.seconds()) {
+            self.second = Some(position.seconds());
+            let duration = app_sink.query_duration::<gst::ClockTime>();
+            self.text = match duration {
+                Some(duration) => format!(" {} / {} ", hms(position), hms(duration)),
+                None => format!(" {} ", hms(position)),
+            };
+            self.fraction = duration
+                .filter(|duration| !duration.is_zero())
+                .map(|duration| position.nseconds() as f64 / duration.nseconds() as f64);
+        }
+        Some(&self.text)
+    }
+
+    /// how far into the file the clock's last text is, from 0 to 1
+    pub(super) fn fraction(&self) -> Option<f64> {
+        self.fraction
+    }
+}
+
+/// the bottom row of `video` as a track filled `fraction` of the way, under the clock
+pub(super) fn seek_bar(fraction: f64, video: VideoArea) -> Overlay {
+    let mut overlay = Overlay::new();
+    let Some(row) = video.height.checked_sub(1) else {
+        return overlay;
+    };
+
+    let played = (f64::from(video.width) * fraction.clamp(0.0, 1.0)).round() as u16;
+    let track = |cells: u16| TRACK.repeat(usize::from(cells));
+    overlay.text(Layer::Video, (0, row), &track(played), PLAYED);
+    overlay.text(
+        Layer::Video,
+        (played, row),
+        &track(video.width - played),
+        UNPLAYED,
+    );
+    overlay
+}
+
+/// the cell of `video` `text` starts at in `corner` of it, `None` when it doesn't fit
+pub(super) fn at(text: &str, corner: Corner, video: VideoArea) -> Option<(u16, u16)> {
+    let width = u16::try_from(text_width(text)).ok()?;
+    if width > video.width || video.height == 0 {
+        return None;
+    }
+
+    let column = match corner {
+        Corner::TopLeft | Corner::BottomLeft => 0,
+        Corner::TopRight | Corner::BottomRight => video.width - width,
+    };
+    let row = match corner {
+        Corner::TopLeft | Corner::TopRight => 0,
+        Corner::BottomLeft | Corner::BottomRight => video.height - 1,
+    };
+    Some((column, row))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn times() {
+        assert_eq!(hms(gst::ClockTime::from_seconds(83)), "00:01:23");
+        assert_eq!(hms(gst::ClockTime::from_seconds(5400)), "01:30:00");
+    }
+
+    #[test]
+    fn corners() {
+        let video = VideoArea {
+            x: 10,
+            y: 2,
+            width: 40,
+            height: 20,
+        };
+        let start = |corner| at("12345", corner, video);
+        assert_eq!(start(Corner::TopLeft), Some((0, 0)));
+        assert_eq!(start(Corner::BottomRight), Some((35, 19)));
+        assert_eq!(at(&"x".repeat(41), Corner::TopLeft, video), None);
+    }
+
+    #[test]
+    fn seek_bar_fills_up() {
+        let video = VideoArea {
+            x: 0,
+            y: 0,
+            width: 10,
+            height: 4,
+        };
+        let bar = |fraction| seek_bar(fraction, video);
+        let mut half = Overlay::new();
+        half.text(Layer::Video, (0, 3), &TRACK.repeat(5), PLAYED);
+        half.text(Layer::Video, (5, 3), &TRACK.repeat(5), UNPLAYED);
+        assert_eq!(bar(0.5), half);
+        assert_ne!(bar(0.0), bar(1.0));
+        assert!(seek_bar(0.5, VideoArea { height: 0, ..video }).is_empty());
+    }
+}
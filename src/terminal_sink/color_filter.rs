@@ -0,0 +1,132 @@
+//! `--color-filter`, frames the way people with a kind of color blindness see them,
+//! a 3x3 matrix over the linear light of every pixel once the frame is down to the cells it's drawn in
+use crate::terminal_sink::pixel::PixelTransform;
+use rgb::Rgb;
+
+/// Which color vision deficiency gets simulated
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorFilter {
+    /// colors as they are
+    #[default]
+    None,
+    /// no red cones
+    Protanopia,
+    /// no green cones
+    Deuteranopia,
+    /// no blue cones
+    Tritanopia,
+}
+
+/// entries of the linear light to 8 bit signal table, finer since the darks are steep
+const OUTPUT_STEPS: usize = 4096;
+
+impl ColorFilter {
+    /// the matrices of Machado, Oliveira and Fernandes (2009) at full severity,
+    /// over linear sRGB, rows are the outputs
+    fn matrix(self) -> Option<[[f32; 3]; 3]> {
+        match self {
+            ColorFilter::None => None,
+            ColorFilter::Protanopia => Some([
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ]),
+            ColorFilter::Deuteranopia => Some([
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ]),
+            ColorFilter::Tritanopia => Some([
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ]),
+        }
+    }
+}
+
+fn srgb_to_linear(signal: f32) -> f32 {
+    match signal <= 0.04045 {
+        true => signal / 12.92,
+        false => ((signal + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+fn linear_to_srgb(light: f32) -> f32 {
+    match light <= 0.0031308 {
+        true => light * 12.92,
+        false => 1.055 * light.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+/// The matrix of a [`ColorFilter`] and the tables getting pixels in and out of linear light
+pub(super) struct ColorTransform {
+    matrix: [[f32; 3]; 3],
+    to_linear: [f32; 256],
+    to_signal: Box<[u8; OUTPUT_STEPS]>,
+}
+
+impl ColorTransform {
+    /// `None` for [`ColorFilter::None`], which leaves frames alone
+    pub(super) fn new(filter: ColorFilter) -> Option<Self> {
+        let matrix = filter.matrix()?;
+        let to_linear = std::array::from_fn(|x| srgb_to_linear(x as f32 / 255.0));
+        let to_signal = Box::new(std::array::from_fn(|x| {
+            let light = x as f32 / (OUTPUT_STEPS - 1) as f32;
+            (linear_to_srgb(light) * 255.0).round() as u8
+        }));
+        Some(Self {
+            matrix,
+            to_linear,
+            to_signal,
+        })
+    }
+}
+
+impl PixelTransform for ColorTransform {
+    fn pixel(&self, rgb: Rgb<u8>) -> Rgb<u8> {
+        let linear = [rgb.r, rgb.g, rgb.b].map(|x| self.to_linear[usize::from(x)]);
+        let [r, g, b] = self.matrix.map(|row| {
+            let light = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+            let index = (light.clamp(0.0, 1.0) * (OUTPUT_STEPS - 1) as f32).round() as usize;
+            self.to_signal[index]
+        });
+        Rgb::new(r, g, b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grays_stay_gray() {
+        for filter in [
+            ColorFilter::Protanopia,
+            ColorFilter::Deuteranopia,
+            ColorFilter::Tritanopia,
+        ] {
+            let transform = ColorTransform::new(filter).unwrap();
+            for x in [0, 64, 128, 255] {
+                let gray = transform.pixel(Rgb::new(x, x, x));
+                for channel in [gray.r, gray.g, gray.b] {
+                    assert!(
+                        channel.abs_diff(x) <= 2,
+                        "{filter:?} turned {x} into {gray:?}"
+                    );
+                }
+            }
+        }
+        assert!(ColorTransform::new(ColorFilter::None).is_none());
+    }
+
+    #[test]
+    fn red_and_green_both_turn_yellow_without_red_cones() {
+        let transform = ColorTransform::new(ColorFilter::Protanopia).unwrap();
+        for color in [Rgb::new(200, 40, 40), Rgb::new(40, 160, 40)] {
+            let seen = transform.pixel(color);
+            assert!(seen.r.abs_diff(seen.g) < 32, "{color:?} became {seen:?}");
+            assert!(seen.b < seen.g, "{color:?} became {seen:?}");
+        }
+    }
+}
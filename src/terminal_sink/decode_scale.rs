@@ -0,0 +1,84 @@
+//! Scaling frames down to about what the terminal can show right after decoding,
+//! so a 4K video doesn't get converted and resized at 4K just to fill a couple hundred cells
+use crate::error::PlayerError;
+use glib::object::ObjectExt;
+use parking_lot::Mutex;
+
+/// How big frames get before reaching the sink
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DecodeScale {
+    /// as big as they were decoded
+    #[default]
+    Off,
+    /// no bigger than a couple of pixels per half block of the terminal, following its size
+    Auto,
+}
+
+/// pixels per half block each way, the resizer still gets a little to average over
+const OVERSAMPLE: u32 = 2;
+
+/// the pixels a cell gets across and down when it's drawn as two half blocks
+const HALF_BLOCK_PIXELS: (u32, u32) = (OVERSAMPLE, 2 * OVERSAMPLE);
+
+/// frames fitting in a terminal of `columns` by `rows` cells of `cell_pixels` each,
+/// the aspect ratio kept
+fn caps_for((columns, rows): (u16, u16), (across, down): (u32, u32)) -> gst::Caps {
+    let pixels = |cells: u16, pixels_per_cell: u32| {
+        i32::try_from((u32::from(cells) * pixels_per_cell).max(2)).unwrap_or(i32::MAX)
+    };
+    // with square pixels videoscale keeps the display aspect ratio when it has to shrink the frame
+    gst::Caps::builder("video/x-raw")
+        .field("width", gst::IntRange::new(1, pixels(columns, across)))
+        .field("height", gst::IntRange::new(1, pixels(rows, down)))
+        .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
+        .build()
+}
+
+/// The capsfilter of the pipeline playing right now, kept at the terminal size by the renderer
+pub(super) struct ScaleTarget {
+    filter: Mutex<Option<gst::Element>>,
+    size: Mutex<Option<(u16, u16)>>,
+    /// what every cell gets across and down, more when the renderer matches glyphs against them
+    cell_pixels: Mutex<(u32, u32)>,
+}
+
+impl Default for ScaleTarget {
+    fn default() -> Self {
+        Self {
+            filter: Mutex::default(),
+            size: Mutex::default(),
+            cell_pixels: Mutex::new(HALF_BLOCK_PIXELS),
+        }
+    }
+}
+
+impl ScaleTarget {
+    /// gives every cell `cell_pixels` across and down from the next size on
+    pub(super) fn set_cell_pixels(&self, cell_pixels: (u16, u16)) {
+        *self.cell_pixels.lock() = (u32::from(cell_pixels.0), u32::from(cell_pixels.1));
+    }
+
+    /// a videoscale and the capsfilter after it, replacing the ones of the last pipeline
+    pub(super) fn elements(&self) -> Result<[gst::Element; 2], PlayerError> {
+        let scale = crate::gstreamer_element("videoscale")?;
+        let filter = crate::gstreamer_element("capsfilter")?;
+        if let Some(size) = *self.size.lock() {
+            filter.set_property("caps", caps_for(size, *self.cell_pixels.lock()));
+        }
+
+        *self.filter.lock() = Some(filter.clone());
+        Ok([scale, filter])
+    }
+
+    /// caps the frames at `size`, capsfilter renegotiates by itself when its caps change
+    pub(super) fn resize(&self, size: (u16, u16)) {
+        if self.size.lock().replace(size) == Some(size) {
+            return;
+        }
+
+        if let Some(filter) = &*self.filter.lock() {
+            tracing::debug!(?size, "scaling decoded frames to the terminal");
+            filter.set_property("caps", caps_for(size, *self.cell_pixels.lock()));
+        }
+    }
+}
@@ -1,13 +1,53 @@
+use crate::terminal_sink::VideoArea;
+#[cfg(feature = "serde")]
+use crate::terminal_sink::grid::{CellGrid, Frame, GridCell};
+use crate::terminal_sink::overlay::{Overlay, Run};
 use crate::terminal_sink::resize::{ImageRef, PodMatrix};
+use crate::terminal_sink::simd;
 use rgb::{ComponentMap, Rgb};
+use std::cmp::Ordering;
 use std::mem::MaybeUninit;
 use std::num::NonZero;
+use std::ops::{Range, RangeInclusive};
+use std::sync::Arc;
 
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
 pub struct Cell {
     rgb_top: Rgb<u8>,
     rgb_bottom: Rgb<u8>,
+    /// 0 for the two pixels, otherwise the symbol of the frame, counting from 1,
+    /// drawn in the top color over the bottom one
+    symbol: u16,
+}
+
+impl Cell {
+    /// a cell drawing `symbol` of the frame in `fg` over `bg`
+    pub(super) fn with_symbol(symbol: u16, fg: Rgb<u8>, bg: Rgb<u8>) -> Self {
+        Self {
+            rgb_top: fg,
+            rgb_bottom: bg,
+            symbol,
+        }
+    }
+
+    pub(super) fn symbol(&self) -> u16 {
+        self.symbol
+    }
+
+    /// what quantizes a cell to `mask` when anded with it, the colors and never the symbol
+    pub(super) fn color_mask(mask: u8) -> Self {
+        let mask = Rgb::new(mask, mask, mask);
+        Self::with_symbol(u16::MAX, mask, mask)
+    }
+
+    fn quantized(self, mask: u8) -> Self {
+        Self {
+            rgb_top: self.rgb_top.map(|x| x & mask),
+            rgb_bottom: self.rgb_bottom.map(|x| x & mask),
+            symbol: self.symbol,
+        }
+    }
 }
 
 // use a lut since this is super hot
@@ -94,176 +134,1154 @@ fn write_u8_ascii(buf: &mut Vec<u8>, n: u8) {
     buf.extend_from_slice(str)
 }
 
-impl Cell {
-    pub fn draw(self, command_buffer: &mut Vec<u8>) {
-        const UNICODE_TOP_HALF_BLOCK: &str = "\u{2580}";
-
-        let Rgb {
-            r: tr,
-            g: tg,
-            b: tb,
-        } = self.rgb_top;
-        let Rgb {
-            r: br,
-            g: bg,
-            b: bb,
-        } = self.rgb_bottom;
-
-        // Foreground
-        command_buffer.extend_from_slice(b"\x1b[38;2;");
-        write_u8_ascii(command_buffer, tr);
-        command_buffer.push(b';');
-        write_u8_ascii(command_buffer, tg);
-        command_buffer.push(b';');
-        write_u8_ascii(command_buffer, tb);
-        command_buffer.push(b'm');
+#[inline(always)]
+const fn u8_ascii_len(n: u8) -> usize {
+    match n {
+        100.. => 3,
+        10.. => 2,
+        0.. => 1,
+    }
+}
 
-        // Background RGB
-        command_buffer.extend_from_slice(b"\x1b[48;2;");
-        write_u8_ascii(command_buffer, br);
-        command_buffer.push(b';');
-        write_u8_ascii(command_buffer, bg);
+const fn u16_ascii_len(n: u16) -> usize {
+    match n {
+        10000.. => 5,
+        1000.. => 4,
+        100.. => 3,
+        10.. => 2,
+        0.. => 1,
+    }
+}
+
+#[inline(always)]
+fn write_rgb(command_buffer: &mut Vec<u8>, rgb: Rgb<u8>) {
+    write_u8_ascii(command_buffer, rgb.r);
+    command_buffer.push(b';');
+    write_u8_ascii(command_buffer, rgb.g);
+    command_buffer.push(b';');
+    write_u8_ascii(command_buffer, rgb.b);
+}
+
+/// the closest of xterm's 256 colors, out of the 6x6x6 cube and the gray ramp
+fn xterm_index(rgb: Rgb<u8>) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |c: u8| match c {
+        0..48 => 0,
+        48..115 => 1,
+        c => (c - 35) / 40,
+    };
+    let distance = |a: Rgb<u8>| {
+        let d = |x: u8, y: u8| u32::from(x.abs_diff(y)).pow(2);
+        d(a.r, rgb.r) + d(a.g, rgb.g) + d(a.b, rgb.b)
+    };
+
+    let (r, g, b) = (level(rgb.r), level(rgb.g), level(rgb.b));
+    let cube = Rgb::new(LEVELS[r as usize], LEVELS[g as usize], LEVELS[b as usize]);
+
+    // the ramp goes from 8 up to 238 in steps of 10
+    let average = (u16::from(rgb.r) + u16::from(rgb.g) + u16::from(rgb.b)) / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + 10 * step;
+
+    match distance(Rgb::new(gray, gray, gray)) < distance(cube) {
+        true => 232 + step,
+        false => 16 + 36 * r + 6 * g + b,
+    }
+}
+
+/// Which colors the terminal can show
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Palette {
+    /// any 24 bit color
+    #[default]
+    Truecolor,
+    /// the closest of the 256 colors of xterm's palette
+    #[value(name = "256")]
+    Xterm256,
+}
+
+/// What cells get drawn with
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Glyphs {
+    /// half blocks, two pixels to a cell
+    #[default]
+    Blocks,
+    /// plain spaces in the average of both pixels, for terminals without the block characters
+    Spaces,
+}
+
+const UNICODE_TOP_HALF_BLOCK: &str = "\u{2580}";
+
+/// length of the SGR sequence setting the given colors, `None` leaves a color as is
+fn sgr_len(palette: Palette, fg: Option<Rgb<u8>>, bg: Option<Rgb<u8>>) -> usize {
+    let color_len = |rgb: Rgb<u8>| match palette {
+        // "\x1b[38;2;" + "r;g;b" + "m"
+        Palette::Truecolor => 10 + u8_ascii_len(rgb.r) + u8_ascii_len(rgb.g) + u8_ascii_len(rgb.b),
+        // "\x1b[38;5;" + "n" + "m"
+        Palette::Xterm256 => 8 + u8_ascii_len(xterm_index(rgb)),
+    };
+
+    match (fg, bg) {
+        (None, None) => 0,
+        (Some(color), None) | (None, Some(color)) => color_len(color),
+        // both colors share a single "\x1b[" and "m"
+        (Some(fg), Some(bg)) => color_len(fg) + color_len(bg) - 2,
+    }
+}
+
+/// the part of a color's SGR parameters after `38;` or `48;`
+fn write_color(command_buffer: &mut Vec<u8>, palette: Palette, rgb: Rgb<u8>) {
+    match palette {
+        Palette::Truecolor => {
+            command_buffer.extend_from_slice(b"2;");
+            write_rgb(command_buffer, rgb);
+        }
+        Palette::Xterm256 => {
+            command_buffer.extend_from_slice(b"5;");
+            write_u8_ascii(command_buffer, xterm_index(rgb));
+        }
+    }
+}
+
+pub(super) fn write_sgr(
+    command_buffer: &mut Vec<u8>,
+    palette: Palette,
+    fg: Option<Rgb<u8>>,
+    bg: Option<Rgb<u8>>,
+) {
+    match (fg, bg) {
+        (None, None) => return,
+        (Some(fg), None) => {
+            command_buffer.extend_from_slice(b"\x1b[38;");
+            write_color(command_buffer, palette, fg);
+        }
+        (None, Some(bg)) => {
+            command_buffer.extend_from_slice(b"\x1b[48;");
+            write_color(command_buffer, palette, bg);
+        }
+        (Some(fg), Some(bg)) => {
+            command_buffer.extend_from_slice(b"\x1b[38;");
+            write_color(command_buffer, palette, fg);
+            command_buffer.extend_from_slice(b";48;");
+            write_color(command_buffer, palette, bg);
+        }
+    }
+    command_buffer.push(b'm');
+}
+
+#[derive(Copy, Clone)]
+enum Glyph {
+    TopHalf,
+    BottomHalf,
+    Full,
+    Space,
+}
+
+impl Glyph {
+    const fn as_bytes(self) -> &'static [u8] {
+        match self {
+            Glyph::TopHalf => UNICODE_TOP_HALF_BLOCK.as_bytes(),
+            Glyph::BottomHalf => "\u{2584}".as_bytes(),
+            Glyph::Full => "\u{2588}".as_bytes(),
+            Glyph::Space => b" ",
+        }
+    }
+}
+
+/// The colors the terminal is currently drawing with, `None` when unknown
+#[derive(Copy, Clone, Default)]
+struct Pen {
+    fg: Option<Rgb<u8>>,
+    bg: Option<Rgb<u8>>,
+    palette: Palette,
+}
+
+impl Pen {
+    /// draws the cell with `glyphs`, picking the cheapest block when `compact`,
+    /// cells with a symbol get theirs out of `symbols` and give `None`
+    fn draw(
+        &mut self,
+        command_buffer: &mut Vec<u8>,
+        cell: Cell,
+        glyphs: Glyphs,
+        compact: bool,
+        symbols: &[Box<str>],
+    ) -> Option<Glyph> {
+        if cell.symbol != 0 {
+            return self.symbol(command_buffer, cell, symbols);
+        }
+        Some(match glyphs {
+            Glyphs::Spaces => self.fill(command_buffer, cell),
+            Glyphs::Blocks if compact => self.stroke(command_buffer, cell),
+            Glyphs::Blocks => self.paint(command_buffer, cell),
+        })
+    }
+
+    /// draws the cell as a space in the average of both its colors
+    fn fill(&mut self, command_buffer: &mut Vec<u8>, cell: Cell) -> Glyph {
+        let average = |top: u8, bottom: u8| ((u16::from(top) + u16::from(bottom)) / 2) as u8;
+        let (top, bottom) = (cell.rgb_top, cell.rgb_bottom);
+        let color = Rgb::new(
+            average(top.r, bottom.r),
+            average(top.g, bottom.g),
+            average(top.b, bottom.b),
+        );
+
+        let bg = Some(color).filter(|&bg| self.bg != Some(bg));
+        write_sgr(command_buffer, self.palette, None, bg);
+        command_buffer.extend_from_slice(Glyph::Space.as_bytes());
+
+        self.bg = Some(color);
+        Glyph::Space
+    }
+
+    /// draws the symbol of the cell in its top color over its bottom one,
+    /// `None` unless it was a space and got filled
+    fn symbol(
+        &mut self,
+        command_buffer: &mut Vec<u8>,
+        cell: Cell,
+        symbols: &[Box<str>],
+    ) -> Option<Glyph> {
+        // a space only shows the background, which keeps flat areas as cheap as filled cells
+        let text = symbols.get(usize::from(cell.symbol) - 1);
+        let Some(text) = text.filter(|text| !text.trim().is_empty()) else {
+            let background = Cell::with_symbol(0, cell.rgb_bottom, cell.rgb_bottom);
+            return Some(self.fill(command_buffer, background));
+        };
+
+        let fg = Some(cell.rgb_top).filter(|&fg| self.fg != Some(fg));
+        let bg = Some(cell.rgb_bottom).filter(|&bg| self.bg != Some(bg));
+
+        write_sgr(command_buffer, self.palette, fg, bg);
+        command_buffer.extend_from_slice(text.as_bytes());
+
+        self.fg = Some(cell.rgb_top);
+        self.bg = Some(cell.rgb_bottom);
+        None
+    }
+
+    /// draws the cell as an upper half block, setting only the colors that changed
+    fn paint(&mut self, command_buffer: &mut Vec<u8>, cell: Cell) -> Glyph {
+        let fg = Some(cell.rgb_top).filter(|&fg| self.fg != Some(fg));
+        let bg = Some(cell.rgb_bottom).filter(|&bg| self.bg != Some(bg));
+
+        write_sgr(command_buffer, self.palette, fg, bg);
+        command_buffer.extend_from_slice(Glyph::TopHalf.as_bytes());
+
+        self.fg = Some(cell.rgb_top);
+        self.bg = Some(cell.rgb_bottom);
+        Glyph::TopHalf
+    }
+
+    /// draws the cell with whichever glyph needs the fewest color changes
+    fn stroke(&mut self, command_buffer: &mut Vec<u8>, cell: Cell) -> Glyph {
+        let Cell {
+            rgb_top: top,
+            rgb_bottom: bottom,
+            ..
+        } = cell;
+
+        let solid = top == bottom;
+        let candidates = [
+            Some((Glyph::TopHalf, Some(top), Some(bottom))),
+            Some((Glyph::BottomHalf, Some(bottom), Some(top))),
+            solid.then_some((Glyph::Space, None, Some(top))),
+            solid.then_some((Glyph::Full, Some(top), None)),
+        ];
+
+        let (glyph, fg, bg) = candidates
+            .into_iter()
+            .flatten()
+            .map(|(glyph, fg, bg)| {
+                let fg = fg.filter(|&fg| self.fg != Some(fg));
+                let bg = bg.filter(|&bg| self.bg != Some(bg));
+                (glyph, fg, bg)
+            })
+            .min_by_key(|&(glyph, fg, bg)| sgr_len(self.palette, fg, bg) + glyph.as_bytes().len())
+            .unwrap();
+
+        write_sgr(command_buffer, self.palette, fg, bg);
+        command_buffer.extend_from_slice(glyph.as_bytes());
+
+        self.fg = fg.or(self.fg);
+        self.bg = bg.or(self.bg);
+        glyph
+    }
+}
+
+/// length of a CSI sequence with a count, a count of 1 is implied
+const fn csi_count_len(n: u16) -> usize {
+    3 + match n {
+        1 => 0,
+        n => u16_ascii_len(n),
+    }
+}
+
+fn write_csi_count(command_buffer: &mut Vec<u8>, n: u16, command: u8) {
+    command_buffer.extend_from_slice(b"\x1b[");
+    if n != 1 {
+        command_buffer.extend_from_slice(itoa::Buffer::new().format(n).as_bytes());
+    }
+    command_buffer.push(command);
+}
+
+fn write_cup(command_buffer: &mut Vec<u8>, (x, y): (u16, u16)) {
+    let mut int_buffer = itoa::Buffer::new();
+
+    // goto is one based
+    command_buffer.extend_from_slice(b"\x1b[");
+    command_buffer.extend_from_slice(int_buffer.format(y.saturating_add(1)).as_bytes());
+    // the column defaults to the first one
+    if x != 0 {
         command_buffer.push(b';');
-        write_u8_ascii(command_buffer, bb);
-        command_buffer.push(b'm');
-        command_buffer.extend_from_slice(UNICODE_TOP_HALF_BLOCK.as_bytes());
+        command_buffer.extend_from_slice(int_buffer.format(x.saturating_add(1)).as_bytes());
+    }
+    command_buffer.push(b'H');
+}
+
+fn write_relative_move(
+    command_buffer: &mut Vec<u8>,
+    (from_x, from_y): (u16, u16),
+    (to_x, to_y): (u16, u16),
+) {
+    match to_y.cmp(&from_y) {
+        Ordering::Greater => write_csi_count(command_buffer, to_y - from_y, b'B'),
+        Ordering::Less => write_csi_count(command_buffer, from_y - to_y, b'A'),
+        Ordering::Equal => {}
+    }
+
+    if to_x == from_x {
+        return;
+    }
+
+    let relative = csi_count_len(to_x.abs_diff(from_x));
+    let carriage_return = 1 + match to_x {
+        0 => 0,
+        x => csi_count_len(x),
+    };
+    // CHA
+    let absolute = 3 + u16_ascii_len(to_x.saturating_add(1));
+
+    if carriage_return < relative.min(absolute) {
+        command_buffer.push(b'\r');
+        if to_x != 0 {
+            write_csi_count(command_buffer, to_x, b'C');
+        }
+    } else if relative <= absolute {
+        match to_x > from_x {
+            true => write_csi_count(command_buffer, to_x - from_x, b'C'),
+            false => write_csi_count(command_buffer, from_x - to_x, b'D'),
+        }
+    } else {
+        command_buffer.extend_from_slice(b"\x1b[");
+        command_buffer.extend_from_slice(itoa::Buffer::new().format(to_x + 1).as_bytes());
+        command_buffer.push(b'G');
     }
 }
 
+/// Follows the terminal cursor so that every move uses the shortest encoding,
+/// and in compact mode picks the cheapest glyph for every cell
+struct Encoder {
+    compact: bool,
+    repeat: bool,
+    glyphs: Glyphs,
+    columns: u16,
+    position: Option<(u16, u16)>,
+    pen: Pen,
+    scratch: Vec<u8>,
+    /// the columns of the row being drawn that something else covers, which gaps can't draw over
+    masked: Vec<Range<u16>>,
+    /// what cells with a symbol draw
+    symbols: Arc<[Box<str>]>,
+}
+
+impl Encoder {
+    fn new(compact: bool, repeat: bool) -> Self {
+        Self {
+            compact,
+            repeat,
+            glyphs: Glyphs::default(),
+            columns: 0,
+            position: None,
+            pen: Pen::default(),
+            scratch: Vec::new(),
+            masked: Vec::new(),
+            symbols: Arc::from([]),
+        }
+    }
+
+    fn begin_frame(&mut self, columns: u16) {
+        self.columns = columns;
+        self.position = None;
+        self.masked.clear();
+        self.pen = Pen {
+            palette: self.pen.palette,
+            ..Pen::default()
+        };
+    }
+
+    fn advance(&mut self, cells: u16) {
+        // after the last column the cursor sits waiting to wrap,
+        // so where it will land is up to the terminal
+        self.position = self.position.and_then(|(x, y)| {
+            x.checked_add(cells)
+                .filter(|&x| x < self.columns)
+                .map(|x| (x, y))
+        });
+    }
+
+    /// draws `count` copies of the same cell
+    fn draw(&mut self, command_buffer: &mut Vec<u8>, cell: Cell, count: u16) {
+        let glyph = self.pen.draw(
+            command_buffer,
+            cell,
+            self.glyphs,
+            self.compact,
+            &self.symbols,
+        );
+        self.advance(1);
+
+        let Some(repeats) = count.checked_sub(1).filter(|&n| n > 0) else {
+            return;
+        };
+
+        // REP repeats the last character drawn, with the same colors
+        let glyph = match glyph {
+            Some(glyph) => glyph.as_bytes(),
+            // only symbols that are there get drawn as themselves
+            None => self.symbols[usize::from(cell.symbol) - 1].as_bytes(),
+        };
+        if self.repeat && csi_count_len(repeats) < usize::from(repeats) * glyph.len() {
+            write_csi_count(command_buffer, repeats, b'b');
+        } else {
+            for _ in 0..repeats {
+                command_buffer.extend_from_slice(glyph);
+            }
+        }
+        self.advance(repeats);
+    }
+
+    fn move_to(
+        &mut self,
+        command_buffer: &mut Vec<u8>,
+        row: &[Cell],
+        row_start: u16,
+        to: (u16, u16),
+    ) {
+        if self.position == Some(to) {
+            return;
+        }
+
+        let Some(from) = self.position else {
+            write_cup(command_buffer, to);
+            self.position = Some(to);
+            return;
+        };
+
+        let start = command_buffer.len();
+        write_cup(command_buffer, to);
+        let mut replace_if_shorter = |scratch: &[u8]| {
+            let shorter = scratch.len() < command_buffer.len() - start;
+            if shorter {
+                command_buffer.truncate(start);
+                command_buffer.extend_from_slice(scratch);
+            }
+            shorter
+        };
+
+        self.scratch.clear();
+        write_relative_move(&mut self.scratch, from, to);
+        replace_if_shorter(&self.scratch);
+
+        // a short gap of unchanged cells can be cheaper to just draw over
+        let ((from_x, from_y), (to_x, to_y)) = (from, to);
+        let clear = self
+            .masked
+            .iter()
+            .all(|masked| to_x <= masked.start || from_x >= masked.end);
+        if clear && from_y == to_y && from_x >= row_start && from_x < to_x && to_x - from_x < 16 {
+            let gap = &row[usize::from(from_x - row_start)..usize::from(to_x - row_start)];
+
+            self.scratch.clear();
+            let mut pen = self.pen;
+            for &cell in gap {
+                pen.draw(
+                    &mut self.scratch,
+                    cell,
+                    self.glyphs,
+                    self.compact,
+                    &self.symbols,
+                );
+            }
+
+            if replace_if_shorter(&self.scratch) {
+                self.pen = pen;
+            }
+        }
+
+        self.position = Some(to);
+    }
+
+    fn draw_run(
+        &mut self,
+        command_buffer: &mut Vec<u8>,
+        row: &[Cell],
+        (offset_width, offset_height): (u16, u16),
+        j: u16,
+        run: Range<u16>,
+    ) {
+        let to = (offset_width + run.start, offset_height + j);
+        self.move_to(command_buffer, row, offset_width, to);
+
+        let cells = &row[usize::from(run.start)..usize::from(run.end)];
+        for same in cells.chunk_by(|a, b| a == b) {
+            self.draw(command_buffer, same[0], same.len() as u16);
+        }
+    }
+}
+
+pub const DEFAULT_PRECISION: u8 = 5;
+pub const PRECISION_RANGE: RangeInclusive<u8> = 1..=8;
+
+const fn precision_mask(bits: u8) -> u8 {
+    let bits = match bits {
+        0 => 1,
+        9.. => 8,
+        bits => bits,
+    };
+    u8::MAX << (8 - bits)
+}
+
+/// whether every channel of both halves of `new` is within `threshold` of `old`,
+/// with the same symbol
+fn within(new: Cell, old: Cell, threshold: u8) -> bool {
+    let colors = |cell: &Cell| [cell.rgb_top, cell.rgb_bottom];
+    let new_colors = colors(&new);
+    let old_colors = colors(&old);
+    new.symbol == old.symbol
+        && bytemuck::bytes_of(&new_colors)
+            .iter()
+            .zip(bytemuck::bytes_of(&old_colors))
+            .all(|(a, b)| a.abs_diff(*b) <= threshold)
+}
+
+/// What a frame gets drawn from
+#[derive(Copy, Clone)]
+pub(super) enum Source<'a> {
+    /// pixels, two stacked in every cell
+    Pixels(ImageRef<'a>),
+    /// cells worked out already, like the symbols of line art
+    Cells(&'a PodMatrix<Cell>),
+}
+
+impl Source<'_> {
+    /// how many columns and rows the frame takes up
+    fn size(self) -> (u16, u16) {
+        match self {
+            Source::Pixels(image_ref) => {
+                let (width, height) = image_ref.size();
+                (
+                    u16::try_from(width).unwrap(),
+                    u16::try_from(height.div_ceil(2)).unwrap(),
+                )
+            }
+            Source::Cells(cells) => cells.size(),
+        }
+    }
+}
+
+/// the state needed to diff and draw a band of rows on its own
+struct RowRenderer {
+    encoder: Encoder,
+    next_row: Vec<Cell>,
+    changed: Vec<u64>,
+    output: Vec<u8>,
+    /// how far a channel can move before the cell counts as changed, 0 for any change at all
+    threshold: u8,
+    /// how many more cells can be drawn this frame, `usize::MAX` when there's no cap
+    budget: usize,
+    /// the bounding box of the cells drawn this frame, relative to the frame
+    damage: Option<Damage>,
+    /// the cells overlays cover, and the ones they stopped covering, relative to the frame
+    overlay: Vec<Damage>,
+    stale: Vec<Damage>,
+}
+
+impl RowRenderer {
+    fn new(compact: bool, repeat: bool) -> Self {
+        Self {
+            encoder: Encoder::new(compact, repeat),
+            next_row: Vec::new(),
+            changed: Vec::new(),
+            output: Vec::new(),
+            threshold: 0,
+            budget: usize::MAX,
+            damage: None,
+            overlay: Vec::new(),
+            stale: Vec::new(),
+        }
+    }
+
+    /// draws the cells overlays stopped covering again, and leaves the ones under them to them,
+    /// those still get what was packed so the backdrops dimming the video follow it
+    fn apply_overlay(&mut self, j: u16, offset: u16, frame_row: &mut [Cell]) {
+        for stale in self.stale.iter().filter(|stale| stale.y.contains(&j)) {
+            for i in stale.x.clone().map(usize::from) {
+                self.changed[i / 64] |= 1 << (i % 64);
+            }
+        }
+
+        self.encoder.masked.clear();
+        for overlay in self.overlay.iter().filter(|overlay| overlay.y.contains(&j)) {
+            let covered = usize::from(overlay.x.start)..usize::from(overlay.x.end);
+            frame_row[covered.clone()].copy_from_slice(&self.next_row[covered.clone()]);
+            for i in covered {
+                self.changed[i / 64] &= !(1 << (i % 64));
+            }
+            self.encoder
+                .masked
+                .push(offset + overlay.x.start..offset + overlay.x.end);
+        }
+    }
+
+    /// keeps the old colors of the changed cells that didn't change by more than the threshold,
+    /// so noise doesn't redraw them every frame
+    fn ignore_noise(&mut self, frame_row: &[Cell]) {
+        let width = frame_row.len();
+        let mut i = simd::next_bit(&self.changed, 0, width, true);
+        while i < width {
+            if within(self.next_row[i], frame_row[i], self.threshold) {
+                self.next_row[i] = frame_row[i];
+                self.changed[i / 64] &= !(1 << (i % 64));
+            }
+            i = simd::next_bit(&self.changed, i + 1, width, true);
+        }
+    }
+
+    /// packs row `j` of the pixels into cells, two stacked in each
+    fn pack_row(&mut self, image_ref: ImageRef, j: u16) {
+        let (_, height) = image_ref.size();
+        let top = u32::from(j) * 2;
+
+        match top + 1 < height {
+            true => {
+                let cells = image_ref.row(top).iter().zip(image_ref.row(top + 1));
+                self.next_row
+                    .extend(cells.map(|(&rgb_top, &rgb_bottom)| Cell {
+                        rgb_top,
+                        rgb_bottom,
+                        symbol: 0,
+                    }));
+            }
+            // the last row of an odd height image only has a top half
+            false => {
+                let cells = image_ref.row(top).iter().map(|&rgb_top| Cell {
+                    rgb_top,
+                    rgb_bottom: Rgb::new(0, 0, 0),
+                    symbol: 0,
+                });
+                self.next_row.extend(cells);
+            }
+        }
+    }
+
+    /// packs and quantizes row `j` of the frame, then draws the runs of changed cells into `frame_row`,
+    /// false when the budget ran out before all of them were drawn
+    fn diff_row(
+        &mut self,
+        source: Source,
+        mask: u8,
+        offset: (u16, u16),
+        j: u16,
+        frame_row: &mut [Cell],
+        command_buffer: &mut Vec<u8>,
+    ) -> bool {
+        self.next_row.clear();
+        match source {
+            Source::Pixels(image_ref) => self.pack_row(image_ref, j),
+            Source::Cells(cells) => self.next_row.extend_from_slice(cells.row(j)),
+        }
+
+        simd::quantize_and_diff(&mut self.next_row, frame_row, mask, &mut self.changed);
+        if self.threshold > 0 {
+            self.ignore_noise(frame_row);
+        }
+        if !self.overlay.is_empty() || !self.stale.is_empty() {
+            self.apply_overlay(j, offset.0, frame_row);
+        }
+        // unchanged cells already are what was packed, so only drawn runs get copied over
+        // and the ones the budget leaves out stay changed for the next frame
+        let width = frame_row.len();
+        let mut start = simd::next_bit(&self.changed, 0, width, true);
+        while start < width {
+            if self.budget == 0 {
+                return false;
+            }
+            let end = simd::next_bit(&self.changed, start, width, false);
+            let drawn = end.min(start.saturating_add(self.budget));
+            frame_row[start..drawn].copy_from_slice(&self.next_row[start..drawn]);
+            self.encoder.draw_run(
+                command_buffer,
+                frame_row,
+                offset,
+                j,
+                start as u16..drawn as u16,
+            );
+            self.budget -= drawn - start;
+            let run = Damage {
+                x: start as u16..drawn as u16,
+                y: j..j + 1,
+            };
+            self.damage = Some(match self.damage.take() {
+                Some(damage) => damage.union(run),
+                None => run,
+            });
+
+            if drawn < end {
+                return false;
+            }
+            start = simd::next_bit(&self.changed, end, width, true);
+        }
+        true
+    }
+}
+
+/// The cells a frame drew to, as ranges of columns and rows
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Damage {
+    x: Range<u16>,
+    y: Range<u16>,
+}
+
+impl Damage {
+    fn of(area: VideoArea) -> Damage {
+        Damage {
+            x: area.x..area.x.saturating_add(area.width),
+            y: area.y..area.y.saturating_add(area.height),
+        }
+    }
+
+    /// the part inside the frame at `offset` of `size` cells, relative to it
+    fn within(&self, (x, y): (u16, u16), (width, height): (u16, u16)) -> Option<Damage> {
+        let clip = |range: &Range<u16>, start: u16, len: u16| {
+            let end = start.saturating_add(len);
+            range.start.clamp(start, end) - start..range.end.clamp(start, end) - start
+        };
+        let damage = Damage {
+            x: clip(&self.x, x, width),
+            y: clip(&self.y, y, height),
+        };
+        (!damage.x.is_empty() && !damage.y.is_empty()).then_some(damage)
+    }
+
+    fn union(self, other: Damage) -> Damage {
+        Damage {
+            x: self.x.start.min(other.x.start)..self.x.end.max(other.x.end),
+            y: self.y.start.min(other.y.start)..self.y.end.max(other.y.end),
+        }
+    }
+}
+
+/// The cells on screen, which the next frame gets diffed against
 pub struct RenderedFrame {
     frame: PodMatrix<Cell>,
+    offset: (u16, u16),
+    /// written before a frame drawn from scratch, so nothing of the last one is left around it
+    clear: Vec<u8>,
+    mask: u8,
+    renderers: Vec<RowRenderer>,
+    pool: Option<rayon::ThreadPool>,
+    /// how many cells a diffed frame can draw at most
+    max_cells: Option<NonZero<usize>>,
+    /// the row a capped frame starts diffing at, where the last one ran out
+    first_row: u16,
+    damage: Option<Damage>,
+    /// changed cells were left for the next frame
+    pending: bool,
+    /// the cells the overlay drawn after the frame covers, in terminal cells
+    overlay: Vec<Damage>,
+    /// the cells the next diff draws whatever they are, since the overlay covered them
+    stale: Vec<Damage>,
+    /// what of the overlay is on the terminal, only runs that aren't get drawn
+    drawn: Vec<Run>,
 }
 
 impl RenderedFrame {
-    pub fn new() -> Self {
+    pub fn new(compact: bool, repeat: bool, threads: NonZero<usize>) -> Self {
+        let pool = (threads.get() > 1).then(|| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads.get())
+                .thread_name(|i| format!("render worker {i}"))
+                .build()
+                .unwrap()
+        });
+
         Self {
             frame: PodMatrix::new(),
+            offset: (0, 0),
+            clear: crate::terminal::CLEAR.as_bytes().to_vec(),
+            mask: precision_mask(DEFAULT_PRECISION),
+            renderers: (0..threads.get())
+                .map(|_| RowRenderer::new(compact, repeat))
+                .collect(),
+            pool,
+            max_cells: None,
+            first_row: 0,
+            damage: None,
+            pending: false,
+            overlay: Vec::new(),
+            stale: Vec::new(),
+            drawn: Vec::new(),
+        }
+    }
+
+    /// where the last frame was drawn, as the cell offset and size
+    pub fn area(&self) -> VideoArea {
+        let (width, height) = self.frame.size();
+        VideoArea {
+            x: self.offset.0,
+            y: self.offset.1,
+            width,
+            height,
+        }
+    }
+
+    /// quantize colors to `bits` bits per channel, fewer bits means fewer cells change
+    pub fn set_precision(&mut self, bits: u8) {
+        self.mask = precision_mask(bits);
+    }
+
+    /// cells whose channels all moved by at most `threshold` since they were drawn keep their
+    /// old colors, frames drawn from scratch always get drawn as they are
+    pub fn set_threshold(&mut self, threshold: u8) {
+        for renderer in &mut self.renderers {
+            renderer.threshold = threshold;
+        }
+    }
+
+    /// draw at most `max` changed cells of a diffed frame and leave the rest to the next one,
+    /// which starts where this one stopped, so slow terminals keep up at the cost of tearing,
+    /// frames drawn from scratch always get drawn whole, and capped frames are diffed on one thread
+    pub fn set_max_cells(&mut self, max: Option<NonZero<usize>>) {
+        self.max_cells = max;
+    }
+
+    /// writes `overlay` over the frame last drawn, and leaves the cells it covers to it from then on
+    /// so diffs don't draw over it, the ones it stops covering get drawn again by the next diff
+    pub fn draw_overlay(&mut self, overlay: &Overlay, command_buffer: &mut Vec<u8>) {
+        let area = self.area();
+        let frame = &self.frame;
+        let runs = overlay.layout(area, |x, y| {
+            let under = frame.row(y - area.y)[usize::from(x - area.x)];
+            let average = |top: u8, bottom: u8| ((u16::from(top) + u16::from(bottom)) / 2) as u8;
+            let (top, bottom) = (under.rgb_top, under.rgb_bottom);
+            Rgb::new(
+                average(top.r, bottom.r),
+                average(top.g, bottom.g),
+                average(top.b, bottom.b),
+            )
+        });
+        let palette = self.renderers[0].encoder.pen.palette;
+
+        let mut colors = None;
+        let mut wrote = false;
+        // the frame leaves the cells under the overlay alone, what's there stays until it changes
+        for run in runs.iter().filter(|run| !self.drawn.contains(run)) {
+            wrote = true;
+            command_buffer
+                .extend_from_slice(crate::terminal::goto(run.x + 1, run.y + 1).as_bytes());
+            for cell in &run.cells {
+                if colors != Some((cell.fg, cell.bg)) {
+                    write_sgr(command_buffer, palette, Some(cell.fg), Some(cell.bg));
+                    colors = Some((cell.fg, cell.bg));
+                }
+                command_buffer.extend_from_slice(cell.text.as_bytes());
+            }
+        }
+        if wrote {
+            command_buffer.extend_from_slice(b"\x1b[0m");
+        }
+
+        let covered = runs
+            .iter()
+            .map(|run| Damage::of(run.area()))
+            .collect::<Vec<_>>();
+        if covered != self.overlay {
+            let old = std::mem::replace(&mut self.overlay, covered);
+            self.stale
+                .extend(old.into_iter().filter(|old| !self.overlay.contains(old)));
+        }
+        self.drawn = runs;
+    }
+
+    /// the bounding box of the cells the last frame drew, `None` when none changed
+    pub fn damage(&self) -> Option<VideoArea> {
+        let Damage { x, y } = self.damage.clone()?;
+        Some(VideoArea {
+            x: self.offset.0 + x.start,
+            y: self.offset.1 + y.start,
+            width: x.end - x.start,
+            height: y.end - y.start,
+        })
+    }
+
+    /// whether the cap on the cells drawn left changed ones for the next frame
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+
+    /// which colors and glyphs cells get drawn with, from the next frame drawn from scratch on
+    pub fn set_style(&mut self, palette: Palette, glyphs: Glyphs) {
+        for renderer in &mut self.renderers {
+            renderer.encoder.pen.palette = palette;
+            renderer.encoder.glyphs = glyphs;
+        }
+    }
+
+    /// what cells with a symbol draw, the first one being symbol 1,
+    /// from the next frame drawn from scratch on
+    pub(super) fn set_symbols(&mut self, symbols: Arc<[Box<str>]>) {
+        for renderer in &mut self.renderers {
+            renderer.encoder.symbols = Arc::clone(&symbols);
+        }
+    }
+
+    /// what clears the space a frame gets drawn into, the whole screen unless told otherwise
+    pub(super) fn set_clear(&mut self, clear: Vec<u8>) {
+        self.clear = clear;
+    }
+
+    /// the cells of the last frame row by row, each ending in a newline rather than
+    /// the cursor being sent anywhere, so they print like text wherever the cursor is
+    pub(super) fn lines(&self) -> Vec<u8> {
+        let Some(encoder) = self.renderers.first().map(|renderer| &renderer.encoder) else {
+            return Vec::new();
+        };
+
+        let (_, height) = self.frame.size();
+        let mut lines = Vec::new();
+        for j in 0..height {
+            let mut pen = Pen {
+                palette: encoder.pen.palette,
+                ..Pen::default()
+            };
+            for &cell in self.frame.row(j) {
+                pen.draw(
+                    &mut lines,
+                    cell,
+                    encoder.glyphs,
+                    encoder.compact,
+                    &encoder.symbols,
+                );
+            }
+            lines.extend_from_slice(b"\x1b[0m\n");
+        }
+        lines
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn snapshot(&self, pts: Option<gst::ClockTime>) -> Frame {
+        let (width, height) = self.frame.size();
+        let symbols = self
+            .renderers
+            .first()
+            .map(|renderer| &renderer.encoder.symbols[..])
+            .unwrap_or_default();
+        let cells = (0..height)
+            .flat_map(|j| self.frame.row(j))
+            .map(|cell| GridCell {
+                fg: [cell.rgb_top.r, cell.rgb_top.g, cell.rgb_top.b],
+                bg: [cell.rgb_bottom.r, cell.rgb_bottom.g, cell.rgb_bottom.b],
+                ch: match cell.symbol {
+                    0 => '\u{2580}',
+                    symbol => symbols
+                        .get(usize::from(symbol) - 1)
+                        .and_then(|symbol| symbol.chars().next())
+                        .unwrap_or(' '),
+                },
+            })
+            .collect();
+
+        Frame {
+            pts: pts.map(gst::ClockTime::nseconds),
+            offset: self.offset,
+            grid: CellGrid {
+                width,
+                height,
+                cells,
+            },
         }
     }
 
     fn render_inner(
         &mut self,
-        image_ref: ImageRef,
+        source: Source,
         overwrite: bool,
         offset: (u16, u16),
+        columns: u16,
         command_buffer: &mut Vec<u8>,
     ) {
-        unsafe fn get_pixel(image_ref: ImageRef, i: u32, j: u32) -> Rgb<u8> {
+        unsafe fn get_pixel(image_ref: ImageRef, mask: u8, i: u32, j: u32) -> Rgb<u8> {
             let rgb = unsafe { image_ref.get_pixel_unchecked(i, j) };
-
-            // quantize to only N bit color
-            const N: u8 = 5;
-            const MASK: u8 = {
-                assert!(N <= 8);
-                u8::MAX << (8 - N)
-            };
-
-            rgb.map(|x| x & MASK)
+            rgb.map(|x| x & mask)
         }
 
-        let (width, height) = image_ref.size();
-        let terminal_size = (
-            u16::try_from(width).unwrap(),
-            u16::try_from(height.div_ceil(2)).unwrap(),
-        );
+        let mask = self.mask;
 
-        let (offset_width, offset_height) = offset;
+        let terminal_size = source.size();
         let (terminal_width, terminal_height) = terminal_size;
 
+        self.offset = offset;
+
         let overwrite = overwrite || terminal_size != self.frame.size();
         if terminal_size != self.frame.size() {
             self.frame.resize(terminal_size);
         }
 
         if overwrite {
-            command_buffer.extend_from_slice(termion::clear::All.as_ref());
+            command_buffer.extend_from_slice(&self.clear);
+            // drawing every cell goes over the overlay too
+            self.drawn.clear();
         }
 
-        let mut int_buffer = itoa::Buffer::new();
-        let mut write_move = move |command_buffer: &mut Vec<u8>, i: u16, j: u16| {
-            // goto is one based
-            let (x, y) = (
-                (offset_width + i).saturating_add(1),
-                (offset_height + j).saturating_add(1),
-            );
+        let renderers = &mut self.renderers;
+        renderers.iter_mut().for_each(|renderer| {
+            renderer.encoder.begin_frame(columns);
+            renderer.budget = usize::MAX;
+            renderer.damage = None;
+        });
+        self.damage = None;
+        self.pending = false;
 
-            command_buffer.extend_from_slice(b"\x1b[");
-            command_buffer.extend_from_slice(int_buffer.format(y).as_bytes());
-            command_buffer.push(b';');
-            command_buffer.extend_from_slice(int_buffer.format(x).as_bytes());
-            command_buffer.push(b'H');
+        let stale = std::mem::take(&mut self.stale);
+        let relative = |areas: &[Damage]| {
+            areas
+                .iter()
+                .filter_map(|area| area.within(offset, terminal_size))
+                .collect::<Vec<_>>()
         };
+        let overlay = relative(&self.overlay);
+        let relative_stale = relative(&stale);
+        for renderer in renderers.iter_mut() {
+            renderer.overlay.clone_from(&overlay);
+            renderer.stale.clone_from(&relative_stale);
+        }
 
         if overwrite {
-            for j in 0..height {
-                for i in 0..width {
-                    let rgb = unsafe { get_pixel(image_ref, i, j) };
-                    let pixel = unsafe { self.frame.get_mut_unchecked(i as u16, (j / 2) as u16) };
-                    match j & 1 {
-                        0 => pixel.rgb_top = rgb,
-                        _ => pixel.rgb_bottom = rgb,
+            match source {
+                Source::Pixels(image_ref) => {
+                    let (width, height) = image_ref.size();
+                    for j in 0..height {
+                        for i in 0..width {
+                            let rgb = unsafe { get_pixel(image_ref, mask, i, j) };
+                            let pixel =
+                                unsafe { self.frame.get_mut_unchecked(i as u16, (j / 2) as u16) };
+                            match j & 1 {
+                                0 => {
+                                    pixel.rgb_top = rgb;
+                                    pixel.symbol = 0;
+                                }
+                                _ => pixel.rgb_bottom = rgb,
+                            }
+                        }
                     }
-                }
-            }
 
-            if (height % 2) != 0 {
-                let last_row =
-                    &mut self.frame.as_mut_slice()[width as usize * (height / 2) as usize..];
-                for pixel in last_row {
-                    pixel.rgb_bottom = Rgb::new(0, 0, 0)
+                    if (height % 2) != 0 {
+                        let last_row = &mut self.frame.as_mut_slice()
+                            [width as usize * (height / 2) as usize..];
+                        for pixel in last_row {
+                            pixel.rgb_bottom = Rgb::new(0, 0, 0)
+                        }
+                    }
+                }
+                Source::Cells(cells) => {
+                    for j in 0..terminal_height {
+                        let cells = cells.row(j).iter();
+                        for (cell, &new) in self.frame.row_mut(j).iter_mut().zip(cells) {
+                            *cell = new.quantized(mask);
+                        }
+                    }
                 }
             }
 
             for j in 0..terminal_height {
-                write_move(command_buffer, 0, j);
-                for i in 0..terminal_width {
-                    unsafe { self.frame.get_mut_unchecked(i, j) }.draw(command_buffer)
-                }
+                let row = self.frame.row(j);
+                renderers[0]
+                    .encoder
+                    .draw_run(command_buffer, row, offset, j, 0..terminal_width);
             }
 
+            self.first_row = 0;
+            self.damage = (terminal_width > 0 && terminal_height > 0).then_some(Damage {
+                x: 0..terminal_width,
+                y: 0..terminal_height,
+            });
             return;
         }
 
-        for j in 0..(height / 2) {
-            let mut last_changed = false;
-            'next_pixel: for i in 0..width {
-                let rgb_t = unsafe { get_pixel(image_ref, i, j * 2) };
-                let rgb_b = unsafe { get_pixel(image_ref, i, j * 2 + 1) };
-                let (i, j) = (i as u16, j as u16);
-                let pixel = unsafe { self.frame.get_mut_unchecked(i, j) };
-                if pixel.rgb_top != rgb_t || pixel.rgb_bottom != rgb_b {
-                    if !last_changed {
-                        last_changed = true;
-                        write_move(command_buffer, i, j);
-                    }
-                    pixel.rgb_top = rgb_t;
-                    pixel.rgb_bottom = rgb_b;
-                    (*pixel).draw(command_buffer);
-                    continue 'next_pixel;
+        if terminal_width == 0 || terminal_height == 0 {
+            return;
+        }
+
+        let Some(pool) = self.pool.as_ref().filter(|_| self.max_cells.is_none()) else {
+            let renderer = &mut renderers[0];
+            renderer.budget = self.max_cells.map_or(usize::MAX, NonZero::get);
+
+            // going round from where the last capped frame stopped gives every row its turn
+            let first_row = self.first_row % terminal_height;
+            self.first_row = 0;
+            for j in (first_row..terminal_height).chain(0..first_row) {
+                let frame_row = self.frame.row_mut(j);
+                if !renderer.diff_row(source, mask, offset, j, frame_row, command_buffer) {
+                    self.first_row = j;
+                    self.pending = true;
+                    // the budget might've left some of them out
+                    self.stale = stale;
+                    break;
                 }
-                last_changed = false;
             }
-        }
+            self.damage = renderer.damage.clone();
+            return;
+        };
 
-        if (height % 2) != 0 {
-            let j = height / 2;
-            let mut last_changed = false;
-            'next_pixel: for i in 0..width {
-                let rgb_t = unsafe { get_pixel(image_ref, i, j * 2) };
-                let (i, j) = (i as u16, j as u16);
-                let pixel = unsafe { self.frame.get_mut_unchecked(i, j) };
-                if pixel.rgb_top != rgb_t {
-                    if !last_changed {
-                        last_changed = true;
-                        write_move(command_buffer, i, j);
+        // every worker gets a band of rows, the bands are written out in order once all are done
+        let band_rows = terminal_height.div_ceil(renderers.len() as u16);
+        let bands = self
+            .frame
+            .as_mut_slice()
+            .chunks_mut(usize::from(band_rows) * usize::from(terminal_width));
+
+        // short frames might leave some workers without a band
+        renderers
+            .iter_mut()
+            .for_each(|renderer| renderer.output.clear());
+
+        pool.scope(|scope| {
+            for ((band, band_start), renderer) in bands
+                .zip((0..).step_by(usize::from(band_rows)))
+                .zip(renderers.iter_mut())
+            {
+                scope.spawn(move |_| {
+                    let mut output = std::mem::take(&mut renderer.output);
+
+                    let rows = band.chunks_mut(usize::from(terminal_width));
+                    for (j, frame_row) in (band_start..).zip(rows) {
+                        renderer.diff_row(source, mask, offset, j, frame_row, &mut output);
                     }
-                    pixel.rgb_top = rgb_t;
-                    (*pixel).draw(command_buffer);
-                    continue 'next_pixel;
-                }
-                last_changed = false;
+
+                    renderer.output = output;
+                });
             }
+        });
+
+        for renderer in renderers.iter() {
+            command_buffer.extend_from_slice(&renderer.output);
         }
+        self.damage = renderers
+            .iter_mut()
+            .filter_map(|renderer| renderer.damage.take())
+            .reduce(Damage::union);
     }
 
     pub fn render(
@@ -271,10 +1289,287 @@ impl RenderedFrame {
         image_ref: ImageRef,
         overwrite: bool,
         offset: (u16, u16),
+        columns: u16,
+        command_buffer: &mut Vec<u8>,
+    ) {
+        self.render_source(
+            Source::Pixels(image_ref),
+            overwrite,
+            offset,
+            columns,
+            command_buffer,
+        );
+    }
+
+    /// the same as [`Self::render`] with the cells worked out already
+    pub(super) fn render_cells(
+        &mut self,
+        cells: &PodMatrix<Cell>,
+        overwrite: bool,
+        offset: (u16, u16),
+        columns: u16,
+        command_buffer: &mut Vec<u8>,
+    ) {
+        self.render_source(
+            Source::Cells(cells),
+            overwrite,
+            offset,
+            columns,
+            command_buffer,
+        );
+    }
+
+    fn render_source(
+        &mut self,
+        source: Source,
+        overwrite: bool,
+        offset: (u16, u16),
+        columns: u16,
         command_buffer: &mut Vec<u8>,
     ) {
-        Self::render_inner(self, image_ref, overwrite, offset, command_buffer);
+        Self::render_inner(self, source, overwrite, offset, columns, command_buffer);
         // Reset cursor for drawing
         command_buffer.extend_from_slice(b"\x1b[0m");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::terminal_sink::overlay::{Layer, TextStyle};
+
+    #[test]
+    fn sgr_len_matches_output() {
+        let colors = [
+            None,
+            Some(Rgb::new(0, 10, 200)),
+            Some(Rgb::new(255, 255, 9)),
+        ];
+        for palette in [Palette::Truecolor, Palette::Xterm256] {
+            for fg in colors {
+                for bg in colors {
+                    let mut buffer = vec![];
+                    write_sgr(&mut buffer, palette, fg, bg);
+                    assert_eq!(buffer.len(), sgr_len(palette, fg, bg));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn xterm_colors_are_close() {
+        assert_eq!(xterm_index(Rgb::new(0, 0, 0)), 16);
+        assert_eq!(xterm_index(Rgb::new(255, 255, 255)), 231);
+        assert_eq!(xterm_index(Rgb::new(255, 0, 0)), 196);
+        assert_eq!(xterm_index(Rgb::new(128, 128, 128)), 244);
+    }
+
+    #[test]
+    fn relative_moves_are_short() {
+        let mut buffer = vec![];
+        write_relative_move(&mut buffer, (10, 4), (12, 5));
+        assert_eq!(buffer, b"\x1b[B\x1b[2C");
+
+        buffer.clear();
+        write_relative_move(&mut buffer, (80, 4), (0, 4));
+        assert_eq!(buffer, b"\r");
+    }
+
+    #[test]
+    fn pen_reuses_colors() {
+        let red = Rgb::new(255, 0, 0);
+        let mut pen = Pen::default();
+        let mut buffer = vec![];
+
+        let cell = Cell {
+            rgb_top: red,
+            rgb_bottom: red,
+            symbol: 0,
+        };
+        pen.stroke(&mut buffer, cell);
+        buffer.clear();
+        pen.stroke(&mut buffer, cell);
+        assert_eq!(buffer.len(), 1);
+    }
+
+    #[test]
+    fn spaces_only_set_the_background() {
+        let (red, blue) = (Rgb::new(255, 0, 0), Rgb::new(0, 0, 255));
+        let symbols: [Box<str>; 2] = [" ".into(), "\u{2580}".into()];
+        let mut pen = Pen::default();
+        let mut buffer = vec![];
+
+        let glyph = pen.symbol(&mut buffer, Cell::with_symbol(1, red, blue), &symbols);
+        assert!(matches!(glyph, Some(Glyph::Space)));
+        let mut expected = vec![];
+        write_sgr(&mut expected, Palette::default(), None, Some(blue));
+        expected.push(b' ');
+        assert_eq!(buffer, expected);
+
+        buffer.clear();
+        pen.symbol(&mut buffer, Cell::with_symbol(2, red, blue), &symbols);
+        assert!(buffer.ends_with("\u{2580}".as_bytes()));
+    }
+
+    #[test]
+    fn runs_use_repeat() {
+        let mut encoder = Encoder::new(false, true);
+        encoder.begin_frame(80);
+
+        let cell = Cell {
+            rgb_top: Rgb::new(1, 2, 3),
+            rgb_bottom: Rgb::new(4, 5, 6),
+            symbol: 0,
+        };
+        let mut buffer = vec![];
+        encoder.draw(&mut buffer, cell, 5);
+        assert!(buffer.ends_with(b"\x1b[4b"));
+
+        // nothing to set up for the next identical cell
+        buffer.clear();
+        encoder.draw(&mut buffer, cell, 1);
+        assert_eq!(buffer, UNICODE_TOP_HALF_BLOCK.as_bytes());
+    }
+
+    #[test]
+    fn noise_below_the_threshold_is_kept_out() {
+        let gray = |v: u8| Cell {
+            rgb_top: Rgb::new(v, v, v),
+            rgb_bottom: Rgb::new(v, v, v),
+            symbol: 0,
+        };
+        let old = [gray(96), gray(96), gray(96)];
+
+        let mut renderer = RowRenderer::new(false, true);
+        renderer.threshold = 8;
+        renderer.next_row = vec![gray(104), gray(200), gray(96)];
+        renderer.changed = vec![0b011];
+        renderer.ignore_noise(&old);
+
+        assert!(renderer.next_row == [gray(96), gray(200), gray(96)]);
+        assert_eq!(renderer.changed, [0b010]);
+    }
+
+    #[test]
+    fn capped_frames_finish_in_the_next_one() {
+        let red = [Rgb::new(248, 0, 0); 8];
+        let black = [Rgb::new(0, 0, 0); 8];
+        let image =
+            |pixels: &[Rgb<u8>]| ImageRef::from_buffer(4, 2, bytemuck::cast_slice(pixels)).unwrap();
+
+        let mut frame = RenderedFrame::new(false, true, NonZero::<usize>::MIN);
+        let mut output = vec![];
+        frame.render(image(&black), true, (2, 1), 80, &mut output);
+        assert_eq!(
+            frame.damage(),
+            Some(VideoArea {
+                x: 2,
+                y: 1,
+                width: 4,
+                height: 1
+            })
+        );
+
+        frame.set_max_cells(NonZero::new(3));
+        frame.render(image(&red), false, (2, 1), 80, &mut output);
+        assert!(frame.pending());
+        assert_eq!(frame.damage().map(|area| area.width), Some(3));
+
+        frame.render(image(&red), false, (2, 1), 80, &mut output);
+        assert!(!frame.pending());
+        assert_eq!(
+            frame.damage().map(|area| (area.x, area.width)),
+            Some((5, 1))
+        );
+
+        frame.render(image(&red), false, (2, 1), 80, &mut output);
+        assert_eq!(frame.damage(), None);
+    }
+
+    #[test]
+    fn overlays_keep_their_cells() {
+        let black = [Rgb::new(0, 0, 0); 8];
+        let mut under_text = black;
+        for i in [1, 2, 5, 6] {
+            under_text[i] = Rgb::new(248, 0, 0);
+        }
+        let image =
+            |pixels: &[Rgb<u8>]| ImageRef::from_buffer(4, 2, bytemuck::cast_slice(pixels)).unwrap();
+        let under = VideoArea {
+            x: 1,
+            y: 0,
+            width: 2,
+            height: 1,
+        };
+
+        let mut frame = RenderedFrame::new(false, true, NonZero::<usize>::MIN);
+        let mut output = vec![];
+        frame.render(image(&black), true, (0, 0), 80, &mut output);
+
+        let mut text = Overlay::new();
+        text.text(Layer::Osd, (1, 0), "ab", TextStyle::default());
+        frame.draw_overlay(&text, &mut output);
+        frame.render(image(&under_text), false, (0, 0), 80, &mut output);
+        // nothing but the covered cells changed, and those are left to the text
+        assert_eq!(frame.damage(), None);
+        let red_cell = Cell {
+            rgb_top: Rgb::new(248, 0, 0),
+            rgb_bottom: Rgb::new(248, 0, 0),
+            symbol: 0,
+        };
+        assert!(frame.frame.row(0)[1..3] == [red_cell; 2]);
+
+        frame.draw_overlay(&Overlay::new(), &mut output);
+        frame.render(image(&under_text), false, (0, 0), 80, &mut output);
+        assert_eq!(frame.damage(), Some(under));
+    }
+
+    #[test]
+    fn overlays_get_drawn_when_they_change() {
+        let black = [Rgb::new(0, 0, 0); 8];
+        let image = ImageRef::from_buffer(4, 2, bytemuck::cast_slice(&black)).unwrap();
+        let text = |text: &str| {
+            let mut overlay = Overlay::new();
+            overlay.text(Layer::Osd, (0, 0), text, TextStyle::default());
+            overlay
+        };
+
+        let mut frame = RenderedFrame::new(false, true, NonZero::<usize>::MIN);
+        let mut output = vec![];
+        frame.render(image, true, (0, 0), 80, &mut output);
+        frame.draw_overlay(&text("1"), &mut output);
+
+        output.clear();
+        frame.render(image, false, (0, 0), 80, &mut output);
+        let unchanged = output.len();
+        frame.draw_overlay(&text("1"), &mut output);
+        assert_eq!(output.len(), unchanged);
+
+        frame.draw_overlay(&text("2"), &mut output);
+        assert!(output.len() > unchanged);
+
+        // drawing the whole frame goes over it
+        frame.render(image, true, (0, 0), 80, &mut output);
+        let drawn = output.len();
+        frame.draw_overlay(&text("2"), &mut output);
+        assert!(output.len() > drawn);
+    }
+
+    #[test]
+    fn moves_only_when_needed() {
+        let mut encoder = Encoder::new(false, false);
+        encoder.begin_frame(80);
+
+        let mut buffer = vec![];
+        encoder.move_to(&mut buffer, &[], 0, (0, 3));
+        assert_eq!(buffer, b"\x1b[4H");
+
+        buffer.clear();
+        encoder.move_to(&mut buffer, &[], 0, (0, 3));
+        assert!(buffer.is_empty());
+
+        buffer.clear();
+        encoder.move_to(&mut buffer, &[], 0, (0, 4));
+        assert_eq!(buffer, b"\x1b[B");
+    }
+}
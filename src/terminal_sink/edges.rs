@@ -0,0 +1,164 @@
+//! `--render edges`, line art out of a Sobel filter over the luminance of the resized frame,
+//! every cell with an edge through it getting the line closest to its direction,
+//! brighter the sharper the edge is, which reads fine on a terminal with only a few grays
+use crate::terminal_sink::diff::Cell;
+use crate::terminal_sink::resize::{ImageRef, PodMatrix};
+use rgb::Rgb;
+use std::sync::Arc;
+
+/// the lines drawn, in the order of their symbols
+const LINES: [&str; 4] = ["\u{2500}", "\u{2502}", "\u{2571}", "\u{2572}"];
+const HORIZONTAL: u16 = 1;
+const VERTICAL: u16 = 2;
+const RISING: u16 = 3;
+const FALLING: u16 = 4;
+
+/// how sharp an edge has to be, out of 255, to get drawn
+const THRESHOLD: u32 = 24;
+/// the gray the faintest edges are drawn in, the sharpest get white
+const FAINTEST: u32 = 96;
+
+/// the symbols the cells of line art draw
+pub(super) fn symbols() -> Arc<[Box<str>]> {
+    LINES.map(Box::<str>::from).into()
+}
+
+/// bt.601 luma in fixed point
+fn luma(rgb: Rgb<u8>) -> i32 {
+    ((u32::from(rgb.r) * 77 + u32::from(rgb.g) * 150 + u32::from(rgb.b) * 29) >> 8) as i32
+}
+
+/// the line along an edge with the gradient `(x, y)`, y going down, `None` off the edges
+fn line((x, y): (i32, i32)) -> Option<(u16, u32)> {
+    // the two pixels of a cell both add up to 4 times 255 each way, so a hard straight edge
+    // comes to 255 and diagonals, adding up both ways, already get there half as sharp
+    let strength = ((x.unsigned_abs() + y.unsigned_abs()) / 8).min(255);
+    if strength < THRESHOLD {
+        return None;
+    }
+
+    // within 22.5 degrees of a straight line, tan(22.5) being about 0.414
+    let (x_abs, y_abs) = (x.unsigned_abs(), y.unsigned_abs());
+    // the brightness changing across makes an up and down edge
+    let symbol = if y_abs * 1000 < x_abs * 414 {
+        VERTICAL
+    } else if x_abs * 1000 < y_abs * 414 {
+        HORIZONTAL
+    } else if (x > 0) == (y > 0) {
+        // brighter towards the bottom right, so the same along the rising diagonal
+        RISING
+    } else {
+        FALLING
+    };
+    Some((symbol, strength))
+}
+
+/// Turns resized frames into line art, keeping its buffers between frames
+pub(super) struct EdgeDetector {
+    luma: Vec<i32>,
+    cells: PodMatrix<Cell>,
+}
+
+impl EdgeDetector {
+    pub(super) fn new() -> Self {
+        Self {
+            luma: Vec::new(),
+            cells: PodMatrix::new(),
+        }
+    }
+
+    /// the cells of `image`, two pixels stacked in each like anything else drawn
+    pub(super) fn cells(&mut self, image: ImageRef) -> &PodMatrix<Cell> {
+        let (width, height) = image.size();
+        self.luma.clear();
+        for j in 0..height {
+            self.luma.extend(image.row(j).iter().map(|&rgb| luma(rgb)));
+        }
+
+        let (width, height) = (width as usize, height as usize);
+        let luma = |i: usize, j: usize| self.luma[j.min(height - 1) * width + i.min(width - 1)];
+        // the edges of the frame repeat outwards
+        let gradient = |i: usize, j: usize| {
+            let (left, right) = (i.saturating_sub(1), i + 1);
+            let (up, down) = (j.saturating_sub(1), j + 1);
+            let x = luma(right, up) + 2 * luma(right, j) + luma(right, down)
+                - luma(left, up)
+                - 2 * luma(left, j)
+                - luma(left, down);
+            let y = luma(left, down) + 2 * luma(i, down) + luma(right, down)
+                - luma(left, up)
+                - 2 * luma(i, up)
+                - luma(right, up);
+            (x, y)
+        };
+
+        // resized frames always fit in the cells of a terminal
+        let rows = height.div_ceil(2);
+        self.cells.resize((width as u16, rows as u16));
+        for row in 0..rows {
+            let cells = self.cells.row_mut(row as u16);
+            for (i, cell) in cells.iter_mut().enumerate() {
+                let top = gradient(i, row * 2);
+                let bottom = match row * 2 + 1 < height {
+                    true => gradient(i, row * 2 + 1),
+                    false => top,
+                };
+                let sum = (top.0 + bottom.0, top.1 + bottom.1);
+
+                let black = Rgb::new(0, 0, 0);
+                *cell = match line(sum) {
+                    Some((symbol, strength)) => {
+                        let gray = (FAINTEST + strength * (255 - FAINTEST) / 255) as u8;
+                        Cell::with_symbol(symbol, Rgb::new(gray, gray, gray), black)
+                    }
+                    None => Cell::with_symbol(0, black, black),
+                };
+            }
+        }
+        &self.cells
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn symbols_of(pixels: &[Rgb<u8>], width: u32, height: u32) -> Vec<Vec<u16>> {
+        let image = ImageRef::from_buffer(width, height, bytemuck::cast_slice(pixels)).unwrap();
+        let mut detector = EdgeDetector::new();
+        let cells = detector.cells(image);
+        (0..cells.height())
+            .map(|j| cells.row(j).iter().map(Cell::symbol).collect())
+            .collect()
+    }
+
+    #[test]
+    fn lines_follow_the_edges() {
+        let (black, white) = (Rgb::new(0, 0, 0), Rgb::new(255, 255, 255));
+        // the right half white
+        let pixels = (0..4 * 6)
+            .map(|i| if i % 6 >= 3 { white } else { black })
+            .collect::<Vec<_>>();
+        assert_eq!(symbols_of(&pixels, 6, 4), [[0, 0, 2, 2, 0, 0]; 2]);
+
+        // the bottom half white
+        let pixels = (0..4 * 4)
+            .map(|i| if i / 4 >= 2 { white } else { black })
+            .collect::<Vec<_>>();
+        assert_eq!(symbols_of(&pixels, 4, 4), [[1; 4]; 2]);
+    }
+
+    #[test]
+    fn diagonals() {
+        assert_eq!(line((400, 400)).map(|(symbol, _)| symbol), Some(RISING));
+        assert_eq!(line((-400, 400)).map(|(symbol, _)| symbol), Some(FALLING));
+        assert_eq!(line((10, 0)), None);
+    }
+
+    #[test]
+    fn hard_straight_edges_get_white() {
+        // black to white across both pixels of a cell
+        assert_eq!(line((2040, 0)), Some((VERTICAL, 255)));
+        assert_eq!(line((0, 2040)), Some((HORIZONTAL, 255)));
+    }
+}
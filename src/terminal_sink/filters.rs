@@ -0,0 +1,407 @@
+//! Frame filters for `--vf`.
+//!
+//! A chain like `crop=640:360,grayscale,sharpen=0.5` runs every filter in order on the
+//! decoded frame, before it gets resized and quantized. Adding a filter only takes
+//! implementing [`Filter`] and listing it in [`REGISTRY`] with a parser for its arguments.
+
+use crate::terminal_sink::resize::{ImageRef, PodMatrix};
+use rgb::{ComponentMap, Rgb};
+use std::str::FromStr;
+
+pub trait Filter: Send {
+    /// writes the filtered `src` into `dst`, which the filter resizes to what it outputs
+    fn apply(&mut self, src: ImageRef, dst: &mut PodMatrix<Rgb<u8>>);
+}
+
+type BuildFilter = fn(Option<&str>) -> Result<Box<dyn Filter>, String>;
+
+pub struct FilterEntry {
+    pub name: &'static str,
+    pub usage: &'static str,
+    build: BuildFilter,
+}
+
+/// every filter `--vf` knows about
+pub static REGISTRY: &[FilterEntry] = &[
+    FilterEntry {
+        name: "crop",
+        usage: "crop=W:H[:X:Y]  keep a W by H area, centered unless X and Y are given",
+        build: Crop::build,
+    },
+    FilterEntry {
+        name: "rotate",
+        usage: "rotate=90|180|270  rotate clockwise",
+        build: Rotate::build,
+    },
+    FilterEntry {
+        name: "grayscale",
+        usage: "grayscale  drop all color",
+        build: Grayscale::build,
+    },
+    FilterEntry {
+        name: "sharpen",
+        usage: "sharpen[=AMOUNT]  boost edges, AMOUNT defaults to 1",
+        build: Sharpen::build,
+    },
+    FilterEntry {
+        name: "lut",
+        usage: "lut=KEY=VALUE:...  remap every channel with gamma, contrast, brightness or invert",
+        build: Lut::build,
+    },
+];
+
+fn usage() -> String {
+    REGISTRY
+        .iter()
+        .fold(String::from("known filters:"), |acc, entry| {
+            acc + "\n  " + entry.usage
+        })
+}
+
+/// a parsed `--vf` chain, the filters get built for each renderer
+#[derive(Clone)]
+pub struct ChainSpec(Vec<(&'static FilterEntry, Option<String>)>);
+
+impl FromStr for ChainSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let filters = s
+            .split(',')
+            .map(str::trim)
+            .filter(|filter| !filter.is_empty())
+            .map(|filter| {
+                let (name, args) = match filter.split_once('=') {
+                    Some((name, args)) => (name, Some(args)),
+                    None => (filter, None),
+                };
+
+                let entry = REGISTRY
+                    .iter()
+                    .find(|entry| entry.name == name)
+                    .ok_or_else(|| format!("unknown filter {name:?}\n{}", usage()))?;
+
+                (entry.build)(args).map_err(|err| format!("{name}: {err}\n  {}", entry.usage))?;
+                Ok((entry, args.map(String::from)))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self(filters))
+    }
+}
+
+impl ChainSpec {
+    pub fn build(&self) -> FilterChain {
+        let filters = self
+            .0
+            .iter()
+            .map(|(entry, args)| (entry.build)(args.as_deref()).unwrap())
+            .collect();
+
+        FilterChain {
+            filters,
+            buffers: [PodMatrix::new(), PodMatrix::new()],
+        }
+    }
+}
+
+pub struct FilterChain {
+    filters: Vec<Box<dyn Filter>>,
+    buffers: [PodMatrix<Rgb<u8>>; 2],
+}
+
+impl FilterChain {
+    pub fn empty() -> Self {
+        Self {
+            filters: vec![],
+            buffers: [PodMatrix::new(), PodMatrix::new()],
+        }
+    }
+
+    pub fn apply<'a>(&'a mut self, image: ImageRef<'a>) -> ImageRef<'a> {
+        let Some((first, rest)) = self.filters.split_first_mut() else {
+            return image;
+        };
+
+        let [front, back] = &mut self.buffers;
+        first.apply(image, front);
+        for filter in rest {
+            filter.apply(front.as_image(), back);
+            std::mem::swap(front, back);
+        }
+
+        front.as_image()
+    }
+}
+
+fn parse<T: FromStr>(what: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {what} {value:?}"))
+}
+
+fn no_args(args: Option<&str>) -> Result<(), String> {
+    match args {
+        None => Ok(()),
+        Some(_) => Err("takes no arguments".to_string()),
+    }
+}
+
+fn frame_size(width: u32, height: u32) -> (u16, u16) {
+    let clamp = |x: u32| u16::try_from(x).unwrap_or(u16::MAX);
+    (clamp(width), clamp(height))
+}
+
+struct Crop {
+    size: (u32, u32),
+    position: Option<(u32, u32)>,
+}
+
+impl Crop {
+    fn build(args: Option<&str>) -> Result<Box<dyn Filter>, String> {
+        let args = args.ok_or("needs a size")?;
+        let numbers = args
+            .split(':')
+            .map(|n| parse::<u32>("number", n))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (size, position) = match numbers[..] {
+            [w, h] => ((w, h), None),
+            [w, h, x, y] => ((w, h), Some((x, y))),
+            _ => return Err("expected W:H or W:H:X:Y".to_string()),
+        };
+
+        Ok(Box::new(Self { size, position }))
+    }
+}
+
+impl Filter for Crop {
+    fn apply(&mut self, src: ImageRef, dst: &mut PodMatrix<Rgb<u8>>) {
+        let (src_width, src_height) = src.size();
+        let width = self.size.0.min(src_width);
+        let height = self.size.1.min(src_height);
+        let (x, y) = match self.position {
+            Some((x, y)) => (x.min(src_width - width), y.min(src_height - height)),
+            None => ((src_width - width) / 2, (src_height - height) / 2),
+        };
+
+        dst.resize(frame_size(width, height));
+        let (width, height) = dst.size();
+        for j in 0..height {
+            let row = &src.row(y + u32::from(j))[x as usize..][..usize::from(width)];
+            dst.row_mut(j).copy_from_slice(row);
+        }
+    }
+}
+
+struct Rotate {
+    quarter_turns: u8,
+}
+
+impl Rotate {
+    fn build(args: Option<&str>) -> Result<Box<dyn Filter>, String> {
+        let quarter_turns = match args.ok_or("needs an angle")? {
+            "90" => 1,
+            "180" => 2,
+            "270" => 3,
+            angle => return Err(format!("can only rotate by 90, 180 or 270, not {angle}")),
+        };
+
+        Ok(Box::new(Self { quarter_turns }))
+    }
+}
+
+impl Filter for Rotate {
+    fn apply(&mut self, src: ImageRef, dst: &mut PodMatrix<Rgb<u8>>) {
+        let (width, height) = src.size();
+        let size = match self.quarter_turns {
+            2 => frame_size(width, height),
+            _ => frame_size(height, width),
+        };
+        dst.resize(size);
+
+        for j in 0..dst.height() {
+            let (j32, row) = (u32::from(j), dst.row_mut(j));
+            for (i, pixel) in (0..).zip(row.iter_mut()) {
+                let (src_i, src_j) = match self.quarter_turns {
+                    1 => (j32, height - 1 - i),
+                    2 => (width - 1 - i, height - 1 - j32),
+                    _ => (width - 1 - j32, i),
+                };
+                *pixel = src.row(src_j)[src_i as usize];
+            }
+        }
+    }
+}
+
+struct Grayscale;
+
+impl Grayscale {
+    fn build(args: Option<&str>) -> Result<Box<dyn Filter>, String> {
+        no_args(args)?;
+        Ok(Box::new(Self))
+    }
+}
+
+impl Filter for Grayscale {
+    fn apply(&mut self, src: ImageRef, dst: &mut PodMatrix<Rgb<u8>>) {
+        let (width, height) = src.size();
+        dst.resize(frame_size(width, height));
+
+        for j in 0..dst.height() {
+            let src_row = src.row(u32::from(j));
+            for (pixel, &rgb) in dst.row_mut(j).iter_mut().zip(src_row) {
+                // bt.601 luma in fixed point
+                let luma =
+                    (u32::from(rgb.r) * 77 + u32::from(rgb.g) * 150 + u32::from(rgb.b) * 29) >> 8;
+                let luma = luma as u8;
+                *pixel = Rgb::new(luma, luma, luma);
+            }
+        }
+    }
+}
+
+struct Sharpen {
+    amount: f32,
+}
+
+impl Sharpen {
+    fn build(args: Option<&str>) -> Result<Box<dyn Filter>, String> {
+        let amount = args.map_or(Ok(1.0), |amount| parse("amount", amount))?;
+        if !(0.0..=10.0).contains(&amount) {
+            return Err("the amount has to be between 0 and 10".to_string());
+        }
+
+        Ok(Box::new(Self { amount }))
+    }
+}
+
+impl Filter for Sharpen {
+    fn apply(&mut self, src: ImageRef, dst: &mut PodMatrix<Rgb<u8>>) {
+        let (width, height) = src.size();
+        dst.resize(frame_size(width, height));
+
+        let (dst_width, dst_height) = dst.size();
+        let (last_i, last_j) = (
+            usize::from(dst_width).saturating_sub(1),
+            u32::from(dst_height).saturating_sub(1),
+        );
+        let amount = self.amount;
+
+        for j in 0..dst_height {
+            let j32 = u32::from(j);
+            let up = src.row(j32.saturating_sub(1));
+            let row = src.row(j32);
+            let down = src.row((j32 + 1).min(last_j));
+
+            for (i, pixel) in dst.row_mut(j).iter_mut().enumerate() {
+                let left = row[i.saturating_sub(1)];
+                let right = row[(i + 1).min(last_i)];
+                let channel = |get: fn(Rgb<u8>) -> u8| {
+                    let center = f32::from(get(row[i]));
+                    let around = [up[i], down[i], left, right]
+                        .into_iter()
+                        .map(|rgb| f32::from(get(rgb)))
+                        .sum::<f32>();
+                    (center + amount * (center * 4.0 - around)).clamp(0.0, 255.0) as u8
+                };
+
+                *pixel = Rgb::new(channel(|p| p.r), channel(|p| p.g), channel(|p| p.b));
+            }
+        }
+    }
+}
+
+struct Lut {
+    table: [u8; 256],
+}
+
+impl Lut {
+    fn build(args: Option<&str>) -> Result<Box<dyn Filter>, String> {
+        let (mut gamma, mut contrast, mut brightness, mut invert) =
+            (1.0_f32, 1.0_f32, 0.0_f32, false);
+
+        for option in args
+            .ok_or("needs at least one of gamma, contrast, brightness or invert")?
+            .split(':')
+        {
+            match option.split_once('=') {
+                Some(("gamma", value)) => gamma = parse("gamma", value)?,
+                Some(("contrast", value)) => contrast = parse("contrast", value)?,
+                Some(("brightness", value)) => brightness = parse("brightness", value)?,
+                None if option == "invert" => invert = true,
+                _ => return Err(format!("unknown option {option:?}")),
+            }
+        }
+
+        if gamma <= 0.0 {
+            return Err("gamma has to be positive".to_string());
+        }
+
+        let table = std::array::from_fn(|x| {
+            let x = (x as f32 / 255.0).powf(1.0 / gamma);
+            let x = (x - 0.5) * contrast + 0.5 + brightness / 255.0;
+            let x = (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+            match invert {
+                true => 255 - x,
+                false => x,
+            }
+        });
+
+        Ok(Box::new(Self { table }))
+    }
+}
+
+impl Filter for Lut {
+    fn apply(&mut self, src: ImageRef, dst: &mut PodMatrix<Rgb<u8>>) {
+        let (width, height) = src.size();
+        dst.resize(frame_size(width, height));
+
+        for j in 0..dst.height() {
+            let src_row = src.row(u32::from(j));
+            for (pixel, &rgb) in dst.row_mut(j).iter_mut().zip(src_row) {
+                *pixel = rgb.map(|x| self.table[usize::from(x)]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn image(width: u16, height: u16) -> PodMatrix<Rgb<u8>> {
+        let mut image = PodMatrix::new();
+        image.resize((width, height));
+        for j in 0..height {
+            for (i, pixel) in (0..).zip(image.row_mut(j)) {
+                *pixel = Rgb::new(i, j as u8, 0);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn parses_chains() {
+        assert!("crop=4:2,rotate=90,grayscale".parse::<ChainSpec>().is_ok());
+        assert!("lut=gamma=2.2:invert, sharpen".parse::<ChainSpec>().is_ok());
+        assert!("blur".parse::<ChainSpec>().is_err());
+        assert!("rotate=45".parse::<ChainSpec>().is_err());
+        assert!("grayscale=1".parse::<ChainSpec>().is_err());
+    }
+
+    #[test]
+    fn crop_then_rotate() {
+        let source = image(4, 3);
+        let mut chain = "crop=2:2:1:1,rotate=90"
+            .parse::<ChainSpec>()
+            .unwrap()
+            .build();
+        let out = chain.apply(source.as_image());
+
+        assert_eq!(out.size(), (2, 2));
+        // the bottom left of the crop ends up at the top left
+        assert_eq!(out.row(0), [Rgb::new(1, 2, 0), Rgb::new(1, 1, 0)]);
+        assert_eq!(out.row(1), [Rgb::new(2, 2, 0), Rgb::new(2, 1, 0)]);
+    }
+}
@@ -0,0 +1,352 @@
+use crate::flag;
+use crate::terminal_sink::background::Background;
+use crate::terminal_sink::hdr::{ToneMap, ToneMapper};
+use crate::terminal_sink::resize::{ImageRef, PodMatrix};
+use crate::terminal_sink::yuv::{self, Coefficients};
+use gst_video::{VideoColorMatrix, VideoFormat, VideoFrameRef};
+use rgb::Rgb;
+
+/// the packed formats the sink takes without a conversion, RGB comes first since it is never repacked
+const PACKED_FORMATS: &[VideoFormat] = &[
+    VideoFormat::Rgb,
+    VideoFormat::Bgr,
+    VideoFormat::Bgrx,
+    VideoFormat::Rgbx,
+];
+
+/// the packed formats with an alpha channel, composited over the background as they get unpacked
+const ALPHA_FORMATS: &[VideoFormat] = &[
+    VideoFormat::Rgba,
+    VideoFormat::Bgra,
+    VideoFormat::Argb,
+    VideoFormat::Abgr,
+];
+
+/// 10 bit formats, HDR video only keeps its highlights for tone mapping in these
+const DEEP_FORMATS: &[VideoFormat] = &[VideoFormat::P01010le, VideoFormat::I42010le];
+
+/// every format the sink takes, most decoders output I420 so taking it lets `videoconvert` pass frames through
+pub fn formats() -> impl Iterator<Item = VideoFormat> {
+    let i420 = (!flag("NO_NATIVE_I420", false)).then_some(VideoFormat::I420);
+    let deep = match flag("NO_NATIVE_10BIT", false) {
+        true => &[][..],
+        false => DEEP_FORMATS,
+    };
+    PACKED_FORMATS
+        .iter()
+        .chain(ALPHA_FORMATS)
+        .copied()
+        .chain(i420)
+        .chain(deep.iter().copied())
+}
+
+/// the 16 bit little endian sample `i` of a row
+fn sample(row: &[u8], i: usize) -> Option<u16> {
+    Some(u16::from_le_bytes([*row.get(2 * i)?, *row.get(2 * i + 1)?]))
+}
+
+/// `len` bytes of row `j` of a plane
+fn plane_row(plane: &[u8], stride: usize, j: usize, len: usize) -> Option<&[u8]> {
+    plane.get(j * stride..)?.get(..len)
+}
+
+/// Gets packed RGB out of a mapped video frame,
+/// borrowing the frame when it already is packed RGB and repacking it otherwise
+pub struct FrameUnpacker {
+    buffer: PodMatrix<Rgb<u8>>,
+    /// what shows through transparent pixels, black when `None`
+    matte: Option<Background>,
+    /// the pixels across and down a checker of the matte spans
+    checker: (u16, u16),
+    tone_map: ToneMap,
+    /// made for the first 10 bit frame, and again whenever the stream's colors change
+    tone_mapper: Option<ToneMapper>,
+}
+
+impl FrameUnpacker {
+    pub fn new() -> Self {
+        Self {
+            buffer: PodMatrix::new(),
+            matte: None,
+            checker: (8, 8),
+            tone_map: ToneMap::default(),
+            tone_mapper: None,
+        }
+    }
+
+    pub fn set_tone_map(&mut self, tone_map: ToneMap) {
+        self.tone_map = tone_map;
+    }
+
+    pub fn set_matte(&mut self, matte: Option<Background>) {
+        self.matte = matte;
+    }
+
+    /// how many pixels across and down of the frames to come a checker of the matte spans
+    pub fn set_checker(&mut self, checker: (u16, u16)) {
+        self.checker = (checker.0.max(1), checker.1.max(1));
+    }
+
+    /// copies every row out of `data`, taking the red, green and blue bytes
+    /// at the `[r, g, b]` offsets out of every `pixel_size` bytes
+    fn repack(
+        &mut self,
+        data: &[u8],
+        (width, height): (u16, u16),
+        stride: usize,
+        pixel_size: usize,
+        [r, g, b]: [usize; 3],
+    ) -> ImageRef<'_> {
+        self.buffer.resize((width, height));
+
+        for (j, row) in (0..height).zip(data.chunks(stride)) {
+            let pixels = row.chunks_exact(pixel_size);
+            for (pixel, bytes) in self.buffer.row_mut(j).iter_mut().zip(pixels) {
+                *pixel = Rgb::new(bytes[r], bytes[g], bytes[b]);
+            }
+        }
+
+        self.buffer.as_image()
+    }
+
+    /// like `repack`, blending every pixel over the matte by the alpha byte at offset `a`
+    fn composite(
+        &mut self,
+        data: &[u8],
+        (width, height): (u16, u16),
+        stride: usize,
+        [r, g, b, a]: [usize; 4],
+    ) -> ImageRef<'_> {
+        let blend = |color: u8, matte: u8, alpha: u8| {
+            let (color, matte, alpha) = (u16::from(color), u16::from(matte), u16::from(alpha));
+            ((color * alpha + matte * (255 - alpha) + 127) / 255) as u8
+        };
+
+        self.buffer.resize((width, height));
+        let (matte, checker) = (self.matte, self.checker);
+        for (j, row) in (0..height).zip(data.chunks(stride)) {
+            let pixels = row.chunks_exact(4);
+            for ((i, pixel), bytes) in (0..).zip(self.buffer.row_mut(j)).zip(pixels) {
+                let behind = matte.map_or(Rgb::new(0, 0, 0), |matte| matte.behind((i, j), checker));
+                let alpha = bytes[a];
+                *pixel = Rgb::new(
+                    blend(bytes[r], behind.r, alpha),
+                    blend(bytes[g], behind.g, alpha),
+                    blend(bytes[b], behind.b, alpha),
+                );
+            }
+        }
+
+        self.buffer.as_image()
+    }
+
+    fn convert_i420(
+        &mut self,
+        frame: &VideoFrameRef<&gst::BufferRef>,
+        (width, height): (u16, u16),
+    ) -> Option<ImageRef<'_>> {
+        // limited range is assumed, it is what pretty much all video uses
+        let coefficients = match frame.info().colorimetry().matrix() {
+            VideoColorMatrix::Bt709 => Coefficients::BT709,
+            _ => Coefficients::BT601,
+        };
+
+        let strides = frame.plane_stride();
+        let stride = |plane: usize| usize::try_from(*strides.get(plane)?).ok();
+        let (luma_stride, cb_stride, cr_stride) = (stride(0)?, stride(1)?, stride(2)?);
+        let (luma, cb, cr) = (
+            frame.plane_data(0).ok()?,
+            frame.plane_data(1).ok()?,
+            frame.plane_data(2).ok()?,
+        );
+
+        let (luma_width, chroma_width) = (usize::from(width), usize::from(width).div_ceil(2));
+
+        self.buffer.resize((width, height));
+        for j in 0..height {
+            let (luma_j, chroma_j) = (usize::from(j), usize::from(j / 2));
+            yuv::convert_row(
+                coefficients,
+                plane_row(luma, luma_stride, luma_j, luma_width)?,
+                plane_row(cb, cb_stride, chroma_j, chroma_width)?,
+                plane_row(cr, cr_stride, chroma_j, chroma_width)?,
+                self.buffer.row_mut(j),
+            );
+        }
+
+        Some(self.buffer.as_image())
+    }
+
+    /// P010 keeps its 10 bits at the top of every sample with Cb and Cr interleaved,
+    /// I420_10LE at the bottom with a plane each
+    fn convert_10bit(
+        &mut self,
+        frame: &VideoFrameRef<&gst::BufferRef>,
+        (width, height): (u16, u16),
+    ) -> Option<ImageRef<'_>> {
+        let colorimetry = frame.info().colorimetry();
+        let (transfer, matrix) = (colorimetry.transfer(), colorimetry.matrix());
+        if !self
+            .tone_mapper
+            .as_ref()
+            .is_some_and(|mapper| mapper.fits(transfer, matrix, self.tone_map))
+        {
+            self.tone_mapper = Some(ToneMapper::new(transfer, matrix, self.tone_map));
+        }
+        let mapper = self.tone_mapper.as_ref()?;
+
+        let interleaved = frame.format() == VideoFormat::P01010le;
+        let planes = match interleaved {
+            true => 2,
+            false => 3,
+        };
+        let strides = frame.plane_stride();
+        let mut plane_rows = vec![];
+        for plane in 0..planes {
+            let stride = usize::try_from(*strides.get(plane)?).ok()?;
+            plane_rows.push((frame.plane_data(plane as u32).ok()?, stride));
+        }
+
+        let (luma_len, chroma_len) = (usize::from(width) * 2, usize::from(width).div_ceil(2) * 2);
+        self.buffer.resize((width, height));
+        for j in 0..height {
+            let (luma_j, chroma_j) = (usize::from(j), usize::from(j / 2));
+            let row = |plane: usize, j: usize, len: usize| {
+                let (data, stride) = plane_rows[plane];
+                plane_row(data, stride, j, len)
+            };
+
+            let luma = row(0, luma_j, luma_len)?;
+            let out = self.buffer.row_mut(j);
+            if interleaved {
+                let chroma = row(1, chroma_j, chroma_len * 2)?;
+                for (i, pixel) in out.iter_mut().enumerate() {
+                    let c = i / 2 * 2;
+                    *pixel = mapper.map(
+                        sample(luma, i)? >> 6,
+                        sample(chroma, c)? >> 6,
+                        sample(chroma, c + 1)? >> 6,
+                    );
+                }
+            } else {
+                let (cb, cr) = (row(1, chroma_j, chroma_len)?, row(2, chroma_j, chroma_len)?);
+                for (i, pixel) in out.iter_mut().enumerate() {
+                    *pixel = mapper.map(
+                        sample(luma, i)? & 0x3ff,
+                        sample(cb, i / 2)? & 0x3ff,
+                        sample(cr, i / 2)? & 0x3ff,
+                    );
+                }
+            }
+        }
+
+        Some(self.buffer.as_image())
+    }
+
+    /// gives up on formats outside of [`formats`]
+    pub fn unpack<'a>(
+        &'a mut self,
+        frame: &'a VideoFrameRef<&gst::BufferRef>,
+    ) -> Option<ImageRef<'a>> {
+        let (width, height) = (frame.width(), frame.height());
+        let size = (u16::try_from(width).ok()?, u16::try_from(height).ok()?);
+        let stride = usize::try_from(*frame.plane_stride().first()?)
+            .ok()
+            .filter(|&stride| stride > 0)?;
+        let data = frame.plane_data(0).ok()?;
+
+        let row_len = usize::from(size.0) * 3;
+        match frame.format() {
+            // rows without padding can be used as they are
+            VideoFormat::Rgb if stride == row_len => {
+                let data = data.get(..row_len * usize::from(size.1))?;
+                ImageRef::from_buffer(width, height, data)
+            }
+            VideoFormat::Rgb => Some(self.repack(data, size, stride, 3, [0, 1, 2])),
+            VideoFormat::Bgr => Some(self.repack(data, size, stride, 3, [2, 1, 0])),
+            VideoFormat::Rgbx => Some(self.repack(data, size, stride, 4, [0, 1, 2])),
+            VideoFormat::Bgrx => Some(self.repack(data, size, stride, 4, [2, 1, 0])),
+            VideoFormat::Rgba => Some(self.composite(data, size, stride, [0, 1, 2, 3])),
+            VideoFormat::Bgra => Some(self.composite(data, size, stride, [2, 1, 0, 3])),
+            VideoFormat::Argb => Some(self.composite(data, size, stride, [1, 2, 3, 0])),
+            VideoFormat::Abgr => Some(self.composite(data, size, stride, [3, 2, 1, 0])),
+            VideoFormat::I420 => self.convert_i420(frame, size),
+            VideoFormat::P01010le | VideoFormat::I42010le => self.convert_10bit(frame, size),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FrameUnpacker;
+    use crate::terminal_sink::background::Background;
+    use gst_video::{VideoFormat, VideoFrameRef, VideoInfo};
+    use rgb::Rgb;
+
+    /// a 2x2 frame with a white column and a black one
+    fn columns(format: VideoFormat) -> (gst::Buffer, VideoInfo) {
+        let info = VideoInfo::builder(format, 2, 2).build().unwrap();
+        let mut buffer = gst::Buffer::with_size(info.size()).unwrap();
+        let mut frame =
+            VideoFrameRef::from_buffer_ref_writable(buffer.get_mut().unwrap(), &info).unwrap();
+        let shift = match format {
+            VideoFormat::P01010le => 6,
+            _ => 0,
+        };
+        for plane in 0..frame.n_planes() {
+            let stride = usize::try_from(frame.plane_stride()[plane as usize]).unwrap();
+            let samples: &[u16] = match plane {
+                0 => &[940, 64],
+                _ => &[512, 512],
+            };
+            for row in frame
+                .plane_data_mut(plane)
+                .unwrap()
+                .chunks_exact_mut(stride)
+            {
+                for (bytes, sample) in row.chunks_exact_mut(2).zip(samples) {
+                    bytes.copy_from_slice(&(sample << shift).to_le_bytes());
+                }
+            }
+        }
+        drop(frame);
+        (buffer, info)
+    }
+
+    #[test]
+    fn ten_bit_formats_convert_alike() {
+        gst::init().unwrap();
+        for format in [VideoFormat::P01010le, VideoFormat::I42010le] {
+            let (buffer, info) = columns(format);
+            let frame = VideoFrameRef::from_buffer_ref_readable(buffer.as_ref(), &info).unwrap();
+            let mut unpacker = FrameUnpacker::new();
+            let image = unpacker.unpack(&frame).unwrap();
+            for j in 0..2 {
+                assert_eq!(
+                    image.row(j),
+                    [Rgb::new(255, 255, 255), Rgb::new(0, 0, 0)],
+                    "{format:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn alpha_blends_over_the_matte() {
+        let mut unpacker = FrameUnpacker::new();
+        unpacker.set_matte(Some(Background::Color(Rgb::new(0, 0, 200))));
+
+        // opaque red, then fully transparent, then half see through white
+        let data = [255, 0, 0, 255, 9, 9, 9, 0, 255, 255, 255, 128];
+        let image = unpacker.composite(&data, (3, 1), 12, [0, 1, 2, 3]);
+        assert_eq!(
+            image.row(0),
+            [
+                Rgb::new(255, 0, 0),
+                Rgb::new(0, 0, 200),
+                Rgb::new(128, 128, 228)
+            ]
+        );
+    }
+}
@@ -0,0 +1,391 @@
+//! What the fuzz targets in `fuzz/` drive, the parts of rendering doing unchecked indexing
+//! on sizes that come from outside, and a terminal that understands what the renderer writes
+//! so a frame drawn as a diff can be checked against the same frame drawn from scratch
+use crate::terminal_sink::diff::{Glyphs, Palette, RenderedFrame};
+use crate::terminal_sink::resize::{ImageRef, PodMatrix, Resizer};
+use std::num::NonZero;
+
+/// `ImageRef::from_buffer` on whatever comes in, reading back every pixel of what it accepts
+pub fn image_from_buffer(width: u32, height: u32, buffer: &[u8]) {
+    let Some(image) = ImageRef::from_buffer(width, height, buffer) else {
+        return;
+    };
+
+    assert_eq!(image.size(), (width, height));
+    for j in 0..height {
+        let row = image.row(j);
+        assert_eq!(row.len(), width as usize);
+        for (i, &rgb) in (0..).zip(row) {
+            // Safety: from_buffer checked that there are width * height pixels
+            assert!(unsafe { image.get_pixel_unchecked(i, j) } == rgb);
+        }
+    }
+}
+
+/// resizes a matrix to every size in turn, the cells it grows by have to come out zeroed
+pub fn resize_matrix(sizes: &[(u16, u16)]) {
+    let mut matrix = PodMatrix::<u8>::new();
+    for &size in sizes {
+        let before = matrix.as_mut_slice().len();
+        matrix.resize(size);
+
+        let (width, height) = size;
+        let cells = matrix.as_mut_slice();
+        assert_eq!(cells.len(), usize::from(width) * usize::from(height));
+        assert!(cells.iter().skip(before).all(|&cell| cell == 0));
+        cells.fill(0xAB);
+
+        for j in 0..height {
+            assert_eq!(matrix.row(j).len(), usize::from(width));
+            // Safety: the matrix is this big
+            if let Some(i) = width.checked_sub(1) {
+                assert_eq!(*unsafe { matrix.get_mut_unchecked(i, j) }, 0xAB);
+            }
+        }
+    }
+}
+
+/// scales an image of `width` by `height` pixels to `to`, if `pixels` are that many
+pub fn resize_image(width: u32, height: u32, pixels: &[u8], to: (u16, u16)) {
+    let Some(image) = ImageRef::from_buffer(width, height, pixels) else {
+        return;
+    };
+
+    let mut resizer = Resizer::new();
+    // a second time with the buffers already there
+    for _ in 0..2 {
+        let resized = resizer.resize(image, to);
+        let (width, height) = resized.size();
+        if width != 0 && height != 0 {
+            assert_eq!((width, height), (to.0.into(), to.1.into()));
+        }
+        for j in 0..height {
+            assert_eq!(resized.row(j).len(), width as usize);
+        }
+    }
+}
+
+/// A color as the terminal was told it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Color {
+    Default,
+    Rgb(u8, u8, u8),
+    Indexed(u8),
+}
+
+/// What a cell looks like, whichever glyph it got drawn with
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Look {
+    top: Color,
+    bottom: Color,
+}
+
+const BLANK: Look = Look {
+    top: Color::Default,
+    bottom: Color::Default,
+};
+
+/// Just enough of a terminal to follow what the renderer writes, panicking on anything else
+#[derive(Debug)]
+pub struct Screen {
+    size: (u16, u16),
+    cells: Vec<Look>,
+    cursor: (u16, u16),
+    /// the cursor is past the last column, the next glyph goes on the next line
+    wrap: bool,
+    fg: Color,
+    bg: Color,
+    /// what REP repeats
+    last: Option<char>,
+}
+
+impl PartialEq for Screen {
+    /// only what's on screen, not where the cursor or colors were left
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size && self.cells == other.cells
+    }
+}
+
+impl Screen {
+    pub fn new(size: (u16, u16)) -> Self {
+        Self {
+            size,
+            cells: vec![BLANK; usize::from(size.0) * usize::from(size.1)],
+            cursor: (0, 0),
+            wrap: false,
+            fg: Color::Default,
+            bg: Color::Default,
+            last: None,
+        }
+    }
+
+    fn go(&mut self, x: u16, y: u16) {
+        let (width, height) = self.size;
+        self.cursor = (
+            x.min(width.saturating_sub(1)),
+            y.min(height.saturating_sub(1)),
+        );
+        self.wrap = false;
+    }
+
+    fn print(&mut self, glyph: char) {
+        let (width, height) = self.size;
+        if self.wrap {
+            self.go(0, self.cursor.1.saturating_add(1));
+        }
+
+        let (fg, bg) = (self.fg, self.bg);
+        let look = match glyph {
+            '\u{2580}' => Look {
+                top: fg,
+                bottom: bg,
+            },
+            '\u{2584}' => Look {
+                top: bg,
+                bottom: fg,
+            },
+            '\u{2588}' => Look {
+                top: fg,
+                bottom: fg,
+            },
+            ' ' => Look {
+                top: bg,
+                bottom: bg,
+            },
+            glyph => panic!("the renderer drew {glyph:?}"),
+        };
+
+        let (x, y) = self.cursor;
+        if x < width && y < height {
+            self.cells[usize::from(y) * usize::from(width) + usize::from(x)] = look;
+        }
+        self.last = Some(glyph);
+        match x + 1 < width {
+            true => self.cursor.0 += 1,
+            false => self.wrap = true,
+        }
+    }
+
+    fn color(params: &mut impl Iterator<Item = u16>) -> Color {
+        let mut next = || u8::try_from(params.next().expect("a truncated color")).unwrap();
+        match next() {
+            2 => Color::Rgb(next(), next(), next()),
+            5 => Color::Indexed(next()),
+            kind => panic!("unknown color kind {kind}"),
+        }
+    }
+
+    fn sgr(&mut self, params: &[Option<u16>]) {
+        let mut params = params.iter().map(|param| param.unwrap_or(0));
+        while let Some(param) = params.next() {
+            match param {
+                0 => (self.fg, self.bg) = (Color::Default, Color::Default),
+                38 => self.fg = Self::color(&mut params),
+                48 => self.bg = Self::color(&mut params),
+                39 => self.fg = Color::Default,
+                49 => self.bg = Color::Default,
+                param => panic!("unknown SGR parameter {param}"),
+            }
+        }
+    }
+
+    fn csi(&mut self, params: &[Option<u16>], command: u8) {
+        let (x, y) = self.cursor;
+        let count = params.first().copied().flatten().unwrap_or(1).max(1);
+        match command {
+            b'H' => {
+                let column = params.get(1).copied().flatten().unwrap_or(1).max(1);
+                self.go(column - 1, count - 1);
+            }
+            b'A' => self.go(x, y.saturating_sub(count)),
+            b'B' => self.go(x, y.saturating_add(count)),
+            b'C' => self.go(x.saturating_add(count), y),
+            b'D' => self.go(x.saturating_sub(count), y),
+            b'G' => self.go(count - 1, y),
+            b'b' => {
+                let last = self.last.expect("REP before anything was drawn");
+                for _ in 0..count {
+                    self.print(last);
+                }
+            }
+            b'J' if params == [Some(2)] => self.cells.fill(BLANK),
+            b'X' => {
+                let start = usize::from(y) * usize::from(self.size.0) + usize::from(x);
+                let end = start + usize::from(count.min(self.size.0 - x));
+                let erased = Look {
+                    top: self.bg,
+                    bottom: self.bg,
+                };
+                self.cells[start..end].fill(erased);
+            }
+            b'm' => self.sgr(params),
+            command => panic!("unknown CSI {params:?} {:?}", char::from(command)),
+        }
+    }
+
+    /// applies everything written to the terminal
+    pub fn feed(&mut self, mut bytes: &[u8]) {
+        while let Some(&byte) = bytes.first() {
+            match byte {
+                0x1b => {
+                    assert_eq!(bytes.get(1), Some(&b'['), "an escape other than CSI");
+                    let end = bytes[2..]
+                        .iter()
+                        .position(|b| (0x40..=0x7e).contains(b))
+                        .expect("an unterminated CSI")
+                        + 2;
+                    let params = std::str::from_utf8(&bytes[2..end]).unwrap();
+                    let params = match params.is_empty() {
+                        true => vec![],
+                        false => params.split(';').map(|param| param.parse().ok()).collect(),
+                    };
+                    self.csi(&params, bytes[end]);
+                    bytes = &bytes[end + 1..];
+                }
+                b'\r' => {
+                    self.go(0, self.cursor.1);
+                    bytes = &bytes[1..];
+                }
+                _ => {
+                    let len = match byte {
+                        0..0x80 => 1,
+                        0xc0..0xe0 => 2,
+                        0xe0..0xf0 => 3,
+                        _ => 4,
+                    };
+                    let glyph =
+                        std::str::from_utf8(&bytes[..len.min(bytes.len())]).expect("broken utf-8");
+                    self.print(glyph.chars().next().unwrap());
+                    bytes = &bytes[len..];
+                }
+            }
+        }
+    }
+}
+
+/// How both frames get drawn
+#[derive(Debug, Copy, Clone)]
+pub struct DiffOptions {
+    pub offset: (u16, u16),
+    pub compact: bool,
+    pub repeat: bool,
+    pub threads: NonZero<usize>,
+    /// bits per channel kept
+    pub precision: u8,
+    pub palette: Palette,
+    pub glyphs: Glyphs,
+}
+
+/// draws `first` from scratch and then `second` as a diff against it, and checks the terminal
+/// ends up the same as drawing only `second` from scratch, both `width` by `height` pixels
+pub fn diff_matches_overwrite(
+    (width, height): (u32, u32),
+    first: &[u8],
+    second: &[u8],
+    options: DiffOptions,
+) {
+    let (Some(first), Some(second)) = (
+        ImageRef::from_buffer(width, height, first),
+        ImageRef::from_buffer(width, height, second),
+    ) else {
+        return;
+    };
+    let (Ok(cells), Ok(rows)) = (u16::try_from(width), u16::try_from(height.div_ceil(2))) else {
+        return;
+    };
+
+    let (x, y) = options.offset;
+    let (Some(columns), Some(screen_rows)) = (x.checked_add(cells), y.checked_add(rows)) else {
+        return;
+    };
+    let size = (columns, screen_rows.max(1));
+
+    let renderer = || {
+        let mut frame = RenderedFrame::new(options.compact, options.repeat, options.threads);
+        frame.set_precision(options.precision);
+        frame.set_style(options.palette, options.glyphs);
+        frame
+    };
+    let mut output = vec![];
+
+    let mut diffed = Screen::new(size);
+    let mut frame = renderer();
+    frame.render(first, true, options.offset, columns, &mut output);
+    diffed.feed(&output);
+    output.clear();
+    frame.render(second, false, options.offset, columns, &mut output);
+    diffed.feed(&output);
+
+    let mut overwritten = Screen::new(size);
+    output.clear();
+    renderer().render(second, true, options.offset, columns, &mut output);
+    overwritten.feed(&output);
+
+    assert_eq!(diffed, overwritten);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn noise(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn screen_follows_the_renderer() {
+        let mut screen = Screen::new((4, 2));
+        screen.feed(b"\x1b[2;2H\x1b[38;2;1;2;3;48;5;16m\xe2\x96\x80\x1b[2b");
+
+        let look = Look {
+            top: Color::Rgb(1, 2, 3),
+            bottom: Color::Indexed(16),
+        };
+        assert_eq!(screen.cells[4..], [BLANK, look, look, look]);
+    }
+
+    #[test]
+    fn diffs_draw_what_overwriting_does() {
+        let sizes = [(1, 1), (5, 3), (40, 9), (70, 4)];
+        let styles = [
+            (Palette::Truecolor, Glyphs::Blocks),
+            (Palette::Xterm256, Glyphs::Blocks),
+            (Palette::Truecolor, Glyphs::Spaces),
+        ];
+
+        for (seed, (width, height)) in (0..).zip(sizes) {
+            let len = width as usize * height as usize * 3;
+            let first = noise(seed, len);
+            // most of the frame stays the same, the rest changes in runs and gaps
+            let mut second = first.clone();
+            for (i, byte) in noise(seed + 100, len).into_iter().enumerate() {
+                if byte < 60 {
+                    second[i] = byte;
+                }
+            }
+
+            for (palette, glyphs) in styles {
+                for (compact, threads) in [(false, 1), (true, 1), (false, 3)] {
+                    let options = DiffOptions {
+                        offset: (seed as u16, 1),
+                        compact,
+                        repeat: true,
+                        threads: NonZero::new(threads).unwrap(),
+                        precision: 5,
+                        palette,
+                        glyphs,
+                    };
+                    diff_matches_overwrite((width, height), &first, &second, options);
+                }
+            }
+        }
+    }
+}
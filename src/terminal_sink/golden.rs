@@ -0,0 +1,154 @@
+//! The escape sequences a [`TerminalRenderer`] draws frames with, compared byte for byte against
+//! the files in `golden/`, `BLESS=1 cargo test` writes what comes out now over them after a change
+//! that's meant to alter it
+use crate::flag;
+use crate::terminal_sink::TerminalRenderer;
+use gst_video::{VideoFormat, VideoFrameRef, VideoInfo};
+use rgb::Rgb;
+use std::num::NonZero;
+use std::path::PathBuf;
+
+const R: Rgb<u8> = Rgb::new(248, 0, 0);
+const G: Rgb<u8> = Rgb::new(0, 248, 0);
+const B: Rgb<u8> = Rgb::new(0, 0, 248);
+const W: Rgb<u8> = Rgb::new(248, 248, 248);
+const K: Rgb<u8> = Rgb::new(0, 0, 0);
+
+/// One frame handed to the renderer, colors that already are what the default precision keeps
+struct Step<'a> {
+    /// rows of pixels, two to a row of cells
+    pixels: &'a [&'a [Rgb<u8>]],
+    overwrite: bool,
+}
+
+/// an RGB video frame of `pixels`
+fn video_frame(pixels: &[&[Rgb<u8>]]) -> (gst::Buffer, VideoInfo) {
+    let (width, height) = (pixels[0].len() as u32, pixels.len() as u32);
+    let info = VideoInfo::builder(VideoFormat::Rgb, width, height)
+        .build()
+        .unwrap();
+    let mut buffer = gst::Buffer::with_size(info.size()).unwrap();
+    let mut frame =
+        VideoFrameRef::from_buffer_ref_writable(buffer.get_mut().unwrap(), &info).unwrap();
+    let stride = usize::try_from(frame.plane_stride()[0]).unwrap();
+    let data = frame.plane_data_mut(0).unwrap();
+    for (row, pixels) in data.chunks_exact_mut(stride).zip(pixels) {
+        row[..pixels.len() * 3].copy_from_slice(bytemuck::cast_slice(pixels));
+    }
+    drop(frame);
+    (buffer, info)
+}
+
+/// draws every step one after the other on a terminal `size` cells big, the frame taking up
+/// `fraction` of it, everything from unpacking the frame on the same way every time,
+/// on a single thread and without a pipeline, handing back what the last one wrote
+///
+/// the frames fit the space they get exactly, so they're drawn as they are
+fn render(steps: &[Step], size: (u16, u16), fraction: f64) -> Vec<u8> {
+    gst::init().unwrap();
+    let mut renderer = TerminalRenderer::new(false, NonZero::<usize>::MIN);
+    renderer.set_size_fraction(fraction);
+    let mut output = vec![];
+
+    for step in steps {
+        let (buffer, info) = video_frame(step.pixels);
+        let frame = VideoFrameRef::from_buffer_ref_readable(buffer.as_ref(), &info).unwrap();
+        let drawn = renderer
+            .render(&frame, size, step.overwrite, None)
+            .expect("the frame unpacks");
+        output.clone_from(drawn);
+    }
+    output
+}
+
+fn check(name: &str, output: &[u8]) {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/terminal_sink/golden")
+        .join(format!("{name}.ans"));
+    if flag("BLESS", false) {
+        std::fs::write(&file, output).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read(&file)
+        .unwrap_or_else(|err| panic!("no golden file at {}: {err}", file.display()));
+    // escaped, so a mismatch shows where it went wrong
+    assert_eq!(
+        output.escape_ascii().to_string(),
+        expected.escape_ascii().to_string(),
+        "{name} doesn't match its golden file",
+    );
+}
+
+#[test]
+fn overwrite() {
+    let pixels: &[&[_]] = &[&[R, G], &[B, B]];
+    let output = render(
+        &[Step {
+            pixels,
+            overwrite: true,
+        }],
+        (2, 1),
+        1.0,
+    );
+    check("overwrite", &output);
+}
+
+#[test]
+fn repeats_at_an_offset() {
+    let pixels: &[&[_]] = &[&[R, R, R, R], &[K, K, K, K]];
+    let output = render(
+        &[Step {
+            pixels,
+            overwrite: true,
+        }],
+        // 4 of the 10 columns and 1 of the 3 rows, centered
+        (10, 3),
+        0.4,
+    );
+    check("offset", &output);
+}
+
+#[test]
+fn odd_height() {
+    let pixels: &[&[_]] = &[&[R, G], &[B, W], &[W, R]];
+    let output = render(
+        &[Step {
+            pixels,
+            overwrite: true,
+        }],
+        (2, 2),
+        1.0,
+    );
+    check("odd_height", &output);
+}
+
+#[test]
+fn diff_against_the_last_frame() {
+    let steps = [
+        Step {
+            pixels: &[&[R, R, R, R], &[K, K, K, K]],
+            overwrite: true,
+        },
+        Step {
+            pixels: &[&[R, G, R, R], &[K, K, K, K]],
+            overwrite: false,
+        },
+    ];
+    check("diff", &render(&steps, (4, 1), 1.0));
+}
+
+#[test]
+fn diff_skips_unchanged_cells() {
+    let steps = [
+        Step {
+            pixels: &[&[R; 8], &[K; 8]],
+            overwrite: true,
+        },
+        Step {
+            pixels: &[&[R, G, R, G, R, R, R, R], &[K; 8]],
+            overwrite: false,
+        },
+    ];
+    check("diff_gap", &render(&steps, (8, 1), 1.0));
+}
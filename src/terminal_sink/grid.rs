@@ -0,0 +1,29 @@
+//! Plain data describing what the renderer put on the terminal,
+//! for tools that want the frames without parsing escape sequences.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GridCell {
+    pub fg: [u8; 3],
+    pub bg: [u8; 3],
+    pub ch: char,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellGrid {
+    pub width: u16,
+    pub height: u16,
+    /// the cells, row by row
+    pub cells: Vec<GridCell>,
+}
+
+/// A rendered frame and where it sits on the terminal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frame {
+    /// presentation timestamp in nanoseconds
+    pub pts: Option<u64>,
+    /// column and row of the top left cell
+    pub offset: (u16, u16),
+    pub grid: CellGrid,
+}
@@ -0,0 +1,213 @@
+//! 10 bit video down to the 8 bit BT.709 the terminal shows,
+//! HDR video getting its PQ or HLG light tone mapped and its BT.2020 colors brought into gamut
+use gst_video::{VideoColorMatrix, VideoTransferFunction};
+use rgb::Rgb;
+
+/// How highlights brighter than SDR white get squeezed in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ToneMap {
+    /// the filmic curve from Uncharted 2, keeps some contrast in the highlights
+    #[default]
+    Hable,
+    /// a gentle curve, a little flat
+    Reinhard,
+    /// everything above SDR white is white
+    Clip,
+}
+
+/// what SDR white is in nits, it becomes 1.0 in linear light
+const REFERENCE_WHITE: f32 = 203.0;
+/// the brightest the content is taken to get, relative to SDR white, most HDR is mastered for 1000 nits
+const PEAK: f32 = 1000.0 / REFERENCE_WHITE;
+/// entries of the signal to linear light table, one per 10 bit code
+const INPUT_STEPS: usize = 1024;
+/// entries of the linear light to 8 bit signal table, finer since the darks are steep
+const OUTPUT_STEPS: usize = 4096;
+
+/// linear BT.2020 light to linear BT.709 light, rows are the outputs
+const BT2020_TO_BT709: [[f32; 3]; 3] = [
+    [1.6605, -0.5876, -0.0728],
+    [-0.1246, 1.1329, -0.0083],
+    [-0.0182, -0.1006, 1.1187],
+];
+
+fn hable(x: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+impl ToneMap {
+    /// linear light relative to SDR white into 0 to 1, `PEAK` ending up at 1
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            ToneMap::Hable => hable(x) / hable(PEAK),
+            ToneMap::Reinhard => x * (1.0 + x / (PEAK * PEAK)) / (1.0 + x),
+            ToneMap::Clip => x.min(1.0),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Transfer {
+    /// SMPTE ST 2084, absolute light up to 10000 nits
+    Pq,
+    /// ARIB STD-B67, relative light
+    Hlg,
+    /// anything else, drawn as it is
+    Sdr,
+}
+
+impl Transfer {
+    fn of(transfer: VideoTransferFunction) -> Self {
+        match transfer {
+            VideoTransferFunction::Smpte2084 => Transfer::Pq,
+            VideoTransferFunction::AribStdB67 => Transfer::Hlg,
+            _ => Transfer::Sdr,
+        }
+    }
+
+    /// the signal from 0 to 1 as linear light relative to SDR white
+    fn linear(self, signal: f32) -> f32 {
+        match self {
+            Transfer::Pq => {
+                const M1: f32 = 2610.0 / 16384.0;
+                const M2: f32 = 2523.0 / 4096.0 * 128.0;
+                const C1: f32 = 3424.0 / 4096.0;
+                const C2: f32 = 2413.0 / 4096.0 * 32.0;
+                const C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+                let e = signal.powf(1.0 / M2);
+                let nits = ((e - C1).max(0.0) / (C2 - C3 * e)).powf(1.0 / M1) * 10000.0;
+                nits / REFERENCE_WHITE
+            }
+            Transfer::Hlg => {
+                const A: f32 = 0.1788328;
+                const B: f32 = 0.2846689;
+                const C: f32 = 0.5599107;
+
+                let scene = match signal <= 0.5 {
+                    true => signal * signal / 3.0,
+                    false => (((signal - C) / A).exp() + B) / 12.0,
+                };
+                // the system gamma of a 1000 nit display, which puts 75% signal at SDR white
+                scene.powf(1.2) * PEAK
+            }
+            Transfer::Sdr => signal,
+        }
+    }
+}
+
+/// the sRGB curve terminals show colors with
+fn encode(linear: f32) -> f32 {
+    match linear <= 0.0031308 {
+        true => linear * 12.92,
+        false => 1.055 * linear.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+/// the entry of a table with `steps` entries for a value from 0 to 1
+fn step(value: f32, steps: usize) -> usize {
+    (value.clamp(0.0, 1.0) * (steps - 1) as f32 + 0.5) as usize
+}
+
+/// Turns 10 bit YCbCr pixels into 8 bit RGB, for the transfer and matrix of one stream
+pub struct ToneMapper {
+    transfer: Transfer,
+    bt2020: bool,
+    curve: ToneMap,
+    to_linear: Vec<f32>,
+    to_signal: Vec<u8>,
+}
+
+impl ToneMapper {
+    pub fn new(transfer: VideoTransferFunction, matrix: VideoColorMatrix, curve: ToneMap) -> Self {
+        let transfer = Transfer::of(transfer);
+        let to_linear = (0..INPUT_STEPS)
+            .map(|i| transfer.linear(i as f32 / (INPUT_STEPS - 1) as f32))
+            .collect();
+        let to_signal = (0..OUTPUT_STEPS)
+            .map(|i| (encode(i as f32 / (OUTPUT_STEPS - 1) as f32) * 255.0 + 0.5) as u8)
+            .collect();
+
+        Self {
+            transfer,
+            bt2020: matrix == VideoColorMatrix::Bt2020,
+            curve,
+            to_linear,
+            to_signal,
+        }
+    }
+
+    /// whether the tables still fit a stream with this transfer and matrix
+    pub fn fits(
+        &self,
+        transfer: VideoTransferFunction,
+        matrix: VideoColorMatrix,
+        curve: ToneMap,
+    ) -> bool {
+        self.transfer == Transfer::of(transfer)
+            && self.bt2020 == (matrix == VideoColorMatrix::Bt2020)
+            && self.curve == curve
+    }
+
+    /// one pixel of limited range 10 bit YCbCr
+    pub fn map(&self, y: u16, cb: u16, cr: u16) -> Rgb<u8> {
+        let y = (f32::from(y) - 64.0) / 876.0;
+        let cb = (f32::from(cb) - 512.0) / 896.0;
+        let cr = (f32::from(cr) - 512.0) / 896.0;
+
+        // HDR is always BT.2020, SDR mostly BT.709
+        let [red_cr, green_cb, green_cr, blue_cb] = match self.bt2020 {
+            true => [1.4746, 0.16455, 0.57135, 1.8814],
+            false => [1.5748, 0.1873, 0.4681, 1.8556],
+        };
+        let signal = [
+            y + red_cr * cr,
+            y - green_cb * cb - green_cr * cr,
+            y + blue_cb * cb,
+        ];
+
+        if self.transfer == Transfer::Sdr {
+            let [r, g, b] = signal.map(|c| (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8);
+            return Rgb::new(r, g, b);
+        }
+
+        let light = signal.map(|c| self.to_linear[step(c, INPUT_STEPS)]);
+        let light = BT2020_TO_BT709
+            .map(|row| (row[0] * light[0] + row[1] * light[1] + row[2] * light[2]).max(0.0));
+
+        // scaling all three by the brightest keeps the hue
+        let brightest = light[0].max(light[1]).max(light[2]);
+        let scale = match brightest > 0.0 {
+            true => self.curve.apply(brightest) / brightest,
+            false => 0.0,
+        };
+        let [r, g, b] = light.map(|c| self.to_signal[step(c * scale, OUTPUT_STEPS)]);
+        Rgb::new(r, g, b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn curves_end_at_the_peak() {
+        for curve in [ToneMap::Hable, ToneMap::Reinhard, ToneMap::Clip] {
+            assert!(curve.apply(0.0).abs() < 1e-6);
+            assert!((curve.apply(PEAK) - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn hlg_reference_white_is_sdr_white() {
+        assert!((Transfer::Hlg.linear(0.75) - 1.0).abs() < 0.02);
+        // 10000 nits at full signal
+        assert!((Transfer::Pq.linear(1.0) * REFERENCE_WHITE - 10000.0).abs() < 1.0);
+    }
+}
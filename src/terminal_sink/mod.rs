@@ -1,40 +1,109 @@
+use crate::cancel::CancellationToken;
 use crate::term_size::TerminalSizeUpdater;
-use crate::terminal_sink::diff::RenderedFrame;
-use crate::terminal_sink::resize::{ImageRef, Resizer};
-use crate::terminal_sink::video_pipe::{SampleConsumer, SampleProducer, SampleReloader};
-use crate::{QuitHandler, flag, resize_image};
+use crate::terminal::{self, Terminal};
+use crate::terminal_sink::bandwidth::BandwidthLimiter;
+use crate::terminal_sink::cast::CastRecorder;
+use crate::terminal_sink::clock::Clock;
+use crate::terminal_sink::decode_scale::ScaleTarget;
+use crate::terminal_sink::diff::PRECISION_RANGE;
+use crate::terminal_sink::filters::ChainSpec;
+use crate::terminal_sink::server::Server;
+use crate::terminal_sink::stats::{FrameDropper, Meter};
+use crate::terminal_sink::sync::SyncMonitor;
+use crate::terminal_sink::video_pipe::{Pulled, SampleConsumer, SampleProducer, SampleReloader};
+use crate::terminal_sink::writer::{Output, OutputWriter};
+use crate::{QuitHandler, ansi_dump, flag};
 use glib::object::Cast;
 use gst::element_error;
 use gst::prelude::ElementExtManual;
 use gst_app::{AppSink, AppSinkCallbacks};
-use gst_video::{VideoFormat, VideoInfo};
+use gst_video::{VideoFrameRef, VideoInfo};
+use parking_lot::Mutex;
 use std::cell::Cell;
+#[cfg(feature = "serde")]
 use std::io::Write;
-use std::os::fd::{AsFd, AsRawFd};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::num::NonZero;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::thread;
-use std::time::Duration;
-use termion::raw::IntoRawMode;
-use termion::screen::IntoAlternateScreen;
+use std::time::{Duration, Instant};
 
+mod background;
+mod bandwidth;
+mod cast;
+mod clock;
+mod color_filter;
+mod decode_scale;
 mod diff;
+mod edges;
+pub mod filters;
+mod frame;
+#[cfg(any(test, feature = "fuzzing"))]
+#[doc(hidden)]
+pub mod fuzzing;
+#[cfg(test)]
+mod golden;
+#[cfg(feature = "serde")]
+pub mod grid;
+mod hdr;
+mod overlay;
+mod pixel;
+mod renderer;
 mod resize;
+mod screenshot;
+mod server;
+mod simd;
+mod spectrum;
+mod stats;
+mod symbols;
+mod sync;
+pub mod termvideosink;
+mod thumbnail;
 mod video_pipe;
+mod writer;
+mod yuv;
 
+pub use background::Background;
+pub use bandwidth::Bandwidth;
+pub use clock::Corner;
+pub use color_filter::ColorFilter;
+pub use decode_scale::DecodeScale;
+pub use diff::{DEFAULT_PRECISION, Glyphs, Palette, RenderedFrame};
+pub use hdr::ToneMap;
+pub use overlay::{Backdrop, Layer, Overlay, TextStyle};
+pub use pixel::{PixelTransform, PostFilter};
+pub use renderer::{CellAspect, RenderMode, RenderTarget, StageTimings, TerminalRenderer};
+pub use resize::{ImageRef, Resizer, Scaler};
+pub use stats::RenderStats;
+pub use symbols::{CELL_PIXELS, Symbol, SymbolSet, matching_grid};
+pub use sync::SyncDebug;
+pub use video_pipe::QueueMode;
+
+/// where a rendered frame goes and what gets drawn on top of it
+struct FrameOutput<'a> {
+    writer: &'a OutputWriter,
+    message: Option<&'a str>,
+    /// drawn over everything else
+    overlay: Option<&'a [u8]>,
+    /// every frame gets a header saying when it shows, for `transcode`
+    dump: bool,
+    /// the first row frames are drawn from with `--inline`, rather than the whole screen
+    top: Option<u16>,
+    /// wrapped in a synchronized update, so the terminal never shows half a frame
+    synchronized: bool,
+    stats: &'a RenderStats,
+}
+
+/// how many bytes the frame took, `None` when the terminal didn't take it in time
 fn render_sample(
     sample: &gst::Sample,
     app_sink: &AppSink,
     term_size: (u16, u16),
     fresh_redraw: bool,
-    command_buffer: &mut Vec<u8>,
-    resizer: &mut Resizer,
-    last_frame: &mut RenderedFrame,
-    stdout: &mut dyn Write,
-) -> Result<(), ()> {
-    // make sure screen buffer is empty
-    command_buffer.clear();
-
+    renderer: &mut TerminalRenderer,
+    output: &FrameOutput,
+) -> Result<Option<usize>, ()> {
     let caps = sample.caps().ok_or_else(|| {
         element_error!(app_sink, gst::ResourceError::Failed, ("Sample has no caps"));
     })?;
@@ -50,62 +119,63 @@ fn render_sample(
             ("Failed to get buffer from appsink")
         );
     })?;
-    let buffer = buffer.map_readable().map_err(|err| {
+    let frame = VideoFrameRef::from_buffer_ref_readable(buffer, &video_info).map_err(|err| {
         element_error!(
             app_sink,
             gst::ResourceError::Failed,
-            ("Failed to map buffer readable; {err}")
+            ("Failed to map video frame readable; {err}")
         );
     })?;
 
-    let res = ImageRef::from_buffer(video_info.width(), video_info.height(), &buffer);
-
-    let image = res.ok_or_else(|| {
+    let command_buffer = match output.top {
+        Some(top) => renderer.render_rows(&frame, top, term_size, fresh_redraw, output.message),
+        None => renderer.render(&frame, term_size, fresh_redraw, output.message),
+    };
+    let command_buffer = command_buffer.ok_or_else(|| {
         element_error!(
             app_sink,
             gst::ResourceError::Failed,
             ("invalid video sample dimentions")
         );
     })?;
+    if let Some(overlay) = output.overlay {
+        command_buffer.extend_from_slice(overlay);
+    }
+    if output.synchronized {
+        let begin = terminal::BEGIN_SYNCHRONIZED_UPDATE.as_bytes();
+        command_buffer.splice(0..0, begin.iter().copied());
+        command_buffer.extend_from_slice(terminal::END_SYNCHRONIZED_UPDATE.as_bytes());
+    }
 
-    let pixels_available = {
-        let (width, height) = term_size;
-        (width, height.saturating_mul(2))
+    let write_error = |err: std::io::Error| {
+        element_error!(
+            app_sink,
+            gst::ResourceError::Write,
+            ("Failed to write to the terminal; {err}")
+        );
     };
 
-    let height_pixels_available = pixels_available.1;
-    let (term_width, term_height) = term_size;
-
-    //                                                                        -fill-
-    let (new_width, new_height) = resize_image::resize_dimensions::<false>(
-        video_info.width(),
-        video_info.height(),
-        term_width.into(),
-        height_pixels_available.into(),
-    );
-
-    let (new_width, new_height) = (new_width as u16, new_height as u16);
-
-    let resized = resizer.resize(image, (new_width, new_height));
-
-    let offset = (
-        (term_width - (new_width)) / 2,
-        (term_height - (new_height.div_ceil(2))) / 2,
-    );
-
-    last_frame.render(resized, fresh_redraw, offset, command_buffer);
+    let bytes = command_buffer.len();
+    output.stats.record_frame_size(bytes);
 
-    stdout.write_all(command_buffer).unwrap();
-    stdout.flush().unwrap();
+    if output.dump {
+        let pts = buffer
+            .pts()
+            .map_or(Duration::ZERO, |pts| Duration::from_nanos(pts.nseconds()));
+        let header = ansi_dump::frame_header(pts, command_buffer.len());
+        output
+            .writer
+            .write(header.as_bytes())
+            .map_err(write_error)?;
+    }
 
-    Ok(())
+    output
+        .writer
+        .submit_frame(command_buffer)
+        .map(|taken| taken.then_some(bytes))
+        .map_err(write_error)
 }
 
-// THE WHOLE THING IS NOT UNWIND SAFE
-
-#[cfg(not(test))]
-const _: () = assert!(cfg!(panic = "abort"));
-
 fn send_new_sample(
     pipe: SampleProducer,
     pull_sample: fn(&AppSink) -> Result<gst::Sample, glib::BoolError>,
@@ -143,7 +213,12 @@ struct DynamicSize {
 impl DynamicSize {
     const TAG_BIT: u64 = 1 << 63;
 
-    pub fn new(app_sink: AppSink, reloader: SampleReloader) -> Self {
+    pub fn new(
+        current: CurrentSink,
+        reloader: SampleReloader,
+        token: CancellationToken,
+        polling: ResizePolling,
+    ) -> Self {
         let size_cache = Arc::new(AtomicU64::new(0));
         let size_cache_clone = Arc::clone(&size_cache);
 
@@ -153,10 +228,13 @@ impl DynamicSize {
             size_cache_clone.store((num as u64) | Self::TAG_BIT, Ordering::Relaxed)
         };
 
-        let app_sink_clone = app_sink.clone();
         let size_cache_updater =
-            TerminalSizeUpdater::new(Duration::from_millis(280), move |new_size| {
-                if app_sink_clone.current_state() == gst::State::Paused {
+            TerminalSizeUpdater::new(polling.interval, polling.debounce, token, move |new_size| {
+                let paused = current
+                    .lock()
+                    .as_ref()
+                    .is_some_and(|sink| sink.current_state() == gst::State::Paused);
+                if paused {
                     let _ = reloader.reload_sample();
                 }
 
@@ -210,104 +288,1203 @@ impl TerminalSizeLoader for StaticSize {
     }
 }
 
-fn run_renderer_thread(consumer: SampleConsumer, app_sink: AppSink, size: Option<(u16, u16)>) {
-    let loader = match size {
-        Some(size) => (&StaticSize::new(size)) as &dyn TerminalSizeLoader,
-        None => &DynamicSize::new(app_sink.clone(), consumer.make_reloader()),
+/// clears the screen and says something is coming, with how far buffering got once it's known
+fn draw_loading(
+    writer: &OutputWriter,
+    (width, height): (u16, u16),
+    buffered: Option<u8>,
+) -> std::io::Result<()> {
+    let loading = match buffered {
+        Some(percent) => format!("Loading\u{2026} {percent}%"),
+        None => "Loading\u{2026}".to_string(),
     };
 
-    trait TTY: Write + AsFd + AsRawFd {}
-    impl<T: Write + AsFd + AsRawFd> TTY for T {}
+    let x = width.saturating_sub(loading.chars().count() as u16) / 2;
+    let screen = format!(
+        "{}{}{loading}",
+        terminal::CLEAR,
+        terminal::goto(x + 1, height / 2 + 1)
+    );
+    writer.write(screen.as_bytes())
+}
+
+/// name of the sink element every pipeline renders through
+pub const ELEMENT_NAME: &str = "terminal player";
 
-    fn make_tty<T: TTY>(tty: T) -> impl Write {
-        tty.into_raw_mode()
-            .expect("terminal needs to support raw terminal I/O mode")
-            .into_alternate_screen()
-            .expect("app should be ran on xterm compatible terminals")
+/// the sink element of the pipeline currently playing
+type CurrentSink = Arc<Mutex<Option<AppSink>>>;
+
+/// Where the renderer draws
+struct Screen {
+    output: Box<dyn Output>,
+    /// puts the terminal back the way it was when dropped
+    restore: Box<dyn Send>,
+    /// where the size comes from when it's not the terminal's or a fixed one, like a client's window
+    size: Option<Box<dyn TerminalSizeLoader + Send>>,
+}
+
+/// The files written next to the terminal, opened before it's taken over
+/// so a path that doesn't work gets reported instead of taking the player down mid playback
+#[derive(Default)]
+struct Recordings {
+    cast: Option<CastRecorder>,
+    #[cfg(feature = "serde")]
+    grid_dump: Option<std::io::BufWriter<std::fs::File>>,
+}
+
+impl Recordings {
+    fn open(options: &Options) -> Result<Self, crate::error::PlayerError> {
+        let open = crate::error::PlayerError::open;
+        let cast = match &options.record_cast {
+            Some(path) => Some(CastRecorder::create(path).map_err(open(path))?),
+            None => None,
+        };
+        #[cfg(feature = "serde")]
+        let grid_dump = match &options.grid_dump {
+            Some(path) => {
+                let file = std::fs::File::create(path).map_err(open(path))?;
+                Some(std::io::BufWriter::new(file))
+            }
+            None => None,
+        };
+        Ok(Self {
+            cast,
+            #[cfg(feature = "serde")]
+            grid_dump,
+        })
     }
+}
 
-    let tty: &mut dyn Write = if flag("NO_TTY", false) {
-        &mut std::io::stdout().lock()
-    } else if !flag("USE_STDOUT", false)
-        && let Ok(tty) = termion::get_tty()
-    {
-        &mut make_tty(tty)
-    } else {
-        &mut make_tty(std::io::stdout().lock())
+fn run_renderer_thread(
+    consumer: SampleConsumer,
+    current: CurrentSink,
+    token: CancellationToken,
+    Screen {
+        output,
+        restore,
+        size,
+    }: Screen,
+    options: Options,
+    recordings: Recordings,
+) {
+    let loader = match (&size, options.size) {
+        (Some(size), _) => &**size as &dyn TerminalSizeLoader,
+        (None, Some(size)) => &StaticSize::new(size),
+        (None, None) => &DynamicSize::new(
+            Arc::clone(&current),
+            consumer.make_reloader(),
+            token,
+            options.resize_polling,
+        ),
     };
 
-    // there will be a clear on the first fetch from the size cache
-    // so wait until first render before clearing
-    tty.write_all(termion::cursor::Hide.as_ref()).unwrap();
-    tty.flush().unwrap();
+    // a dump gets every frame, however long it takes
+    let dump = options.dump_ansi.is_some();
+    // clients of a server have terminals of their own, which never get suspended
+    let on_terminal = size.is_none();
+    let inline = terminal::inline::rows().filter(|_| on_terminal && !dump);
+
+    // frames that can't get out within this long are dropped and redrawn in full later
+    let output_deadline =
+        (!dump && !flag("NO_OUTPUT_DROP", false)).then_some(Duration::from_millis(100));
+    let mut writer = OutputWriter::new(output, output_deadline);
+    if let Some(cast) = recordings.cast {
+        writer.record_cast(cast);
+    }
+    if dump && let Some(size) = options.size {
+        let _ = writer.write(ansi_dump::header(size).as_bytes());
+    }
 
-    // 8mb default
-    let mut screen_buff = Vec::with_capacity(8 * 1024 * 1024);
-    let mut resizer = Resizer::new();
-    let mut last_frame = RenderedFrame::new();
+    let mut renderer = TerminalRenderer::with_repeat(
+        options.compact,
+        !flag("NO_REPEAT", false),
+        options.render_threads,
+    );
+    if let Some(filters) = &options.filters {
+        renderer.set_filters(filters.build());
+    }
+    renderer.set_cell_aspect(options.cell_aspect);
+    renderer.set_diff_threshold(options.diff_threshold);
+    renderer.set_max_cells(options.max_cells_per_frame);
+    // a dump has all the time in the world
+    let mut limiter = options
+        .max_bandwidth
+        .filter(|_| !dump)
+        .map(BandwidthLimiter::new);
+    renderer.set_style(options.palette, options.glyphs);
+    renderer.set_background(options.background);
+    renderer.set_tone_map(options.tone_map);
+    renderer.set_color_filter(options.color_filter);
+    renderer.set_symbol_set(options.symbols.clone());
+    if let Some(font_cell) = options.font_cell {
+        renderer.set_font_cell(font_cell);
+    }
+    renderer.set_render_mode(options.render_mode);
+    let mut dropper = FrameDropper::new(
+        !dump && !flag("NO_FRAME_DROP", false),
+        Arc::clone(&options.stats),
+    );
+    // a dump isn't drawn against any clock
+    let mut sync = options.sync_debug.filter(|_| !dump).map(SyncMonitor::new);
+
+    #[cfg(feature = "serde")]
+    let mut grid_dump = recordings.grid_dump;
+
+    // set when something other than a frame was drawn
+    let mut force_redraw = false;
+    // the loading splash is up, and no frame of the new pipeline is yet
+    let mut splash = false;
+    let mut thumbnails = TerminalRenderer::new(options.compact, NonZero::<usize>::MIN);
+    thumbnails.set_style(options.palette, options.glyphs);
+    let mut last_preview = false;
+    let mut meter = Meter::new();
+    let mut clock = Clock::default();
 
     'render_loop: loop {
-        let sample = match consumer.pull_sample() {
-            Ok(sample) => sample,
+        let sample = match consumer.pull() {
+            Ok(Pulled::Sample(sample)) => sample,
+            Ok(Pulled::Loading) => {
+                let mut size = loader.load().size;
+                if let Some(rows) = inline {
+                    size.1 = rows.count;
+                }
+                writer.resize(size);
+                if let Some(scale) = &options.scale {
+                    scale.resize(size);
+                }
+                // only frames go into a dump
+                // and drawing inline mustn't clear the screen around it
+                let suspended = on_terminal && options.settings.suspended();
+                let quiet = dump || suspended || inline.is_some();
+                if !quiet && draw_loading(&writer, size, None).is_err() {
+                    break 'render_loop;
+                }
+                splash = !quiet;
+                force_redraw = true;
+                continue 'render_loop;
+            }
+            Ok(Pulled::Buffering(percent)) => {
+                // before the first frame the splash says it, afterwards a message over the video
+                if splash {
+                    let mut size = loader.load().size;
+                    if let Some(rows) = inline {
+                        size.1 = rows.count;
+                    }
+                    if draw_loading(&writer, size, Some(percent)).is_err() {
+                        break 'render_loop;
+                    }
+                } else if !dump {
+                    // only changes come through, a stuck percent has to stay up
+                    let shown_for = match percent {
+                        100 => BUFFERING_SHOWN_FOR,
+                        _ => BUFFERING_STUCK_FOR,
+                    };
+                    let message = format!("buffering {percent}%");
+                    options.settings.show_message(message, shown_for);
+                    options.settings.redraw();
+                }
+                continue 'render_loop;
+            }
             Err(()) => break 'render_loop,
         };
 
-        let size_res = loader.load();
+        let Some(app_sink) = current.lock().clone() else {
+            continue 'render_loop;
+        };
+
+        // the screen is someone else's meanwhile, so it's all drawn again on resuming
+        if on_terminal && options.settings.suspended() {
+            force_redraw = true;
+            continue 'render_loop;
+        }
+
+        if options.settings.take_screenshot_request() {
+            screenshot::spawn(
+                sample.clone(),
+                options.screenshot_dir.clone(),
+                Arc::clone(&options.settings),
+                consumer.make_reloader(),
+            );
+        }
+
+        let mut message = options.settings.message();
+        if options.settings.stats_shown() {
+            let (decoded, dropped) = consumer.counts();
+            let stats = meter.describe(&options.stats, decoded, dropped);
+            message = Some(match message {
+                Some(message) => format!("{message}\n{stats}"),
+                None => stats,
+            });
+        }
+
+        // a redraw has to happen, late or not
+        let mut size_res = loader.load();
+        if let Some(rows) = inline {
+            size_res.size.1 = rows.count;
+        }
+        writer.resize(size_res.size);
+        if let Some(scale) = &options.scale {
+            scale.resize(size_res.size);
+        }
+        let precision = options.settings.precision();
+        match &limiter {
+            Some(limiter) => {
+                renderer.set_precision(limiter.precision(precision));
+                renderer.set_size_fraction(limiter.size());
+            }
+            None => renderer.set_precision(precision),
+        }
+        renderer.set_scaler(options.settings.scaler());
+        renderer.set_post_filter(options.settings.post_filter());
+        let corner = options.settings.clock();
+        renderer.set_corner_text(
+            corner.and_then(|corner| Some((clock.text(&sample, &app_sink)?, corner))),
+        );
+        let mut overlay = options
+            .settings
+            .spectrum()
+            .map(|levels| spectrum::overlay(&levels, renderer.area()))
+            .unwrap_or_default();
+        // the bottom row clicks seek along shows where the file is at along with the time
+        if corner.is_some()
+            && let Some(fraction) = clock.fraction()
+        {
+            overlay.extend(&clock::seek_bar(fraction, renderer.area()));
+        }
+        renderer.set_overlay(overlay);
 
+        // the thumbnail covers part of the frame, so it's drawn anew every time
+        let preview = options
+            .settings
+            .preview()
+            .filter(|_| inline.is_none())
+            .and_then(|sample| thumbnail::draw(&mut thumbnails, &sample, size_res.size));
+        let preview_cleared = last_preview && preview.is_none();
+        last_preview = preview.is_some();
+        let fresh_redraw =
+            size_res.changed || core::mem::take(&mut force_redraw) || preview_cleared;
+        if fresh_redraw {
+            tracing::debug!(
+                size = ?size_res.size,
+                resized = size_res.changed,
+                "drawing the frame in full"
+            );
+        }
+        if !fresh_redraw && dropper.should_drop(&sample, &app_sink) {
+            tracing::trace!(pts = ?sample.buffer().and_then(|buffer| buffer.pts()), "frame dropped");
+            continue 'render_loop;
+        }
+        // paused frames are still drawn, otherwise nothing would show what changed
+        if !fresh_redraw
+            && let Some(limiter) = &mut limiter
+            && app_sink.current_state() == gst::State::Playing
+            && limiter.should_skip(options.stats.frame_bytes(), Instant::now())
+        {
+            tracing::trace!("frame skipped to stay under the bandwidth");
+            continue 'render_loop;
+        }
+
+        let started = Instant::now();
         let res = render_sample(
             &sample,
             &app_sink,
             size_res.size,
-            size_res.changed,
-            &mut screen_buff,
-            &mut resizer,
-            &mut last_frame,
-            tty,
+            fresh_redraw,
+            &mut renderer,
+            &FrameOutput {
+                writer: &writer,
+                message: message.as_deref(),
+                overlay: preview.as_deref(),
+                dump,
+                top: inline.map(|rows| rows.top),
+                // a dump gets played back by something that draws whole frames anyway
+                synchronized: options.synchronized_updates && !dump,
+                stats: &options.stats,
+            },
         );
 
-        if res.is_err() {
-            break;
+        options.settings.set_video_area(renderer.area());
+        match res {
+            Ok(Some(bytes)) => {
+                splash = false;
+                dropper.rendered(started.elapsed());
+                if let Some(sync) = &mut sync {
+                    sync.drawn(&sample, &app_sink);
+                }
+                if let Some(limiter) = &mut limiter {
+                    limiter.wrote(bytes, Instant::now());
+                }
+                // what the cap left out still has to make it on screen, even while paused
+                if renderer.frame().pending() {
+                    let _ = consumer.make_reloader().reload_sample();
+                }
+            }
+            // the terminal never saw this frame, so the next diff can't build on it
+            Ok(None) => {
+                tracing::debug!("the terminal didn't take the frame in time");
+                options.stats.record_output_drop();
+                force_redraw = true;
+            }
+            Err(()) => break,
+        }
+
+        #[cfg(feature = "serde")]
+        if let Some(ref mut dump) = grid_dump {
+            let frame = renderer
+                .frame()
+                .snapshot(sample.buffer().and_then(|buffer| buffer.pts()));
+            let res = serde_json::to_writer(&mut *dump, &frame)
+                .map_err(std::io::Error::from)
+                .and_then(|()| dump.write_all(b"\n"));
+            if let Err(err) = res {
+                tracing::error!(%err, "couldn't write the grid dump, it stops here");
+                grid_dump = None;
+            }
+        }
+    }
+
+    let _ = writer.flush();
+    // the writer has to be done before the terminal gets restored
+    drop(writer);
+    drop(restore);
+}
+
+/// The cells the video takes up on the terminal, counting from 0
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VideoArea {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl VideoArea {
+    /// how far along the bottom row cell `x` is, for using that row as a seek bar
+    pub fn seek_fraction(&self, (x, y): (u16, u16)) -> Option<f64> {
+        let bottom = (self.y + self.height).checked_sub(1)?;
+        let column = x
+            .checked_sub(self.x)
+            .filter(|&column| column < self.width)?;
+        (y == bottom).then(|| f64::from(column) / f64::from(self.width.saturating_sub(1).max(1)))
+    }
+}
+
+/// how long the name of a scaler or filter stays up after cycling to it
+const SCALER_SHOWN_FOR: Duration = Duration::from_secs(2);
+/// how long the buffering progress stays over the video once it's done
+const BUFFERING_SHOWN_FOR: Duration = Duration::from_secs(1);
+/// and how long while it isn't, without it getting any further
+const BUFFERING_STUCK_FOR: Duration = Duration::from_secs(30);
+
+/// Rendering knobs that can be turned while playing,
+/// and where the renderer put the video so clicks can be mapped onto it
+pub struct LiveSettings {
+    precision: AtomicU8,
+    /// the index of the [`Scaler`]
+    scaler: AtomicU8,
+    post_filter: Mutex<Option<PostFilter>>,
+    /// what cycling to posterize posterizes to, the levels it was last given
+    posterize_levels: AtomicU8,
+    area: Mutex<Option<VideoArea>>,
+    /// the next frame drawn gets saved
+    screenshot: AtomicBool,
+    /// a line of text shown over the video until it expires
+    message: Mutex<Option<(String, Instant)>>,
+    /// lines of text shown over the video until dismissed
+    panel: Mutex<Option<String>>,
+    /// whether the frame rates and such are drawn over the video
+    stats: AtomicBool,
+    /// whether the position is shown, and the index of the [`Corner`] it's in
+    clock: AtomicBool,
+    clock_corner: AtomicU8,
+    /// how loud the bands of the sound last were, `None` while the spectrum isn't shown
+    spectrum: Mutex<Option<Vec<f32>>>,
+    /// a thumbnail of where a seek is going, drawn in a corner
+    preview: Mutex<Option<gst::Sample>>,
+    /// makes the renderer draw again while paused, set once it runs
+    reloader: OnceLock<SampleReloader>,
+    /// set while the terminal isn't ours to draw on, like when stopped in the background
+    suspended: AtomicBool,
+}
+
+impl LiveSettings {
+    pub fn new(precision: u8, show_stats: bool) -> Self {
+        Self {
+            precision: AtomicU8::new(precision),
+            scaler: AtomicU8::new(Scaler::default() as u8),
+            post_filter: Mutex::new(None),
+            posterize_levels: AtomicU8::new(4),
+            area: Mutex::new(None),
+            screenshot: AtomicBool::new(false),
+            message: Mutex::new(None),
+            panel: Mutex::new(None),
+            stats: AtomicBool::new(show_stats),
+            clock: AtomicBool::new(false),
+            clock_corner: AtomicU8::new(Corner::default() as u8),
+            spectrum: Mutex::new(None),
+            preview: Mutex::new(None),
+            reloader: OnceLock::new(),
+            suspended: AtomicBool::new(false),
+        }
+    }
+
+    /// stops drawing on the terminal until resumed
+    pub fn suspend(&self) {
+        self.suspended.store(true, Ordering::Relaxed);
+    }
+
+    /// draws on the terminal again, the last frame in full right away
+    pub fn resume(&self) {
+        self.suspended.store(false, Ordering::Relaxed);
+        self.redraw();
+    }
+
+    fn suspended(&self) -> bool {
+        self.suspended.load(Ordering::Relaxed)
+    }
+
+    /// draws the last frame again, for changes to show while paused
+    fn redraw(&self) {
+        if let Some(reloader) = self.reloader.get() {
+            let _ = reloader.reload_sample();
+        }
+    }
+
+    /// saves the frame on screen as a png, the renderer shows where it went
+    pub fn request_screenshot(&self) {
+        self.screenshot.store(true, Ordering::Relaxed);
+        self.redraw();
+    }
+
+    pub fn toggle_stats(&self) {
+        self.stats.fetch_xor(true, Ordering::Relaxed);
+        self.redraw();
+    }
+
+    fn stats_shown(&self) -> bool {
+        self.stats.load(Ordering::Relaxed)
+    }
+
+    /// shows the position and duration in `corner` of the video
+    pub fn show_clock(&self, corner: Corner) {
+        self.clock_corner.store(corner as u8, Ordering::Relaxed);
+        self.clock.store(true, Ordering::Relaxed);
+        self.redraw();
+    }
+
+    /// shows or hides the position, in the corner it was last in
+    pub fn toggle_clock(&self) {
+        self.clock.fetch_xor(true, Ordering::Relaxed);
+        self.redraw();
+    }
+
+    fn clock(&self) -> Option<Corner> {
+        self.clock
+            .load(Ordering::Relaxed)
+            .then(|| Corner::from_index(self.clock_corner.load(Ordering::Relaxed)))
+    }
+
+    /// shows the spectrum of the sound under the video or hides it, whether it's shown now
+    pub fn toggle_spectrum(&self) -> bool {
+        let mut spectrum = self.spectrum.lock();
+        *spectrum = match spectrum.take() {
+            Some(_) => None,
+            None => Some(vec![]),
+        };
+        let shown = spectrum.is_some();
+        drop(spectrum);
+        self.redraw();
+        shown
+    }
+
+    pub fn spectrum_shown(&self) -> bool {
+        self.spectrum.lock().is_some()
+    }
+
+    /// what the spectrum shows from now on, each band from 0 to 1, lowest first,
+    /// drawn right away so it keeps up whatever the frame rate of the video is
+    pub fn update_spectrum(&self, levels: Vec<f32>) {
+        let mut spectrum = self.spectrum.lock();
+        if let Some(shown) = &mut *spectrum {
+            *shown = levels;
+            drop(spectrum);
+            self.redraw();
+        }
+    }
+
+    fn spectrum(&self) -> Option<Vec<f32>> {
+        self.spectrum.lock().clone()
+    }
+
+    pub fn show_preview(&self, sample: gst::Sample) {
+        *self.preview.lock() = Some(sample);
+        self.redraw();
+    }
+
+    pub fn hide_preview(&self) {
+        *self.preview.lock() = None;
+        self.redraw();
+    }
+
+    fn preview(&self) -> Option<gst::Sample> {
+        self.preview.lock().clone()
+    }
+
+    /// shows the panel `make` describes, or hides it if it's already up
+    pub fn toggle_panel(&self, make: impl FnOnce() -> String) {
+        let mut panel = self.panel.lock();
+        *panel = match panel.take() {
+            Some(_) => None,
+            None => Some(make()),
+        };
+        drop(panel);
+        self.redraw();
+    }
+
+    fn take_screenshot_request(&self) -> bool {
+        self.screenshot.swap(false, Ordering::Relaxed)
+    }
+
+    fn show_message(&self, message: String, duration: Duration) {
+        *self.message.lock() = Some((message, Instant::now() + duration));
+    }
+
+    /// shows `message` over the video for `duration`, drawn right away and gone once it's over,
+    /// paused or not
+    pub fn notify(&self, message: String, duration: Duration) {
+        self.show_message(message, duration);
+        self.redraw();
+        if let Some(reloader) = self.reloader.get() {
+            let _ = reloader.reload_sample_at(Instant::now() + duration);
+        }
+    }
+
+    /// the message if it didn't expire yet, above the panel if there is one
+    fn message(&self) -> Option<String> {
+        let mut lock = self.message.lock();
+        let message = match &*lock {
+            Some((message, until)) if Instant::now() < *until => Some(message.clone()),
+            _ => {
+                *lock = None;
+                None
+            }
+        };
+        drop(lock);
+
+        match (message, self.panel.lock().clone()) {
+            (Some(message), Some(panel)) => Some(format!("{message}\n{panel}")),
+            (message, panel) => message.or(panel),
+        }
+    }
+
+    pub fn video_area(&self) -> Option<VideoArea> {
+        *self.area.lock()
+    }
+
+    fn set_video_area(&self, area: VideoArea) {
+        *self.area.lock() = Some(area);
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision.load(Ordering::Relaxed)
+    }
+
+    pub fn adjust_precision(&self, by: i8) {
+        let _ = self
+            .precision
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |precision| {
+                let (min, max) = (*PRECISION_RANGE.start(), *PRECISION_RANGE.end());
+                Some(precision.saturating_add_signed(by).clamp(min, max))
+            });
+    }
+
+    pub fn scaler(&self) -> Scaler {
+        Scaler::from_index(self.scaler.load(Ordering::Relaxed))
+    }
+
+    pub fn set_scaler(&self, scaler: Scaler) {
+        self.scaler.store(scaler as u8, Ordering::Relaxed);
+        self.redraw();
+    }
+
+    /// goes on to the next scaler, saying which one it is over the video
+    pub fn cycle_scaler(&self) {
+        let scaler = self.scaler().next();
+        self.scaler.store(scaler as u8, Ordering::Relaxed);
+        let name = clap::ValueEnum::to_possible_value(&scaler).unwrap();
+        self.show_message(format!("scaler: {}", name.get_name()), SCALER_SHOWN_FOR);
+        self.redraw();
+    }
+
+    pub fn post_filter(&self) -> Option<PostFilter> {
+        *self.post_filter.lock()
+    }
+
+    pub fn set_post_filter(&self, filter: Option<PostFilter>) {
+        if let Some(PostFilter::Posterize(levels)) = filter {
+            self.posterize_levels.store(levels, Ordering::Relaxed);
         }
+        *self.post_filter.lock() = filter;
+        self.redraw();
     }
 
-    tty.write_all(termion::cursor::Show.as_ref()).unwrap()
+    /// goes on to the next filter, none after the last, saying which one it is over the video
+    pub fn cycle_post_filter(&self) {
+        let levels = self.posterize_levels.load(Ordering::Relaxed);
+        let filter = PostFilter::next(*self.post_filter.lock(), levels);
+        *self.post_filter.lock() = filter;
+        let name = filter.map_or_else(|| "none".to_string(), |filter| filter.to_string());
+        self.show_message(format!("filter: {name}"), SCALER_SHOWN_FOR);
+        self.redraw();
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ResizePolling {
+    /// how often the terminal size gets checked while no frames come in
+    pub interval: Duration,
+    /// how long a new size has to stay the same before redrawing for it
+    pub debounce: Duration,
+}
+
+#[derive(Clone)]
+struct Options {
+    /// render at a fixed size instead of following the terminal
+    size: Option<(u16, u16)>,
+    /// draw cells with whichever glyph needs the fewest color changes
+    compact: bool,
+    /// how many threads diff the rows of a frame
+    render_threads: NonZero<usize>,
+    /// how samples wait for the renderer
+    queue_mode: QueueMode,
+    sink_properties: SinkProperties,
+    /// the `--vf` chain run on every frame
+    filters: Option<ChainSpec>,
+    /// how many times taller than wide a cell is
+    cell_aspect: CellAspect,
+    /// how far a channel can move before a cell gets drawn again
+    diff_threshold: u8,
+    /// how many changed cells a frame draws at most, the rest wait for the next one
+    max_cells_per_frame: Option<NonZero<usize>>,
+    /// what gets written to the terminal a second at most
+    max_bandwidth: Option<Bandwidth>,
+    synchronized_updates: bool,
+    palette: Palette,
+    glyphs: Glyphs,
+    /// painted around the video
+    background: Option<Background>,
+    /// how HDR highlights get brought down to what the terminal shows
+    tone_map: ToneMap,
+    /// the color blindness simulated
+    color_filter: ColorFilter,
+    render_mode: RenderMode,
+    /// what `render_mode` symbols picks from
+    symbols: SymbolSet,
+    /// the pixels a cell of the terminal font takes up, when known
+    font_cell: Option<(u16, u16)>,
+    /// the capsfilter following the terminal size, for `--decode-scale auto`
+    scale: Option<Arc<ScaleTarget>>,
+    /// where the screenshot key saves frames
+    screenshot_dir: PathBuf,
+    resize_polling: ResizePolling,
+    /// log how far the frames drawn are from the clock, and maybe make up for it
+    sync_debug: Option<SyncDebug>,
+    settings: Arc<LiveSettings>,
+    stats: Arc<RenderStats>,
+    /// send frames to everyone connecting here instead of drawing them on the terminal
+    serve: Option<std::net::SocketAddr>,
+    /// render to this file instead of the terminal, at the fixed `size`
+    dump_ansi: Option<PathBuf>,
+    /// record everything drawn as an asciinema cast
+    record_cast: Option<PathBuf>,
+    /// write every rendered frame as a line of json
+    #[cfg(feature = "serde")]
+    grid_dump: Option<std::path::PathBuf>,
+}
+
+/// How the appsinks made for the pipelines deal with frames being late
+#[derive(Copy, Clone, Debug)]
+struct SinkProperties {
+    /// later than this frames don't get passed on at all, `None` for however late
+    max_lateness: Option<Duration>,
+    /// tell the elements upstream how late frames are, so decoders skip the ones that won't make it
+    qos: bool,
+    /// keep only the newest frame waiting for the renderer
+    drop: bool,
 }
 
-pub fn create(quit_handler: &mut QuitHandler, size: Option<(u16, u16)>) -> gst::Element {
-    let caps = gst_video::VideoCapsBuilder::new()
-        .format(VideoFormat::Rgb)
-        .build();
-
-    let renderer_enabled = !flag("NO_DISPLAY_OUTPUT", false);
-
-    let (producer, consumer) = video_pipe::video_pipe();
-
-    let app = AppSink::builder()
-        .name("terminal player")
-        .sync(true)
-        .caps(&caps)
-        .callbacks(
-            AppSinkCallbacks::builder()
-                .new_sample_if(
-                    send_new_sample(producer.clone(), AppSink::pull_sample),
-                    renderer_enabled,
-                )
-                .new_preroll_if(
-                    send_new_sample(producer.clone(), AppSink::pull_preroll),
-                    renderer_enabled,
-                )
-                .build(),
-        )
-        .build();
-
-    if renderer_enabled {
-        let app_clone = app.clone();
-        let jh = thread::spawn(move || run_renderer_thread(consumer, app_clone, size));
-        quit_handler.add(move || {
-            producer.close();
-            jh.join().unwrap()
+/// The terminal half of the player, which outlives the pipelines feeding it
+/// so switching files never leaves the alternate screen
+pub struct TerminalVideoSink {
+    producer: SampleProducer,
+    current: CurrentSink,
+    renderer_enabled: bool,
+    /// pass samples on as soon as they are decoded instead of when they're due
+    unsynced: bool,
+    sink_properties: SinkProperties,
+    scale: Option<Arc<ScaleTarget>>,
+}
+
+impl TerminalVideoSink {
+    pub fn builder() -> TerminalVideoSinkBuilder {
+        TerminalVideoSinkBuilder {
+            options: Options {
+                size: None,
+                compact: false,
+                render_threads: NonZero::<usize>::MIN,
+                queue_mode: QueueMode::default(),
+                sink_properties: SinkProperties {
+                    max_lateness: None,
+                    qos: true,
+                    drop: false,
+                },
+                filters: None,
+                cell_aspect: CellAspect::default(),
+                diff_threshold: 0,
+                max_cells_per_frame: None,
+                max_bandwidth: None,
+                synchronized_updates: false,
+                palette: Palette::default(),
+                glyphs: Glyphs::default(),
+                background: None,
+                tone_map: ToneMap::default(),
+                color_filter: ColorFilter::default(),
+                render_mode: RenderMode::default(),
+                symbols: SymbolSet::default(),
+                font_cell: None,
+                scale: None,
+                screenshot_dir: PathBuf::from("."),
+                resize_polling: ResizePolling {
+                    interval: Duration::from_millis(280),
+                    debounce: Duration::from_millis(100),
+                },
+                sync_debug: None,
+                settings: Arc::new(LiveSettings::new(DEFAULT_PRECISION, false)),
+                stats: Arc::default(),
+                serve: None,
+                dump_ansi: None,
+                record_cast: None,
+                #[cfg(feature = "serde")]
+                grid_dump: None,
+            },
+        }
+    }
+
+    fn start(
+        quit_handler: &mut QuitHandler,
+        options: Options,
+    ) -> Result<Self, crate::error::PlayerError> {
+        let renderer_enabled = !flag("NO_DISPLAY_OUTPUT", false);
+
+        let unsynced = options.dump_ansi.is_some();
+        let sink_properties = options.sink_properties;
+        // a dump can't skip any frame, and dropping keeps just the newest,
+        // the appsink's own dropping never comes into it as its samples get pulled straight away
+        let (queue_mode, capacity) = match (unsynced, sink_properties.drop) {
+            (true, _) => (QueueMode::Fifo, video_pipe::CAPACITY),
+            (false, true) => (QueueMode::Latest, 1),
+            (false, false) => (options.queue_mode, video_pipe::CAPACITY),
+        };
+        let (producer, consumer) = video_pipe::video_pipe(queue_mode, capacity);
+        // the symbols get matched against a block of pixels a cell, which have to be decoded
+        if let Some(scale) = &options.scale
+            && options.render_mode == RenderMode::Symbols
+        {
+            scale.set_cell_pixels(matching_grid(options.font_cell.unwrap_or(CELL_PIXELS)));
+        }
+        let current = CurrentSink::default();
+        // clients all have sizes of their own
+        let scale = match options.serve {
+            Some(_) => None,
+            None => options.scale.clone(),
+        };
+
+        if renderer_enabled {
+            let recordings = Recordings::open(&options)?;
+            let _ = options.settings.reloader.set(consumer.make_reloader());
+            let token = quit_handler.token();
+            let current = Arc::clone(&current);
+
+            let jh = match options.serve {
+                Some(addr) => {
+                    let mut client_options = options.clone();
+                    // there's no one terminal to record
+                    client_options.record_cast = None;
+                    client_options.scale = None;
+                    // nor one sink of the server's to shift for every client
+                    client_options.sync_debug = None;
+                    #[cfg(feature = "serde")]
+                    {
+                        client_options.grid_dump = None;
+                    }
+
+                    let token = token.clone();
+                    let server = Server::bind(addr, move |consumer, screen| {
+                        let (current, token) = (Arc::clone(&current), token.clone());
+                        run_renderer_thread(
+                            consumer,
+                            current,
+                            token,
+                            screen,
+                            client_options.clone(),
+                            Recordings::default(),
+                        )
+                    })
+                    .map_err(crate::error::PlayerError::Terminal)?;
+                    thread::spawn(move || server::distribute(consumer, server))
+                }
+                None => {
+                    let screen = match &options.dump_ansi {
+                        Some(path) => {
+                            let output = std::fs::File::create(path)
+                                .map_err(crate::error::PlayerError::open(path))?;
+                            Screen {
+                                output: Box::new(output),
+                                restore: Box::new(()),
+                                size: None,
+                            }
+                        }
+                        None => {
+                            let Terminal { output, restore } =
+                                terminal::take().map_err(crate::error::PlayerError::Terminal)?;
+                            Screen {
+                                output: Box::new(output),
+                                restore,
+                                size: None,
+                            }
+                        }
+                    };
+
+                    let token = token.clone();
+                    thread::spawn(move || {
+                        run_renderer_thread(consumer, current, token, screen, options, recordings)
+                    })
+                }
+            };
+
+            let producer = producer.clone();
+            token.on_cancel(move || producer.close());
+            quit_handler.add(move || jh.join().unwrap())
+        }
+
+        Ok(Self {
+            producer,
+            current,
+            renderer_enabled,
+            unsynced,
+            sink_properties,
+            scale,
         })
     }
 
-    app.upcast()
+    /// clears the screen and shows a loading indicator until the next sample arrives
+    pub fn show_loading(&self) {
+        self.producer.show_loading();
+    }
+
+    /// says how far the pipeline got filling its buffers, on the loading indicator
+    /// while there's no frame yet and over the video after
+    pub fn show_buffering(&self, percent: u8) {
+        self.producer.show_buffering(percent);
+    }
+
+    /// the elements to put in front of the sink with `--decode-scale auto`, none otherwise,
+    /// the ones made before stop following the terminal size
+    pub fn scale_elements(&self) -> Result<Vec<gst::Element>, crate::error::PlayerError> {
+        match &self.scale {
+            Some(scale) => Ok(scale.elements()?.into()),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn caps() -> gst::Caps {
+        TerminalRenderer::caps()
+    }
+
+    fn callbacks(&self) -> AppSinkCallbacks {
+        let producer = &self.producer;
+        let renderer_enabled = self.renderer_enabled;
+
+        AppSinkCallbacks::builder()
+            .new_sample_if(
+                send_new_sample(producer.clone(), AppSink::pull_sample),
+                renderer_enabled,
+            )
+            .new_preroll_if(
+                send_new_sample(producer.clone(), AppSink::pull_preroll),
+                renderer_enabled,
+            )
+            .build()
+    }
+
+    /// makes a new sink element rendering to the terminal, frames get timed against the newest one,
+    /// so the pipelines of the ones made before have to be stopped first or theirs get drawn too
+    pub fn element(&self) -> gst::Element {
+        let SinkProperties {
+            max_lateness, qos, ..
+        } = self.sink_properties;
+        let max_lateness = max_lateness.map_or(-1, |lateness| {
+            i64::try_from(lateness.as_nanos()).unwrap_or(i64::MAX)
+        });
+        let app = AppSink::builder()
+            .name(ELEMENT_NAME)
+            .sync(!self.unsynced)
+            .max_lateness(max_lateness)
+            // a dump has nothing to be late for
+            .qos(qos && !self.unsynced)
+            .caps(&Self::caps())
+            .callbacks(self.callbacks())
+            .build();
+
+        *self.current.lock() = Some(app.clone());
+
+        app.upcast()
+    }
+
+    /// renders the samples of an appsink some pipeline description made, like [`Self::element`] does
+    /// with its own, `false` when `element` isn't an appsink
+    pub fn adopt(&self, element: &gst::Element) -> bool {
+        let Some(app) = element.downcast_ref::<AppSink>() else {
+            return false;
+        };
+
+        app.set_caps(Some(&Self::caps()));
+        app.set_callbacks(self.callbacks());
+        *self.current.lock() = Some(app.clone());
+        true
+    }
+}
+
+/// Sets up a [`TerminalVideoSink`], anything not set is drawn the way the player does by default
+pub struct TerminalVideoSinkBuilder {
+    options: Options,
+}
+
+impl TerminalVideoSinkBuilder {
+    /// render at a fixed size instead of following the terminal
+    pub fn size(mut self, size: Option<(u16, u16)>) -> Self {
+        self.options.size = size;
+        self
+    }
+
+    /// draw cells with whichever glyph needs the fewest color changes
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.options.compact = compact;
+        self
+    }
+
+    /// how many threads diff the rows of a frame
+    pub fn render_threads(mut self, threads: NonZero<usize>) -> Self {
+        self.options.render_threads = threads;
+        self
+    }
+
+    /// later than this frames get left out by the sink and never reach the renderer,
+    /// however late by default
+    pub fn max_lateness(mut self, lateness: Option<Duration>) -> Self {
+        self.options.sink_properties.max_lateness = lateness;
+        self
+    }
+
+    /// send QoS events upstream so decoders skip frames that would be late anyway,
+    /// instead of decoding them only for the renderer to drop them, on by default
+    pub fn qos(mut self, qos: bool) -> Self {
+        self.options.sink_properties.qos = qos;
+        self
+    }
+
+    /// have the sink keep only the newest frame waiting for the renderer, throwing out the one
+    /// before instead of queueing a few the way [`QueueMode::Latest`] does, off by default
+    pub fn drop_when_full(mut self, drop: bool) -> Self {
+        self.options.sink_properties.drop = drop;
+        self
+    }
+
+    /// how samples wait for the renderer
+    pub fn queue_mode(mut self, queue_mode: QueueMode) -> Self {
+        self.options.queue_mode = queue_mode;
+        self
+    }
+
+    /// filters run on every frame, in order
+    pub fn filters(mut self, filters: Option<ChainSpec>) -> Self {
+        self.options.filters = filters;
+        self
+    }
+
+    /// how many times taller than wide a cell is, 2 by default
+    pub fn cell_aspect(mut self, cell_aspect: CellAspect) -> Self {
+        self.options.cell_aspect = cell_aspect;
+        self
+    }
+
+    /// cells whose channels all moved by at most `threshold` since they were drawn
+    /// keep their old colors, so noisy video doesn't redraw everything every frame, 0 by default
+    pub fn diff_threshold(mut self, threshold: u8) -> Self {
+        self.options.diff_threshold = threshold;
+        self
+    }
+
+    /// draw at most `max` changed cells a frame and leave the rest to the following frames,
+    /// for terminals too slow to take whole frames, uncapped by default
+    pub fn max_cells_per_frame(mut self, max: Option<NonZero<usize>>) -> Self {
+        self.options.max_cells_per_frame = max;
+        self
+    }
+
+    /// keep what gets written to the terminal under `max` a second, by skipping frames
+    /// and, when that isn't enough, drawing with fewer colors and smaller, unlimited by default
+    pub fn max_bandwidth(mut self, max: Option<Bandwidth>) -> Self {
+        self.options.max_bandwidth = max;
+        self
+    }
+
+    /// wrap every frame in a synchronized update, so terminals that know them never show
+    /// half of one, off by default
+    pub fn synchronized_updates(mut self, synchronized: bool) -> Self {
+        self.options.synchronized_updates = synchronized;
+        self
+    }
+
+    /// which colors the terminal can show, all of them by default
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.options.palette = palette;
+        self
+    }
+
+    /// what cells get drawn with, half blocks by default
+    pub fn glyphs(mut self, glyphs: Glyphs) -> Self {
+        self.options.glyphs = glyphs;
+        self
+    }
+
+    /// paints the cells around the video, instead of leaving them to the terminal
+    pub fn background(mut self, background: Option<Background>) -> Self {
+        self.options.background = background;
+        self
+    }
+
+    /// how 10 bit HDR video gets tone mapped, hable by default
+    pub fn tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.options.tone_map = tone_map;
+        self
+    }
+
+    /// draws frames the way someone with `filter` sees them, none by default
+    pub fn color_filter(mut self, filter: ColorFilter) -> Self {
+        self.options.color_filter = filter;
+        self
+    }
+
+    /// what frames get drawn as, their pixels by default
+    pub fn render_mode(mut self, mode: RenderMode) -> Self {
+        self.options.render_mode = mode;
+        self
+    }
+
+    /// the glyphs [`RenderMode::Symbols`] draws cells with, blocks by default
+    pub fn symbols(mut self, set: SymbolSet) -> Self {
+        self.options.symbols = set;
+        self
+    }
+
+    /// the pixels across and down a cell of the terminal font takes up,
+    /// [`RenderMode::Symbols`] matches cells in its shape, [`CELL_PIXELS`] when not known
+    pub fn font_cell(mut self, font_cell: Option<(u16, u16)>) -> Self {
+        self.options.font_cell = font_cell;
+        self
+    }
+
+    /// where [`LiveSettings::request_screenshot`] saves frames
+    pub fn screenshot_dir(mut self, dir: PathBuf) -> Self {
+        self.options.screenshot_dir = dir;
+        self
+    }
+
+    pub fn resize_polling(mut self, polling: ResizePolling) -> Self {
+        self.options.resize_polling = polling;
+        self
+    }
+
+    /// log how far the frames getting onto the terminal are from the pipeline clock,
+    /// and with [`SyncDebug::Correct`] shift them to make up for it, off by default
+    pub fn sync_debug(mut self, mode: Option<SyncDebug>) -> Self {
+        self.options.sync_debug = mode;
+        self
+    }
+
+    /// knobs to turn while playing
+    pub fn settings(mut self, settings: Arc<LiveSettings>) -> Self {
+        self.options.settings = settings;
+        self
+    }
+
+    /// where the renderer counts what it did
+    pub fn stats(mut self, stats: Arc<RenderStats>) -> Self {
+        self.options.stats = stats;
+        self
+    }
+
+    /// send frames to everyone connecting here instead of drawing them on the terminal
+    pub fn serve(mut self, addr: Option<std::net::SocketAddr>) -> Self {
+        self.options.serve = addr;
+        self
+    }
+
+    /// render to this file instead of the terminal, at the fixed `size`
+    pub fn dump_ansi(mut self, path: Option<PathBuf>) -> Self {
+        self.options.dump_ansi = path;
+        self
+    }
+
+    /// scale frames down to the terminal size before they reach the sink
+    pub fn decode_scale(mut self, decode_scale: DecodeScale) -> Self {
+        self.options.scale = match decode_scale {
+            DecodeScale::Off => None,
+            DecodeScale::Auto => Some(Arc::default()),
+        };
+        self
+    }
+
+    /// record everything drawn as an asciinema cast
+    pub fn record_cast(mut self, path: Option<PathBuf>) -> Self {
+        self.options.record_cast = path;
+        self
+    }
+
+    /// write every rendered frame as a line of json
+    #[cfg(feature = "serde")]
+    pub fn grid_dump(mut self, path: Option<PathBuf>) -> Self {
+        self.options.grid_dump = path;
+        self
+    }
+
+    /// takes over the terminal until `quit_handler` is dropped
+    pub fn start(
+        self,
+        quit_handler: &mut QuitHandler,
+    ) -> Result<TerminalVideoSink, crate::error::PlayerError> {
+        TerminalVideoSink::start(quit_handler, self.options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::VideoArea;
+
+    #[test]
+    fn the_bottom_row_seeks() {
+        let video = VideoArea {
+            x: 10,
+            y: 2,
+            width: 41,
+            height: 20,
+        };
+        assert_eq!(video.seek_fraction((10, 21)), Some(0.0));
+        assert_eq!(video.seek_fraction((30, 21)), Some(0.5));
+        assert_eq!(video.seek_fraction((50, 21)), Some(1.0));
+        // off the end of the row, or above it
+        assert_eq!(video.seek_fraction((51, 21)), None);
+        assert_eq!(video.seek_fraction((30, 20)), None);
+    }
 }
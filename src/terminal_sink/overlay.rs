@@ -0,0 +1,379 @@
+//! Text drawn over the video once a frame is diffed, like messages and the clock,
+//! laid out into cells with wide characters taking two of them and marks staying on the one before,
+//! the cells it covers kept out of the diffs so the frame doesn't flicker through
+//!
+//! text goes on layers stacked in the order of [`Layer`], each blended over what's under it
+//! as opaque as that layer is set to be
+use crate::terminal_sink::VideoArea;
+use rgb::Rgb;
+use std::collections::BTreeMap;
+use unicode_width::UnicodeWidthChar;
+
+/// What text is there for, which picks what it gets drawn over, later ones go on top
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    /// right on the video, under everything else
+    Video,
+    Subtitle,
+    /// messages, the clock and the rest of what the player shows
+    #[default]
+    Osd,
+    Debug,
+}
+
+impl Layer {
+    const COUNT: usize = 4;
+}
+
+/// What shows behind text
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backdrop {
+    Solid(Rgb<u8>),
+    /// whatever is under it, darkened to this many percent of its brightness
+    Dim(u8),
+}
+
+/// How a span of text looks
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TextStyle {
+    pub fg: Rgb<u8>,
+    pub backdrop: Backdrop,
+}
+
+impl Default for TextStyle {
+    /// white on black, like the message box always was
+    fn default() -> Self {
+        Self {
+            fg: Rgb::new(255, 255, 255),
+            backdrop: Backdrop::Solid(Rgb::new(0, 0, 0)),
+        }
+    }
+}
+
+/// A line of text starting at a cell of the video, counting from its top left
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Span {
+    layer: Layer,
+    at: (u16, u16),
+    text: String,
+    style: TextStyle,
+}
+
+/// a cell of laid out text, its character with the marks on it and the colors it ends up in
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct TextCell {
+    pub(super) text: String,
+    /// takes this cell and the one after it
+    pub(super) wide: bool,
+    pub(super) fg: Rgb<u8>,
+    pub(super) bg: Rgb<u8>,
+}
+
+/// Cells next to each other on a row, in terminal cells
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Run {
+    pub(super) x: u16,
+    pub(super) y: u16,
+    pub(super) cells: Vec<TextCell>,
+}
+
+impl Run {
+    /// how many columns the run covers
+    pub(super) fn width(&self) -> u16 {
+        self.cells.iter().map(|cell| 1 + u16::from(cell.wide)).sum()
+    }
+
+    pub(super) fn area(&self) -> VideoArea {
+        VideoArea {
+            x: self.x,
+            y: self.y,
+            width: self.width(),
+            height: 1,
+        }
+    }
+}
+
+/// how many columns `ch` takes up on a terminal, `None` for control characters
+fn char_width(ch: char) -> Option<u16> {
+    match ch.is_control() {
+        true => None,
+        false => ch.width().map(|width| width.min(2) as u16),
+    }
+}
+
+/// how many columns `text` takes up on a terminal
+pub fn text_width(text: &str) -> usize {
+    text.chars().filter_map(char_width).map(usize::from).sum()
+}
+
+/// `over` laid over `under` with `alpha` of 255 hiding it
+fn blend(over: Rgb<u8>, under: Rgb<u8>, alpha: u8) -> Rgb<u8> {
+    let alpha = u16::from(alpha);
+    let mix = |over: u8, under: u8| {
+        ((u16::from(over) * alpha + u16::from(under) * (255 - alpha) + 127) / 255) as u8
+    };
+    Rgb::new(
+        mix(over.r, under.r),
+        mix(over.g, under.g),
+        mix(over.b, under.b),
+    )
+}
+
+/// Text to draw over every frame, wherever it falls inside the video
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Overlay {
+    spans: Vec<Span>,
+    /// how opaque every layer is
+    alpha: [u8; Layer::COUNT],
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self {
+            spans: Vec::new(),
+            alpha: [u8::MAX; Layer::COUNT],
+        }
+    }
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// how opaque the text of `layer` and what's behind it is, 255 hides what's under it
+    /// and the default, lower lets the video or the layers under it show through
+    pub fn set_alpha(&mut self, layer: Layer, alpha: u8) {
+        self.alpha[layer as usize] = alpha;
+    }
+
+    /// writes `text` on `layer` from cell `at` of the video on, every line of it a row further down
+    pub fn text(&mut self, layer: Layer, at: (u16, u16), text: &str, style: TextStyle) {
+        for (row, line) in (0..).zip(text.lines()) {
+            self.spans.push(Span {
+                layer,
+                at: (at.0, at.1.saturating_add(row)),
+                text: line.to_string(),
+                style,
+            });
+        }
+    }
+
+    /// everything of `other` drawn over this
+    pub fn extend(&mut self, other: &Overlay) {
+        self.spans.extend_from_slice(&other.spans);
+    }
+
+    pub fn clear(&mut self) {
+        self.spans.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// the cells of every span that fall inside `video`, layer over layer with what's under each
+    /// blended in, `under` being the color of the video at a cell,
+    /// wide characters that would stick out become a space and control characters get left out
+    pub(super) fn layout(&self, video: VideoArea, under: impl Fn(u16, u16) -> Rgb<u8>) -> Vec<Run> {
+        let mut spans = self.spans.iter().collect::<Vec<_>>();
+        // stable, so spans of the same layer keep the order they were written in
+        spans.sort_by_key(|span| span.layer);
+
+        // by row then column so the runs come out in order
+        let mut cells = BTreeMap::<(u16, u16), TextCell>::new();
+        for span in spans {
+            let alpha = self.alpha[span.layer as usize];
+            for (x, y, text, wide) in span.cells(video) {
+                // whatever this lands half on turns into a space
+                if x > video.x
+                    && let Some(left) = cells.get_mut(&(y, x - 1))
+                    && left.wide
+                {
+                    left.text = " ".to_string();
+                    left.wide = false;
+                }
+                let below = cells
+                    .get(&(y, x))
+                    .map_or_else(|| under(x, y), |cell| cell.bg);
+                let last = x + u16::from(wide);
+                for column in x..=last {
+                    if let Some(old) = cells.remove(&(y, column))
+                        && old.wide
+                        && column == last
+                    {
+                        let half = TextCell {
+                            text: " ".to_string(),
+                            wide: false,
+                            fg: old.bg,
+                            bg: old.bg,
+                        };
+                        cells.insert((y, column + 1), half);
+                    }
+                }
+
+                let backdrop = match span.style.backdrop {
+                    Backdrop::Solid(rgb) => rgb,
+                    Backdrop::Dim(percent) => dim(below, percent),
+                };
+                let bg = blend(backdrop, below, alpha);
+                let fg = blend(span.style.fg, bg, alpha);
+                cells.insert((y, x), TextCell { text, wide, fg, bg });
+            }
+        }
+
+        let mut runs = Vec::<Run>::new();
+        for ((y, x), cell) in cells {
+            match runs.last_mut() {
+                Some(run) if run.y == y && run.x + run.width() == x => run.cells.push(cell),
+                _ => runs.push(Run {
+                    x,
+                    y,
+                    cells: vec![cell],
+                }),
+            }
+        }
+        runs
+    }
+}
+
+impl Span {
+    /// where the characters of the span go in terminal cells, clipped to `video`,
+    /// with whether they're wide
+    fn cells(&self, video: VideoArea) -> Vec<(u16, u16, String, bool)> {
+        let (column, row) = self.at;
+        if row >= video.height || column >= video.width {
+            return vec![];
+        }
+
+        let room = video.width - column;
+        let mut used = 0;
+        let mut cells = Vec::<(u16, u16, String, bool)>::new();
+        for ch in self.text.chars() {
+            match char_width(ch) {
+                None => continue,
+                Some(0) => {
+                    if let Some((.., text, _)) = cells.last_mut() {
+                        text.push(ch);
+                    }
+                }
+                Some(width) => {
+                    let x = video.x + column + used;
+                    let y = video.y + row;
+                    if used + width > room {
+                        // half of it would be off the video
+                        if used < room {
+                            cells.push((x, y, " ".to_string(), false));
+                        }
+                        break;
+                    }
+                    used += width;
+                    cells.push((x, y, ch.to_string(), width == 2));
+                }
+            }
+        }
+        cells
+    }
+}
+
+/// `rgb` darkened to `percent` of its brightness
+pub(super) fn dim(rgb: Rgb<u8>, percent: u8) -> Rgb<u8> {
+    let scale = |channel: u8| (u16::from(channel) * u16::from(percent.min(100)) / 100) as u8;
+    Rgb::new(scale(rgb.r), scale(rgb.g), scale(rgb.b))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const VIDEO: VideoArea = VideoArea {
+        x: 2,
+        y: 1,
+        width: 6,
+        height: 3,
+    };
+
+    const GRAY: Rgb<u8> = Rgb::new(100, 100, 100);
+
+    fn texts(run: &Run) -> Vec<&str> {
+        run.cells.iter().map(|cell| cell.text.as_str()).collect()
+    }
+
+    #[test]
+    fn wide_characters_take_two_cells() {
+        let mut overlay = Overlay::new();
+        overlay.text(Layer::Osd, (0, 0), "a\u{4e2d}b", TextStyle::default());
+        let runs = overlay.layout(VIDEO, |_, _| GRAY);
+        assert_eq!(runs.len(), 1);
+        assert_eq!((runs[0].x, runs[0].y, runs[0].width()), (2, 1, 4));
+        assert_eq!(text_width("a\u{4e2d}b"), 4);
+    }
+
+    #[test]
+    fn clipped_to_the_video() {
+        let mut overlay = Overlay::new();
+        overlay.text(Layer::Osd, (3, 2), "ab\u{4e2d}\nnext", TextStyle::default());
+        let runs = overlay.layout(VIDEO, |_, _| GRAY);
+        // the second line would be below the video, the wide character half off it
+        assert_eq!(runs.len(), 1);
+        assert_eq!(texts(&runs[0]), ["a", "b", " "]);
+    }
+
+    #[test]
+    fn marks_stay_on_their_character() {
+        let mut overlay = Overlay::new();
+        overlay.text(Layer::Osd, (0, 0), "e\u{301}\u{7}x", TextStyle::default());
+        assert_eq!(
+            texts(&overlay.layout(VIDEO, |_, _| GRAY)[0]),
+            ["e\u{301}", "x"]
+        );
+    }
+
+    #[test]
+    fn higher_layers_go_on_top() {
+        let mut overlay = Overlay::new();
+        let red = TextStyle {
+            fg: Rgb::new(255, 0, 0),
+            ..TextStyle::default()
+        };
+        overlay.text(Layer::Debug, (1, 0), "d", red);
+        overlay.text(
+            Layer::Subtitle,
+            (0, 0),
+            "\u{4e2d}\u{4e2d}",
+            TextStyle::default(),
+        );
+        let runs = overlay.layout(VIDEO, |_, _| GRAY);
+        // the debug text cuts the first wide character in half
+        assert_eq!(texts(&runs[0]), [" ", "d", "\u{4e2d}"]);
+        assert_eq!(runs[0].cells[1].fg, red.fg);
+    }
+
+    #[test]
+    fn layers_blend_with_whats_under() {
+        let mut overlay = Overlay::new();
+        overlay.set_alpha(Layer::Subtitle, 128);
+        overlay.text(Layer::Subtitle, (0, 0), "a", TextStyle::default());
+        let runs = overlay.layout(VIDEO, |_, _| Rgb::new(200, 200, 200));
+        assert_eq!(runs[0].cells[0].bg, Rgb::new(100, 100, 100));
+
+        // the layer over it blends with the subtitle, not the video
+        overlay.text(
+            Layer::Osd,
+            (0, 0),
+            "b",
+            TextStyle {
+                fg: Rgb::new(255, 255, 255),
+                backdrop: Backdrop::Dim(50),
+            },
+        );
+        let runs = overlay.layout(VIDEO, |_, _| Rgb::new(200, 200, 200));
+        assert_eq!(texts(&runs[0]), ["b"]);
+        assert_eq!(runs[0].cells[0].bg, Rgb::new(50, 50, 50));
+    }
+
+    #[test]
+    fn dimming() {
+        assert_eq!(dim(Rgb::new(200, 100, 0), 50), Rgb::new(100, 50, 0));
+    }
+}
@@ -0,0 +1,206 @@
+//! The stage between resizing and drawing, running functions over every pixel of the frame
+//! once it's down to the cells it's drawn in, which makes even expensive ones cheap:
+//! the `--filter` effects, whatever gets plugged in with [`TerminalRenderer::set_pixel_transform`]
+//! and last the `--color-filter` simulation of who's watching
+//!
+//! [`TerminalRenderer::set_pixel_transform`]: crate::TerminalRenderer::set_pixel_transform
+use crate::terminal_sink::color_filter::ColorTransform;
+use crate::terminal_sink::resize::{ImageRef, PodMatrix};
+use rgb::{ComponentMap, Rgb};
+use std::fmt;
+use std::str::FromStr;
+
+/// A function of one pixel, closures taking and returning an `Rgb<u8>` are ones too
+pub trait PixelTransform: Send {
+    fn pixel(&self, rgb: Rgb<u8>) -> Rgb<u8>;
+}
+
+impl<F: Fn(Rgb<u8>) -> Rgb<u8> + Send> PixelTransform for F {
+    fn pixel(&self, rgb: Rgb<u8>) -> Rgb<u8> {
+        self(rgb)
+    }
+}
+
+/// how many levels a channel gets posterized to without saying
+const POSTERIZE_LEVELS: u8 = 4;
+
+/// One of the effects of `--filter`, cycled through with f while playing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PostFilter {
+    /// the negative
+    Invert,
+    /// the brown of old photographs
+    Sepia,
+    /// every channel down to this many levels, fewer than 2 posterizes to 2
+    Posterize(u8),
+}
+
+impl FromStr for PostFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "invert" => Ok(PostFilter::Invert),
+            None if s == "sepia" => Ok(PostFilter::Sepia),
+            None if s == "posterize" => Ok(PostFilter::Posterize(POSTERIZE_LEVELS)),
+            Some(("posterize", levels)) => match levels.parse() {
+                Ok(levels @ 2..) => Ok(PostFilter::Posterize(levels)),
+                _ => Err(format!("posterize needs 2 to 255 levels, not {levels:?}")),
+            },
+            _ => Err(format!(
+                "unknown filter {s:?}, expected invert, sepia or posterize[:N]"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PostFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostFilter::Invert => f.write_str("invert"),
+            PostFilter::Sepia => f.write_str("sepia"),
+            PostFilter::Posterize(levels) => write!(f, "posterize:{levels}"),
+        }
+    }
+}
+
+impl PostFilter {
+    /// the one after `filter`, going through none after the last,
+    /// posterizing to `levels`
+    pub fn next(filter: Option<Self>, levels: u8) -> Option<Self> {
+        match filter {
+            None => Some(PostFilter::Invert),
+            Some(PostFilter::Invert) => Some(PostFilter::Sepia),
+            Some(PostFilter::Sepia) => Some(PostFilter::Posterize(levels.max(2))),
+            Some(PostFilter::Posterize(_)) => None,
+        }
+    }
+}
+
+impl PixelTransform for PostFilter {
+    fn pixel(&self, rgb: Rgb<u8>) -> Rgb<u8> {
+        match *self {
+            PostFilter::Invert => rgb.map(|x| 255 - x),
+            PostFilter::Sepia => {
+                let (r, g, b) = (u32::from(rgb.r), u32::from(rgb.g), u32::from(rgb.b));
+                let channel = |x: u32| (x / 1000).min(255) as u8;
+                Rgb::new(
+                    channel(393 * r + 769 * g + 189 * b),
+                    channel(349 * r + 686 * g + 168 * b),
+                    channel(272 * r + 534 * g + 131 * b),
+                )
+            }
+            PostFilter::Posterize(levels) => {
+                let steps = u32::from(levels.max(2) - 1);
+                rgb.map(|x| {
+                    let level = (u32::from(x) * steps + 127) / 255;
+                    (level * 255 / steps) as u8
+                })
+            }
+        }
+    }
+}
+
+/// Everything run over the pixels of resized frames, in order
+pub(super) struct PixelStage {
+    pub(super) post_filter: Option<PostFilter>,
+    pub(super) custom: Option<Box<dyn PixelTransform>>,
+    /// last, it's the eyes of who's watching
+    pub(super) color: Option<ColorTransform>,
+    buffer: PodMatrix<Rgb<u8>>,
+}
+
+impl PixelStage {
+    pub(super) fn new() -> Self {
+        Self {
+            post_filter: None,
+            custom: None,
+            color: None,
+            buffer: PodMatrix::new(),
+        }
+    }
+
+    /// `image` with every transform run over it, as it is without any
+    pub(super) fn apply<'a>(&'a mut self, image: ImageRef<'a>) -> ImageRef<'a> {
+        let transforms = [
+            self.post_filter
+                .as_ref()
+                .map(|filter| filter as &dyn PixelTransform),
+            self.custom.as_deref(),
+            self.color
+                .as_ref()
+                .map(|color| color as &dyn PixelTransform),
+        ];
+        if transforms.iter().all(Option::is_none) {
+            return image;
+        }
+
+        let (width, height) = image.size();
+        // resized frames always fit in the cells of a terminal
+        self.buffer.resize((width as u16, height as u16));
+        for j in 0..self.buffer.height() {
+            let src_row = image.row(u32::from(j));
+            for (pixel, &rgb) in self.buffer.row_mut(j).iter_mut().zip(src_row) {
+                *pixel = transforms
+                    .iter()
+                    .flatten()
+                    .fold(rgb, |rgb, transform| transform.pixel(rgb));
+            }
+        }
+        self.buffer.as_image()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_filters() {
+        assert_eq!("invert".parse::<PostFilter>(), Ok(PostFilter::Invert));
+        assert_eq!(
+            "posterize".parse::<PostFilter>(),
+            Ok(PostFilter::Posterize(4))
+        );
+        assert_eq!(
+            "posterize:8".parse::<PostFilter>(),
+            Ok(PostFilter::Posterize(8))
+        );
+        assert!("posterize:1".parse::<PostFilter>().is_err());
+        assert!("blur".parse::<PostFilter>().is_err());
+        assert_eq!(PostFilter::Posterize(3).to_string(), "posterize:3");
+    }
+
+    #[test]
+    fn transforms_pixels() {
+        let rgb = Rgb::new(10, 128, 250);
+        assert_eq!(PostFilter::Invert.pixel(rgb), Rgb::new(245, 127, 5));
+        assert_eq!(PostFilter::Posterize(2).pixel(rgb), Rgb::new(0, 255, 255));
+        assert_eq!(PostFilter::Posterize(3).pixel(rgb), Rgb::new(0, 127, 255));
+        for levels in [0, 1] {
+            assert_eq!(
+                PostFilter::Posterize(levels).pixel(rgb),
+                Rgb::new(0, 255, 255)
+            );
+        }
+        let sepia = PostFilter::Sepia.pixel(Rgb::new(255, 255, 255));
+        assert!(sepia.r >= sepia.g && sepia.g > sepia.b);
+    }
+
+    #[test]
+    fn cycles_through_none() {
+        let mut filter = None;
+        let mut seen = vec![];
+        for _ in 0..4 {
+            filter = PostFilter::next(filter, 6);
+            seen.push(filter);
+        }
+        let expected = [
+            Some(PostFilter::Invert),
+            Some(PostFilter::Sepia),
+            Some(PostFilter::Posterize(6)),
+            None,
+        ];
+        assert_eq!(seen, expected);
+    }
+}
@@ -0,0 +1,559 @@
+use crate::resize_image;
+use crate::terminal;
+use crate::terminal_sink::VideoArea;
+use crate::terminal_sink::background::{self, Background};
+use crate::terminal_sink::clock::{self, Corner};
+use crate::terminal_sink::color_filter::{ColorFilter, ColorTransform};
+use crate::terminal_sink::diff::{Glyphs, Palette, RenderedFrame};
+use crate::terminal_sink::edges::{self, EdgeDetector};
+use crate::terminal_sink::filters::FilterChain;
+use crate::terminal_sink::frame::{self, FrameUnpacker};
+use crate::terminal_sink::hdr::ToneMap;
+use crate::terminal_sink::overlay::{Layer, Overlay, TextStyle, text_width};
+use crate::terminal_sink::pixel::{PixelStage, PixelTransform, PostFilter};
+use crate::terminal_sink::resize::{Resizer, Scaler};
+use crate::terminal_sink::symbols::{self, SymbolMatcher, SymbolSet};
+use gst_video::VideoFrameRef;
+use std::io::{self, Write};
+use std::num::NonZero;
+use std::time::{Duration, Instant};
+
+/// lays the lines of `message` out over the top left of the video as a box
+fn message_box(overlay: &mut Overlay, message: &str) {
+    let width = message.lines().map(text_width).max().unwrap_or(0);
+    for (row, line) in (0..).zip(message.lines()) {
+        let padding = width - text_width(line);
+        let line = format!("{line}{:padding$}", "");
+        overlay.text(Layer::Osd, (0, row), &line, TextStyle::default());
+    }
+}
+
+/// A rectangle of the terminal for [`TerminalRenderer::render_to`] to draw into,
+/// like the area a TUI laid out for a video widget
+pub struct RenderTarget<W> {
+    /// the top left cell, counting from 0
+    pub origin: (u16, u16),
+    /// how many columns and rows there are to draw in
+    pub size: (u16, u16),
+    /// where the escape sequences go, usually the terminal
+    pub writer: W,
+}
+
+/// blanks the cells of a rectangle with the default colors
+fn clear_region((x, y): (u16, u16), (width, height): (u16, u16)) -> Vec<u8> {
+    let mut clear = b"\x1b[0m".to_vec();
+    for row in 0..height {
+        let erase = format!("{}\x1b[{width}X", terminal::goto(x + 1, y + row + 1));
+        clear.extend_from_slice(erase.as_bytes());
+    }
+    clear
+}
+
+/// Where a frame gets fitted into
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Space {
+    /// the whole terminal, this big
+    Screen((u16, u16)),
+    Region {
+        origin: (u16, u16),
+        size: (u16, u16),
+    },
+}
+
+/// How many times taller than wide a terminal cell is, always a finite number above 0
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CellAspect(f64);
+
+impl CellAspect {
+    /// `None` for anything but a finite number above 0
+    pub fn new(ratio: f64) -> Option<Self> {
+        (ratio.is_finite() && ratio > 0.0).then_some(CellAspect(ratio))
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+/// cells twice as tall as wide, which most fonts come close to
+impl Default for CellAspect {
+    fn default() -> Self {
+        CellAspect(2.0)
+    }
+}
+
+impl std::str::FromStr for CellAspect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<f64>()
+            .ok()
+            .and_then(CellAspect::new)
+            .ok_or_else(|| "the ratio must be a number above 0".to_string())
+    }
+}
+
+/// What frames get drawn as
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum RenderMode {
+    /// the pixels, two to a cell
+    #[default]
+    Pixels,
+    /// line art, box drawing lines along the edges
+    Edges,
+    /// whichever of the `--symbols` comes closest to each cell, in whichever two colors
+    Symbols,
+}
+
+/// How long each step of drawing took, added up over every frame drawn
+#[derive(Debug, Default, Copy, Clone)]
+pub struct StageTimings {
+    pub frames: u64,
+    /// getting the pixels out of the frame, tone mapping included
+    pub unpack: Duration,
+    pub filters: Duration,
+    pub resize: Duration,
+    /// the color filters and pixel transforms run on the resized pixels
+    pub post_filters: Duration,
+    /// comparing cells with the last frame and writing the escape sequences for the ones that changed
+    pub diff: Duration,
+}
+
+/// Turns video frames into the escape sequences drawing them centered in a terminal,
+/// every frame only redrawing the cells that changed since the one before
+pub struct TerminalRenderer {
+    unpacker: FrameUnpacker,
+    filters: FilterChain,
+    resizer: Resizer,
+    /// run over the pixels of frames once they're resized
+    pixels: PixelStage,
+    mode: RenderMode,
+    edges: EdgeDetector,
+    symbols: SymbolMatcher,
+    /// how many times taller than wide a cell is, each holds two pixels stacked
+    cell_aspect: CellAspect,
+    frame: RenderedFrame,
+    command_buffer: Vec<u8>,
+    /// what was drawn into last
+    space: Option<Space>,
+    palette: Palette,
+    /// painted around the video, `None` leaves it to the terminal
+    background: Option<Background>,
+    /// how much of the space frames get fitted into, 1 fills it
+    size_fraction: f64,
+    /// drawn in a corner of the video after every frame
+    corner_text: Option<(String, Corner)>,
+    /// drawn over every frame, under messages and the corner text
+    overlay: Overlay,
+    /// everything drawn over the frame being drawn
+    scratch: Overlay,
+    timings: StageTimings,
+}
+
+impl TerminalRenderer {
+    /// `compact` draws cells with whichever glyph takes the fewest bytes,
+    /// `threads` diff the rows of a frame
+    pub fn new(compact: bool, threads: NonZero<usize>) -> Self {
+        Self::with_repeat(compact, true, threads)
+    }
+
+    pub(super) fn with_repeat(compact: bool, repeat: bool, threads: NonZero<usize>) -> Self {
+        Self {
+            unpacker: FrameUnpacker::new(),
+            filters: FilterChain::empty(),
+            resizer: Resizer::new(),
+            pixels: PixelStage::new(),
+            mode: RenderMode::default(),
+            edges: EdgeDetector::new(),
+            symbols: SymbolMatcher::new(SymbolSet::default(), symbols::CELL_PIXELS),
+            cell_aspect: CellAspect::default(),
+            frame: RenderedFrame::new(compact, repeat, threads),
+            // 8mb default
+            command_buffer: Vec::with_capacity(8 * 1024 * 1024),
+            space: None,
+            palette: Palette::default(),
+            background: None,
+            size_fraction: 1.0,
+            corner_text: None,
+            overlay: Overlay::new(),
+            scratch: Overlay::new(),
+            timings: StageTimings::default(),
+        }
+    }
+
+    /// the raw video caps frames have to come in as
+    pub fn caps() -> gst::Caps {
+        gst_video::VideoCapsBuilder::new()
+            .format_list(frame::formats())
+            .build()
+    }
+
+    /// runs `filters` on every frame before it gets drawn
+    pub fn set_filters(&mut self, filters: FilterChain) {
+        self.filters = filters;
+    }
+
+    /// how frames get scaled to the cells they're drawn in, triangle by default
+    pub fn set_scaler(&mut self, scaler: Scaler) {
+        self.resizer.set_scaler(scaler);
+    }
+
+    /// simulates `filter` on every frame from the next one on, once it's been resized
+    pub fn set_color_filter(&mut self, filter: ColorFilter) {
+        self.pixels.color = ColorTransform::new(filter);
+    }
+
+    /// runs `filter` over every resized frame from the next one on, before the color filter
+    pub fn set_post_filter(&mut self, filter: Option<PostFilter>) {
+        self.pixels.post_filter = filter;
+    }
+
+    /// runs `transform` over every pixel of resized frames from the next one on,
+    /// after the post filter and before the color filter
+    pub fn set_pixel_transform(&mut self, transform: Option<Box<dyn PixelTransform>>) {
+        self.pixels.custom = transform;
+    }
+
+    /// what frames get drawn as, from the next one on, which gets drawn from scratch
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        if mode == self.mode {
+            return;
+        }
+        match mode {
+            RenderMode::Pixels => {}
+            RenderMode::Edges => self.frame.set_symbols(edges::symbols()),
+            RenderMode::Symbols => self.frame.set_symbols(self.symbols.set().texts()),
+        }
+        self.mode = mode;
+        self.space = None;
+    }
+
+    /// the glyphs [`RenderMode::Symbols`] picks from, blocks by default
+    pub fn set_symbol_set(&mut self, set: SymbolSet) {
+        self.symbols = SymbolMatcher::new(set, self.symbols.grid());
+        if self.mode == RenderMode::Symbols {
+            self.frame.set_symbols(self.symbols.set().texts());
+            self.space = None;
+        }
+    }
+
+    /// how many pixels across and down a cell of the terminal font is,
+    /// which [`RenderMode::Symbols`] matches cells in the shape of
+    pub fn set_font_cell(&mut self, font_cell: (u16, u16)) {
+        let set = self.symbols.set().clone();
+        self.symbols = SymbolMatcher::new(set, symbols::matching_grid(font_cell));
+        self.space = None;
+    }
+
+    /// the columns and pixel rows an `image` pixels big image takes up in `size` cells
+    fn fitted(
+        &self,
+        (image_width, image_height): (u32, u32),
+        (width, height): (u16, u16),
+    ) -> (u16, u16) {
+        // pixels drawn in the terminal are only square when cells are twice as tall as wide
+        let image_height =
+            ((f64::from(image_height) * 2.0 / self.cell_aspect.get()).round() as u32).max(1);
+
+        let fit = |cells: u16| {
+            ((f64::from(cells) * self.size_fraction).round() as u16)
+                .max(1)
+                .min(cells)
+        };
+
+        //                                                                        -fill-
+        let (new_width, new_height) = resize_image::resize_dimensions::<false>(
+            image_width,
+            image_height,
+            fit(width).into(),
+            u32::from(fit(height)) * 2,
+        );
+        (new_width as u16, new_height as u16)
+    }
+
+    /// how many times taller than wide a terminal cell is, 2 by default
+    pub fn set_cell_aspect(&mut self, cell_aspect: CellAspect) {
+        self.cell_aspect = cell_aspect;
+    }
+
+    /// which colors and glyphs cells get drawn with, changing them takes a fresh redraw
+    pub fn set_style(&mut self, palette: Palette, glyphs: Glyphs) {
+        self.frame.set_style(palette, glyphs);
+        self.palette = palette;
+        self.space = None;
+    }
+
+    /// paints the cells around the video with `background`, from the next frame on,
+    /// frames with an alpha channel get composited over it too
+    pub fn set_background(&mut self, background: Option<Background>) {
+        self.background = background;
+        self.unpacker.set_matte(background);
+        self.space = None;
+    }
+
+    /// what squeezes the highlights of HDR frames into what the terminal shows
+    pub fn set_tone_map(&mut self, tone_map: ToneMap) {
+        self.unpacker.set_tone_map(tone_map);
+    }
+
+    /// fit frames into `fraction` of the space they're drawn in each way, still centered, 1 by default
+    pub fn set_size_fraction(&mut self, fraction: f64) {
+        self.size_fraction = fraction.clamp(0.0, 1.0);
+    }
+
+    /// draws `text` in `corner` of the video from the next frame on, again only once it changes,
+    /// without the frame drawing over it or it leaving anything behind once it's gone
+    pub fn set_corner_text(&mut self, text: Option<(&str, Corner)>) {
+        if self
+            .corner_text
+            .as_ref()
+            .map(|(text, corner)| (text.as_str(), *corner))
+            != text
+        {
+            self.corner_text = text.map(|(text, corner)| (text.to_string(), corner));
+        }
+    }
+
+    /// draws `overlay` over every frame from the next one on, the cells it covers are left to it
+    /// and the ones it stops covering drawn again
+    pub fn set_overlay(&mut self, overlay: Overlay) {
+        self.overlay = overlay;
+    }
+
+    /// quantize colors to `bits` bits per channel, fewer bits means fewer cells change
+    pub fn set_precision(&mut self, bits: u8) {
+        self.frame.set_precision(bits);
+    }
+
+    /// leave cells alone whose channels moved by no more than `threshold`, 0 by default
+    pub fn set_diff_threshold(&mut self, threshold: u8) {
+        self.frame.set_threshold(threshold);
+    }
+
+    /// draw at most `max` changed cells a frame, spreading the rest over the next ones
+    pub fn set_max_cells(&mut self, max: Option<NonZero<usize>>) {
+        self.frame.set_max_cells(max);
+    }
+
+    /// where the last frame was drawn, in cells
+    pub fn area(&self) -> VideoArea {
+        self.frame.area()
+    }
+
+    /// the cells of the last frame drawn
+    pub fn frame(&self) -> &RenderedFrame {
+        &self.frame
+    }
+
+    /// how long drawing took so far, step by step
+    pub fn timings(&self) -> StageTimings {
+        self.timings
+    }
+
+    /// the escape sequences drawing `frame` fitted into a terminal of `term_size` cells,
+    /// with `message` boxed over its top left, `None` for frames that can't be unpacked
+    ///
+    /// without `fresh_redraw` only what changed since the last frame gets drawn,
+    /// so the terminal has to have gotten everything rendered since the last fresh redraw,
+    /// the buffer can be swapped out for another, it gets cleared before the next frame
+    pub fn render(
+        &mut self,
+        frame: &VideoFrameRef<&gst::BufferRef>,
+        term_size: (u16, u16),
+        fresh_redraw: bool,
+        message: Option<&str>,
+    ) -> Option<&mut Vec<u8>> {
+        self.draw(frame, Space::Screen(term_size), fresh_redraw, message)
+    }
+
+    /// the escape sequences drawing `frame` fitted into `size` cells line by line from the cursor,
+    /// for printing it like text, `None` for frames that can't be unpacked
+    pub fn render_lines(
+        &mut self,
+        frame: &VideoFrameRef<&gst::BufferRef>,
+        size: (u16, u16),
+    ) -> Option<Vec<u8>> {
+        self.draw(frame, Space::Screen(size), true, None)?;
+        Some(self.frame.lines())
+    }
+
+    /// the same as [`Self::render`] but fitted into the rows from `top` down,
+    /// taking all `size` columns, without clearing anything above or below
+    pub(super) fn render_rows(
+        &mut self,
+        frame: &VideoFrameRef<&gst::BufferRef>,
+        top: u16,
+        size: (u16, u16),
+        fresh_redraw: bool,
+        message: Option<&str>,
+    ) -> Option<&mut Vec<u8>> {
+        let space = Space::Region {
+            origin: (0, top),
+            size,
+        };
+        self.draw(frame, space, fresh_redraw, message)
+    }
+
+    /// writes `frame` fitted into the rectangle of `target` to its writer,
+    /// without touching a cell outside of it, clearing the screen or switching screens
+    ///
+    /// the same rules for `fresh_redraw` apply as for [`Self::render`],
+    /// moving or resizing the target always draws from scratch
+    pub fn render_to<W: Write>(
+        &mut self,
+        frame: &VideoFrameRef<&gst::BufferRef>,
+        target: &mut RenderTarget<W>,
+        fresh_redraw: bool,
+    ) -> io::Result<()> {
+        let space = Space::Region {
+            origin: target.origin,
+            size: target.size,
+        };
+        let bytes = self
+            .draw(frame, space, fresh_redraw, None)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "can't unpack the frame"))?;
+        target.writer.write_all(bytes)
+    }
+
+    fn draw(
+        &mut self,
+        frame: &VideoFrameRef<&gst::BufferRef>,
+        space: Space,
+        fresh_redraw: bool,
+        message: Option<&str>,
+    ) -> Option<&mut Vec<u8>> {
+        // make sure screen buffer is empty
+        self.command_buffer.clear();
+
+        let moved = self.space != Some(space);
+        if moved {
+            self.frame.set_clear(match (self.background, space) {
+                // painting every cell leaves nothing to clear
+                (Some(background), Space::Screen(size)) => {
+                    background.paint(self.palette, (0, 0), size)
+                }
+                (Some(background), Space::Region { origin, size }) => {
+                    background.paint(self.palette, origin, size)
+                }
+                (None, Space::Screen(_)) => terminal::CLEAR.as_bytes().to_vec(),
+                (None, Space::Region { origin, size }) => clear_region(origin, size),
+            });
+            self.space = Some(space);
+        }
+        let fresh_redraw = fresh_redraw || moved;
+        let (origin, size) = match space {
+            Space::Screen(size) => ((0, 0), size),
+            Space::Region { origin, size } => (origin, size),
+        };
+
+        // checkers showing through the frame are as big as the cells around it,
+        // the filters rarely change its shape
+        if self.background == Some(Background::Checkerboard) {
+            let frame_size = (frame.width(), frame.height());
+            let (columns, pixel_rows) = self.fitted(frame_size, size);
+            let cells = (columns, pixel_rows.div_ceil(2));
+            self.unpacker
+                .set_checker(background::checker_pixels(frame_size, cells));
+        }
+
+        let start = Instant::now();
+        let image = self.unpacker.unpack(frame)?;
+        let unpacked = Instant::now();
+
+        let image = self.filters.apply(image);
+        let filtered = Instant::now();
+
+        let (width, height) = size;
+        let (new_width, new_height) = self.fitted(image.size(), size);
+
+        let resize_to = match self.mode {
+            // every cell gets matched against a block of pixels instead of just two
+            RenderMode::Symbols => (
+                new_width * self.symbols.grid().0,
+                new_height.div_ceil(2) * self.symbols.grid().1,
+            ),
+            RenderMode::Pixels | RenderMode::Edges => (new_width, new_height),
+        };
+        let resized = self.resizer.resize(image, resize_to);
+        let resized_at = Instant::now();
+
+        let resized = self.pixels.apply(resized);
+        let post_filtered = Instant::now();
+
+        let offset = (
+            origin.0 + (width - new_width) / 2,
+            origin.1 + (height - new_height.div_ceil(2)) / 2,
+        );
+
+        match self.mode {
+            RenderMode::Pixels => self.frame.render(
+                resized,
+                fresh_redraw,
+                offset,
+                origin.0 + width,
+                &mut self.command_buffer,
+            ),
+            RenderMode::Edges => self.frame.render_cells(
+                self.edges.cells(resized),
+                fresh_redraw,
+                offset,
+                origin.0 + width,
+                &mut self.command_buffer,
+            ),
+            RenderMode::Symbols => self.frame.render_cells(
+                self.symbols.cells(resized),
+                fresh_redraw,
+                offset,
+                origin.0 + width,
+                &mut self.command_buffer,
+            ),
+        }
+
+        let timings = &mut self.timings;
+        timings.frames += 1;
+        timings.unpack += unpacked - start;
+        timings.filters += filtered - unpacked;
+        timings.resize += resized_at - filtered;
+        timings.post_filters += post_filtered - resized_at;
+        timings.diff += post_filtered.elapsed();
+
+        // the diff of the next frame leaves these cells alone
+        self.scratch.clone_from(&self.overlay);
+        if let Some(message) = message {
+            message_box(&mut self.scratch, message);
+        }
+        if let Some((text, corner)) = &self.corner_text
+            && let Some(at) = clock::at(text, *corner, self.frame.area())
+        {
+            self.scratch
+                .text(Layer::Osd, at, text, TextStyle::default());
+        }
+        self.frame
+            .draw_overlay(&self.scratch, &mut self.command_buffer);
+
+        Some(&mut self.command_buffer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CellAspect, clear_region};
+
+    #[test]
+    fn cell_aspects_are_above_zero() {
+        assert_eq!(CellAspect::new(2.25).map(CellAspect::get), Some(2.25));
+        for ratio in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(CellAspect::new(ratio), None);
+        }
+        assert!("0".parse::<CellAspect>().is_err());
+        assert_eq!("1.5".parse::<CellAspect>(), Ok(CellAspect(1.5)));
+    }
+
+    #[test]
+    fn region_clears_only_its_cells() {
+        assert_eq!(
+            clear_region((2, 1), (3, 2)),
+            b"\x1b[0m\x1b[2;3H\x1b[3X\x1b[3;3H\x1b[3X"
+        );
+    }
+}
@@ -53,6 +53,18 @@ impl<T: Pod> PodMatrix<T> {
         }
     }
 
+    pub fn row(&self, j: u16) -> &[T] {
+        let width = usize::from(self.width());
+        let start = usize::from(j) * width;
+        &self.cells[start..start + width]
+    }
+
+    pub fn row_mut(&mut self, j: u16) -> &mut [T] {
+        let width = usize::from(self.width());
+        let start = usize::from(j) * width;
+        &mut self.cells[start..start + width]
+    }
+
     pub const fn as_mut_slice(&mut self) -> &mut [T] {
         self.cells.as_mut_slice()
     }
@@ -101,6 +113,8 @@ impl<'a> ImageRef<'a> {
         })
     }
 
+    /// # Safety
+    /// `i` and `j` have to be inside of the image
     pub unsafe fn get_pixel_unchecked(&self, i: u32, j: u32) -> Rgb<u8> {
         unsafe {
             // Safety: up to called
@@ -116,6 +130,12 @@ impl<'a> ImageRef<'a> {
         }
     }
 
+    pub fn row(&self, j: u32) -> &'a [Rgb<u8>] {
+        let width = self.size.0 as usize;
+        let start = j as usize * width;
+        &self.pixels[start..start + width]
+    }
+
     pub fn size(&self) -> (u32, u32) {
         self.size
     }
@@ -143,11 +163,53 @@ impl PodMatrix<Rgb<u8>> {
     }
 }
 
+/// How frames get scaled to the cells they're drawn in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Scaler {
+    /// the closest pixel, the fastest by far and blocky
+    Nearest,
+    /// linear, cheap and a little soft
+    #[default]
+    Triangle,
+    /// Catmull-Rom, sharper than triangle
+    Catmull,
+    /// the sharpest, at the most work, for bringing big videos down to small terminals
+    Lanczos3,
+}
+
+impl Scaler {
+    const ALL: [Scaler; 4] = [
+        Scaler::Nearest,
+        Scaler::Triangle,
+        Scaler::Catmull,
+        Scaler::Lanczos3,
+    ];
+
+    /// the one after this, going back to the first after the last
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    pub(super) fn from_index(index: u8) -> Self {
+        Self::ALL[usize::from(index) % Self::ALL.len()]
+    }
+
+    fn filter(self) -> resize::Type {
+        match self {
+            Scaler::Nearest => resize::Type::Point,
+            Scaler::Triangle => resize::Type::Triangle,
+            Scaler::Catmull => resize::Type::Catrom,
+            Scaler::Lanczos3 => resize::Type::Lanczos3,
+        }
+    }
+}
+
 type ResizerInner = resize::Resizer<resize::formats::Rgb<u8, u8>>;
 
 fn make_inner_resizer(
     (src_width, src_height): (NonZero<usize>, NonZero<usize>),
     (dst_width, dst_height): (NonZero<u16>, NonZero<u16>),
+    scaler: Scaler,
 ) -> ResizerInner {
     let to_size = |x: NonZero<u16>| usize::from(x.get());
     let resizer = resize::new(
@@ -156,7 +218,7 @@ fn make_inner_resizer(
         to_size(dst_width),
         to_size(dst_height),
         resize::Pixel::RGB8,
-        resize::Type::Triangle,
+        scaler.filter(),
     );
 
     // the width and height are both non zero
@@ -169,9 +231,17 @@ struct ResizingBuffer {
     resizer: ResizerInner,
 }
 
+/// Scales images to the size they get drawn at, keeping its buffers between frames
 pub struct Resizer {
     image_buffer: PodMatrix<Rgb<u8>>,
     resizing_buffer: Option<ResizingBuffer>,
+    scaler: Scaler,
+}
+
+impl Default for Resizer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Resizer {
@@ -179,6 +249,16 @@ impl Resizer {
         Self {
             image_buffer: PodMatrix::new(),
             resizing_buffer: None,
+            scaler: Scaler::default(),
+        }
+    }
+
+    /// scales with `scaler` from the next frame on, triangle by default
+    pub fn set_scaler(&mut self, scaler: Scaler) {
+        if scaler != self.scaler {
+            self.scaler = scaler;
+            // made again on the next frame
+            self.resizing_buffer = None;
         }
     }
 
@@ -218,14 +298,15 @@ impl Resizer {
 
                 if buffer_changed {
                     buffer.last_src_dimentions = src_dimentions;
-                    buffer.resizer = make_inner_resizer(src_dimentions, dst_dimentions);
+                    buffer.resizer =
+                        make_inner_resizer(src_dimentions, dst_dimentions, self.scaler);
                 }
                 &mut buffer.resizer
             }
             None => {
                 let buff = self.resizing_buffer.insert(ResizingBuffer {
                     last_src_dimentions: src_dimentions,
-                    resizer: make_inner_resizer(src_dimentions, dst_dimentions),
+                    resizer: make_inner_resizer(src_dimentions, dst_dimentions, self.scaler),
                 });
                 &mut buff.resizer
             }
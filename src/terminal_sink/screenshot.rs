@@ -0,0 +1,56 @@
+use crate::terminal_sink::LiveSettings;
+use crate::terminal_sink::video_pipe::SampleReloader;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// how long the saved path stays on screen
+const SHOWN_FOR: Duration = Duration::from_secs(3);
+
+/// encodes the decoded frame as it came out of the pipeline, not what the terminal got to see
+fn save(sample: &gst::Sample, dir: &Path) -> io::Result<PathBuf> {
+    let png = gst_video::convert_sample(
+        sample,
+        &gst::Caps::new_empty_simple("image/png"),
+        gst::ClockTime::from_seconds(5),
+    )
+    .map_err(io::Error::other)?;
+
+    let buffer = png
+        .buffer()
+        .ok_or_else(|| io::Error::other("the encoder gave back no image"))?;
+    let map = buffer.map_readable().map_err(io::Error::other)?;
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("video-less-{millis}.png"));
+    std::fs::write(&path, map.as_slice())?;
+    Ok(path)
+}
+
+/// saves `sample` into `dir` off the render thread, then says where it went
+pub fn spawn(
+    sample: gst::Sample,
+    dir: PathBuf,
+    settings: Arc<LiveSettings>,
+    reloader: SampleReloader,
+) {
+    thread::spawn(move || {
+        let message = match save(&sample, &dir) {
+            Ok(path) => format!("saved {}", path.display()),
+            Err(err) => format!("couldn't save the screenshot: {err}"),
+        };
+        settings.show_message(message, SHOWN_FOR);
+
+        // a paused video doesn't draw by itself, neither to show the message nor to clear it
+        let _ = reloader.reload_sample();
+        thread::sleep(SHOWN_FOR);
+        let _ = reloader.reload_sample();
+    });
+}
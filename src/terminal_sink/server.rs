@@ -0,0 +1,414 @@
+use crate::terminal;
+use crate::terminal_sink::video_pipe::{
+    self, Pulled, QueueMode, SampleConsumer, SampleProducer, SampleReloader,
+};
+use crate::terminal_sink::writer::Output;
+use crate::terminal_sink::{Screen, TerminalSizeLoadResult, TerminalSizeLoader};
+use parking_lot::Mutex;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// what clients that don't tell their size get drawn at
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+/// a client that can't take a frame within this long gets dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+/// how often the thread taking connections checks whether the server is still around
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+/// the most of a subnegotiation that's kept, NAWS takes 5, the rest of a longer one is dropped
+const MAX_SUBNEGOTIATION: usize = 64;
+
+// the telnet bytes that matter here, see RFC 854 and RFC 1073 for NAWS
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const ECHO: u8 = 1;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+const NAWS: u8 = 31;
+
+/// asks for the window size and keeps the client from echoing or buffering lines
+const NEGOTIATION: [u8; 9] = [IAC, DO, NAWS, IAC, WILL, ECHO, IAC, WILL, SUPPRESS_GO_AHEAD];
+
+/// resets colors, clears and shows the cursor again for a client leaving
+const GOODBYE: &str = "\x1b[0m\x1b[2J\x1b[H\x1b[?25h";
+
+/// The window size a client last told
+#[derive(Default)]
+struct ClientSize {
+    size: Mutex<Option<(u16, u16)>>,
+    changed: AtomicBool,
+}
+
+impl TerminalSizeLoader for Arc<ClientSize> {
+    fn load(&self) -> TerminalSizeLoadResult {
+        TerminalSizeLoadResult {
+            changed: self.changed.swap(false, Ordering::Relaxed),
+            size: self.size.lock().unwrap_or(DEFAULT_SIZE),
+        }
+    }
+}
+
+/// What a client's renderer writes to, a client that stopped taking frames gets hung up on
+/// instead of failing the pipeline everyone watches
+struct ClientOutput {
+    stream: TcpStream,
+    producer: SampleProducer,
+}
+
+impl Write for ClientOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.stream.write(buf) {
+            Err(err) if err.kind() != io::ErrorKind::Interrupted => {
+                // the renderer stops at its next sample, the reader once the socket is shut
+                self.producer.close();
+                let _ = self.stream.shutdown(Shutdown::Both);
+                Ok(buf.len())
+            }
+            res => res,
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output for ClientOutput {
+    fn wait_writable(&self) -> io::Result<()> {
+        // the socket blocks, so a write never says it would
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+}
+
+struct Client {
+    id: u64,
+    stream: TcpStream,
+    /// the client's own renderer takes its samples from here
+    producer: SampleProducer,
+    reloader: SampleReloader,
+    size: Arc<ClientSize>,
+    renderer: Option<JoinHandle<()>>,
+}
+
+impl Client {
+    fn hang_up(mut self) {
+        self.producer.close();
+        if let Some(renderer) = self.renderer.take() {
+            let _ = renderer.join();
+        }
+        let _ = (&self.stream).write_all(GOODBYE.as_bytes());
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// draws the samples of one client at its own size until its pipe gets closed
+type Renderer = Arc<dyn Fn(SampleConsumer, Screen) + Send + Sync>;
+
+/// A TCP/telnet server handing every sample to a renderer per client,
+/// so each one gets frames fitted to its own window
+pub struct Server {
+    clients: Mutex<Vec<Client>>,
+    /// the sample shown last, so whoever joins while paused has something to look at
+    last: Mutex<Option<gst::Sample>>,
+    renderer: Renderer,
+    next_id: AtomicU64,
+}
+
+impl Server {
+    pub fn bind(
+        addr: SocketAddr,
+        renderer: impl Fn(SampleConsumer, Screen) + Send + Sync + 'static,
+    ) -> io::Result<Arc<Self>> {
+        let listener = TcpListener::bind(addr)?;
+        // blocking on accept would only notice the server is gone at the next connection
+        listener.set_nonblocking(true)?;
+        let server = Arc::new(Self {
+            clients: Mutex::new(vec![]),
+            last: Mutex::new(None),
+            renderer: Arc::new(renderer),
+            next_id: AtomicU64::new(0),
+        });
+
+        let weak = Arc::downgrade(&server);
+        thread::spawn(move || {
+            loop {
+                let accepted = listener.accept();
+                let Some(server) = weak.upgrade() else {
+                    return;
+                };
+                match accepted {
+                    // some platforms hand out connections as nonblocking as the listener
+                    Ok((stream, _)) if stream.set_nonblocking(false).is_ok() => server.join(stream),
+                    Ok(_) => {}
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        drop(server);
+                        thread::sleep(ACCEPT_POLL);
+                    }
+                    Err(_) => {}
+                }
+            }
+        });
+
+        Ok(server)
+    }
+
+    fn join(self: &Arc<Self>, mut stream: TcpStream) {
+        let hello = [&NEGOTIATION[..], terminal::HIDE_CURSOR.as_bytes()].concat();
+        if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err()
+            || stream.write_all(&hello).is_err()
+        {
+            return;
+        }
+
+        let (Ok(reader), Ok(output)) = (stream.try_clone(), stream.try_clone()) else {
+            return;
+        };
+
+        let (producer, consumer) = video_pipe::video_pipe(QueueMode::Latest, video_pipe::CAPACITY);
+        if let Some(last) = self.last.lock().clone() {
+            let _ = producer.push_sample(last);
+        }
+
+        let size = Arc::<ClientSize>::default();
+        let reloader = consumer.make_reloader();
+        let screen = Screen {
+            output: Box::new(ClientOutput {
+                stream: output,
+                producer: producer.clone(),
+            }),
+            restore: Box::new(()),
+            size: Some(Box::new(Arc::clone(&size))),
+        };
+        let renderer = Arc::clone(&self.renderer);
+        let renderer = thread::spawn(move || renderer(consumer, screen));
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.clients.lock().push(Client {
+            id,
+            stream,
+            producer,
+            reloader,
+            size,
+            renderer: Some(renderer),
+        });
+
+        let weak = Arc::downgrade(self);
+        thread::spawn(move || read_client(weak, id, reader));
+    }
+
+    fn resize(&self, id: u64, size: (u16, u16)) {
+        let clients = self.clients.lock();
+        if let Some(client) = clients.iter().find(|client| client.id == id) {
+            *client.size.size.lock() = Some(size);
+            client.size.changed.store(true, Ordering::Relaxed);
+            // a paused video doesn't draw by itself
+            let _ = client.reloader.reload_sample();
+        }
+    }
+
+    fn leave(&self, id: u64) {
+        let mut clients = self.clients.lock();
+        if let Some(index) = clients.iter().position(|client| client.id == id) {
+            let mut client = clients.swap_remove(index);
+            drop(clients);
+            // the renderer might be stuck writing to a client that stopped reading
+            client.renderer = None;
+            client.hang_up();
+        }
+    }
+
+    fn broadcast(&self, sample: gst::Sample) {
+        for client in self.clients.lock().iter() {
+            let _ = client.producer.push_sample(sample.clone());
+        }
+        *self.last.lock() = Some(sample);
+    }
+
+    fn show_loading(&self) {
+        for client in self.clients.lock().iter() {
+            client.producer.show_loading();
+        }
+        *self.last.lock() = None;
+    }
+
+    fn show_buffering(&self, percent: u8) {
+        for client in self.clients.lock().iter() {
+            client.producer.show_buffering(percent);
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        for client in self.clients.get_mut().drain(..) {
+            client.hang_up();
+        }
+    }
+}
+
+/// hands out the samples coming from the pipeline until it closes, then hangs up on everyone
+pub fn distribute(consumer: SampleConsumer, server: Arc<Server>) {
+    loop {
+        match consumer.pull() {
+            Ok(Pulled::Sample(sample)) => server.broadcast(sample),
+            Ok(Pulled::Loading) => server.show_loading(),
+            Ok(Pulled::Buffering(percent)) => server.show_buffering(percent),
+            Err(()) => break,
+        }
+    }
+}
+
+/// Where a client's input is in the middle of a telnet command
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Telnet {
+    Data,
+    Command,
+    /// the option of a WILL, WONT, DO or DONT
+    Option,
+    Subnegotiation,
+    SubnegotiationCommand,
+}
+
+/// What a client's keys mean to the server
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Input {
+    Resize(u16, u16),
+    Quit,
+}
+
+/// Splits what a client sends into telnet commands and keys
+struct Parser {
+    state: Telnet,
+    subnegotiation: Vec<u8>,
+}
+
+impl Parser {
+    fn new() -> Self {
+        Self {
+            state: Telnet::Data,
+            subnegotiation: vec![],
+        }
+    }
+
+    fn feed(&mut self, byte: u8) -> Option<Input> {
+        self.state = match (self.state, byte) {
+            (Telnet::Data, IAC) => Telnet::Command,
+            // q or ctrl-c
+            (Telnet::Data, b'q' | b'Q' | 3) => return Some(Input::Quit),
+            (Telnet::Data, _) => Telnet::Data,
+
+            (Telnet::Command, WILL | WONT | DO | DONT) => Telnet::Option,
+            (Telnet::Command, SB) => {
+                self.subnegotiation.clear();
+                Telnet::Subnegotiation
+            }
+            (Telnet::Command, _) | (Telnet::Option, _) => Telnet::Data,
+
+            (Telnet::Subnegotiation, IAC) => Telnet::SubnegotiationCommand,
+            (Telnet::Subnegotiation, _) => {
+                self.keep(byte);
+                Telnet::Subnegotiation
+            }
+            // a doubled IAC is a 255 in the data
+            (Telnet::SubnegotiationCommand, IAC) => {
+                self.keep(IAC);
+                Telnet::Subnegotiation
+            }
+            (Telnet::SubnegotiationCommand, SE) => {
+                self.state = Telnet::Data;
+                return match self.subnegotiation[..] {
+                    [NAWS, w1, w2, h1, h2] => Some(Input::Resize(
+                        u16::from_be_bytes([w1, w2]),
+                        u16::from_be_bytes([h1, h2]),
+                    )),
+                    _ => None,
+                };
+            }
+            (Telnet::SubnegotiationCommand, _) => Telnet::Data,
+        };
+
+        None
+    }
+
+    /// adds `byte` to the subnegotiation, unless it's already longer than any that matters
+    fn keep(&mut self, byte: u8) {
+        if self.subnegotiation.len() < MAX_SUBNEGOTIATION {
+            self.subnegotiation.push(byte);
+        }
+    }
+}
+
+fn read_client(server: Weak<Server>, id: u64, mut stream: TcpStream) {
+    let mut parser = Parser::new();
+    let mut buffer = [0; 256];
+
+    loop {
+        let read = match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+
+        let Some(server) = server.upgrade() else {
+            return;
+        };
+
+        for &byte in &buffer[..read] {
+            match parser.feed(byte) {
+                Some(Input::Resize(width, height)) if width > 0 && height > 0 => {
+                    server.resize(id, (width, height))
+                }
+                Some(Input::Quit) => return server.leave(id),
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(server) = server.upgrade() {
+        server.leave(id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn window_size_gets_read() {
+        let mut parser = Parser::new();
+        let input = [IAC, WILL, NAWS, IAC, SB, NAWS, 0, 120, 0, 40, IAC, SE];
+        let parsed = input
+            .into_iter()
+            .filter_map(|byte| parser.feed(byte))
+            .collect::<Vec<_>>();
+        assert_eq!(parsed, [Input::Resize(120, 40)]);
+
+        // 255 columns are sent as a doubled IAC
+        let input = [IAC, SB, NAWS, 0, IAC, IAC, 0, 50, IAC, SE, b'q'];
+        let parsed = input
+            .into_iter()
+            .filter_map(|byte| parser.feed(byte))
+            .collect::<Vec<_>>();
+        assert_eq!(parsed, [Input::Resize(255, 50), Input::Quit]);
+    }
+
+    #[test]
+    fn long_subnegotiations_get_dropped() {
+        let mut parser = Parser::new();
+        let mut input = vec![IAC, SB, NAWS, 0, 120, 0, 40];
+        input.extend([7; 10_000]);
+        input.extend([IAC, SE, b'q']);
+        let parsed = input
+            .into_iter()
+            .filter_map(|byte| parser.feed(byte))
+            .collect::<Vec<_>>();
+        assert_eq!(parsed, [Input::Quit]);
+        assert_eq!(parser.subnegotiation.len(), MAX_SUBNEGOTIATION);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::terminal_sink::diff::Cell;
+
+const CHUNK_CELLS: usize = 32;
+const CHUNK_BYTES: usize = CHUNK_CELLS * size_of::<Cell>();
+
+/// a bit per byte of a chunk, set when the byte differs
+type ByteMask = [u64; CHUNK_BYTES / 64];
+
+/// what every byte of 16 bytes of cells gets anded with, cells never straddle them
+type LaneMask = [u8; 16];
+
+const _: () = assert!(size_of::<LaneMask>() % size_of::<Cell>() == 0);
+
+/// quantizes the colors of cells to `mask`, leaving their symbols as they are
+fn lane_mask(mask: u8) -> LaneMask {
+    let cell = Cell::color_mask(mask);
+    let cell = bytemuck::bytes_of(&cell);
+    std::array::from_fn(|i| cell[i % cell.len()])
+}
+
+fn quantize_and_compare_scalar(new: &mut [u8], old: &[u8], mask: &LaneMask) -> ByteMask {
+    let mut differs = ByteMask::default();
+    for (i, (new, &old)) in new.iter_mut().zip(old).enumerate() {
+        *new &= mask[i % mask.len()];
+        differs[i / 64] |= u64::from(*new != old) << (i % 64);
+    }
+    differs
+}
+
+// sse2 is part of the x86_64 baseline, so there is nothing to detect at runtime
+#[cfg(target_arch = "x86_64")]
+fn quantize_and_compare(new: &mut [u8], old: &[u8], mask: &LaneMask) -> ByteMask {
+    use std::arch::x86_64::*;
+
+    assert!(new.len() == CHUNK_BYTES && old.len() == CHUNK_BYTES);
+
+    let mut differs = ByteMask::default();
+    // Safety: both chunks were checked to be CHUNK_BYTES long
+    unsafe {
+        let mask = _mm_loadu_si128(mask.as_ptr().cast::<__m128i>());
+        for lane in 0..CHUNK_BYTES / 16 {
+            let new = new.as_mut_ptr().add(lane * 16).cast::<__m128i>();
+            let old = old.as_ptr().add(lane * 16).cast::<__m128i>();
+            let quantized = _mm_and_si128(_mm_loadu_si128(new), mask);
+            _mm_storeu_si128(new, quantized);
+            let equal = _mm_cmpeq_epi8(quantized, _mm_loadu_si128(old));
+
+            let lane_differs = !(_mm_movemask_epi8(equal) as u32) & 0xFFFF;
+            differs[lane / 4] |= u64::from(lane_differs) << ((lane % 4) * 16);
+        }
+    }
+    differs
+}
+
+// neon is part of the aarch64 baseline, so there is nothing to detect at runtime
+#[cfg(target_arch = "aarch64")]
+fn quantize_and_compare(new: &mut [u8], old: &[u8], mask: &LaneMask) -> ByteMask {
+    use std::arch::aarch64::*;
+
+    assert!(new.len() == CHUNK_BYTES && old.len() == CHUNK_BYTES);
+
+    let mut differs = ByteMask::default();
+    // Safety: both chunks were checked to be CHUNK_BYTES long
+    unsafe {
+        let mask = vld1q_u8(mask.as_ptr());
+        for lane in 0..CHUNK_BYTES / 16 {
+            let new = new.as_mut_ptr().add(lane * 16);
+            let quantized = vandq_u8(vld1q_u8(new), mask);
+            vst1q_u8(new, quantized);
+            let equal = vceqq_u8(quantized, vld1q_u8(old.as_ptr().add(lane * 16)));
+
+            // there is no movemask on neon, narrow every byte to a nibble instead
+            let nibbles = vshrn_n_u16::<4>(vreinterpretq_u16_u8(equal));
+            let nibbles = vget_lane_u64::<0>(vreinterpret_u64_u8(nibbles));
+
+            // then squash the low bit of every nibble of a differing byte together
+            let mut lane_differs = !nibbles & 0x1111_1111_1111_1111;
+            lane_differs = (lane_differs | (lane_differs >> 3)) & 0x0303_0303_0303_0303;
+            lane_differs = (lane_differs | (lane_differs >> 6)) & 0x000F_000F_000F_000F;
+            lane_differs = (lane_differs | (lane_differs >> 12)) & 0x0000_00FF_0000_00FF;
+            lane_differs = (lane_differs | (lane_differs >> 24)) & 0xFFFF;
+
+            differs[lane / 4] |= lane_differs << ((lane % 4) * 16);
+        }
+    }
+    differs
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn quantize_and_compare(new: &mut [u8], old: &[u8], mask: &LaneMask) -> ByteMask {
+    quantize_and_compare_scalar(new, old, mask)
+}
+
+/// folds the byte mask of a chunk into a bit per cell
+fn cells_changed(differs: ByteMask) -> u32 {
+    let mut changed = 0;
+    for cell in 0..CHUNK_CELLS {
+        let bit = cell * size_of::<Cell>();
+        let (word, shift) = (bit / 64, bit % 64);
+
+        let mut bytes = differs[word] >> shift;
+        if shift + size_of::<Cell>() > 64 {
+            bytes |= differs[word + 1] << (64 - shift);
+        }
+
+        if bytes & ((1 << size_of::<Cell>()) - 1) != 0 {
+            changed |= 1 << cell;
+        }
+    }
+    changed
+}
+
+/// quantizes the colors of `new` in place with `mask` and sets bit `i` of `changed`
+/// for every cell `i` that then differs from `old`
+pub fn quantize_and_diff(new: &mut [Cell], old: &[Cell], mask: u8, changed: &mut Vec<u64>) {
+    assert_eq!(new.len(), old.len());
+
+    let cells = new.len();
+    changed.clear();
+    changed.resize(cells.div_ceil(64), 0);
+
+    let mask = lane_mask(mask);
+    let new: &mut [u8] = bytemuck::cast_slice_mut(new);
+    let old: &[u8] = bytemuck::cast_slice(old);
+
+    let full_chunks = cells / CHUNK_CELLS;
+    let (new_chunks, new_tail) = new.split_at_mut(full_chunks * CHUNK_BYTES);
+    let (old_chunks, old_tail) = old.split_at(new_chunks.len());
+
+    let chunks = new_chunks
+        .chunks_exact_mut(CHUNK_BYTES)
+        .zip(old_chunks.chunks_exact(CHUNK_BYTES));
+
+    for (chunk, (new, old)) in chunks.enumerate() {
+        let chunk_changed = cells_changed(quantize_and_compare(new, old, &mask));
+        changed[chunk / 2] |= u64::from(chunk_changed) << ((chunk % 2) * 32);
+    }
+
+    // the tail is shorter than a chunk, so it fits in a single byte mask
+    if !new_tail.is_empty() {
+        let chunk = full_chunks;
+        let tail_changed = cells_changed(quantize_and_compare_scalar(new_tail, old_tail, &mask));
+        changed[chunk / 2] |= u64::from(tail_changed) << ((chunk % 2) * 32);
+    }
+}
+
+/// finds the first bit at or after `from` that is `value`, or `len` if there is none
+pub fn next_bit(bits: &[u64], from: usize, len: usize, value: bool) -> usize {
+    let mut i = from;
+    while i < len {
+        let word = match value {
+            true => bits[i / 64],
+            false => !bits[i / 64],
+        };
+
+        let word = word >> (i % 64);
+        if word != 0 {
+            return (i + word.trailing_zeros() as usize).min(len);
+        }
+
+        i = (i / 64 + 1) * 64;
+    }
+    len
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    fn noise(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunks_match_scalar() {
+        for (seed, mask) in [(1, 0xFF), (2, 0xF8), (3, 0x80)] {
+            let old = noise(seed, CHUNK_BYTES);
+            let mut new = noise(seed + 100, CHUNK_BYTES);
+            // leave some bytes and cells equal
+            new[..40].copy_from_slice(&old[..40]);
+            new[100] = old[100];
+
+            let mask = lane_mask(mask);
+            let mut scalar = new.clone();
+            let expected = quantize_and_compare_scalar(&mut scalar, &old, &mask);
+            let got = quantize_and_compare(&mut new, &old, &mask);
+            assert_eq!(got, expected);
+            assert_eq!(new, scalar);
+        }
+    }
+
+    #[test]
+    fn diff_marks_changed_cells() {
+        let old = vec![Cell::zeroed(); 75];
+        let mut new = old.clone();
+        for i in [0, 10, 31, 32, 63, 64, 70, 74] {
+            bytemuck::bytes_of_mut(&mut new[i])[i % 6] = 0xFF;
+        }
+        // quantized away
+        bytemuck::bytes_of_mut(&mut new[40])[0] = 0x01;
+        // symbols aren't quantized
+        bytemuck::bytes_of_mut(&mut new[50])[6] = 0x01;
+
+        let mut changed = vec![];
+        quantize_and_diff(&mut new, &old, 0xF0, &mut changed);
+
+        let set = (0..75)
+            .filter(|&i| changed[i / 64] & (1 << (i % 64)) != 0)
+            .collect::<Vec<_>>();
+        assert_eq!(set, [0, 10, 31, 32, 50, 63, 64, 70, 74]);
+        assert!(new[40] == Cell::zeroed());
+    }
+
+    #[test]
+    fn next_bit_finds_runs() {
+        let bits = [0b1110 | (1 << 63), 0b1];
+        assert_eq!(next_bit(&bits, 0, 100, true), 1);
+        assert_eq!(next_bit(&bits, 1, 100, false), 4);
+        assert_eq!(next_bit(&bits, 4, 100, true), 63);
+        assert_eq!(next_bit(&bits, 63, 100, false), 65);
+        assert_eq!(next_bit(&bits, 65, 100, true), 100);
+        assert_eq!(next_bit(&bits, 65, 100, false), 65);
+    }
+}
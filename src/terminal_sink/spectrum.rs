@@ -0,0 +1,96 @@
+//! The bars the v key draws along the bottom of the video, from what the `spectrum` element
+//! of the sound posts, as part of the overlay so they go over the frame without it flickering
+use crate::terminal_sink::VideoArea;
+use crate::terminal_sink::overlay::{Backdrop, Layer, Overlay, TextStyle};
+use rgb::Rgb;
+
+/// an eighth of a cell more of a bar each
+const EIGHTHS: [char; 8] = [
+    '\u{2581}', '\u{2582}', '\u{2583}', '\u{2584}', '\u{2585}', '\u{2586}', '\u{2587}', '\u{2588}',
+];
+
+/// how much of the video the bars get to be tall, at most this many rows
+const MAX_ROWS: u16 = 8;
+
+const STYLE: TextStyle = TextStyle {
+    fg: Rgb::new(80, 220, 120),
+    backdrop: Backdrop::Dim(40),
+};
+
+/// how loud each of `columns` is, the bands spread over them on a log scale
+/// so the low ones where most of the sound is don't get squeezed into a few columns
+fn columns(levels: &[f32], columns: u16) -> Vec<f32> {
+    let bands = levels.len() as f64;
+    (0..columns)
+        .map(|column| {
+            // from 1 to the number of bands
+            let at = |column: u16| bands.powf(f64::from(column) / f64::from(columns));
+            let start = at(column) as usize - 1;
+            let end = (at(column + 1).ceil() as usize).clamp(start + 1, levels.len());
+            let covered = levels.get(start..end).unwrap_or_default();
+            covered.iter().copied().fold(0.0, f32::max)
+        })
+        .collect()
+}
+
+/// the lines of `rows` rows of bars `levels` high, top one first
+fn bars(levels: &[f32], rows: u16) -> Vec<String> {
+    (0..rows)
+        .rev()
+        .map(|row| {
+            levels
+                .iter()
+                .map(|level| {
+                    // in eighths of a cell from the bottom of this row
+                    let eighths =
+                        (level * f32::from(rows) * 8.0).round() as i32 - i32::from(row) * 8;
+                    match eighths {
+                        ..=0 => ' ',
+                        1..=8 => EIGHTHS[eighths as usize - 1],
+                        _ => EIGHTHS[7],
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// the bars of `levels` along the bottom of `video`, a quarter of it tall
+pub(super) fn overlay(levels: &[f32], video: VideoArea) -> Overlay {
+    let mut overlay = Overlay::new();
+    let rows = (video.height / 4).clamp(1, MAX_ROWS);
+    if levels.is_empty() || video.height < rows {
+        return overlay;
+    }
+
+    let top = video.height - rows;
+    let lines = bars(&columns(levels, video.width), rows);
+    for (row, line) in (top..).zip(lines) {
+        overlay.text(Layer::Osd, (0, row), &line, STYLE);
+    }
+    overlay
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bars_grow_from_the_bottom() {
+        assert_eq!(
+            bars(&[0.0, 0.5, 1.0], 2),
+            ["  \u{2588}", " \u{2588}\u{2588}"]
+        );
+        assert_eq!(bars(&[1.0 / 16.0], 1), ["\u{2581}"]);
+    }
+
+    #[test]
+    fn every_column_gets_a_band() {
+        let levels = (0..64).map(|band| band as f32 / 64.0).collect::<Vec<_>>();
+        let columns = columns(&levels, 10);
+        assert_eq!(columns.len(), 10);
+        assert!(columns.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(columns[9], 63.0 / 64.0);
+        assert_eq!(columns[0], 0.0);
+    }
+}
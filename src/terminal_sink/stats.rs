@@ -0,0 +1,205 @@
+use gst::prelude::ElementExtManual;
+use gst_app::AppSink;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+pub struct RenderStats {
+    rendered: AtomicU64,
+    dropped_late: AtomicU64,
+    dropped_output: AtomicU64,
+    render_latency_micros: AtomicU64,
+    frame_bytes: AtomicU64,
+}
+
+impl RenderStats {
+    pub fn rendered(&self) -> u64 {
+        self.rendered.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped_late(&self) -> u64 {
+        self.dropped_late.load(Ordering::Relaxed)
+    }
+
+    /// frames the terminal couldn't take in time
+    pub fn dropped_output(&self) -> u64 {
+        self.dropped_output.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn record_output_drop(&self) {
+        self.dropped_output.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// moving average of how long it takes to get a frame onto the terminal
+    pub fn render_latency(&self) -> Duration {
+        Duration::from_micros(self.render_latency_micros.load(Ordering::Relaxed))
+    }
+
+    /// moving average of how many bytes of escape sequences a frame takes
+    pub fn frame_bytes(&self) -> u64 {
+        self.frame_bytes.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn record_frame_size(&self, bytes: usize) {
+        let bytes = bytes as u64;
+        let _ = self
+            .frame_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |average| {
+                // the same weight of 1/8 for the newest frame as the latency
+                Some((average * 7 + bytes) / 8)
+            });
+    }
+}
+
+/// Frames per second of decoding and rendering, for the stats overlay
+pub struct Meter {
+    since: Instant,
+    decoded: u64,
+    rendered: u64,
+    decode_fps: f64,
+    render_fps: f64,
+}
+
+impl Meter {
+    /// how long the counts are taken over
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    pub fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            decoded: 0,
+            rendered: 0,
+            decode_fps: 0.0,
+            render_fps: 0.0,
+        }
+    }
+
+    /// the lines of the overlay, `decoded` and `dropped_in_queue` counted by the pipe
+    pub fn describe(&mut self, stats: &RenderStats, decoded: u64, dropped_in_queue: u64) -> String {
+        let elapsed = self.since.elapsed();
+        if elapsed >= Self::WINDOW {
+            let rate =
+                |now: u64, then: u64| now.saturating_sub(then) as f64 / elapsed.as_secs_f64();
+            self.decode_fps = rate(decoded, self.decoded);
+            self.render_fps = rate(stats.rendered(), self.rendered);
+            self.since = Instant::now();
+            self.decoded = decoded;
+            self.rendered = stats.rendered();
+        }
+
+        format!(
+            "decode  {:>6.1} fps\n\
+             render  {:>6.1} fps\n\
+             dropped {dropped_in_queue:>6} queue {:>6} late {:>6} output\n\
+             frame   {:>6.1} kB\n\
+             latency {:>6.1} ms",
+            self.decode_fps,
+            self.render_fps,
+            stats.dropped_late(),
+            stats.dropped_output(),
+            stats.frame_bytes() as f64 / 1000.0,
+            stats.render_latency().as_secs_f64() * 1000.0,
+        )
+    }
+}
+
+impl Display for RenderStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rendered {} frames, dropped {} late frames and {} the terminal couldn't take, render latency {:.1?}",
+            self.rendered(),
+            self.dropped_late(),
+            self.dropped_output(),
+            self.render_latency()
+        )
+    }
+}
+
+/// how late the sample already is, compared to when it should have been shown
+fn lateness(sample: &gst::Sample, app_sink: &AppSink) -> Option<gst::ClockTime> {
+    let pts = sample.buffer()?.pts()?;
+    let segment = sample.segment()?.downcast_ref::<gst::ClockTime>()?;
+    let running_time = segment.to_running_time(pts)?;
+    let now = app_sink.current_running_time()?;
+    now.checked_sub(running_time)
+}
+
+/// Skips samples that can't make it to the terminal before the next one is due
+/// by keeping track of how long rendering takes
+pub struct FrameDropper {
+    enabled: bool,
+    average_latency: Duration,
+    consecutive_drops: u32,
+    stats: Arc<RenderStats>,
+}
+
+impl FrameDropper {
+    // always show something, even if the terminal simply can't keep up
+    const MAX_CONSECUTIVE_DROPS: u32 = 4;
+    const FALLBACK_FRAME_DURATION: gst::ClockTime = gst::ClockTime::from_mseconds(33);
+
+    pub fn new(enabled: bool, stats: Arc<RenderStats>) -> Self {
+        Self {
+            enabled,
+            average_latency: Duration::ZERO,
+            consecutive_drops: 0,
+            stats,
+        }
+    }
+
+    pub fn should_drop(&mut self, sample: &gst::Sample, app_sink: &AppSink) -> bool {
+        // when paused the clock keeps on going, but the same sample stays on screen
+        if !self.enabled
+            || self.consecutive_drops >= Self::MAX_CONSECUTIVE_DROPS
+            || app_sink.current_state() != gst::State::Playing
+        {
+            self.consecutive_drops = 0;
+            return false;
+        }
+
+        let Some(lateness) = lateness(sample, app_sink) else {
+            return false;
+        };
+        // timestamps that jumped, as live streams' do, say nothing about rendering falling behind,
+        // however far behind it is otherwise dropping is how it catches up
+        let discont = sample
+            .buffer()
+            .is_some_and(|buffer| buffer.flags().contains(gst::BufferFlags::DISCONT));
+        if discont {
+            self.consecutive_drops = 0;
+            return false;
+        }
+
+        let duration = sample
+            .buffer()
+            .and_then(|buffer| buffer.duration())
+            .unwrap_or(Self::FALLBACK_FRAME_DURATION);
+
+        let expected_latency = gst::ClockTime::from_nseconds(
+            u64::try_from(self.average_latency.as_nanos()).unwrap_or(u64::MAX),
+        );
+
+        // by the time it would be drawn its time on screen would be over
+        let drop = lateness.saturating_add(expected_latency) > duration;
+        if drop {
+            self.consecutive_drops += 1;
+            self.stats.dropped_late.fetch_add(1, Ordering::Relaxed);
+        }
+        drop
+    }
+
+    pub fn rendered(&mut self, latency: Duration) {
+        self.consecutive_drops = 0;
+        // exponential moving average, with a weight of 1/8 for the newest frame
+        self.average_latency = (self.average_latency * 7 + latency) / 8;
+
+        let micros = u64::try_from(self.average_latency.as_micros()).unwrap_or(u64::MAX);
+        self.stats
+            .render_latency_micros
+            .store(micros, Ordering::Relaxed);
+        self.stats.rendered.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,493 @@
+//! `--render symbols`, every cell drawn as whichever glyph of a set, in whichever two colors,
+//! comes closest to the pixels of the frame it covers, the way chafa's symbol mode does,
+//! which brings out a lot more detail than two pixels a cell
+use crate::terminal_sink::diff::Cell;
+use crate::terminal_sink::resize::{ImageRef, PodMatrix};
+use rgb::Rgb;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// the pixels across and down the built in symbols are drawn at,
+/// and a cell gets matched against when the size of the font isn't known
+pub const CELL_PIXELS: (u16, u16) = (8, 16);
+
+/// the most pixels a cell gets matched against, a bit each of a coverage
+const MAX_PIXELS: u32 = u128::BITS;
+
+/// the pixels across and down a cell of a font `font_cell` pixels big gets matched at,
+/// as many as there are bits in a coverage with about the shape of the font's
+pub fn matching_grid((width, height): (u16, u16)) -> (u16, u16) {
+    let (width, height) = (f64::from(width.max(1)), f64::from(height.max(1)));
+    (1..=MAX_PIXELS)
+        .rev()
+        .map(|rows| {
+            let columns = (f64::from(rows) * width / height).round().max(1.0) as u32;
+            (columns, rows)
+        })
+        .find(|&(columns, rows)| columns * rows <= MAX_PIXELS)
+        .map_or(CELL_PIXELS, |(columns, rows)| (columns as u16, rows as u16))
+}
+
+/// A glyph a cell can be drawn as and a bitmap of the part of the cell it covers,
+/// at whatever size it was drawn at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub text: Box<str>,
+    /// how many pixels across the rows of `bitmap` are
+    width: u16,
+    /// row by row from the top left, set for the pixels drawn in the foreground color
+    bitmap: Box<[bool]>,
+}
+
+impl Symbol {
+    /// `text` covering the pixels of a cell [`CELL_PIXELS`] big that `covered` is true for,
+    /// given the column and row
+    pub fn new(text: &str, covered: impl Fn(u32, u32) -> bool) -> Self {
+        let (width, height) = (u32::from(CELL_PIXELS.0), u32::from(CELL_PIXELS.1));
+        let bitmap = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| covered(x, y))
+            .collect();
+        Self {
+            text: text.into(),
+            width: CELL_PIXELS.0,
+            bitmap,
+        }
+    }
+
+    /// a bit for each of the pixels of a cell `grid` pixels across and down, row by row
+    /// from the top left, set for the ones with a covered pixel of the bitmap under their middle
+    fn coverage(&self, (columns, rows): (u16, u16)) -> u128 {
+        let width = usize::from(self.width);
+        let height = self.bitmap.len() / width;
+        let (columns, rows) = (usize::from(columns), usize::from(rows));
+        (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| (x, y)))
+            .enumerate()
+            .filter(|&(_, (x, y))| {
+                let column = (2 * x + 1) * width / (2 * columns);
+                let row = (2 * y + 1) * height / (2 * rows);
+                self.bitmap[row * width + column]
+            })
+            .fold(0, |coverage, (bit, _)| coverage | 1 << bit)
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = String;
+
+    /// `TEXT=BITMAP`, the rows of the bitmap split by `/` with a `#` for every pixel of the font
+    /// the glyph covers and a `.` for the rest, like `▀=####/####/..../....` in a font
+    /// 4 pixels across and 4 down, best drawn at the size of `--font-cell`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((text, bitmap)) = s.rsplit_once('=') else {
+            return Err(format!("expected TEXT=BITMAP, not {s:?}"));
+        };
+        if text.is_empty() {
+            return Err(format!("{s:?} has a bitmap but no text"));
+        }
+
+        let rows = bitmap
+            .split('/')
+            .map(|row| {
+                row.chars()
+                    .map(|pixel| match pixel {
+                        '#' => Ok(true),
+                        '.' => Ok(false),
+                        pixel => Err(format!(
+                            "the bitmap of {text:?} has a {pixel:?}, expected only # and ."
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let width = rows[0].len();
+        if width == 0 || rows.iter().any(|row| row.len() != width) {
+            return Err(format!(
+                "the rows of the bitmap of {text:?} need the same number of pixels, at least one"
+            ));
+        }
+        let Ok(width) = u16::try_from(width) else {
+            return Err(format!("the bitmap of {text:?} is too wide"));
+        };
+
+        Ok(Self {
+            text: text.into(),
+            width,
+            bitmap: rows.into_iter().flatten().collect(),
+        })
+    }
+}
+
+/// the groups of symbols a set can be made of, in the order they're listed
+const CLASSES: &[(&str, fn() -> Vec<Symbol>)] = &[
+    ("half", half),
+    ("quadrants", quadrants),
+    ("eighths", eighths),
+    ("shades", shades),
+    ("wedges", wedges),
+    ("ascii", ascii),
+];
+
+/// the classes names that stand for several
+const ALIASES: &[(&str, &[&str])] = &[
+    ("blocks", &["half", "quadrants", "eighths"]),
+    (
+        "all",
+        &["half", "quadrants", "eighths", "shades", "wedges", "ascii"],
+    ),
+];
+
+fn half() -> Vec<Symbol> {
+    vec![
+        Symbol::new("\u{2580}", |_, y| y < 8),
+        Symbol::new("\u{2584}", |_, y| y >= 8),
+        Symbol::new("\u{258C}", |x, _| x < 4),
+        Symbol::new("\u{2590}", |x, _| x >= 4),
+    ]
+}
+
+fn quadrants() -> Vec<Symbol> {
+    let quadrant = |x: u32, y: u32| usize::from(x >= 4) + 2 * usize::from(y >= 8);
+    // which of the top left, top right, bottom left and bottom right each covers
+    let quadrants: [(&str, [bool; 4]); 10] = [
+        ("\u{2598}", [true, false, false, false]),
+        ("\u{259D}", [false, true, false, false]),
+        ("\u{2596}", [false, false, true, false]),
+        ("\u{2597}", [false, false, false, true]),
+        ("\u{259A}", [true, false, false, true]),
+        ("\u{259E}", [false, true, true, false]),
+        ("\u{2599}", [true, false, true, true]),
+        ("\u{259B}", [true, true, true, false]),
+        ("\u{259C}", [true, true, false, true]),
+        ("\u{259F}", [false, true, true, true]),
+    ];
+    quadrants
+        .into_iter()
+        .map(|(text, covers)| Symbol::new(text, |x, y| covers[quadrant(x, y)]))
+        .collect()
+}
+
+fn eighths() -> Vec<Symbol> {
+    // the lower ones from an eighth up, and the left ones from an eighth across,
+    // leaving out the halves
+    let lower = [
+        "\u{2581}", "\u{2582}", "\u{2583}", "", "\u{2585}", "\u{2586}", "\u{2587}",
+    ];
+    let left = [
+        "\u{258F}", "\u{258E}", "\u{258D}", "", "\u{258B}", "\u{258A}", "\u{2589}",
+    ];
+    let lower = (1..)
+        .zip(lower)
+        .map(|(eighths, text)| (text, eighths, true));
+    let left = (1..)
+        .zip(left)
+        .map(|(eighths, text)| (text, eighths, false));
+    lower
+        .chain(left)
+        .filter(|(text, _, _)| !text.is_empty())
+        .map(|(text, eighths, lower)| match lower {
+            true => Symbol::new(text, move |_, y| y >= 16 - 2 * eighths),
+            false => Symbol::new(text, move |x, _| x < eighths),
+        })
+        .collect()
+}
+
+fn shades() -> Vec<Symbol> {
+    vec![
+        Symbol::new("\u{2591}", |x, y| x % 2 == 0 && y % 2 == 0),
+        Symbol::new("\u{2592}", |x, y| (x + y) % 2 == 0),
+        Symbol::new("\u{2593}", |x, y| x % 2 == 0 || y % 2 == 0),
+    ]
+}
+
+fn wedges() -> Vec<Symbol> {
+    // corner to corner, two rows a column of the 8 by 16 they're drawn at
+    vec![
+        Symbol::new("\u{25E2}", |x, y| 2 * x + y >= 15),
+        Symbol::new("\u{25E3}", |x, y| y >= 2 * x),
+        Symbol::new("\u{25E4}", |x, y| 2 * x + y < 15),
+        Symbol::new("\u{25E5}", |x, y| y < 2 * x),
+    ]
+}
+
+fn ascii() -> Vec<Symbol> {
+    let stem = |x: u32| (3..5).contains(&x);
+    vec![
+        Symbol::new(".", move |x, y| stem(x) && (12..14).contains(&y)),
+        Symbol::new(":", move |x, y| {
+            stem(x) && ((4..6).contains(&y) || (11..13).contains(&y))
+        }),
+        Symbol::new("'", move |x, y| stem(x) && (1..5).contains(&y)),
+        Symbol::new("-", |x, y| (1..7).contains(&x) && (7..9).contains(&y)),
+        Symbol::new("_", |_, y| y >= 14),
+        Symbol::new("=", |x, y| {
+            (1..7).contains(&x) && ((5..7).contains(&y) || (9..11).contains(&y))
+        }),
+        Symbol::new("|", move |x, _| stem(x)),
+        Symbol::new("/", |x, y| (2 * x).abs_diff(15 - y) <= 1),
+        Symbol::new("\\", |x, y| (2 * x).abs_diff(y) <= 1),
+        Symbol::new("+", move |x, y| {
+            (stem(x) && (3..13).contains(&y)) || ((1..7).contains(&x) && (7..9).contains(&y))
+        }),
+    ]
+}
+
+/// The glyphs cells can be drawn as, always with a space for the cells of a single color
+#[derive(Debug, Clone)]
+pub struct SymbolSet(Arc<[Symbol]>);
+
+impl FromStr for SymbolSet {
+    type Err = String;
+
+    /// class names joined with `+`, like `blocks+ascii`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut classes = vec![];
+        for name in s.split('+').map(str::trim) {
+            match ALIASES.iter().find(|(alias, _)| *alias == name) {
+                Some((_, names)) => classes.extend_from_slice(names),
+                None if CLASSES.iter().any(|(class, _)| *class == name) => classes.push(name),
+                None => {
+                    let known = CLASSES
+                        .iter()
+                        .map(|(class, _)| *class)
+                        .chain(ALIASES.iter().map(|(alias, _)| *alias))
+                        .collect::<Vec<_>>();
+                    return Err(format!(
+                        "unknown symbols {name:?}, expected some of {} joined with +",
+                        known.join(", ")
+                    ));
+                }
+            }
+        }
+
+        let symbols = CLASSES
+            .iter()
+            .filter(|(class, _)| classes.contains(class))
+            .flat_map(|(_, symbols)| symbols());
+        Ok(Self::new(symbols))
+    }
+}
+
+impl Default for SymbolSet {
+    fn default() -> Self {
+        "blocks".parse().unwrap()
+    }
+}
+
+impl SymbolSet {
+    /// a set of `symbols`, the first of the ones drawing the same text winning
+    pub fn new(symbols: impl IntoIterator<Item = Symbol>) -> Self {
+        let mut set = vec![Symbol::new(" ", |_, _| false)];
+        for symbol in symbols {
+            if !set.iter().any(|known| known.text == symbol.text) {
+                set.push(symbol);
+            }
+        }
+        Self(set.into())
+    }
+
+    /// these and `symbols` too, which win over the ones of the set drawing the same text
+    pub fn with(&self, symbols: impl IntoIterator<Item = Symbol>) -> Self {
+        Self::new(symbols.into_iter().chain(self.0.iter().skip(1).cloned()))
+    }
+
+    /// what the frame drawing cells with these has to draw for their symbols
+    pub(super) fn texts(&self) -> Arc<[Box<str>]> {
+        self.0.iter().map(|symbol| symbol.text.clone()).collect()
+    }
+}
+
+/// Picks the symbol and colors for every cell of a resized frame, keeping its buffers between frames
+pub(super) struct SymbolMatcher {
+    set: SymbolSet,
+    /// the pixels across and down every cell is matched at
+    grid: (u16, u16),
+    /// what each symbol of the set covers of the `grid`
+    coverages: Vec<u128>,
+    pixels: Vec<[i32; 3]>,
+    cells: PodMatrix<Cell>,
+}
+
+/// the average of `sum` over `count` pixels
+fn average(sum: [i32; 3], count: i32) -> Rgb<u8> {
+    let [r, g, b] = sum.map(|x| (x / count.max(1)) as u8);
+    Rgb::new(r, g, b)
+}
+
+impl SymbolMatcher {
+    /// matches cells of `grid` pixels, [`matching_grid`] of the font, against the symbols of `set`
+    pub(super) fn new(set: SymbolSet, grid: (u16, u16)) -> Self {
+        let coverages = set.0.iter().map(|symbol| symbol.coverage(grid)).collect();
+        Self {
+            set,
+            grid,
+            coverages,
+            pixels: Vec::with_capacity(usize::from(grid.0) * usize::from(grid.1)),
+            cells: PodMatrix::new(),
+        }
+    }
+
+    pub(super) fn set(&self) -> &SymbolSet {
+        &self.set
+    }
+
+    /// the pixels across and down a cell the frames have to be resized to
+    pub(super) fn grid(&self) -> (u16, u16) {
+        self.grid
+    }
+
+    /// the symbol and colors the pixels of a cell are closest to
+    fn best(&self) -> Cell {
+        let total = self.pixels.iter().fold([0; 3], |sum, pixel| {
+            std::array::from_fn(|c| sum[c] + pixel[c])
+        });
+        let squared = |sum: [i32; 3]| sum.iter().map(|&x| f64::from(x).powi(2)).sum::<f64>();
+
+        // the squared error of splitting the pixels into two colors is what they add up to squared
+        // less the squared sums of either color over how many pixels each has,
+        // so the best split has the most of that
+        let pixels = self.pixels.len() as i32;
+        let mut best = (f64::MIN, 0, total, 0);
+        for (index, &coverage) in self.coverages.iter().enumerate() {
+            let mut covered = [0; 3];
+            let mut count = 0;
+            let mut bits = coverage;
+            while bits != 0 {
+                let pixel = self.pixels[bits.trailing_zeros() as usize];
+                covered = std::array::from_fn(|c| covered[c] + pixel[c]);
+                count += 1;
+                bits &= bits - 1;
+            }
+            let uncovered: [i32; 3] = std::array::from_fn(|c| total[c] - covered[c]);
+
+            let uncovered_count = pixels - count;
+            let score = match (count, uncovered_count) {
+                (0, _) => squared(uncovered) / f64::from(uncovered_count),
+                (_, 0) => squared(covered) / f64::from(count),
+                _ => {
+                    squared(covered) / f64::from(count)
+                        + squared(uncovered) / f64::from(uncovered_count)
+                }
+            };
+            if score > best.0 {
+                best = (score, index, covered, count);
+            }
+        }
+
+        let (_, index, covered, count) = best;
+        let uncovered = std::array::from_fn(|c| total[c] - covered[c]);
+        let bg = average(uncovered, pixels - count);
+        let fg = match count {
+            0 => bg,
+            count => average(covered, count),
+        };
+        Cell::with_symbol(index as u16 + 1, fg, bg)
+    }
+
+    /// the cells of `image`, which has the pixels of the grid for every one of them
+    pub(super) fn cells(&mut self, image: ImageRef) -> &PodMatrix<Cell> {
+        let (width, height) = image.size();
+        let (cell_width, cell_height) = (u32::from(self.grid.0), u32::from(self.grid.1));
+        let (columns, rows) = (width / cell_width, height / cell_height);
+
+        // resized frames always fit in the cells of a terminal
+        self.cells.resize((columns as u16, rows as u16));
+        for row in 0..rows {
+            for column in 0..columns {
+                self.pixels.clear();
+                for y in row * cell_height..(row + 1) * cell_height {
+                    let line = &image.row(y)[(column * cell_width) as usize..];
+                    let line = line[..cell_width as usize]
+                        .iter()
+                        .map(|rgb| [rgb.r, rgb.g, rgb.b].map(i32::from));
+                    self.pixels.extend(line);
+                }
+                let cell = self.best();
+                self.cells.row_mut(row as u16)[column as usize] = cell;
+            }
+        }
+        &self.cells
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_sets() {
+        let texts = |set: &str| {
+            let set = set.parse::<SymbolSet>().unwrap();
+            set.0
+                .iter()
+                .map(|symbol| symbol.text.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            texts("half"),
+            [" ", "\u{2580}", "\u{2584}", "\u{258C}", "\u{2590}"]
+        );
+        assert_eq!(texts("blocks").len(), 1 + 4 + 10 + 12);
+        assert_eq!(texts("ascii+half")[1], "\u{2580}");
+        assert!("blocks+emoji".parse::<SymbolSet>().is_err());
+    }
+
+    #[test]
+    fn parses_glyphs() {
+        let half = Symbol::new("\u{2580}", |_, y| y < 8).coverage(CELL_PIXELS);
+        let coverage = |s: &str| s.parse::<Symbol>().unwrap().coverage(CELL_PIXELS);
+        assert_eq!(coverage("\u{2580}=####/####/..../...."), half);
+        assert_eq!(coverage("\u{2580}=#/."), half);
+        assert_eq!(
+            "==..##".parse::<Symbol>().map(|symbol| symbol.text),
+            Ok("=".into())
+        );
+        assert!("x=##/#".parse::<Symbol>().is_err());
+        assert!("x=#o".parse::<Symbol>().is_err());
+        assert!("=##".parse::<Symbol>().is_err());
+
+        let set = "half"
+            .parse::<SymbolSet>()
+            .unwrap()
+            .with(["x=#.".parse::<Symbol>().unwrap()]);
+        assert_eq!(&*set.texts()[1], "x");
+        assert_eq!(set.texts().len(), 6);
+    }
+
+    #[test]
+    fn grids_follow_the_font() {
+        assert_eq!(matching_grid((8, 16)), CELL_PIXELS);
+        assert_eq!(matching_grid((10, 20)), CELL_PIXELS);
+        assert_eq!(matching_grid((9, 20)), (7, 16));
+        assert_eq!(matching_grid((16, 16)), (11, 11));
+
+        // a wedge still goes corner to corner in a wider cell
+        let wedge = Symbol::new("\u{25E3}", |x, y| y >= 2 * x);
+        let corners = |coverage: u128, (columns, rows): (u16, u16)| {
+            let bit = |x: u16, y: u16| coverage >> (y * columns + x) & 1 == 1;
+            (bit(0, rows - 1), bit(columns - 1, 0))
+        };
+        assert_eq!(corners(wedge.coverage((11, 11)), (11, 11)), (true, false));
+    }
+
+    #[test]
+    fn picks_the_closest_symbol() {
+        let (red, blue) = ([255, 0, 0], [0, 0, 255]);
+        let mut matcher = SymbolMatcher::new("blocks".parse().unwrap(), CELL_PIXELS);
+        let pixels = usize::from(CELL_PIXELS.0) * usize::from(CELL_PIXELS.1);
+        // the left quarter red
+        matcher.pixels = (0..pixels)
+            .map(|i| if i % 8 < 2 { red } else { blue })
+            .collect();
+        let cell = matcher.best();
+        let symbol = &matcher.set.0[usize::from(cell.symbol()) - 1];
+        assert_eq!(&*symbol.text, "\u{258E}");
+        assert!(cell == Cell::with_symbol(cell.symbol(), Rgb::new(255, 0, 0), Rgb::new(0, 0, 255)));
+
+        // a single color needs no symbol
+        matcher.pixels = vec![red; pixels];
+        let cell = matcher.best();
+        assert_eq!(&*matcher.set.0[usize::from(cell.symbol()) - 1].text, " ");
+    }
+}
@@ -0,0 +1,190 @@
+//! `--sync-debug`, how far the frames getting onto the terminal are from where the pipeline clock is,
+//! logged every second, and with `correct` the sink's `ts-offset` moved to hand frames over earlier
+//! by as much as the terminal keeps falling behind, so they end up on screen when the sound plays
+use gst::prelude::{ElementExtManual, ObjectExt};
+use gst_app::AppSink;
+use std::time::{Duration, Instant};
+
+/// What `--sync-debug` does
+#[derive(clap::ValueEnum, Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum SyncDebug {
+    /// only log the drift
+    #[default]
+    Log,
+    /// log it and shift the video to make up for it once it stays off for a while
+    Correct,
+}
+
+/// how often the drift gets logged
+const LOG_EVERY: Duration = Duration::from_secs(1);
+/// off by less than this the frames are left be
+const TOLERANCE: i64 = 40_000_000;
+/// how long the drift has to stay past the tolerance before the offset moves
+const SUSTAINED: Duration = Duration::from_secs(2);
+/// handing frames over more than this early means something other than drawing is behind
+const MAX_OFFSET: i64 = 1_000_000_000;
+/// later than this the timestamps jumped, rather than the terminal falling behind
+const DISCONTINUITY: i64 = 1_000_000_000;
+
+/// nanoseconds the frame of `sample` is behind the clock, negative when it's early
+fn drift(sample: &gst::Sample, app_sink: &AppSink) -> Option<i64> {
+    let pts = sample.buffer()?.pts()?;
+    let segment = sample.segment()?.downcast_ref::<gst::ClockTime>()?;
+    let due = i64::try_from(segment.to_running_time(pts)?.nseconds()).ok()?;
+    let now = i64::try_from(app_sink.current_running_time()?.nseconds()).ok()?;
+    Some(now - due)
+}
+
+/// Keeps a moving average of the drift and works out the offset making up for it
+#[derive(Debug)]
+struct Drift {
+    average: i64,
+    /// since when the average has been past the tolerance
+    off_since: Option<Instant>,
+    /// the `ts-offset` the sink has, never more than zero
+    offset: i64,
+}
+
+impl Drift {
+    fn new() -> Self {
+        Self {
+            average: 0,
+            off_since: None,
+            offset: 0,
+        }
+    }
+
+    /// takes in the drift of another frame, the offset to move the sink to
+    /// once it's been off for long enough, which is up to the caller to take
+    fn update(&mut self, drift: i64, now: Instant) -> Option<i64> {
+        // with a weight of 1/8 for the newest frame, like the render latency
+        self.average = (self.average * 7 + drift) / 8;
+        if self.average.abs() < TOLERANCE {
+            self.off_since = None;
+            return None;
+        }
+
+        let since = *self.off_since.get_or_insert(now);
+        if now.duration_since(since) < SUSTAINED {
+            return None;
+        }
+
+        // frames handed over this much earlier make it on time at the rate they are late now
+        let offset = (self.offset - self.average).clamp(-MAX_OFFSET, 0);
+        self.off_since = None;
+        self.average = 0;
+        (offset != self.offset).then_some(offset)
+    }
+}
+
+/// Watches the frames of one sink after another as they get drawn
+pub(super) struct SyncMonitor {
+    mode: SyncDebug,
+    drift: Drift,
+    sink: Option<AppSink>,
+    logged_at: Instant,
+    frames: u32,
+    worst: i64,
+}
+
+impl SyncMonitor {
+    pub(super) fn new(mode: SyncDebug) -> Self {
+        Self {
+            mode,
+            drift: Drift::new(),
+            sink: None,
+            logged_at: Instant::now(),
+            frames: 0,
+            worst: 0,
+        }
+    }
+
+    /// the frame of `sample` just got onto the terminal
+    pub(super) fn drawn(&mut self, sample: &gst::Sample, app_sink: &AppSink) {
+        // when paused the clock keeps on going, with the same frame on screen
+        if app_sink.current_state() != gst::State::Playing {
+            return;
+        }
+        // a new pipeline starts in sync, with an offset of its own
+        if self.sink.as_ref() != Some(app_sink) {
+            self.sink = Some(app_sink.clone());
+            self.drift = Drift::new();
+        }
+        let Some(drift) = drift(sample, app_sink).filter(|drift| drift.abs() < DISCONTINUITY)
+        else {
+            return;
+        };
+
+        self.frames += 1;
+        if drift.abs() > self.worst.abs() {
+            self.worst = drift;
+        }
+        if self.logged_at.elapsed() >= LOG_EVERY {
+            tracing::info!(
+                frames = self.frames,
+                average_ms = self.drift.average as f64 / 1e6,
+                worst_ms = self.worst as f64 / 1e6,
+                ts_offset_ms = self.drift.offset as f64 / 1e6,
+                "video drift from the clock"
+            );
+            self.logged_at = Instant::now();
+            self.frames = 0;
+            self.worst = 0;
+        }
+
+        let Some(offset) = self.drift.update(drift, Instant::now()) else {
+            return;
+        };
+        if self.mode == SyncDebug::Log {
+            tracing::info!(
+                ts_offset_ms = offset as f64 / 1e6,
+                "frames would have to be handed over earlier to stay in sync"
+            );
+            return;
+        }
+
+        tracing::info!(
+            ts_offset_ms = offset as f64 / 1e6,
+            "handing frames over earlier to make up for the drift"
+        );
+        self.drift.offset = offset;
+        app_sink.set_property("ts-offset", offset);
+        // what can't make it in time any more gets left out by the sink, not drawn late,
+        // unless --max-lateness already says how late is too late
+        if app_sink.property::<i64>("max-lateness") < 0 {
+            app_sink.set_property("max-lateness", TOLERANCE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_sustained_drift_moves_the_offset() {
+        let start = Instant::now();
+        let mut drift = Drift::new();
+        let late = 100_000_000;
+        // the average needs a few frames to get past the tolerance
+        for frame in 0..10 {
+            assert_eq!(
+                drift.update(late, start + Duration::from_millis(frame)),
+                None
+            );
+        }
+        assert_eq!(drift.update(late, start + SUSTAINED), None);
+        let offset = drift.update(late, start + Duration::from_secs(3)).unwrap();
+        assert!((-late..-TOLERANCE).contains(&offset));
+        drift.offset = offset;
+        // taken, the average starts over
+        assert_eq!(drift.update(late, start + Duration::from_secs(4)), None);
+
+        // back in sync leaves it where it is
+        let mut drift = Drift::new();
+        for frame in 0..100 {
+            let at = start + Duration::from_millis(frame * 40);
+            assert_eq!(drift.update(TOLERANCE / 2, at), None);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::terminal;
+use crate::terminal_sink::frame;
+use crate::terminal_sink::{DEFAULT_PRECISION, TerminalRenderer};
+use glib::prelude::ToValue;
+use gst::subclass::prelude::*;
+use gst_base::subclass::prelude::*;
+use gst_video::subclass::prelude::*;
+use parking_lot::Mutex;
+use std::io;
+use std::num::NonZero;
+use std::sync::LazyLock;
+
+/// what gets drawn at when the descriptor isn't a terminal
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+
+static CAT: LazyLock<gst::DebugCategory> = LazyLock::new(|| {
+    gst::DebugCategory::new(
+        super::NAME,
+        gst::DebugColorFlags::empty(),
+        Some("Terminal video sink"),
+    )
+});
+
+/// Which glyphs cells get drawn with
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "GstTermVideoSinkCharset")]
+pub enum Charset {
+    #[default]
+    #[enum_value(name = "Upper half blocks only", nick = "half-blocks")]
+    HalfBlocks,
+    #[enum_value(name = "Whichever block takes the fewest bytes", nick = "compact")]
+    Compact,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Settings {
+    color_depth: u32,
+    charset: Charset,
+    fd: i32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            color_depth: DEFAULT_PRECISION.into(),
+            charset: Charset::default(),
+            fd: 1,
+        }
+    }
+}
+
+struct State {
+    info: Option<gst_video::VideoInfo>,
+    renderer: TerminalRenderer,
+    /// what the last frame was drawn at, `None` before the first one
+    size: Option<(u16, u16)>,
+}
+
+#[derive(Default)]
+pub struct TermVideoSink {
+    settings: Mutex<Settings>,
+    state: Mutex<Option<State>>,
+}
+
+/// the size of the terminal `fd` is, if it is one
+fn fd_size(fd: i32) -> Option<(u16, u16)> {
+    #[cfg(unix)]
+    {
+        terminal::fd_size(fd)
+    }
+
+    #[cfg(not(unix))]
+    {
+        matches!(fd, 1 | 2).then(terminal::size).flatten()
+    }
+}
+
+fn write_fd(fd: i32, bytes: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::FromRawFd;
+
+        // the descriptor belongs to whoever set the property
+        let mut file = std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+        file.write_all(bytes)
+    }
+
+    #[cfg(not(unix))]
+    match fd {
+        1 => io::stdout().lock().write_all(bytes),
+        2 => io::stderr().lock().write_all(bytes),
+        _ => Err(io::Error::other(
+            "only stdout and stderr can be written to here",
+        )),
+    }
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for TermVideoSink {
+    const NAME: &'static str = "GstTermVideoSink";
+    type Type = super::TermVideoSink;
+    type ParentType = gst_video::VideoSink;
+}
+
+impl ObjectImpl for TermVideoSink {
+    fn properties() -> &'static [glib::ParamSpec] {
+        static PROPERTIES: LazyLock<Vec<glib::ParamSpec>> = LazyLock::new(|| {
+            vec![
+                glib::ParamSpecUInt::builder("color-depth")
+                    .nick("Color depth")
+                    .blurb("Bits kept of every color channel, fewer make frames cheaper to update")
+                    .minimum(1)
+                    .maximum(8)
+                    .default_value(DEFAULT_PRECISION.into())
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("charset", Charset::default())
+                    .nick("Charset")
+                    .blurb("Which glyphs cells get drawn with")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecInt::builder("fd")
+                    .nick("File descriptor")
+                    .blurb("Where frames get written and whose terminal size they fit")
+                    .minimum(0)
+                    .default_value(1)
+                    .mutable_ready()
+                    .build(),
+            ]
+        });
+
+        PROPERTIES.as_ref()
+    }
+
+    fn set_property(&self, _id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+        let mut settings = self.settings.lock();
+        match pspec.name() {
+            "color-depth" => settings.color_depth = value.get().unwrap(),
+            "charset" => settings.charset = value.get().unwrap(),
+            "fd" => settings.fd = value.get().unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn property(&self, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+        let settings = self.settings.lock();
+        match pspec.name() {
+            "color-depth" => settings.color_depth.to_value(),
+            "charset" => settings.charset.to_value(),
+            "fd" => settings.fd.to_value(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl GstObjectImpl for TermVideoSink {}
+
+impl ElementImpl for TermVideoSink {
+    fn metadata() -> Option<&'static gst::subclass::ElementMetadata> {
+        static METADATA: LazyLock<gst::subclass::ElementMetadata> = LazyLock::new(|| {
+            gst::subclass::ElementMetadata::new(
+                "Terminal video sink",
+                "Sink/Video",
+                "Draws video into a terminal with colored half blocks",
+                env!("CARGO_PKG_NAME"),
+            )
+        });
+
+        Some(&*METADATA)
+    }
+
+    fn pad_templates() -> &'static [gst::PadTemplate] {
+        static PAD_TEMPLATES: LazyLock<Vec<gst::PadTemplate>> = LazyLock::new(|| {
+            let caps = gst_video::VideoCapsBuilder::new()
+                .format_list(frame::formats())
+                .build();
+            vec![
+                gst::PadTemplate::new(
+                    "sink",
+                    gst::PadDirection::Sink,
+                    gst::PadPresence::Always,
+                    &caps,
+                )
+                .unwrap(),
+            ]
+        });
+
+        PAD_TEMPLATES.as_ref()
+    }
+}
+
+impl BaseSinkImpl for TermVideoSink {
+    fn start(&self) -> Result<(), gst::ErrorMessage> {
+        let settings = *self.settings.lock();
+        let renderer =
+            TerminalRenderer::new(settings.charset == Charset::Compact, NonZero::<usize>::MIN);
+
+        *self.state.lock() = Some(State {
+            info: None,
+            renderer,
+            size: None,
+        });
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), gst::ErrorMessage> {
+        let fd = self.settings.lock().fd;
+        let drew = self
+            .state
+            .lock()
+            .take()
+            .is_some_and(|state| state.size.is_some());
+
+        if drew {
+            let goodbye = format!("\x1b[0m{}\n", terminal::SHOW_CURSOR);
+            let _ = write_fd(fd, goodbye.as_bytes());
+        }
+        Ok(())
+    }
+
+    fn set_caps(&self, caps: &gst::Caps) -> Result<(), gst::LoggableError> {
+        let info = gst_video::VideoInfo::from_caps(caps)
+            .map_err(|_| gst::loggable_error!(CAT, "Failed to parse caps {caps}"))?;
+
+        gst::debug!(
+            CAT,
+            imp = self,
+            "drawing {}x{} frames",
+            info.width(),
+            info.height()
+        );
+        if let Some(state) = &mut *self.state.lock() {
+            state.info = Some(info);
+        }
+        Ok(())
+    }
+}
+
+impl VideoSinkImpl for TermVideoSink {
+    fn show_frame(&self, buffer: &gst::Buffer) -> Result<gst::FlowSuccess, gst::FlowError> {
+        let settings = *self.settings.lock();
+        let mut state = self.state.lock();
+        let state = state.as_mut().ok_or(gst::FlowError::Flushing)?;
+        let info = state.info.as_ref().ok_or(gst::FlowError::NotNegotiated)?;
+
+        let frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer.as_ref(), info)
+            .map_err(|err| {
+                gst::element_imp_error!(
+                    self,
+                    gst::ResourceError::Failed,
+                    ("Failed to map video frame readable; {err}")
+                );
+                gst::FlowError::Error
+            })?;
+
+        let write_error = |err: io::Error| {
+            gst::element_imp_error!(
+                self,
+                gst::ResourceError::Write,
+                ("Failed to write to the terminal; {err}")
+            );
+            gst::FlowError::Error
+        };
+
+        // a new size makes for a new layout, so nothing on screen can be built on
+        let size = fd_size(settings.fd).unwrap_or(DEFAULT_SIZE);
+        let fresh_redraw = state.size != Some(size);
+        if fresh_redraw {
+            let clear = format!("{}{}", terminal::HIDE_CURSOR, terminal::CLEAR);
+            write_fd(settings.fd, clear.as_bytes()).map_err(write_error)?;
+        }
+
+        state.renderer.set_precision(settings.color_depth as u8);
+        let bytes = state
+            .renderer
+            .render(&frame, size, fresh_redraw, None)
+            .ok_or_else(|| {
+                gst::element_imp_error!(
+                    self,
+                    gst::ResourceError::Failed,
+                    ("invalid video sample dimentions")
+                );
+                gst::FlowError::Error
+            })?;
+        write_fd(settings.fd, bytes).map_err(write_error)?;
+        state.size = Some(size);
+
+        Ok(gst::FlowSuccess::Ok)
+    }
+}
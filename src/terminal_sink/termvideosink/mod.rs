@@ -0,0 +1,24 @@
+//! `termvideosink`, the renderer as a plain GStreamer element for any pipeline, e.g.
+//! `gst-launch-1.0 videotestsrc ! videoconvert ! termvideosink charset=compact`
+//!
+//! It draws straight to a file descriptor without taking over the terminal,
+//! the player itself keeps using [`TerminalVideoSink`](super::TerminalVideoSink)
+//! since that one outlives the pipelines feeding it
+use glib::prelude::StaticType;
+
+mod imp;
+
+pub use imp::Charset;
+
+/// the name the element gets registered under
+pub const NAME: &str = "termvideosink";
+
+glib::wrapper! {
+    pub struct TermVideoSink(ObjectSubclass<imp::TermVideoSink>)
+        @extends gst_video::VideoSink, gst_base::BaseSink, gst::Element, gst::Object;
+}
+
+/// makes `termvideosink` available to element factories, with `None` for this process only
+pub fn register(plugin: Option<&gst::Plugin>) -> Result<(), glib::BoolError> {
+    gst::Element::register(plugin, NAME, gst::Rank::NONE, TermVideoSink::static_type())
+}
@@ -0,0 +1,30 @@
+use crate::terminal_sink::renderer::{RenderTarget, TerminalRenderer};
+use gst_video::{VideoFrameRef, VideoInfo};
+
+/// below this many cells a thumbnail isn't worth drawing
+const MIN_SIZE: (u16, u16) = (8, 3);
+
+/// the escape sequences drawing `sample` in the bottom right corner of a terminal of `term_size`,
+/// a quarter of it wide and tall, `None` when that's too small or the sample can't be read
+pub(super) fn draw(
+    renderer: &mut TerminalRenderer,
+    sample: &gst::Sample,
+    (columns, rows): (u16, u16),
+) -> Option<Vec<u8>> {
+    let size = (columns / 4, rows / 4);
+    if size.0 < MIN_SIZE.0 || size.1 < MIN_SIZE.1 {
+        return None;
+    }
+
+    let info = VideoInfo::from_caps(sample.caps()?).ok()?;
+    let frame = VideoFrameRef::from_buffer_ref_readable(sample.buffer()?, &info).ok()?;
+
+    // the frame under it changes every time, so there's nothing to diff against
+    let mut target = RenderTarget {
+        origin: (columns - size.0 - 1, rows - size.1 - 1),
+        size,
+        writer: Vec::new(),
+    };
+    renderer.render_to(&frame, &mut target, true).ok()?;
+    Some(target.writer)
+}
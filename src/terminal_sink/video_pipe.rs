@@ -1,25 +1,70 @@
 use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
 use std::sync::{Arc, Weak};
+use std::time::Instant;
 
-enum RenderState {
-    None,
-    HasSample { sample: gst::Sample, pulled: bool },
-    Closed,
+/// how many samples can wait for the renderer
+pub const CAPACITY: usize = 3;
+
+/// What happens when samples come in faster than they get rendered
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum QueueMode {
+    /// keep the newest samples and drop the oldest once the queue is full
+    #[default]
+    Latest,
+    /// keep every sample, the pipeline waits while the queue is full
+    Fifo,
+}
+
+struct RenderState {
+    samples: VecDeque<gst::Sample>,
+    /// the sample drawn last, kept around for redraws
+    last: Option<gst::Sample>,
+    reload: bool,
+    /// when the last sample gets drawn again by itself, like once a message is over
+    reload_at: Option<Instant>,
+    loading: bool,
+    /// how far along buffering is, until the render thread saw it
+    buffering: Option<u8>,
+    /// the last buffering report of this pipeline, the same one again isn't worth drawing
+    reported: Option<u8>,
+    closed: bool,
+    /// every sample that came in
+    pushed: u64,
+    /// samples pushed out of the queue by newer ones
+    dropped: u64,
+}
+
+pub enum Pulled {
+    Sample(gst::Sample),
+    /// the next sample will come from a new pipeline
+    Loading,
+    /// the pipeline is this many percent done filling its buffers
+    Buffering(u8),
 }
 
 struct RenderingContext {
+    mode: QueueMode,
+    /// how many samples wait at most
+    capacity: usize,
     state: Mutex<RenderState>,
     sample_notification: Condvar,
+    space_notification: Condvar,
+}
+
+impl RenderingContext {
+    fn close(&self) {
+        self.state.lock().closed = true;
+        self.sample_notification.notify_one();
+        self.space_notification.notify_all();
+    }
 }
 
 struct RenderingContextPipe(Arc<RenderingContext>);
 
 impl Drop for RenderingContextPipe {
     fn drop(&mut self) {
-        let mut lock = self.0.state.lock();
-        *lock = RenderState::Closed;
-        drop(lock);
-        self.0.sample_notification.notify_one();
+        self.0.close();
     }
 }
 
@@ -28,68 +73,134 @@ pub struct SampleProducer(Arc<RenderingContextPipe>);
 
 impl SampleProducer {
     pub fn push_sample(&self, sample: gst::Sample) -> Result<(), ()> {
-        let this: &RenderingContext = &*self.0.0;
+        let this: &RenderingContext = &self.0.0;
 
         let mut lock = this.state.lock();
-        match &mut *lock {
-            // still rendering...
-            RenderState::HasSample {
-                sample: old_sample,
-                pulled: false,
-            } => *old_sample = sample,
-            RenderState::Closed => return Err(()),
-            slot => {
-                *slot = RenderState::HasSample {
-                    sample,
-                    pulled: false,
-                };
-                drop(lock);
-                this.sample_notification.notify_one();
+        while lock.samples.len() >= this.capacity && !lock.closed {
+            match this.mode {
+                // still rendering...
+                QueueMode::Latest => {
+                    lock.samples.pop_front();
+                    lock.dropped += 1;
+                }
+                QueueMode::Fifo => this.space_notification.wait(&mut lock),
             }
         }
 
+        if lock.closed {
+            return Err(());
+        }
+
+        lock.samples.push_back(sample);
+        lock.pushed += 1;
+        drop(lock);
+        this.sample_notification.notify_one();
+
         Ok(())
     }
 
-    pub fn close(&self) {
-        let this: &RenderingContext = &*self.0.0;
-        *this.state.lock() = RenderState::Closed;
+    pub fn show_loading(&self) {
+        let this: &RenderingContext = &self.0.0;
+
+        let mut lock = this.state.lock();
+        if lock.closed {
+            return;
+        }
+        // whatever is still queued belongs to the last pipeline
+        lock.samples.clear();
+        lock.last = None;
+        lock.loading = true;
+        lock.buffering = None;
+        lock.reported = None;
+        drop(lock);
+        this.sample_notification.notify_one();
+        this.space_notification.notify_all();
+    }
+
+    /// says how full the buffers are, only the latest report is kept
+    /// and only when it changed since the one before
+    pub fn show_buffering(&self, percent: u8) {
+        let this: &RenderingContext = &self.0.0;
+
+        let mut lock = this.state.lock();
+        if lock.closed || lock.reported == Some(percent) {
+            return;
+        }
+        lock.reported = Some(percent);
+        lock.buffering = Some(percent);
+        drop(lock);
         this.sample_notification.notify_one();
     }
+
+    pub fn close(&self) {
+        self.0.0.close();
+    }
 }
 
 pub struct SampleConsumer(RenderingContextPipe);
 
 impl SampleConsumer {
-    pub fn pull_sample(&self) -> Result<gst::Sample, ()> {
-        let this: &RenderingContext = &*self.0.0;
+    pub fn pull(&self) -> Result<Pulled, ()> {
+        let this: &RenderingContext = &self.0.0;
 
         let mut lock = this.state.lock();
         loop {
-            match &mut *lock {
-                RenderState::None | RenderState::HasSample { pulled: true, .. } => {
-                    this.sample_notification.wait(&mut lock)
+            if lock.closed {
+                return Err(());
+            }
+
+            if lock.loading {
+                lock.loading = false;
+                return Ok(Pulled::Loading);
+            }
+
+            if let Some(percent) = lock.buffering.take() {
+                return Ok(Pulled::Buffering(percent));
+            }
+
+            if let Some(sample) = lock.samples.pop_front() {
+                lock.last = Some(sample.clone());
+                lock.reload = false;
+                drop(lock);
+                this.space_notification.notify_one();
+                return Ok(Pulled::Sample(sample));
+            }
+
+            if std::mem::take(&mut lock.reload)
+                && let Some(ref sample) = lock.last
+            {
+                return Ok(Pulled::Sample(sample.clone()));
+            }
+
+            match lock.reload_at {
+                Some(at) if Instant::now() >= at => {
+                    lock.reload_at = None;
+                    lock.reload = true;
                 }
-                RenderState::HasSample {
-                    sample,
-                    pulled: pulled @ false,
-                } => {
-                    *pulled = true;
-                    break Ok(sample.clone());
+                Some(at) => {
+                    this.sample_notification.wait_until(&mut lock, at);
                 }
-                RenderState::Closed => return Err(()),
+                None => this.sample_notification.wait(&mut lock),
             }
         }
     }
 
+    /// how many samples came in so far, and how many of those never got pulled
+    pub fn counts(&self) -> (u64, u64) {
+        let lock = self.0.0.state.lock();
+        (lock.pushed, lock.dropped)
+    }
+
     pub fn make_reloader(&self) -> SampleReloader {
         SampleReloader(Arc::downgrade(&self.0.0))
     }
 }
 
+#[derive(Clone)]
 pub struct SampleReloader(Weak<RenderingContext>);
 
 impl SampleReloader {
+    /// draws the last sample again, unless newer ones are already waiting
     pub fn reload_sample(&self) -> Result<(), ()> {
         let Some(this) = self.0.upgrade() else {
             return Err(());
@@ -98,31 +209,100 @@ impl SampleReloader {
         let this: &RenderingContext = &this;
 
         let mut lock = this.state.lock();
-        match &mut *lock {
-            RenderState::None => Ok(()),
-            RenderState::HasSample { pulled, .. } => {
-                *pulled = false;
-                drop(lock);
-                this.sample_notification.notify_one();
-                Ok(())
-            }
-            RenderState::Closed => Err(()),
+        if lock.closed {
+            return Err(());
+        }
+
+        lock.reload = true;
+        drop(lock);
+        this.sample_notification.notify_one();
+        Ok(())
+    }
+
+    /// draws the last sample again once `at` comes, in place of the time asked for before
+    pub fn reload_sample_at(&self, at: Instant) -> Result<(), ()> {
+        let Some(this) = self.0.upgrade() else {
+            return Err(());
+        };
+
+        let this: &RenderingContext = &this;
+
+        let mut lock = this.state.lock();
+        if lock.closed {
+            return Err(());
         }
+
+        lock.reload_at = Some(at);
+        drop(lock);
+        this.sample_notification.notify_one();
+        Ok(())
     }
 }
 
-pub fn video_pipe() -> (SampleProducer, SampleConsumer) {
-    let ctx = Arc::new(
-        const {
-            RenderingContext {
-                state: Mutex::new(RenderState::None),
-                sample_notification: Condvar::new(),
-            }
-        },
-    );
+/// a pipe holding up to `capacity` samples, at least one
+pub fn video_pipe(mode: QueueMode, capacity: usize) -> (SampleProducer, SampleConsumer) {
+    let capacity = capacity.max(1);
+    let ctx = Arc::new(RenderingContext {
+        mode,
+        capacity,
+        state: Mutex::new(RenderState {
+            samples: VecDeque::with_capacity(capacity),
+            last: None,
+            reload: false,
+            reload_at: None,
+            loading: false,
+            buffering: None,
+            reported: None,
+            closed: false,
+            pushed: 0,
+            dropped: 0,
+        }),
+        sample_notification: Condvar::new(),
+        space_notification: Condvar::new(),
+    });
 
     let pipe1 = RenderingContextPipe(Arc::clone(&ctx));
     let pipe2 = RenderingContextPipe(Arc::clone(&ctx));
 
     (SampleProducer(Arc::new(pipe1)), SampleConsumer(pipe2))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buffering_is_reported_when_it_changes() {
+        let (producer, consumer) = video_pipe(QueueMode::Latest, CAPACITY);
+        producer.show_buffering(40);
+        assert!(matches!(consumer.pull(), Ok(Pulled::Buffering(40))));
+        producer.show_buffering(40);
+        assert_eq!(consumer.0.0.state.lock().buffering, None);
+        producer.show_buffering(60);
+        assert!(matches!(consumer.pull(), Ok(Pulled::Buffering(60))));
+
+        // the next pipeline starts over
+        producer.show_loading();
+        assert!(matches!(consumer.pull(), Ok(Pulled::Loading)));
+        producer.show_buffering(60);
+        assert!(matches!(consumer.pull(), Ok(Pulled::Buffering(60))));
+    }
+
+    #[test]
+    fn timed_reloads_wait_for_their_time() {
+        gst::init().unwrap();
+        let (_producer, consumer) = video_pipe(QueueMode::Latest, CAPACITY);
+        consumer.0.0.state.lock().last = Some(gst::Sample::builder().build());
+
+        let reloader = consumer.make_reloader();
+        let at = Instant::now() + std::time::Duration::from_millis(50);
+        reloader
+            .reload_sample_at(at + std::time::Duration::from_secs(60))
+            .unwrap();
+        // asking again moves the one reload there is
+        reloader.reload_sample_at(at).unwrap();
+        assert!(matches!(consumer.pull(), Ok(Pulled::Sample(_))));
+        assert!(Instant::now() >= at);
+        assert_eq!(consumer.0.0.state.lock().reload_at, None);
+    }
+}
@@ -0,0 +1,369 @@
+use crate::terminal_sink::cast::CastRecorder;
+use parking_lot::{Condvar, Mutex};
+use std::fs::File;
+use std::io::{self, IoSlice, Write};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct Queue {
+    buffers: Vec<Vec<u8>>,
+    free: Vec<Vec<u8>>,
+    writing: bool,
+    closed: bool,
+    error: Option<io::Error>,
+}
+
+impl Queue {
+    fn busy(&self) -> bool {
+        self.writing || !self.buffers.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+/// blocks until `file` can take more bytes, for when it was left non blocking
+#[cfg(unix)]
+fn wait_writable(file: &File) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let mut poll_fd = libc::pollfd {
+        fd: file.as_raw_fd(),
+        events: libc::POLLOUT,
+        revents: 0,
+    };
+
+    // Safety: poll_fd is a valid pollfd, and we pass a count of 1
+    match unsafe { libc::poll(&mut poll_fd, 1, -1) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// consoles never are non blocking
+#[cfg(not(unix))]
+fn wait_writable(_: &File) -> io::Result<()> {
+    Err(io::ErrorKind::WouldBlock.into())
+}
+
+/// Where the writer thread sends what gets drawn
+pub trait Output: Write + Send + 'static {
+    /// blocks until more bytes fit, after a write said it would block
+    fn wait_writable(&self) -> io::Result<()>;
+}
+
+impl Output for File {
+    fn wait_writable(&self) -> io::Result<()> {
+        wait_writable(self)
+    }
+}
+
+/// writes every buffer in order, picking up after partial writes, and flushes them
+/// so the time taken covers the bytes actually reaching the terminal
+fn write_all_vectored(file: &mut dyn Output, buffers: &[Vec<u8>]) -> io::Result<()> {
+    let mut slices = buffers
+        .iter()
+        .map(|buffer| IoSlice::new(buffer))
+        .collect::<Vec<_>>();
+    let mut slices = &mut slices[..];
+
+    while !slices.is_empty() {
+        match file.write_vectored(slices) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(written) => IoSlice::advance_slices(&mut slices, written),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => file.wait_writable()?,
+            Err(err) => return Err(err),
+        }
+    }
+
+    loop {
+        match file.flush() {
+            Ok(()) => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => file.wait_writable()?,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn run_writer_thread(mut file: Box<dyn Output>, shared: &Shared) {
+    loop {
+        let buffers = {
+            let mut queue = shared.queue.lock();
+            while queue.buffers.is_empty() && !queue.closed {
+                shared.changed.wait(&mut queue);
+            }
+
+            if queue.buffers.is_empty() {
+                return;
+            }
+
+            queue.writing = true;
+            std::mem::take(&mut queue.buffers)
+        };
+
+        let res = write_all_vectored(&mut *file, &buffers);
+
+        let mut queue = shared.queue.lock();
+        queue.writing = false;
+        if let Err(err) = res {
+            queue.error.get_or_insert(err);
+        }
+
+        // keep a couple of the big frame buffers around instead of growing new ones
+        for mut buffer in buffers {
+            if queue.free.len() < 2 {
+                buffer.clear();
+                queue.free.push(buffer);
+            }
+        }
+
+        shared.changed.notify_all();
+    }
+}
+
+/// Writes to the terminal from its own thread so a slow terminal
+/// only ever costs a frame instead of stalling the renderer
+pub struct OutputWriter {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    deadline: Option<Duration>,
+    /// taken out once writing to it fails
+    cast: Mutex<Option<CastRecorder>>,
+}
+
+impl OutputWriter {
+    /// writes to `file`, frames get dropped if the last one still isn't written after `deadline`
+    pub fn new(file: Box<dyn Output>, deadline: Option<Duration>) -> Self {
+        let shared = Arc::new(Shared::default());
+
+        let thread = thread::Builder::new()
+            .name("terminal writer".to_string())
+            .spawn({
+                let shared = Arc::clone(&shared);
+                move || run_writer_thread(file, &shared)
+            })
+            .unwrap();
+
+        Self {
+            shared,
+            thread: Some(thread),
+            deadline,
+            cast: Mutex::new(None),
+        }
+    }
+
+    /// keeps a copy of everything that goes out from now on
+    pub fn record_cast(&mut self, cast: CastRecorder) {
+        *self.cast.lock() = Some(cast);
+    }
+
+    /// runs `write` on the recording, which stops once it fails rather than the player
+    fn with_cast(&self, write: impl FnOnce(&mut CastRecorder) -> io::Result<()>) {
+        let mut cast = self.cast.lock();
+        if let Some(recorder) = cast.as_mut()
+            && let Err(err) = write(recorder)
+        {
+            tracing::error!(%err, "couldn't write the cast, it stops here");
+            *cast = None;
+        }
+    }
+
+    /// lets the recording know the terminal is `size` big now
+    pub fn resize(&self, size: (u16, u16)) {
+        self.with_cast(|cast| cast.resize(size));
+    }
+
+    fn record(&self, bytes: &[u8]) {
+        self.with_cast(|cast| cast.output(bytes));
+    }
+
+    fn push(&self, queue: &mut Queue, buffer: &mut Vec<u8>) {
+        let empty = queue.free.pop().unwrap_or_default();
+        queue.buffers.push(std::mem::replace(buffer, empty));
+        self.shared.changed.notify_all();
+    }
+
+    /// queues `frame` once the previous one is written, handing back an empty buffer in its place
+    ///
+    /// returns `Ok(false)` without queueing anything if the terminal didn't catch up in time
+    pub fn submit_frame(&self, frame: &mut Vec<u8>) -> io::Result<bool> {
+        let deadline = self.deadline.map(|deadline| Instant::now() + deadline);
+
+        let mut queue = self.shared.queue.lock();
+        while queue.busy() && queue.error.is_none() {
+            match deadline {
+                Some(deadline) => {
+                    if self
+                        .shared
+                        .changed
+                        .wait_until(&mut queue, deadline)
+                        .timed_out()
+                        && queue.busy()
+                    {
+                        return Ok(false);
+                    }
+                }
+                None => self.shared.changed.wait(&mut queue),
+            }
+        }
+
+        if let Some(err) = queue.error.take() {
+            return Err(err);
+        }
+
+        self.record(frame);
+        self.push(&mut queue, frame);
+        Ok(true)
+    }
+
+    /// queues `bytes` behind everything else, these are never dropped
+    pub fn write(&self, bytes: &[u8]) -> io::Result<()> {
+        let mut queue = self.shared.queue.lock();
+        if let Some(err) = queue.error.take() {
+            return Err(err);
+        }
+
+        self.record(bytes);
+        queue.buffers.push(bytes.to_vec());
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    /// waits for everything queued to be written
+    pub fn flush(&self) -> io::Result<()> {
+        let mut queue = self.shared.queue.lock();
+        while queue.busy() && queue.error.is_none() {
+            self.shared.changed.wait(&mut queue);
+        }
+
+        queue.error.take().map_or(Ok(()), Err)
+    }
+}
+
+impl Drop for OutputWriter {
+    fn drop(&mut self) {
+        self.shared.queue.lock().closed = true;
+        self.shared.changed.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::mpsc;
+
+    /// takes at most a few bytes per write, and says it would block every other call
+    #[derive(Default)]
+    struct Trickle {
+        written: Vec<u8>,
+        blocking: bool,
+        calls: usize,
+        waits: Cell<usize>,
+        flushes: usize,
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.blocking && self.calls % 2 == 0 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let taken = buf.len().min(3);
+            self.written.extend_from_slice(&buf[..taken]);
+            Ok(taken)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    impl Output for Trickle {
+        fn wait_writable(&self) -> io::Result<()> {
+            self.waits.set(self.waits.get() + 1);
+            Ok(())
+        }
+    }
+
+    fn buffers() -> Vec<Vec<u8>> {
+        vec![
+            b"\x1b[2J".to_vec(),
+            vec![],
+            b"hello".to_vec(),
+            b"world!".to_vec(),
+        ]
+    }
+
+    #[test]
+    fn partial_writes_get_picked_up() {
+        let mut file = Trickle::default();
+        write_all_vectored(&mut file, &buffers()).unwrap();
+        assert_eq!(file.written, buffers().concat());
+        assert_eq!(file.flushes, 1);
+        assert_eq!(file.waits.get(), 0);
+    }
+
+    #[test]
+    fn blocking_writes_wait_and_go_on() {
+        let mut file = Trickle {
+            blocking: true,
+            ..Trickle::default()
+        };
+        write_all_vectored(&mut file, &buffers()).unwrap();
+        assert_eq!(file.written, buffers().concat());
+        assert!(file.waits.get() > 0);
+        assert_eq!(file.flushes, 1);
+    }
+
+    /// takes every write but only gets them out once it's let go
+    struct Stalled {
+        release: mpsc::Receiver<()>,
+    }
+
+    impl Write for Stalled {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            // the sender going away lets every flush through
+            let _ = self.release.recv();
+            Ok(())
+        }
+    }
+
+    impl Output for Stalled {
+        fn wait_writable(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stalled_terminals_drop_frames() {
+        let (release, stalled) = mpsc::channel();
+        let writer = OutputWriter::new(
+            Box::new(Stalled { release: stalled }),
+            Some(Duration::from_millis(50)),
+        );
+
+        assert!(writer.submit_frame(&mut b"first".to_vec()).unwrap());
+        assert!(!writer.submit_frame(&mut b"second".to_vec()).unwrap());
+
+        drop(release);
+        writer.flush().unwrap();
+        assert!(writer.submit_frame(&mut b"third".to_vec()).unwrap());
+    }
+}
@@ -0,0 +1,189 @@
+use rgb::Rgb;
+
+/// Fixed point YCbCr to RGB factors in 1/64ths, for limited range video
+#[derive(Copy, Clone)]
+pub struct Coefficients {
+    luma: i16,
+    red_cr: i16,
+    green_cb: i16,
+    green_cr: i16,
+    blue_cb: i16,
+}
+
+impl Coefficients {
+    /// standard definition video
+    pub const BT601: Self = Self {
+        luma: 74,
+        red_cr: 102,
+        green_cb: 25,
+        green_cr: 52,
+        blue_cb: 129,
+    };
+
+    /// high definition video
+    pub const BT709: Self = Self {
+        luma: 74,
+        red_cr: 115,
+        green_cb: 14,
+        green_cr: 34,
+        blue_cb: 135,
+    };
+}
+
+fn convert_scalar(coefficients: Coefficients, y: u8, cb: u8, cr: u8) -> Rgb<u8> {
+    let c = i16::from(y) - 16;
+    let d = i16::from(cb) - 128;
+    let e = i16::from(cr) - 128;
+
+    // the same saturating 16 bit math as the simd path, so both give the same colors
+    let luma = (coefficients.luma * c).saturating_add(32);
+    let channel = |x: i16| (x >> 6).clamp(0, 255) as u8;
+
+    Rgb::new(
+        channel(luma.saturating_add(coefficients.red_cr * e)),
+        channel(
+            luma.saturating_sub(coefficients.green_cb * d)
+                .saturating_sub(coefficients.green_cr * e),
+        ),
+        channel(luma.saturating_add(coefficients.blue_cb * d)),
+    )
+}
+
+fn convert_row_scalar(
+    coefficients: Coefficients,
+    y: &[u8],
+    cb: &[u8],
+    cr: &[u8],
+    out: &mut [Rgb<u8>],
+) {
+    for (i, (pixel, &y)) in out.iter_mut().zip(y).enumerate() {
+        *pixel = convert_scalar(coefficients, y, cb[i / 2], cr[i / 2]);
+    }
+}
+
+/// converts 8 pixels at a time, returns how many pixels it converted
+#[cfg(target_arch = "x86_64")]
+fn convert_row_simd(
+    coefficients: Coefficients,
+    y: &[u8],
+    cb: &[u8],
+    cr: &[u8],
+    out: &mut [Rgb<u8>],
+) -> usize {
+    use std::arch::x86_64::*;
+
+    let pixels = out.len().min(y.len()).min(cb.len() * 2).min(cr.len() * 2);
+    let blocks = pixels / 8;
+
+    // Safety: every load reads 8 luma bytes and 4 bytes of each chroma row
+    // out of blocks that were checked to be in bounds above
+    unsafe {
+        let splat = |x: i16| _mm_set1_epi16(x);
+        let zero = _mm_setzero_si128();
+
+        for block in 0..blocks {
+            let (luma_at, chroma_at) = (block * 8, block * 4);
+
+            let load_chroma = |plane: &[u8]| {
+                let bytes = plane[chroma_at..chroma_at + 4].try_into().unwrap();
+                let chroma = _mm_cvtsi32_si128(i32::from_le_bytes(bytes));
+                // every chroma sample covers two pixels
+                let chroma = _mm_unpacklo_epi8(chroma, chroma);
+                _mm_sub_epi16(_mm_unpacklo_epi8(chroma, zero), splat(128))
+            };
+
+            let luma = _mm_loadl_epi64(y.as_ptr().add(luma_at).cast());
+            let c = _mm_sub_epi16(_mm_unpacklo_epi8(luma, zero), splat(16));
+            let d = load_chroma(cb);
+            let e = load_chroma(cr);
+
+            let luma = _mm_adds_epi16(_mm_mullo_epi16(c, splat(coefficients.luma)), splat(32));
+            let red = _mm_adds_epi16(luma, _mm_mullo_epi16(e, splat(coefficients.red_cr)));
+            let green = _mm_subs_epi16(
+                _mm_subs_epi16(luma, _mm_mullo_epi16(d, splat(coefficients.green_cb))),
+                _mm_mullo_epi16(e, splat(coefficients.green_cr)),
+            );
+            let blue = _mm_adds_epi16(luma, _mm_mullo_epi16(d, splat(coefficients.blue_cb)));
+
+            // shifting and packing clamps every channel to 0..=255
+            let channel = |x: __m128i| {
+                let mut bytes = [0_u8; 16];
+                let packed = _mm_packus_epi16(_mm_srai_epi16::<6>(x), zero);
+                _mm_storeu_si128(bytes.as_mut_ptr().cast(), packed);
+                bytes
+            };
+
+            let (red, green, blue) = (channel(red), channel(green), channel(blue));
+            for (i, pixel) in out[luma_at..luma_at + 8].iter_mut().enumerate() {
+                *pixel = Rgb::new(red[i], green[i], blue[i]);
+            }
+        }
+    }
+
+    blocks * 8
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn convert_row_simd(_: Coefficients, _: &[u8], _: &[u8], _: &[u8], _: &mut [Rgb<u8>]) -> usize {
+    0
+}
+
+/// converts a row of planar 4:2:0 video, `cb` and `cr` hold a sample for every two pixels
+pub fn convert_row(
+    coefficients: Coefficients,
+    y: &[u8],
+    cb: &[u8],
+    cr: &[u8],
+    out: &mut [Rgb<u8>],
+) {
+    let done = convert_row_simd(coefficients, y, cb, cr, out);
+
+    let (y, out) = (&y[done.min(y.len())..], &mut out[done..]);
+    let (cb, cr) = (&cb[done / 2..], &cr[done / 2..]);
+    convert_row_scalar(coefficients, y, cb, cr, out);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn simd_matches_scalar() {
+        let y = (0..=255).step_by(3).map(|x| x as u8).collect::<Vec<_>>();
+        let cb = (0..y.len().div_ceil(2))
+            .map(|x| (x * 7) as u8)
+            .collect::<Vec<_>>();
+        let cr = (0..y.len().div_ceil(2))
+            .map(|x| 255 - (x * 5) as u8)
+            .collect::<Vec<_>>();
+
+        for coefficients in [Coefficients::BT601, Coefficients::BT709] {
+            let mut expected = vec![Rgb::new(0, 0, 0); y.len()];
+            convert_row_scalar(coefficients, &y, &cb, &cr, &mut expected);
+
+            let mut got = vec![Rgb::new(0, 0, 0); y.len()];
+            convert_row(coefficients, &y, &cb, &cr, &mut got);
+
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn limited_range_extremes() {
+        for coefficients in [Coefficients::BT601, Coefficients::BT709] {
+            let convert = |y| convert_scalar(coefficients, y, 128, 128);
+            assert_eq!(convert(16), Rgb::new(0, 0, 0));
+
+            // grays stay gray, get brighter with the luma and end up about white,
+            // give or take what the fixed point factors lose
+            let grays = (16..=235).map(convert).collect::<Vec<_>>();
+            assert!(
+                grays
+                    .iter()
+                    .all(|gray| gray.r == gray.g && gray.g == gray.b)
+            );
+            assert!(grays.is_sorted_by_key(|gray| gray.r));
+            assert!(grays[grays.len() - 1].r >= 250);
+        }
+    }
+}
@@ -0,0 +1,376 @@
+//! `videoplayer wall`, several videos decoded by a pipeline each and drawn side by side
+//! into tiles of the terminal with [`RenderTarget`]s, for keeping an eye on a few camera streams
+//! at once, without sound and until q is pressed
+use crate::live;
+use glib::object::{Cast, ObjectExt};
+use glib::prelude::ToValue;
+use gst::prelude::{ElementExt, GstBinExtManual, GstObjectExt, PadExt};
+use gst_app::{AppSink, AppSinkCallbacks};
+use gstreamer_app as gst_app;
+use gstreamer_video as gst_video;
+use parking_lot::Mutex;
+use std::io::Write;
+use std::num::NonZero;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::time::Duration;
+use video_less::cancel::CancellationToken;
+use video_less::error::PlayerError;
+use video_less::terminal::{self, Event, Key};
+use video_less::{RenderTarget, TerminalRenderer, gstreamer_element, terminal_sink};
+
+/// how often the terminal size gets checked while no frame comes in
+pub const RESIZE_POLL: Duration = Duration::from_millis(200);
+
+/// How many tiles across and down the terminal gets split into, `COLUMNSxROWS`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Grid {
+    pub columns: NonZero<u16>,
+    pub rows: NonZero<u16>,
+}
+
+impl std::str::FromStr for Grid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let form = || "the grid must be in the form {COLUMNS}x{ROWS} (e.g. 2x2)".to_string();
+        let (columns, rows) = s.split_once('x').ok_or_else(form)?;
+        let parse = |v: &str| v.trim().parse::<NonZero<u16>>().map_err(|_| form());
+        Ok(Grid {
+            columns: parse(columns)?,
+            rows: parse(rows)?,
+        })
+    }
+}
+
+impl Grid {
+    /// the squarest grid with a tile for each of `count` videos, wider rather than taller
+    pub fn fitting(count: usize) -> Self {
+        let count = u16::try_from(count).unwrap_or(u16::MAX).max(1);
+        let columns = (1..=count)
+            .find(|columns| columns * columns >= count)
+            .unwrap_or(count);
+        Grid {
+            columns: NonZero::new(columns).unwrap(),
+            rows: NonZero::new(count.div_ceil(columns)).unwrap(),
+        }
+    }
+
+    pub fn tiles(self) -> usize {
+        usize::from(self.columns.get()) * usize::from(self.rows.get())
+    }
+
+    /// the top left cell and the size of tile `index` on a terminal `size` big, row by row,
+    /// what doesn't divide evenly spread over the tiles
+    fn tile(self, (width, height): (u16, u16), index: usize) -> ((u16, u16), (u16, u16)) {
+        let (columns, rows) = (self.columns.get(), self.rows.get());
+        let index = u16::try_from(index).unwrap_or(u16::MAX);
+        let (column, row) = (index % columns, index / columns);
+        let at = |cell: u16, of: u16, length: u16| {
+            (u32::from(length) * u32::from(cell) / u32::from(of)) as u16
+        };
+        let (x, y) = (at(column, columns, width), at(row, rows, height));
+        let size = (
+            at(column + 1, columns, width) - x,
+            at(row + 1, rows, height) - y,
+        );
+        ((x, y), size)
+    }
+}
+
+/// What the tiles tell the thread drawing them
+pub enum Update {
+    /// tile has a new frame waiting
+    Frame(usize),
+    /// tile ended with this message to show instead
+    Stopped(usize, String),
+    Key(Key),
+}
+
+/// whether `key` is one that quits
+pub fn quits(key: Key) -> bool {
+    matches!(key, Key::Char('q' | 'Q') | Key::Ctrl('c') | Key::Esc)
+}
+
+/// passes the keys pressed on to `updates` until `token` gets cancelled
+pub fn forward_keys(token: CancellationToken, updates: Sender<Update>) {
+    std::thread::spawn(move || {
+        for event in terminal::events(token) {
+            if let Event::Key(key) = event
+                && updates.send(Update::Key(key)).is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+/// the uri playing `video` takes, for uridecodebin to find a source for whatever it is
+fn uri(video: &Path) -> Result<String, PlayerError> {
+    if let Some(uri) = live::uri(video) {
+        return Ok(uri.to_string());
+    }
+    let open_error = |source| PlayerError::Open {
+        path: video.to_path_buf(),
+        source,
+    };
+    let path = std::path::absolute(video).map_err(open_error)?;
+    glib::filename_to_uri(&path, None)
+        .map(String::from)
+        .map_err(|err| open_error(std::io::Error::other(err)))
+}
+
+/// decodes the video of `video` in `pipeline` into `slot`, telling `updates` about every frame
+pub fn add_video(
+    pipeline: &gst::Pipeline,
+    video: &Path,
+    index: usize,
+    slot: Arc<Mutex<Option<gst::Sample>>>,
+    updates: Sender<Update>,
+) -> Result<(), PlayerError> {
+    let decode = gst::ElementFactory::make("uridecodebin")
+        .property("uri", uri(video)?)
+        .build()
+        .map_err(PlayerError::element("uridecodebin"))?;
+    let convert = gstreamer_element("videoconvert")?;
+    let sink = AppSink::builder()
+        .caps(&TerminalRenderer::caps())
+        .callbacks(
+            AppSinkCallbacks::builder()
+                .new_sample(move |sink| {
+                    let sample = sink.pull_sample().map_err(|_| gst::FlowError::Flushing)?;
+                    // the newest frame wins, the thread drawing them never holds decoding up
+                    *slot.lock() = Some(sample);
+                    let _ = updates.send(Update::Frame(index));
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        )
+        .build();
+
+    let link = PlayerError::link;
+    pipeline
+        .add_many([&decode, &convert, sink.upcast_ref()])
+        .map_err(link("the elements of a tile"))?;
+    convert
+        .link(&sink)
+        .map_err(link("the converter of a tile to its sink"))?;
+
+    // the sound is left undecoded, there's no telling which tile it would be for,
+    // its pads come out as they are and never get linked
+    decode.connect("autoplug-continue", false, |args| {
+        let caps = args[2].get::<gst::Caps>().ok()?;
+        let is_audio = caps
+            .structure(0)
+            .is_some_and(|structure| structure.name().starts_with("audio/"));
+        Some((!is_audio).to_value())
+    });
+    let video_entry = convert.static_pad("sink").unwrap();
+    decode.connect_pad_added(move |_, src_pad| {
+        let caps = src_pad
+            .current_caps()
+            .unwrap_or_else(|| src_pad.query_caps(None));
+        let is_video = caps
+            .structure(0)
+            .is_some_and(|structure| structure.name().starts_with("video/"));
+        if is_video && !video_entry.is_linked() {
+            let _ = src_pad.link(&video_entry);
+        }
+    });
+    Ok(())
+}
+
+/// tells `updates` once `pipeline` ends or fails, as tile `index`
+pub fn watch(pipeline: &gst::Pipeline, index: usize, updates: Sender<Update>) {
+    let bus = pipeline.bus().unwrap();
+    bus.set_sync_handler(move |_, message| {
+        let stopped = match message.view() {
+            gst::MessageView::Eos(_) => Some("ended".to_string()),
+            gst::MessageView::Error(err) => Some(format!(
+                "{}: {}",
+                err.src()
+                    .map(|src| src.name().to_string())
+                    .unwrap_or_default(),
+                err.error()
+            )),
+            _ => None,
+        };
+        if let Some(stopped) = stopped {
+            let _ = updates.send(Update::Stopped(index, stopped));
+        }
+        gst::BusSyncReply::Drop
+    });
+}
+
+/// a pipeline of its own for tile `index`
+fn pipeline(
+    video: &Path,
+    index: usize,
+    slot: Arc<Mutex<Option<gst::Sample>>>,
+    updates: Sender<Update>,
+) -> Result<gst::Pipeline, PlayerError> {
+    let pipeline = gst::Pipeline::new();
+    add_video(&pipeline, video, index, slot, updates.clone())?;
+    watch(&pipeline, index, updates);
+    Ok(pipeline)
+}
+
+/// the frame of `sample` fitted into `target`
+pub fn draw<W: Write>(
+    renderer: &mut TerminalRenderer,
+    sample: &gst::Sample,
+    target: &mut RenderTarget<W>,
+    fresh_redraw: bool,
+) -> std::io::Result<()> {
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, "can't map the frame");
+    let info = sample
+        .caps()
+        .and_then(|caps| gst_video::VideoInfo::from_caps(caps).ok())
+        .ok_or_else(invalid)?;
+    let buffer = sample.buffer().ok_or_else(invalid)?;
+    let frame =
+        gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, &info).map_err(|_| invalid())?;
+    renderer.render_to(&frame, target, fresh_redraw)
+}
+
+/// `message` in the middle of the tile at `origin`, cut to fit its width
+pub fn caption((x, y): (u16, u16), (width, height): (u16, u16), message: &str) -> String {
+    let message = message.chars().take(usize::from(width)).collect::<String>();
+    let length = u16::try_from(message.chars().count()).unwrap_or(width);
+    let column = x + (width - length) / 2;
+    format!(
+        "{}\x1b[0m{message}",
+        terminal::goto(column + 1, y + height / 2 + 1)
+    )
+}
+
+/// plays all of `videos` at once, each in its tile of `grid`, until q is pressed
+/// or every one of them ended
+pub fn play(
+    videos: &[PathBuf],
+    grid: Grid,
+    palette: terminal_sink::Palette,
+    glyphs: terminal_sink::Glyphs,
+) -> Result<(), PlayerError> {
+    let (updates, received) = mpsc::channel();
+    let slots = videos
+        .iter()
+        .map(|_| Arc::<Mutex<Option<gst::Sample>>>::default())
+        .collect::<Vec<_>>();
+    let pipelines = videos
+        .iter()
+        .zip(&slots)
+        .enumerate()
+        .map(|(index, (video, slot))| pipeline(video, index, Arc::clone(slot), updates.clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let terminal::Terminal {
+        mut output,
+        restore,
+    } = terminal::take().map_err(PlayerError::Terminal)?;
+    let token = CancellationToken::default();
+    forward_keys(token.clone(), updates);
+
+    for pipeline in &pipelines {
+        let _ = pipeline.set_state(gst::State::Playing);
+    }
+
+    let mut renderers = videos
+        .iter()
+        .map(|_| {
+            let mut renderer = TerminalRenderer::new(false, NonZero::<usize>::MIN);
+            renderer.set_style(palette, glyphs);
+            renderer
+        })
+        .collect::<Vec<_>>();
+    let mut last = vec![None::<gst::Sample>; videos.len()];
+    let mut stopped = vec![None::<String>; videos.len()];
+    let mut size = None;
+    let res = loop {
+        let current = terminal::size().unwrap_or((80, 24));
+        // a new size lays every tile out again, from a clear screen
+        let redraw = size.replace(current) != Some(current);
+        if redraw {
+            let _ = output.write_all(terminal::CLEAR.as_bytes());
+        }
+
+        let updated = match received.recv_timeout(RESIZE_POLL) {
+            Ok(Update::Key(key)) if quits(key) => break Ok(()),
+            Ok(Update::Key(_)) | Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break Ok(()),
+            Ok(Update::Frame(index)) => {
+                if let Some(sample) = slots[index].lock().take() {
+                    last[index] = Some(sample);
+                }
+                Some(index)
+            }
+            Ok(Update::Stopped(index, message)) => {
+                stopped[index] = Some(message);
+                if stopped.iter().all(Option::is_some) {
+                    break Ok(());
+                }
+                Some(index)
+            }
+        };
+
+        let mut drawn = (0..videos.len()).filter(|&index| redraw || updated == Some(index));
+        let res = drawn.try_for_each(|index| {
+            let (origin, tile) = grid.tile(current, index);
+            if tile.0 == 0 || tile.1 == 0 {
+                return Ok(());
+            }
+            let mut target = RenderTarget {
+                origin,
+                size: tile,
+                writer: &mut output,
+            };
+            if let Some(sample) = &last[index] {
+                draw(&mut renderers[index], sample, &mut target, redraw)?;
+            }
+            match &stopped[index] {
+                Some(message) => output.write_all(caption(origin, tile, message).as_bytes()),
+                None => Ok(()),
+            }
+        });
+        if let Err(err) = res.and_then(|()| output.flush()) {
+            break Err(PlayerError::Terminal(err));
+        }
+    };
+
+    token.cancel();
+    for pipeline in &pipelines {
+        let _ = pipeline.set_state(gst::State::Null);
+    }
+    drop(restore);
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grids() {
+        let grid = |columns, rows| Grid {
+            columns: NonZero::new(columns).unwrap(),
+            rows: NonZero::new(rows).unwrap(),
+        };
+        assert_eq!("2x3".parse(), Ok(grid(2, 3)));
+        assert!("2x0".parse::<Grid>().is_err());
+        assert_eq!(Grid::fitting(1), grid(1, 1));
+        assert_eq!(Grid::fitting(3), grid(2, 2));
+        assert_eq!(Grid::fitting(5), grid(3, 2));
+    }
+
+    #[test]
+    fn tiles_cover_the_terminal() {
+        let grid = Grid {
+            columns: NonZero::new(3).unwrap(),
+            rows: NonZero::new(2).unwrap(),
+        };
+        assert_eq!(grid.tile((80, 24), 0), ((0, 0), (26, 12)));
+        assert_eq!(grid.tile((80, 24), 1), ((26, 0), (27, 12)));
+        assert_eq!(grid.tile((80, 24), 5), ((53, 12), (27, 12)));
+    }
+}